@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use discern::async_trait;
+use discern::command::Command;
+use discern::command::CommandBus;
+use discern::command::CommandHandler;
+#[cfg(feature = "sync")]
+use discern::command::SyncCommandHandler;
+use discern::command_bus;
+use discern::middleware::CommandMiddleware;
+use discern::middleware::Next;
+use discern::middleware::Outcome;
+use discern::query::BorrowedQueryHandler;
+use discern::query::Query;
+use discern::query::QueryBus;
+use discern::query::QueryHandler;
+use discern::query_bus;
+use discern::registry::CommandHandlerRegistry;
+use discern::registry::QueryHandlerRegistry;
+use discern::typed::TypedBus;
+use tokio::runtime::Runtime;
+
+#[derive(Debug)]
+struct IncrementCommand(u64);
+
+impl Command for IncrementCommand {
+    type Metadata = u64;
+    type Error = ();
+}
+
+struct IncrementCommandHandler;
+
+#[async_trait]
+impl CommandHandler<IncrementCommand> for IncrementCommandHandler {
+    async fn handle(&self, command: IncrementCommand) -> Result<u64, ()> {
+        Ok(command.0 + 1)
+    }
+}
+
+#[cfg(feature = "sync")]
+struct SyncIncrementCommandHandler;
+
+#[cfg(feature = "sync")]
+impl SyncCommandHandler<IncrementCommand> for SyncIncrementCommandHandler {
+    fn handle(&self, command: IncrementCommand) -> Result<u64, ()> {
+        Ok(command.0 + 1)
+    }
+}
+
+#[derive(Debug)]
+struct ChecksumQuery([u8; 64]);
+
+impl Query for ChecksumQuery {
+    type Output = u64;
+    type Error = ();
+}
+
+struct PassThroughMiddleware;
+
+#[async_trait]
+impl CommandMiddleware for PassThroughMiddleware {
+    async fn handle(&self, next: Next<'_>) -> Outcome {
+        next.run().await
+    }
+}
+
+struct ChecksumQueryHandler;
+
+#[async_trait]
+impl QueryHandler<ChecksumQuery> for ChecksumQueryHandler {
+    async fn handle(&self, query: ChecksumQuery) -> Result<u64, ()> {
+        Ok(query.0.iter().map(|byte| *byte as u64).sum())
+    }
+}
+
+#[derive(Debug)]
+struct LargeQuery(Vec<u8>);
+
+impl Query for LargeQuery {
+    type Output = u64;
+    type Error = ();
+}
+
+struct LargeQueryHandler;
+
+#[async_trait]
+impl BorrowedQueryHandler<LargeQuery> for LargeQueryHandler {
+    async fn handle(&self, query: &LargeQuery) -> Result<u64, ()> {
+        Ok(query.0[0] as u64 + query.0[query.0.len() - 1] as u64)
+    }
+}
+
+fn current_thread_runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+fn command_bus(c: &mut Criterion) {
+    let runtime = current_thread_runtime();
+    let mut group = c.benchmark_group("command_bus");
+
+    let command_bus: CommandBus = command_bus! {
+        IncrementCommand => IncrementCommandHandler,
+    };
+
+    group.bench_function("dispatch", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { command_bus.dispatch(IncrementCommand(1)).await })
+    });
+
+    let command_bus_with_middleware: CommandBus = command_bus! {
+        middleware: [PassThroughMiddleware],
+        IncrementCommand => IncrementCommandHandler,
+    };
+
+    group.bench_function("dispatch_with_middleware", |b| {
+        b.to_async(&runtime).iter(|| async {
+            command_bus_with_middleware
+                .dispatch(IncrementCommand(1))
+                .await
+        })
+    });
+
+    let typed_bus = TypedBus::new((IncrementCommandHandler,));
+
+    group.bench_function("dispatch_typed", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { typed_bus.dispatch(IncrementCommand(1)).await })
+    });
+
+    #[cfg(feature = "sync")]
+    {
+        let mut registry = CommandHandlerRegistry::new();
+        registry.register_sync(SyncIncrementCommandHandler);
+        let command_bus = CommandBus::new(registry);
+
+        group.bench_function("dispatch_sync_handler", |b| {
+            b.to_async(&runtime)
+                .iter(|| async { command_bus.dispatch(IncrementCommand(1)).await })
+        });
+    }
+
+    group.bench_function("build_registry", |b| {
+        b.iter(|| {
+            let mut registry = CommandHandlerRegistry::new();
+            registry.register(IncrementCommandHandler);
+            registry
+        })
+    });
+
+    group.finish();
+}
+
+fn query_bus(c: &mut Criterion) {
+    let runtime = current_thread_runtime();
+    let mut group = c.benchmark_group("query_bus");
+
+    let query_bus: QueryBus = query_bus! {
+        ChecksumQuery => ChecksumQueryHandler,
+    };
+
+    group.bench_function("dispatch", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { query_bus.dispatch(ChecksumQuery([1; 64])).await })
+    });
+
+    let typed_bus = TypedBus::new((ChecksumQueryHandler,));
+
+    group.bench_function("dispatch_typed", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { typed_bus.query(ChecksumQuery([1; 64])).await })
+    });
+
+    let mut registry = QueryHandlerRegistry::new();
+    registry.register_borrowed(LargeQueryHandler);
+    let query_bus = QueryBus::new(registry);
+    let query = LargeQuery(vec![1; 64 * 1024]);
+
+    group.throughput(Throughput::Bytes(query.0.len() as u64));
+    group.bench_function("dispatch_ref_64kib", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { query_bus.dispatch_ref(&query).await })
+    });
+
+    group.bench_function("dispatch_cloned_64kib", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { query_bus.dispatch(LargeQuery(query.0.clone())).await })
+    });
+
+    group.finish();
+}
+
+fn concurrent_dispatch(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("concurrent_dispatch");
+
+    let command_bus: Arc<CommandBus> = Arc::new(command_bus! {
+        IncrementCommand => IncrementCommandHandler,
+    });
+
+    for tasks in [1, 16, 256] {
+        group.throughput(Throughput::Elements(tasks));
+        group.bench_with_input(
+            BenchmarkId::new("command_bus", tasks),
+            &tasks,
+            |b, tasks| {
+                b.to_async(&runtime).iter(|| {
+                    let command_bus = command_bus.clone();
+
+                    async move {
+                        let handles: Vec<_> = (0..*tasks)
+                            .map(|i| {
+                                let command_bus = command_bus.clone();
+
+                                tokio::spawn(async move {
+                                    command_bus.dispatch(IncrementCommand(i)).await
+                                })
+                            })
+                            .collect();
+
+                        for handle in handles {
+                            handle.await.unwrap().unwrap();
+                        }
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+
+    let stats = command_bus.stats();
+    assert_eq!(stats.dispatched, stats.succeeded);
+    assert_eq!(stats.in_flight, 0);
+}
+
+criterion_group!(benches, command_bus, query_bus, concurrent_dispatch);
+criterion_main!(benches);
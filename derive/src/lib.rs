@@ -0,0 +1,804 @@
+//! The `discern-derive` crate provides derive macros for the [discern](https://docs.rs/discern) crate.
+//!
+//! The macros are re-exported by `discern` when its `derive` feature is enabled, and should be used through it,
+//! since the generated code refers to the `discern` crate.
+//!
+//! - [Command](derive@Command): Derives the `Command` trait.
+//! - [Query](derive@Query): Derives the `Query` trait.
+//! - [MessageName](derive@MessageName): Derives the `MessageName` trait.
+//! - [command_handler](macro@command_handler): Registers a command handler in the inventory.
+//! - [query_handler](macro@query_handler): Registers a query handler in the inventory.
+//! - [instrument](macro@instrument): Runs a handler inside a `tracing` span.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::format_ident;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+use syn::parse_macro_input;
+use syn::parse_quote;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Expr;
+use syn::Fields;
+use syn::GenericArgument;
+use syn::Ident;
+use syn::ImplItem;
+use syn::ItemImpl;
+use syn::LitInt;
+use syn::LitStr;
+use syn::PathArguments;
+use syn::Token;
+use syn::Type;
+
+/// Derives the `Command` trait.
+///
+/// The associated types are given using the `#[command(...)]` attribute:
+///
+/// - `metadata`: The metadata type, defaults to `()`.
+/// - `error`: The error type, defaults to [BoxedError](https://docs.rs/discern/latest/discern/error/struct.BoxedError.html).
+///   The error enum can also be declared inline, see below.
+/// - `name`: The stable name of the command, defaults to the name of the type.
+/// - `version`: The version of the command, defaults to `1`.
+/// - `requires`: A permission required to dispatch the command, can be repeated. The `RequiredPermissions` trait is
+///   implemented, and returned by `Command::required_permissions`, which the `AuthorizationMiddleware` consults.
+///
+/// The `MessageName` trait is implemented as well, and returned by `Command::name`, and `Command::descriptor`, along
+/// with the schema hash of the command, computed from the names, and types, of its fields.
+///
+/// # Example
+///
+/// ```
+/// use discern::command::Command;
+/// use discern::error::BoxedError;
+///
+/// #[derive(Debug)]
+/// enum CreateUserError {
+///    UsernameAlreadyExists,
+///    EmailAlreadyExists,
+/// }
+///
+/// #[derive(Debug, Command)]
+/// #[command(metadata = u64, error = CreateUserError)]
+/// struct CreateUserCommand {
+///    username: String,
+///    email: String,
+/// }
+///
+/// #[derive(Debug, Command)]
+/// #[command(name = "users.delete", version = 2, requires = "users:write", requires = "users:delete")]
+/// struct DeleteUserCommand {
+///    user_id: u64,
+/// }
+///
+/// fn assert_command<C: Command<Metadata = M, Error = E>, M, E>() {}
+///
+/// assert_command::<CreateUserCommand, u64, CreateUserError>();
+/// assert_command::<DeleteUserCommand, (), BoxedError>();
+///
+/// assert_eq!(CreateUserCommand::name(), "CreateUserCommand");
+/// assert_eq!(DeleteUserCommand::name(), "users.delete");
+/// assert_eq!(DeleteUserCommand::descriptor().version, 2);
+/// assert!(DeleteUserCommand::descriptor().schema_hash.is_some());
+/// assert_eq!(DeleteUserCommand::required_permissions(), ["users:write", "users:delete"]);
+/// assert!(CreateUserCommand::required_permissions().is_empty());
+/// ```
+///
+/// # Inline errors
+///
+/// Errors made of a fixed set of unit variants are declared inline, using `error { Variant => "message", ... }`.
+/// An enum named after the command, without its `Command` suffix, e.g. `RenameUserError`, is generated, along with
+/// its `Display`, and `Error`, implementations, writing the message of each variant.
+///
+/// ```
+/// use discern::command::Command;
+///
+/// #[derive(Debug, Command)]
+/// #[command(error { NotFound => "user not found", UsernameTaken => "username already taken" })]
+/// pub struct RenameUserCommand {
+///    user_id: u64,
+///    username: String,
+/// }
+///
+/// fn assert_error<E: std::error::Error>(_error: E) {}
+///
+/// assert_eq!(RenameUserError::NotFound.to_string(), "user not found");
+/// assert_error(RenameUserError::UsernameTaken);
+/// ```
+#[proc_macro_derive(Command, attributes(command))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_command(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives the `Query` trait.
+///
+/// The associated types are given using the `#[query(...)]` attribute:
+///
+/// - `output`: The output type, required.
+/// - `error`: The error type, defaults to [BoxedError](https://docs.rs/discern/latest/discern/error/struct.BoxedError.html).
+///   The error enum can also be declared inline, using `error { Variant => "message", ... }`, see
+///   [Command](derive@Command), generating an enum named after the query, without its `Query` suffix.
+/// - `name`: The stable name of the query, defaults to the name of the type.
+/// - `version`: The version of the query, defaults to `1`.
+///
+/// The `MessageName` trait is implemented as well, and returned by `Query::name`, and `Query::descriptor`, along
+/// with the schema hash of the query, computed from the names, and types, of its fields.
+///
+/// # Example
+///
+/// ```
+/// use discern::error::BoxedError;
+/// use discern::query::Query;
+///
+/// #[derive(Debug)]
+/// struct User {
+///    id: u64,
+///    username: String,
+/// }
+///
+/// #[derive(Debug)]
+/// enum GetUserError {
+///    UserNotFound,
+/// }
+///
+/// #[derive(Debug, Query)]
+/// #[query(output = User, error = GetUserError)]
+/// struct GetUserQuery {
+///    user_id: u64,
+/// }
+///
+/// #[derive(Debug, Query)]
+/// #[query(output = Vec<User>)]
+/// struct ListUsersQuery;
+///
+/// fn assert_query<Q: Query<Output = O, Error = E>, O, E>() {}
+///
+/// assert_query::<GetUserQuery, User, GetUserError>();
+/// assert_query::<ListUsersQuery, Vec<User>, BoxedError>();
+///
+/// assert_eq!(GetUserQuery::name(), "GetUserQuery");
+///
+/// #[derive(Debug, Query)]
+/// #[query(output = User, error { NotFound => "user not found" })]
+/// struct FindUserQuery {
+///    username: String,
+/// }
+///
+/// assert_eq!(FindUserError::NotFound.to_string(), "user not found");
+/// ```
+#[proc_macro_derive(Query, attributes(query))]
+pub fn derive_query(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_query(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives the `MessageName` trait.
+///
+/// The name is given using the `#[message(name = "...", version = ...)]` attribute, and defaults to the name of the
+/// type, the version defaulting to `1`. The schema hash is computed from the names, and types, of the fields. Commands,
+/// and queries, deriving `Command`, or `Query`, already implement `MessageName`, and must not derive it.
+///
+/// # Example
+///
+/// ```
+/// use discern::event::Event;
+/// use discern::message::MessageName;
+///
+/// #[derive(Debug, MessageName)]
+/// #[message(name = "users.created")]
+/// struct UserCreatedEvent {
+///    user_id: u64,
+/// }
+///
+/// impl Event for UserCreatedEvent {
+///    type Error = std::io::Error;
+///
+///    fn name() -> &'static str {
+///        Self::NAME
+///    }
+/// }
+///
+/// assert_eq!(UserCreatedEvent::NAME, "users.created");
+/// assert_eq!(<UserCreatedEvent as Event>::name(), "users.created");
+/// ```
+#[proc_macro_derive(MessageName, attributes(message))]
+pub fn derive_message_name(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    parse_attributes(&input, "message", [])
+        .map(|([], message)| expand_message_name(&input, message))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a command handler in the inventory of the `discern` crate.
+///
+/// The attribute is placed on the `impl CommandHandler<C> for H` block, or the `impl SyncCommandHandler<C> for H`
+/// block. The handler is constructed using its `Default` implementation, or using the function given to the
+/// `constructor` argument, e.g. `#[command_handler(constructor = CreateUserCommandHandler::new)]`.
+///
+/// See the [inventory](https://docs.rs/discern/latest/discern/inventory/index.html) module of the `discern` crate.
+#[proc_macro_attribute]
+pub fn command_handler(arguments: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+
+    expand_handler(arguments, item, Kind::Command)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers a query handler in the inventory of the `discern` crate.
+///
+/// The attribute is placed on the `impl QueryHandler<Q> for H` block, the `impl BorrowedQueryHandler<Q> for H` block,
+/// or the `impl SyncQueryHandler<Q> for H` block. The handler is constructed using its `Default` implementation, or
+/// using the function given to the `constructor` argument, e.g. `#[query_handler(constructor = GetUserQueryHandler::new)]`.
+///
+/// See the [inventory](https://docs.rs/discern/latest/discern/inventory/index.html) module of the `discern` crate.
+#[proc_macro_attribute]
+pub fn query_handler(arguments: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+
+    expand_handler(arguments, item, Kind::Query)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Runs a handler inside a `handler` span of the `tracing` crate, recording its outcome, and logging its errors.
+///
+/// The attribute is placed on the `impl CommandHandler<C> for H` block, the `impl ContextualCommandHandler<C> for H`
+/// block, the `impl QueryHandler<Q> for H` block, or the `impl EventHandler<E> for H` block, above the
+/// `#[async_trait]` attribute. The handler is named after its type, unless overridden using the `name` argument,
+/// e.g. `#[instrument(name = "users.create")]`.
+///
+/// See the [instrument](https://docs.rs/discern/latest/discern/instrument/index.html) module of the `discern` crate.
+#[proc_macro_attribute]
+pub fn instrument(arguments: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+
+    expand_instrument(arguments, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Expands the `instrument` attribute.
+fn expand_instrument(arguments: TokenStream, mut item: ItemImpl) -> syn::Result<TokenStream2> {
+    let mut name: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            if name.is_some() {
+                return Err(meta.error("duplicate attribute"));
+            }
+
+            name = Some(meta.value()?.parse()?);
+
+            Ok(())
+        } else {
+            Err(meta.error("unsupported instrument attribute, expected `name`"))
+        }
+    });
+    syn::parse::Parser::parse(parser, arguments)?;
+
+    let Some((_, trait_path, _)) = &item.trait_ else {
+        return Err(syn::Error::new_spanned(
+            &item.self_ty,
+            "expected an implementation of a handler trait",
+        ));
+    };
+
+    let segment = trait_path.segments.last().unwrap();
+    let (kind, message_trait) = match segment.ident.to_string().as_str() {
+        "CommandHandler" | "ContextualCommandHandler" => {
+            ("command", quote!(::discern::command::Command))
+        }
+        "QueryHandler" => ("query", quote!(::discern::query::Query)),
+        "EventHandler" => ("event", quote!(::discern::event::Event)),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                trait_path,
+                "expected an implementation of `CommandHandler`, `ContextualCommandHandler`, `QueryHandler`, or `EventHandler`",
+            ))
+        }
+    };
+
+    let message = match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => {
+            arguments.args.iter().find_map(|argument| match argument {
+                GenericArgument::Type(message) => Some(message.clone()),
+                _ => None,
+            })
+        }
+        _ => None,
+    };
+    let Some(message) = message else {
+        return Err(syn::Error::new_spanned(
+            segment,
+            "expected the handled message type as a generic argument",
+        ));
+    };
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            let handler = match &*item.self_ty {
+                Type::Path(path) => path.path.segments.last().map(|segment| &segment.ident),
+                _ => None,
+            };
+            let Some(handler) = handler else {
+                return Err(syn::Error::new_spanned(
+                    &item.self_ty,
+                    "expected a named handler type, or a `name` argument",
+                ));
+            };
+
+            LitStr::new(&handler.to_string(), handler.span())
+        }
+    };
+
+    let span = item.self_ty.clone();
+    let handle = item.items.iter_mut().find_map(|impl_item| match impl_item {
+        ImplItem::Fn(function) if function.sig.ident == "handle" => Some(function),
+        _ => None,
+    });
+    let Some(handle) = handle else {
+        return Err(syn::Error::new_spanned(
+            span,
+            "expected an implementation of the `handle` function",
+        ));
+    };
+
+    if handle.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            &handle.sig,
+            "expected an `async` handle function",
+        ));
+    }
+
+    let block = &handle.block;
+    handle.block = parse_quote!({
+        ::discern::instrument::handler(
+            #kind,
+            #name,
+            <#message as #message_trait>::name(),
+            async move #block,
+        )
+        .await
+    });
+
+    Ok(quote!(#item))
+}
+
+/// The kind of messages handled by an annotated handler.
+#[derive(Clone, Copy)]
+enum Kind {
+    Command,
+    Query,
+}
+
+/// Expands the `command_handler` and `query_handler` attributes.
+fn expand_handler(arguments: TokenStream, item: ItemImpl, kind: Kind) -> syn::Result<TokenStream2> {
+    let mut constructor: Option<Expr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("constructor") {
+            if constructor.is_some() {
+                return Err(meta.error("duplicate attribute"));
+            }
+
+            constructor = Some(meta.value()?.parse()?);
+
+            Ok(())
+        } else {
+            Err(meta.error("unsupported handler attribute, expected `constructor`"))
+        }
+    });
+    syn::parse::Parser::parse(parser, arguments)?;
+
+    let Some((_, trait_path, _)) = &item.trait_ else {
+        return Err(syn::Error::new_spanned(
+            &item.self_ty,
+            "expected an implementation of a handler trait",
+        ));
+    };
+
+    let segment = trait_path.segments.last().unwrap();
+    let register = match (kind, segment.ident.to_string().as_str()) {
+        (Kind::Command, "CommandHandler") | (Kind::Query, "QueryHandler") => quote!(register),
+        (Kind::Command, "SyncCommandHandler") | (Kind::Query, "SyncQueryHandler") => {
+            quote!(register_sync)
+        }
+        (Kind::Query, "BorrowedQueryHandler") => quote!(register_borrowed),
+        (Kind::Command, _) => {
+            return Err(syn::Error::new_spanned(
+                trait_path,
+                "expected an implementation of `CommandHandler`, or `SyncCommandHandler`",
+            ))
+        }
+        (Kind::Query, _) => {
+            return Err(syn::Error::new_spanned(
+                trait_path,
+                "expected an implementation of `QueryHandler`, `BorrowedQueryHandler`, or `SyncQueryHandler`",
+            ))
+        }
+    };
+
+    let message = match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => {
+            arguments.args.iter().find_map(|argument| match argument {
+                GenericArgument::Type(message) => Some(message),
+                _ => None,
+            })
+        }
+        _ => None,
+    };
+    let Some(message) = message else {
+        return Err(syn::Error::new_spanned(
+            segment,
+            "expected the handled message type as a generic argument",
+        ));
+    };
+
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "generic handlers cannot be registered in the inventory",
+        ));
+    }
+
+    let handler = &item.self_ty;
+    let constructor = match constructor {
+        Some(constructor) => quote!((#constructor)()),
+        None => quote!(<#handler as ::core::default::Default>::default()),
+    };
+
+    let registration = match kind {
+        Kind::Command => quote!(::discern::inventory::CommandHandlerRegistration),
+        Kind::Query => quote!(::discern::inventory::QueryHandlerRegistration),
+    };
+
+    Ok(quote! {
+        #item
+
+        ::discern::inventory::__private::submit! {
+            #registration::new(|registry| {
+                registry.#register::<#message>(#constructor);
+            })
+        }
+    })
+}
+
+/// Expands the `Command` derive.
+fn expand_command(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ([metadata, error], mut message) =
+        parse_attributes(&input, "command", ["metadata", "error"])?;
+
+    let metadata = metadata.unwrap_or_else(|| parse_quote!(()));
+    let (error, inline_error) = expand_error(&input, "Command", error, message.errors.take())?;
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let requires = core::mem::take(&mut message.requires);
+    let (required_permissions, permissions) = if requires.is_empty() {
+        (TokenStream2::new(), TokenStream2::new())
+    } else {
+        (
+            quote! {
+                impl #impl_generics ::discern::authorization::RequiredPermissions for #name #type_generics #where_clause {
+                    const PERMISSIONS: &'static [&'static str] = &[#(#requires),*];
+                }
+            },
+            quote! {
+                fn required_permissions() -> &'static [&'static str] {
+                    <Self as ::discern::authorization::RequiredPermissions>::PERMISSIONS
+                }
+            },
+        )
+    };
+
+    let message_name = expand_message_name(&input, message);
+
+    Ok(quote! {
+        #message_name
+        #inline_error
+        #required_permissions
+
+        impl #impl_generics ::discern::command::Command for #name #type_generics #where_clause {
+            type Metadata = #metadata;
+            type Error = #error;
+
+            fn name() -> &'static str {
+                <Self as ::discern::message::MessageName>::NAME
+            }
+
+            fn descriptor() -> ::discern::message::MessageDescriptor {
+                ::discern::message::MessageDescriptor::of::<Self>(::discern::message::MessageKind::Command)
+            }
+
+            #permissions
+        }
+    })
+}
+
+/// Expands the `Query` derive.
+fn expand_query(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ([output, error], mut message) = parse_attributes(&input, "query", ["output", "error"])?;
+
+    let Some(output) = output else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "missing `#[query(output = ...)]` attribute",
+        ));
+    };
+    let (error, inline_error) = expand_error(&input, "Query", error, message.errors.take())?;
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let message_name = expand_message_name(&input, message);
+
+    Ok(quote! {
+        #message_name
+        #inline_error
+
+        impl #impl_generics ::discern::query::Query for #name #type_generics #where_clause {
+            type Output = #output;
+            type Error = #error;
+
+            fn name() -> &'static str {
+                <Self as ::discern::message::MessageName>::NAME
+            }
+
+            fn descriptor() -> ::discern::message::MessageDescriptor {
+                ::discern::message::MessageDescriptor::of::<Self>(::discern::message::MessageKind::Query)
+            }
+        }
+    })
+}
+
+/// The arguments shared by all the message attributes.
+#[derive(Default)]
+struct MessageAttributes {
+    /// The `name = "..."` argument.
+    name: Option<LitStr>,
+    /// The `version = ...` argument.
+    version: Option<LitInt>,
+    /// The `error { ... }` argument, if the error enum is declared inline.
+    errors: Option<Vec<InlineError>>,
+    /// The `requires = "..."` arguments, only supported by commands.
+    requires: Vec<LitStr>,
+}
+
+/// A variant of an error enum declared inline, e.g. `NotFound => "user not found"`.
+struct InlineError {
+    /// The name of the variant.
+    variant: Ident,
+    /// The message written by the `Display` implementation.
+    message: LitStr,
+}
+
+impl Parse for InlineError {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let variant = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let message = input.parse()?;
+
+        Ok(Self { variant, message })
+    }
+}
+
+/// Parses the arguments of the `#[<attribute>(<key> = <type>, name = "...", version = ...)]` attributes of the input.
+///
+/// The types are returned in the order of `keys`, along with the message arguments.
+fn parse_attributes<const N: usize>(
+    input: &DeriveInput,
+    attribute: &str,
+    keys: [&str; N],
+) -> syn::Result<([Option<Type>; N], MessageAttributes)> {
+    let mut types = [const { None }; N];
+    let mut message = MessageAttributes::default();
+
+    for attr in &input.attrs {
+        if !attr.path().is_ident(attribute) {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                if message.name.is_some() {
+                    return Err(meta.error("duplicate attribute"));
+                }
+
+                message.name = Some(meta.value()?.parse()?);
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("version") {
+                if message.version.is_some() {
+                    return Err(meta.error("duplicate attribute"));
+                }
+
+                let version: LitInt = meta.value()?.parse()?;
+                version.base10_parse::<u32>()?;
+                message.version = Some(version);
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("requires") && attribute == "command" {
+                message.requires.push(meta.value()?.parse()?);
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("error")
+                && keys.contains(&"error")
+                && meta.input.peek(syn::token::Brace)
+            {
+                if message.errors.is_some() {
+                    return Err(meta.error("duplicate attribute"));
+                }
+
+                let content;
+                syn::braced!(content in meta.input);
+                let errors = content.parse_terminated(InlineError::parse, Token![,])?;
+                message.errors = Some(errors.into_iter().collect());
+
+                return Ok(());
+            }
+
+            match keys.iter().position(|key| meta.path.is_ident(key)) {
+                Some(index) => parse_type(&meta, &mut types[index]),
+                None => Err(meta.error(format!(
+                    "unsupported {} attribute, expected one of: {}",
+                    attribute,
+                    keys.iter()
+                        .chain(&["name", "version"])
+                        .chain(if attribute == "command" {
+                            &["requires"][..]
+                        } else {
+                            &[]
+                        })
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))),
+            }
+        })?;
+    }
+
+    Ok((types, message))
+}
+
+/// Returns the error type of a message, generating the error enum declared inline, if any.
+///
+/// The generated enum is named after the message, without the given suffix, e.g. `CreateUserError` for
+/// `CreateUserCommand`.
+fn expand_error(
+    input: &DeriveInput,
+    suffix: &str,
+    error: Option<Type>,
+    errors: Option<Vec<InlineError>>,
+) -> syn::Result<(Type, TokenStream2)> {
+    let Some(errors) = errors else {
+        let error = error.unwrap_or_else(|| parse_quote!(::discern::error::BoxedError));
+
+        return Ok((error, TokenStream2::new()));
+    };
+
+    if let Some(error) = error {
+        return Err(syn::Error::new_spanned(
+            error,
+            "the error type cannot be given along with an inline error enum",
+        ));
+    }
+
+    let message = input.ident.to_string();
+    let ident = format_ident!(
+        "{}Error",
+        message.strip_suffix(suffix).unwrap_or(&message),
+        span = input.ident.span()
+    );
+    let documentation = format!("The errors of [`{}`].", message);
+    let visibility = &input.vis;
+    let variants: Vec<&Ident> = errors.iter().map(|error| &error.variant).collect();
+    let messages: Vec<&LitStr> = errors.iter().map(|error| &error.message).collect();
+
+    let inline_error = quote! {
+        #[doc = #documentation]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #visibility enum #ident {
+            #(
+                #[doc = #messages]
+                #variants,
+            )*
+        }
+
+        impl ::core::fmt::Display for #ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match *self {
+                    #(Self::#variants => f.write_str(#messages),)*
+                }
+            }
+        }
+
+        impl ::core::error::Error for #ident {}
+    };
+
+    Ok((parse_quote!(#ident), inline_error))
+}
+
+/// Expands the implementation of the `MessageName` trait, named after the type unless `name` is given.
+fn expand_message_name(input: &DeriveInput, message: MessageAttributes) -> TokenStream2 {
+    let name = message
+        .name
+        .unwrap_or_else(|| LitStr::new(&input.ident.to_string(), input.ident.span()));
+    let version = message
+        .version
+        .map_or_else(|| quote!(1), |version| quote!(#version));
+    let schema_hash = schema_hash(input);
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::discern::message::MessageName for #ident #type_generics #where_clause {
+            const NAME: &'static str = #name;
+            const VERSION: u32 = #version;
+            const SCHEMA_HASH: ::core::option::Option<u64> = ::core::option::Option::Some(#schema_hash);
+        }
+    }
+}
+
+/// Returns the FNV-1a hash of the shape of the input: the names of its variants, and the names, and types, of
+/// their fields.
+fn schema_hash(input: &DeriveInput) -> u64 {
+    fn push_fields(schema: &mut String, fields: &Fields) {
+        for (index, field) in fields.iter().enumerate() {
+            let ty = &field.ty;
+            match &field.ident {
+                Some(ident) => schema.push_str(&format!("{}:{};", ident, quote!(#ty))),
+                None => schema.push_str(&format!("{}:{};", index, quote!(#ty))),
+            }
+        }
+    }
+
+    let mut schema = String::new();
+
+    match &input.data {
+        Data::Struct(data) => push_fields(&mut schema, &data.fields),
+        Data::Enum(data) => {
+            for variant in &data.variants {
+                schema.push_str(&format!("{}{{", variant.ident));
+                push_fields(&mut schema, &variant.fields);
+                schema.push('}');
+            }
+        }
+        Data::Union(data) => {
+            push_fields(&mut schema, &Fields::Named(data.fields.clone()));
+        }
+    }
+
+    schema.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Parses the type given to an attribute, e.g. `error = CreateUserError`, rejecting duplicates.
+fn parse_type(meta: &ParseNestedMeta<'_>, slot: &mut Option<Type>) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate attribute"));
+    }
+
+    *slot = Some(meta.value()?.parse()?);
+
+    Ok(())
+}
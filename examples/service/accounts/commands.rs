@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use discern::async_trait;
+use discern::command::Command;
+use discern::command::CommandHandler;
+
+use super::errors::AccountError;
+use super::repository::AccountRepository;
+
+/// Opens a new account, and returns its identifier.
+#[derive(Debug)]
+pub struct OpenAccountCommand {
+    pub owner: String,
+}
+
+impl Command for OpenAccountCommand {
+    type Metadata = u64;
+    type Error = AccountError;
+}
+
+pub struct OpenAccountCommandHandler {
+    pub repository: Arc<AccountRepository>,
+}
+
+#[async_trait]
+impl CommandHandler<OpenAccountCommand> for OpenAccountCommandHandler {
+    async fn handle(&self, command: OpenAccountCommand) -> Result<u64, AccountError> {
+        if command.owner.is_empty() {
+            return Err(AccountError::EmptyOwner);
+        }
+
+        Ok(self.repository.create(command.owner).id)
+    }
+}
@@ -0,0 +1,24 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+
+/// The errors of the `accounts` context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountError {
+    /// The owner of the account is empty.
+    EmptyOwner,
+    /// No account exists with the given identifier.
+    NotFound(u64),
+}
+
+impl Display for AccountError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::EmptyOwner => write!(f, "the owner of an account must not be empty"),
+            Self::NotFound(account_id) => write!(f, "account #{} does not exist", account_id),
+        }
+    }
+}
+
+impl Error for AccountError {}
@@ -0,0 +1,37 @@
+//! The `accounts` bounded context, which manages customer accounts.
+
+use std::sync::Arc;
+
+use discern::registry::CommandHandlerRegistry;
+use discern::registry::EventHandlerRegistry;
+use discern::registry::QueryHandlerRegistry;
+
+use self::commands::OpenAccountCommandHandler;
+use self::queries::GetAccountQueryHandler;
+use self::repository::AccountRepository;
+use self::subscribers::CountPlacedOrders;
+
+pub mod commands;
+pub mod errors;
+pub mod queries;
+pub mod repository;
+pub mod subscribers;
+
+/// Registers the handlers, and the subscribers, of the `accounts` context.
+pub fn register(
+    commands: &mut CommandHandlerRegistry,
+    queries: &mut QueryHandlerRegistry,
+    events: &mut EventHandlerRegistry,
+) {
+    let repository = Arc::new(AccountRepository::default());
+
+    commands.register(OpenAccountCommandHandler {
+        repository: repository.clone(),
+    });
+
+    queries.register(GetAccountQueryHandler {
+        repository: repository.clone(),
+    });
+
+    events.subscribe(CountPlacedOrders { repository });
+}
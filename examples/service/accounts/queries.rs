@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use discern::async_trait;
+use discern::query::Query;
+use discern::query::QueryHandler;
+
+use super::errors::AccountError;
+use super::repository::Account;
+use super::repository::AccountRepository;
+
+/// Returns the account with the given identifier.
+#[derive(Debug)]
+pub struct GetAccountQuery {
+    pub account_id: u64,
+}
+
+impl Query for GetAccountQuery {
+    type Output = Account;
+    type Error = AccountError;
+}
+
+pub struct GetAccountQueryHandler {
+    pub repository: Arc<AccountRepository>,
+}
+
+#[async_trait]
+impl QueryHandler<GetAccountQuery> for GetAccountQueryHandler {
+    async fn handle(&self, query: GetAccountQuery) -> Result<Account, AccountError> {
+        self.repository
+            .find(query.account_id)
+            .ok_or(AccountError::NotFound(query.account_id))
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// An account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub id: u64,
+    pub owner: String,
+    /// The number of orders placed by the account, maintained from the events of the `orders` context.
+    pub orders: u32,
+}
+
+/// An in-memory store of accounts.
+///
+/// A real service would use a database instead, the handlers only depend on the methods of the repository.
+#[derive(Debug, Default)]
+pub struct AccountRepository {
+    accounts: Mutex<HashMap<u64, Account>>,
+}
+
+impl AccountRepository {
+    /// Stores a new account, and returns it.
+    pub fn create(&self, owner: String) -> Account {
+        let mut accounts = self.accounts.lock().unwrap();
+
+        let account = Account {
+            id: accounts.len() as u64 + 1,
+            owner,
+            orders: 0,
+        };
+
+        accounts.insert(account.id, account.clone());
+
+        account
+    }
+
+    /// Increments the number of orders placed by the given account, if it exists.
+    pub fn record_order(&self, account_id: u64) {
+        if let Some(account) = self.accounts.lock().unwrap().get_mut(&account_id) {
+            account.orders += 1;
+        }
+    }
+
+    /// Returns the account with the given identifier, if any.
+    pub fn find(&self, account_id: u64) -> Option<Account> {
+        self.accounts.lock().unwrap().get(&account_id).cloned()
+    }
+}
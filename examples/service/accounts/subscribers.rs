@@ -0,0 +1,22 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use discern::async_trait;
+use discern::event::EventHandler;
+
+use super::repository::AccountRepository;
+use crate::orders::events::OrderPlacedEvent;
+
+/// Counts the orders placed by each account, reacting to the events of the `orders` context.
+pub struct CountPlacedOrders {
+    pub repository: Arc<AccountRepository>,
+}
+
+#[async_trait]
+impl EventHandler<OrderPlacedEvent> for CountPlacedOrders {
+    async fn handle(&self, event: &OrderPlacedEvent) -> Result<(), Infallible> {
+        self.repository.record_order(event.account_id);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+//! The HTTP adapter of the service, translating requests into commands, and queries.
+//!
+//! The adapter is the only place aware of HTTP, the contexts only know about their messages. It speaks just enough
+//! HTTP/1.1 for the example, handling one request per connection, with form encoded bodies, and JSON responses:
+//!
+//! - `POST /accounts` with `owner=...`: Opens an account, see [OpenAccountCommand].
+//! - `GET /accounts/{id}`: Returns an account, see [GetAccountQuery].
+//! - `POST /accounts/{id}/orders` with `product=...&quantity=...`: Places an order, see [PlaceOrderCommand].
+//! - `GET /accounts/{id}/orders`: Lists the orders of an account, see [ListOrdersQuery].
+//!
+//! A real service would use an HTTP framework instead, the routes would stay the same.
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use discern::error::DispatchError;
+use tokio::runtime::Handle;
+
+use crate::accounts::commands::OpenAccountCommand;
+use crate::accounts::errors::AccountError;
+use crate::accounts::queries::GetAccountQuery;
+use crate::orders::commands::PlaceOrderCommand;
+use crate::orders::errors::OrderError;
+use crate::orders::queries::ListOrdersQuery;
+use crate::Service;
+
+/// An HTTP request.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+/// An HTTP response, with a JSON body.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn new(status: u16, body: String) -> Self {
+        Self { status, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self::new(status, format!("{{\"error\":{:?}}}", message.to_string()))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            404 => "Not Found",
+            422 => "Unprocessable Entity",
+            _ => "Service Unavailable",
+        }
+    }
+}
+
+/// Accepts connections on the given listener, and answers their requests, one at a time.
+///
+/// The listener is blocking, so this function must run on a dedicated thread, the messages are dispatched on the
+/// given runtime.
+pub fn serve(listener: TcpListener, service: Arc<Service>, runtime: Handle) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+
+        let response = match read(&mut stream) {
+            Some(request) => runtime.block_on(route(&service, request)),
+            None => Response::error(400, "malformed request"),
+        };
+
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            response.reason(),
+            response.body.len(),
+            response.body
+        );
+    }
+}
+
+/// Routes a request to the bus handling it.
+pub async fn route(service: &Service, request: Request) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["accounts"]) => {
+            let command = OpenAccountCommand {
+                owner: field(&request.body, "owner").unwrap_or_default(),
+            };
+
+            match service.command_bus.dispatch(command).await {
+                Ok(account_id) => Response::new(201, format!("{{\"id\":{}}}", account_id)),
+                Err(error) => account_error(error),
+            }
+        }
+        ("GET", ["accounts", account_id]) => {
+            let Ok(account_id) = account_id.parse() else {
+                return Response::error(404, "not found");
+            };
+
+            match service
+                .query_bus
+                .dispatch(GetAccountQuery { account_id })
+                .await
+            {
+                Ok(account) => Response::new(
+                    200,
+                    format!(
+                        "{{\"id\":{},\"owner\":{:?},\"orders\":{}}}",
+                        account.id, account.owner, account.orders
+                    ),
+                ),
+                Err(error) => account_error(error),
+            }
+        }
+        ("POST", ["accounts", account_id, "orders"]) => {
+            let Ok(account_id) = account_id.parse() else {
+                return Response::error(404, "not found");
+            };
+
+            let Some(quantity) = field(&request.body, "quantity").and_then(|q| q.parse().ok())
+            else {
+                return Response::error(422, "the quantity must be a number");
+            };
+
+            let command = PlaceOrderCommand {
+                account_id,
+                product: field(&request.body, "product").unwrap_or_default(),
+                quantity,
+            };
+
+            match service.command_bus.dispatch(command).await {
+                Ok(order_id) => Response::new(201, format!("{{\"id\":{}}}", order_id)),
+                Err(DispatchError::Handler(error @ OrderError::UnknownAccount(_))) => {
+                    Response::error(404, error)
+                }
+                Err(DispatchError::Handler(error @ OrderError::EmptyOrder)) => {
+                    Response::error(422, error)
+                }
+                Err(error) => Response::error(503, error),
+            }
+        }
+        ("GET", ["accounts", account_id, "orders"]) => {
+            let Ok(account_id) = account_id.parse() else {
+                return Response::error(404, "not found");
+            };
+
+            match service
+                .query_bus
+                .dispatch(ListOrdersQuery { account_id })
+                .await
+            {
+                Ok(orders) => {
+                    let orders: Vec<String> = orders
+                        .iter()
+                        .map(|order| {
+                            format!(
+                                "{{\"id\":{},\"product\":{:?},\"quantity\":{}}}",
+                                order.id, order.product, order.quantity
+                            )
+                        })
+                        .collect();
+
+                    Response::new(200, format!("[{}]", orders.join(",")))
+                }
+                Err(error) => Response::error(503, error),
+            }
+        }
+        _ => Response::error(404, "not found"),
+    }
+}
+
+/// Maps the errors of the `accounts` context to responses.
+fn account_error(error: DispatchError<AccountError>) -> Response {
+    match error {
+        DispatchError::Handler(error @ AccountError::NotFound(_)) => Response::error(404, error),
+        DispatchError::Handler(error @ AccountError::EmptyOwner) => Response::error(422, error),
+        error => Response::error(503, error),
+    }
+}
+
+/// Returns the value of a field of a form encoded body.
+fn field(body: &str, name: &str) -> Option<String> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.replace('+', " "))
+}
+
+/// Reads a request from the given stream.
+fn read(stream: &mut TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok()?;
+            }
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        method,
+        path,
+        body: String::from_utf8(body).ok()?,
+    })
+}
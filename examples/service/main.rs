@@ -0,0 +1,119 @@
+//! An example service, showing the recommended layout of an application built with discern.
+//!
+//! Each bounded context lives in its own module, which defines its commands, queries, events, handlers, and
+//! storage, and exposes a `register` function adding its handlers to the registries. The application wires the
+//! contexts together into a single command bus, query bus, and event bus. Contexts never call each other directly,
+//! instead:
+//!
+//! - They dispatch messages through the buses, e.g. the `orders` context checks that an account exists using the
+//!   `GetAccountQuery` of the `accounts` context.
+//! - They react to the events of the other contexts, e.g. the `accounts` context counts the orders of each account
+//!   using the `OrderPlacedEvent` of the `orders` context, which is staged in an [outbox](crate::outbox) along with
+//!   the order, and relayed to the event bus in the background.
+//!
+//! The [HTTP adapter](crate::http) exposes the buses to clients, and the tests exercise the service through it.
+//!
+//! Run it using `cargo run --example service`, then, e.g.:
+//!
+//! ```text
+//! curl -d owner=alice http://127.0.0.1:3000/accounts
+//! curl -d 'product=keyboard&quantity=1' http://127.0.0.1:3000/accounts/1/orders
+//! curl http://127.0.0.1:3000/accounts/1/orders
+//! curl http://127.0.0.1:3000/accounts/1
+//! ```
+//!
+//! The address can be changed using the `SERVICE_ADDRESS` environment variable, and the tests run using
+//! `cargo test --example service`.
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+use discern::command::CommandBus;
+use discern::event::EventBus;
+use discern::query::QueryBus;
+use discern::registry::CommandHandlerRegistry;
+use discern::registry::EventHandlerRegistry;
+use discern::registry::QueryHandlerRegistry;
+use tokio::runtime::Handle;
+
+use crate::orders::events::OrderPlacedEvent;
+use crate::outbox::Outbox;
+
+mod accounts;
+mod http;
+mod orders;
+mod outbox;
+#[cfg(test)]
+mod tests;
+
+/// The buses of the application, with the handlers of every bounded context.
+struct Service {
+    command_bus: CommandBus,
+    query_bus: QueryBus,
+    event_bus: EventBus,
+    outbox: Arc<Outbox<OrderPlacedEvent>>,
+}
+
+impl Service {
+    /// Creates the buses, and registers the handlers of every bounded context.
+    fn new() -> Self {
+        let mut command_registry = CommandHandlerRegistry::new();
+        let mut query_registry = QueryHandlerRegistry::new();
+        let mut event_registry = EventHandlerRegistry::new();
+
+        let query_bus = QueryBus::new(QueryHandlerRegistry::new());
+        let outbox = Arc::new(Outbox::default());
+
+        accounts::register(
+            &mut command_registry,
+            &mut query_registry,
+            &mut event_registry,
+        );
+        orders::register(
+            &mut command_registry,
+            &mut query_registry,
+            query_bus.clone(),
+            outbox.clone(),
+        );
+
+        // The `orders` handlers hold a clone of the query bus, registering the handlers afterwards makes them
+        // visible to the clones as well.
+        query_bus.merge(query_registry);
+
+        Self {
+            command_bus: CommandBus::new(command_registry),
+            query_bus,
+            event_bus: EventBus::new(event_registry),
+            outbox,
+        }
+    }
+
+    /// Publishes the events staged in the outbox, and returns the number of events delivered.
+    async fn relay(&self) -> usize {
+        self.outbox.relay(&self.event_bus).await
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let service = Arc::new(Service::new());
+
+    let relay = service.clone();
+    tokio::spawn(async move {
+        loop {
+            relay.relay().await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+
+    let address = std::env::var("SERVICE_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let listener = TcpListener::bind(&address).expect("failed to bind the HTTP listener");
+
+    println!("listening on http://{}", address);
+
+    let runtime = Handle::current();
+    tokio::task::spawn_blocking(move || http::serve(listener, service, runtime))
+        .await
+        .expect("the HTTP adapter stopped");
+}
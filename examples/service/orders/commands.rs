@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use discern::async_trait;
+use discern::command::Command;
+use discern::command::CommandHandler;
+use discern::error::DispatchError;
+use discern::query::QueryBus;
+
+use super::errors::OrderError;
+use super::repository::OrderRepository;
+use crate::accounts::errors::AccountError;
+use crate::accounts::queries::GetAccountQuery;
+
+/// Places an order for the given account, and returns its identifier.
+#[derive(Debug)]
+pub struct PlaceOrderCommand {
+    pub account_id: u64,
+    pub product: String,
+    pub quantity: u32,
+}
+
+impl Command for PlaceOrderCommand {
+    type Metadata = u64;
+    type Error = OrderError;
+}
+
+pub struct PlaceOrderCommandHandler {
+    pub repository: Arc<OrderRepository>,
+    pub query_bus: QueryBus,
+}
+
+#[async_trait]
+impl CommandHandler<PlaceOrderCommand> for PlaceOrderCommandHandler {
+    async fn handle(&self, command: PlaceOrderCommand) -> Result<u64, OrderError> {
+        if command.quantity == 0 {
+            return Err(OrderError::EmptyOrder);
+        }
+
+        // The account is owned by the `accounts` context, which is queried through the bus.
+        let account = self
+            .query_bus
+            .dispatch(GetAccountQuery {
+                account_id: command.account_id,
+            })
+            .await
+            .map_err(|error| match error {
+                DispatchError::Handler(AccountError::NotFound(account_id)) => {
+                    OrderError::UnknownAccount(account_id)
+                }
+                error => unreachable!("unexpected error: {}", error),
+            })?;
+
+        Ok(self
+            .repository
+            .create(account.id, command.product, command.quantity))
+    }
+}
@@ -0,0 +1,26 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+
+/// The errors of the `orders` context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderError {
+    /// The quantity of the order is zero.
+    EmptyOrder,
+    /// No account exists with the given identifier.
+    UnknownAccount(u64),
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::EmptyOrder => write!(f, "an order must contain at least one item"),
+            Self::UnknownAccount(account_id) => {
+                write!(f, "account #{} does not exist", account_id)
+            }
+        }
+    }
+}
+
+impl Error for OrderError {}
@@ -0,0 +1,15 @@
+use std::convert::Infallible;
+
+use discern::event::Event;
+
+/// An order was placed, published through the outbox of the `orders` context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderPlacedEvent {
+    pub order_id: u64,
+    pub account_id: u64,
+    pub quantity: u32,
+}
+
+impl Event for OrderPlacedEvent {
+    type Error = Infallible;
+}
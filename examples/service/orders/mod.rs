@@ -0,0 +1,39 @@
+//! The `orders` bounded context, which manages the orders placed by customers.
+
+use std::sync::Arc;
+
+use discern::query::QueryBus;
+use discern::registry::CommandHandlerRegistry;
+use discern::registry::QueryHandlerRegistry;
+
+use self::commands::PlaceOrderCommandHandler;
+use self::events::OrderPlacedEvent;
+use self::queries::ListOrdersQueryHandler;
+use self::repository::OrderRepository;
+use crate::outbox::Outbox;
+
+pub mod commands;
+pub mod errors;
+pub mod events;
+pub mod queries;
+pub mod repository;
+
+/// Registers the handlers of the `orders` context.
+///
+/// The query bus is used to query the other contexts, and the events of the context are staged in the given
+/// outbox.
+pub fn register(
+    commands: &mut CommandHandlerRegistry,
+    queries: &mut QueryHandlerRegistry,
+    query_bus: QueryBus,
+    outbox: Arc<Outbox<OrderPlacedEvent>>,
+) {
+    let repository = Arc::new(OrderRepository::new(outbox));
+
+    commands.register(PlaceOrderCommandHandler {
+        repository: repository.clone(),
+        query_bus,
+    });
+
+    queries.register(ListOrdersQueryHandler { repository });
+}
@@ -0,0 +1,31 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use discern::async_trait;
+use discern::query::Query;
+use discern::query::QueryHandler;
+
+use super::repository::Order;
+use super::repository::OrderRepository;
+
+/// Returns the orders placed by the given account.
+#[derive(Debug)]
+pub struct ListOrdersQuery {
+    pub account_id: u64,
+}
+
+impl Query for ListOrdersQuery {
+    type Output = Vec<Order>;
+    type Error = Infallible;
+}
+
+pub struct ListOrdersQueryHandler {
+    pub repository: Arc<OrderRepository>,
+}
+
+#[async_trait]
+impl QueryHandler<ListOrdersQuery> for ListOrdersQueryHandler {
+    async fn handle(&self, query: ListOrdersQuery) -> Result<Vec<Order>, Infallible> {
+        Ok(self.repository.find_by_account(query.account_id))
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use super::events::OrderPlacedEvent;
+use crate::outbox::Outbox;
+
+/// An order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub id: u64,
+    pub account_id: u64,
+    pub product: String,
+    pub quantity: u32,
+}
+
+/// An in-memory store of orders.
+#[derive(Debug)]
+pub struct OrderRepository {
+    orders: Mutex<Vec<Order>>,
+    outbox: Arc<Outbox<OrderPlacedEvent>>,
+}
+
+impl OrderRepository {
+    /// Creates a new repository, staging its events in the given outbox.
+    pub fn new(outbox: Arc<Outbox<OrderPlacedEvent>>) -> Self {
+        Self {
+            orders: Mutex::new(Vec::new()),
+            outbox,
+        }
+    }
+
+    /// Stores a new order, stages an [OrderPlacedEvent], and returns the identifier of the order.
+    pub fn create(&self, account_id: u64, product: String, quantity: u32) -> u64 {
+        let mut orders = self.orders.lock().unwrap();
+
+        let id = orders.len() as u64 + 1;
+        orders.push(Order {
+            id,
+            account_id,
+            product,
+            quantity,
+        });
+
+        // The event is staged while the lock is held, so it is stored along with the order, or not at all.
+        self.outbox.stage(OrderPlacedEvent {
+            order_id: id,
+            account_id,
+            quantity,
+        });
+
+        id
+    }
+
+    /// Returns the orders placed by the given account.
+    pub fn find_by_account(&self, account_id: u64) -> Vec<Order> {
+        self.orders
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|order| order.account_id == account_id)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,58 @@
+//! A transactional outbox, relaying the events of a context to the event bus.
+//!
+//! Publishing an event right after storing a change loses the event if the service stops in between, and publishes
+//! events for changes that failed to be stored if done the other way around. Instead, repositories stage their
+//! events in an outbox, within the same transaction as the change itself, and a relay publishes the staged events
+//! afterwards, until they are delivered.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use discern::event::Event;
+use discern::event::EventBus;
+
+/// The events staged by a repository, waiting to be published.
+#[derive(Debug)]
+pub struct Outbox<E> {
+    pending: Mutex<VecDeque<E>>,
+}
+
+impl<E: Event + Clone> Outbox<E> {
+    /// Stages an event, to be published by the next relay.
+    ///
+    /// Repositories call this method while holding the lock guarding their change, which stands for the
+    /// transaction of a real database.
+    pub fn stage(&self, event: E) {
+        self.pending.lock().unwrap().push_back(event);
+    }
+
+    /// Publishes the staged events, in order, and returns the number of events delivered.
+    ///
+    /// The relay stops at the first event that is not delivered, which stays staged, and is published again by
+    /// the next relay, so subscribers must tolerate receiving an event more than once.
+    pub async fn relay(&self, event_bus: &EventBus) -> usize {
+        let mut delivered = 0;
+        loop {
+            let Some(event) = self.pending.lock().unwrap().front().cloned() else {
+                return delivered;
+            };
+
+            if let Err(error) = event_bus.publish(event).await {
+                eprintln!("failed to relay an event: {:?}", error);
+
+                return delivered;
+            }
+
+            self.pending.lock().unwrap().pop_front();
+            delivered += 1;
+        }
+    }
+}
+
+impl<E> Default for Outbox<E> {
+    fn default() -> Self {
+        Self {
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+}
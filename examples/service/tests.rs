@@ -0,0 +1,181 @@
+//! Tests exercising the service through its HTTP adapter, the way its clients use it.
+
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use tokio::runtime::Handle;
+
+use crate::http::route;
+use crate::http::serve;
+use crate::http::Request;
+use crate::http::Response;
+use crate::Service;
+
+/// Sends a request to the service, without going through a socket.
+async fn send(service: &Service, method: &str, path: &str, body: &str) -> Response {
+    let request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        body: body.to_string(),
+    };
+
+    route(service, request).await
+}
+
+#[tokio::test]
+async fn it_opens_accounts() {
+    let service = Service::new();
+
+    let response = send(&service, "POST", "/accounts", "owner=alice").await;
+    assert_eq!(
+        response,
+        Response {
+            status: 201,
+            body: r#"{"id":1}"#.to_string()
+        }
+    );
+
+    let response = send(&service, "GET", "/accounts/1", "").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, r#"{"id":1,"owner":"alice","orders":0}"#);
+
+    let response = send(&service, "POST", "/accounts", "owner=").await;
+    assert_eq!(response.status, 422);
+
+    let response = send(&service, "GET", "/accounts/2", "").await;
+    assert_eq!(response.status, 404);
+}
+
+#[tokio::test]
+async fn it_places_orders() {
+    let service = Service::new();
+    send(&service, "POST", "/accounts", "owner=alice").await;
+
+    let response = send(
+        &service,
+        "POST",
+        "/accounts/1/orders",
+        "product=keyboard&quantity=2",
+    )
+    .await;
+    assert_eq!(
+        response,
+        Response {
+            status: 201,
+            body: r#"{"id":1}"#.to_string()
+        }
+    );
+
+    let response = send(&service, "GET", "/accounts/1/orders", "").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body,
+        r#"[{"id":1,"product":"keyboard","quantity":2}]"#
+    );
+}
+
+#[tokio::test]
+async fn it_rejects_invalid_orders() {
+    let service = Service::new();
+    send(&service, "POST", "/accounts", "owner=alice").await;
+
+    let response = send(
+        &service,
+        "POST",
+        "/accounts/42/orders",
+        "product=mouse&quantity=1",
+    )
+    .await;
+    assert_eq!(response.status, 404);
+    assert_eq!(response.body, r#"{"error":"account #42 does not exist"}"#);
+
+    let response = send(
+        &service,
+        "POST",
+        "/accounts/1/orders",
+        "product=mouse&quantity=0",
+    )
+    .await;
+    assert_eq!(response.status, 422);
+
+    let response = send(&service, "POST", "/accounts/1/orders", "product=mouse").await;
+    assert_eq!(response.status, 422);
+
+    let response = send(&service, "GET", "/accounts/1/orders", "").await;
+    assert_eq!(response.body, "[]");
+}
+
+#[tokio::test]
+async fn it_relays_staged_events() {
+    let service = Service::new();
+    send(&service, "POST", "/accounts", "owner=alice").await;
+    send(
+        &service,
+        "POST",
+        "/accounts/1/orders",
+        "product=keyboard&quantity=1",
+    )
+    .await;
+    send(
+        &service,
+        "POST",
+        "/accounts/1/orders",
+        "product=mouse&quantity=2",
+    )
+    .await;
+
+    // The events are staged along with the orders, but not published yet.
+    let response = send(&service, "GET", "/accounts/1", "").await;
+    assert_eq!(response.body, r#"{"id":1,"owner":"alice","orders":0}"#);
+
+    assert_eq!(service.relay().await, 2);
+    assert_eq!(service.relay().await, 0);
+
+    let response = send(&service, "GET", "/accounts/1", "").await;
+    assert_eq!(response.body, r#"{"id":1,"owner":"alice","orders":2}"#);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn it_serves_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let service = Arc::new(Service::new());
+    let runtime = Handle::current();
+    std::thread::spawn(move || serve(listener, service, runtime));
+
+    let response = tokio::task::spawn_blocking(move || {
+        request(
+            address,
+            "POST /accounts HTTP/1.1\r\nContent-Length: 11\r\n\r\nowner=alice",
+        )
+    })
+    .await
+    .unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 201 Created"));
+    assert!(response.ends_with(r#"{"id":1}"#));
+
+    let response =
+        tokio::task::spawn_blocking(move || request(address, "GET /accounts/1 HTTP/1.1\r\n\r\n"))
+            .await
+            .unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with(r#"{"id":1,"owner":"alice","orders":0}"#));
+}
+
+/// Sends a raw request over a socket, and returns the raw response.
+fn request(address: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
@@ -0,0 +1,338 @@
+//! The `accounting` module provides resource accounting for handlers.
+//!
+//! Latency alone doesn't tell which messages are expensive to handle: a handler waiting on the network for a
+//! second costs almost nothing, while one serializing a large response for a millisecond can dominate the CPU
+//! usage of a service. A [ResourceLedger] records the time each handler spends being polled, and, when the
+//! [CountingAllocator] is installed, the memory it allocates, per message type.
+//!
+//! - [ResourceLedger]: Records the resources used by handlers, per message type.
+//! - [AccountedHandler]: A handler whose resource usage is recorded in a [ResourceLedger].
+//! - [ResourceUsage]: The resources used by the handlers of a message type.
+//! - [CountingAllocator]: A global allocator counting the memory allocated by each thread.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+
+thread_local! {
+    /// The number of bytes allocated by the current thread, maintained by the [CountingAllocator].
+    static ALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns the number of bytes allocated by the current thread so far.
+fn allocated() -> u64 {
+    ALLOCATED.try_with(Cell::get).unwrap_or(0)
+}
+
+/// A global allocator counting the memory allocated by each thread.
+///
+/// The [ResourceLedger] only records the memory allocated by handlers when this allocator is installed as the
+/// global allocator, otherwise the allocated memory is always reported as zero. The allocations are forwarded to
+/// the wrapped allocator, the system allocator by default.
+///
+/// # Example
+///
+/// ```
+/// use discern::accounting::CountingAllocator;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+/// #
+/// # fn main() {}
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A = System> {
+    #[doc(hidden)]
+    allocator: A,
+}
+
+/// The `CountingAllocator` implementation for the system allocator.
+impl CountingAllocator<System> {
+    /// Creates a new `CountingAllocator`, wrapping the system allocator.
+    pub const fn system() -> Self {
+        Self { allocator: System }
+    }
+}
+
+/// The `CountingAllocator` implementation.
+impl<A> CountingAllocator<A> {
+    /// Creates a new `CountingAllocator`, wrapping the given allocator.
+    pub const fn new(allocator: A) -> Self {
+        Self { allocator }
+    }
+
+    /// Counts the given number of bytes as allocated by the current thread.
+    fn count(size: usize) {
+        // The counter doesn't need a destructor, so this only fails once the thread is being torn down.
+        let _ = ALLOCATED
+            .try_with(|allocated| allocated.set(allocated.get().wrapping_add(size as u64)));
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+
+        self.allocator.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+
+        self.allocator.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.allocator.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count(new_size.saturating_sub(layout.size()));
+
+        self.allocator.realloc(ptr, layout, new_size)
+    }
+}
+
+/// The resources used by the handlers of a message type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// The name of the message, as returned by [Command::name] or [Query::name].
+    pub message: &'static str,
+    /// The number of messages handled.
+    pub executions: u64,
+    /// The total time the handlers spent being polled, an approximation of the CPU time they used.
+    pub busy: Duration,
+    /// The total time between the start, and the completion, of the handlers, including the time spent waiting.
+    pub elapsed: Duration,
+    /// The total number of bytes allocated while polling the handlers, only recorded when the
+    /// [CountingAllocator] is installed.
+    pub allocated: u64,
+}
+
+/// The `ResourceUsage` implementation.
+impl ResourceUsage {
+    /// Creates an empty `ResourceUsage` for the given message type.
+    fn new(message: &'static str) -> Self {
+        Self {
+            message,
+            executions: 0,
+            busy: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            allocated: 0,
+        }
+    }
+}
+
+/// Records the resources used by handlers, per message type.
+///
+/// Handlers are wrapped using [ResourceLedger::track], and the ledger is shared between every wrapped handler.
+/// Only the work performed while polling the handler is accounted for, the work of tasks it spawns is not.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct ExportReportCommand {
+/// #     rows: usize,
+/// # }
+/// #
+/// # impl Command for ExportReportCommand {
+/// #     type Metadata = String;
+/// #     type Error = std::io::Error;
+/// # }
+/// use std::sync::Arc;
+///
+/// use discern::accounting::ResourceLedger;
+/// use discern::async_trait;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+///
+/// struct ExportReportCommandHandler;
+///
+/// #[async_trait]
+/// impl CommandHandler<ExportReportCommand> for ExportReportCommandHandler {
+///     async fn handle(&self, command: ExportReportCommand) -> Result<String, std::io::Error> {
+///         Ok((0..command.rows).map(|row| format!("{}\n", row)).collect())
+///     }
+/// }
+///
+/// let ledger = Arc::new(ResourceLedger::new());
+///
+/// let command_bus = command_bus! {
+///     ExportReportCommand => ledger.track(ExportReportCommandHandler),
+/// };
+///
+/// command_bus.dispatch(ExportReportCommand { rows: 1000 }).await.unwrap();
+///
+/// let usage = ledger.usage_of(ExportReportCommand::name()).unwrap();
+///
+/// assert_eq!(usage.executions, 1);
+/// assert!(usage.busy <= usage.elapsed);
+/// # });
+/// ```
+#[derive(Default)]
+pub struct ResourceLedger {
+    #[doc(hidden)]
+    usage: Mutex<HashMap<&'static str, ResourceUsage>>,
+}
+
+/// The `ResourceLedger` implementation.
+impl ResourceLedger {
+    /// Creates a new, empty, `ResourceLedger`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the given handler, recording its resource usage in this ledger.
+    pub fn track<H>(self: &Arc<Self>, handler: H) -> AccountedHandler<H> {
+        AccountedHandler {
+            handler,
+            ledger: self.clone(),
+        }
+    }
+
+    /// Returns the resources used by the handlers of every message type, the most busy first.
+    pub fn usage(&self) -> Vec<ResourceUsage> {
+        let mut usage: Vec<ResourceUsage> = self
+            .usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect();
+
+        usage.sort_by(|a, b| b.busy.cmp(&a.busy).then(a.message.cmp(b.message)));
+
+        usage
+    }
+
+    /// Returns the resources used by the handlers of the given message type, if any was handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The name of the message type, as returned by [Command::name] or [Query::name].
+    pub fn usage_of(&self, message: &str) -> Option<ResourceUsage> {
+        self.usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(message)
+            .cloned()
+    }
+
+    /// Removes the recorded usage of every message type.
+    pub fn reset(&self) {
+        self.usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Polls the given future to completion, recording the resources it used for the given message type.
+    async fn account<F: Future>(&self, message: &'static str, future: F) -> F::Output {
+        let start = Instant::now();
+        let mut busy = Duration::ZERO;
+        let mut allocated = 0u64;
+        let mut future = pin!(future);
+
+        let output = std::future::poll_fn(|cx| {
+            let polled_at = Instant::now();
+            let allocated_before = self::allocated();
+
+            let poll = future.as_mut().poll(cx);
+
+            busy += polled_at.elapsed();
+            allocated += self::allocated().wrapping_sub(allocated_before);
+
+            poll
+        })
+        .await;
+
+        let mut usage = self.usage.lock().unwrap_or_else(PoisonError::into_inner);
+        let usage = usage
+            .entry(message)
+            .or_insert_with(|| ResourceUsage::new(message));
+
+        usage.executions += 1;
+        usage.busy += busy;
+        usage.elapsed += start.elapsed();
+        usage.allocated += allocated;
+
+        output
+    }
+}
+
+/// Debug implementation for `ResourceLedger`
+impl Debug for ResourceLedger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("ResourceLedger")
+            .field("usage", &self.usage())
+            .finish()
+    }
+}
+
+/// A handler whose resource usage is recorded in a [ResourceLedger].
+///
+/// `AccountedHandler` is created using [ResourceLedger::track].
+pub struct AccountedHandler<H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    ledger: Arc<ResourceLedger>,
+}
+
+#[async_trait]
+impl<C, H> CommandHandler<C> for AccountedHandler<H>
+where
+    C: Command,
+    H: CommandHandler<C>,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        self.ledger
+            .account(C::name(), CommandHandler::handle(&self.handler, command))
+            .await
+    }
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for AccountedHandler<H>
+where
+    Q: Query,
+    H: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        self.ledger
+            .account(Q::name(), QueryHandler::handle(&self.handler, query))
+            .await
+    }
+}
+
+/// Debug implementation for `AccountedHandler`
+impl<H> Debug for AccountedHandler<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("AccountedHandler")
+            .field("ledger", &self.ledger)
+            .finish()
+    }
+}
@@ -0,0 +1,365 @@
+//! The `admin` module provides built-in administrative commands and queries ( requires the `admin` feature ).
+//!
+//! Operational tooling usually needs to inspect, and control, a running bus. Instead of exposing a separate API,
+//! this module provides commands and queries whose handlers are wired to the control APIs of the buses they are
+//! registered in, so that the buses can be managed through themselves, e.g. from a remote admin endpoint that
+//! already dispatches messages.
+//!
+//! - [register]: Registers the administrative handlers.
+//! - [GetBusStats]: Returns the counters of both buses.
+//! - [ListHandlers]: Returns the registered command and query types.
+//! - [DisableCommand], [EnableCommand]: Disable, or re-enable, a command type by name.
+//! - [DrainBus], [ResumeBus]: Enter, or exit, maintenance mode.
+//!
+//! The administrative commands are not affected by maintenance mode, but they can be disabled individually.
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandBus;
+use crate::command::CommandHandler;
+use crate::command::WeakCommandBus;
+use crate::diagnostics::MissingHandler;
+use crate::query::Query;
+use crate::query::QueryBus;
+use crate::query::QueryHandler;
+use crate::query::WeakQueryBus;
+use crate::runtime::Runtime;
+use crate::stats::BusStats;
+
+/// The interval at which [DrainBus] checks whether the in-flight commands completed.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Registers the administrative handlers.
+///
+/// The commands are registered in the `command_bus`, and the queries in the `query_bus`. The handlers only hold
+/// weak references to the buses, so they don't keep them alive.
+///
+/// # Arguments
+///
+/// * `command_bus` - The command bus to manage.
+/// * `query_bus` - The query bus to manage.
+/// * `runtime` - The runtime used by [DrainBus] to wait for in-flight commands.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::async_trait;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct DeleteUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<DeleteUserCommand> for DeleteUserCommandHandler {
+/// #    async fn handle(&self, command: DeleteUserCommand) -> Result<(), std::io::Error> {
+/// #       Ok(())
+/// #   }
+/// # }
+/// use discern::admin;
+/// use discern::admin::DisableCommand;
+/// use discern::admin::DrainBus;
+/// use discern::admin::GetBusStats;
+/// use discern::admin::ListHandlers;
+/// use discern::admin::ResumeBus;
+/// use discern::command_bus;
+/// use discern::error::DispatchError;
+/// use discern::query_bus;
+/// use discern::runtime::TokioRuntime;
+///
+/// let command_bus = command_bus! {
+///    DeleteUserCommand => DeleteUserCommandHandler { /* ... */ },
+/// };
+/// let query_bus = query_bus! {};
+///
+/// admin::register(&command_bus, &query_bus, TokioRuntime);
+///
+/// let handlers = query_bus.dispatch(ListHandlers).await.unwrap();
+/// assert!(handlers.commands.iter().any(|name| name.ends_with("DeleteUserCommand")));
+///
+/// // Stop the write side, waiting for in-flight commands to complete.
+/// command_bus.dispatch(DrainBus).await.unwrap();
+///
+/// let result = command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await;
+/// assert!(matches!(result, Err(DispatchError::Disabled)));
+///
+/// command_bus.dispatch(ResumeBus).await.unwrap();
+///
+/// // Disable a single command type, by name.
+/// let name = handlers.commands.iter().find(|name| name.ends_with("DeleteUserCommand")).unwrap();
+/// command_bus.dispatch(DisableCommand { name: name.to_string() }).await.unwrap();
+///
+/// let result = command_bus.try_dispatch(DeleteUserCommand { user_id: 1 }).await;
+/// assert!(matches!(result, Err(DispatchError::Disabled)));
+///
+/// let stats = query_bus.dispatch(GetBusStats).await.unwrap();
+/// assert_eq!(stats.commands.dispatched, 3);
+/// # });
+/// ```
+pub fn register(command_bus: &CommandBus, query_bus: &QueryBus, runtime: impl Runtime) {
+    let handler = AdminHandler {
+        command_bus: command_bus.downgrade(),
+        query_bus: query_bus.downgrade(),
+        runtime: Arc::new(runtime),
+        draining: Arc::new(AtomicUsize::new(0)),
+    };
+
+    let switchboard = command_bus.switchboard();
+    switchboard.exempt::<DisableCommand>();
+    switchboard.exempt::<EnableCommand>();
+    switchboard.exempt::<DrainBus>();
+    switchboard.exempt::<ResumeBus>();
+
+    command_bus.register::<DisableCommand>(handler.clone());
+    command_bus.register::<EnableCommand>(handler.clone());
+    command_bus.register::<DrainBus>(handler.clone());
+    command_bus.register::<ResumeBus>(handler.clone());
+    query_bus.register::<GetBusStats>(handler.clone());
+    query_bus.register::<ListHandlers>(handler);
+}
+
+/// A query returning the counters of the command bus, and the query bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GetBusStats;
+
+/// The counters returned by [GetBusStats].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AdminStats {
+    /// The counters of the command bus.
+    pub commands: BusStats,
+    /// The counters of the query bus.
+    pub queries: BusStats,
+}
+
+impl Query for GetBusStats {
+    type Output = AdminStats;
+    type Error = Infallible;
+}
+
+/// A query returning the names of the commands, and queries, that have a registered handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListHandlers;
+
+/// The handlers returned by [ListHandlers].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct HandlerList {
+    /// The names of the commands that have a registered handler.
+    pub commands: Vec<&'static str>,
+    /// The names of the queries that have a registered handler.
+    pub queries: Vec<&'static str>,
+}
+
+impl Query for ListHandlers {
+    type Output = HandlerList;
+    type Error = Infallible;
+}
+
+/// A command disabling a command type, by its name, as returned by [ListHandlers].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DisableCommand {
+    /// The name of the command to disable.
+    pub name: String,
+}
+
+impl Command for DisableCommand {
+    type Metadata = ();
+    type Error = AdminError;
+}
+
+/// A command re-enabling a command type disabled using [DisableCommand].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EnableCommand {
+    /// The name of the command to enable.
+    pub name: String,
+}
+
+impl Command for EnableCommand {
+    type Metadata = ();
+    type Error = AdminError;
+}
+
+/// A command putting the command bus into maintenance mode, and waiting for the in-flight commands to complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DrainBus;
+
+impl Command for DrainBus {
+    type Metadata = ();
+    type Error = Infallible;
+}
+
+/// A command taking the command bus out of maintenance mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResumeBus;
+
+impl Command for ResumeBus {
+    type Metadata = ();
+    type Error = Infallible;
+}
+
+/// The error returned by the administrative commands.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AdminError {
+    /// No handler is registered for a command with the given name.
+    UnknownCommand(MissingHandler),
+}
+
+impl Display for AdminError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::UnknownCommand(missing) => Display::fmt(missing, f),
+        }
+    }
+}
+
+impl Error for AdminError {}
+
+/// The handler of the administrative commands, and queries.
+#[doc(hidden)]
+#[derive(Clone)]
+struct AdminHandler {
+    command_bus: WeakCommandBus,
+    query_bus: WeakQueryBus,
+    runtime: Arc<dyn Runtime>,
+    draining: Arc<AtomicUsize>,
+}
+
+impl AdminHandler {
+    fn command_bus(&self) -> Option<CommandBus> {
+        self.command_bus.upgrade()
+    }
+}
+
+#[async_trait]
+impl QueryHandler<GetBusStats> for AdminHandler {
+    async fn handle(&self, _query: GetBusStats) -> Result<AdminStats, Infallible> {
+        Ok(AdminStats {
+            commands: self
+                .command_bus()
+                .map(|bus| bus.stats())
+                .unwrap_or_default(),
+            queries: self
+                .query_bus
+                .upgrade()
+                .map(|bus| bus.stats())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[async_trait]
+impl QueryHandler<ListHandlers> for AdminHandler {
+    async fn handle(&self, _query: ListHandlers) -> Result<HandlerList, Infallible> {
+        Ok(HandlerList {
+            commands: self
+                .command_bus()
+                .map(|bus| bus.handlers())
+                .unwrap_or_default(),
+            queries: self
+                .query_bus
+                .upgrade()
+                .map(|bus| bus.handlers())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[async_trait]
+impl CommandHandler<DisableCommand> for AdminHandler {
+    async fn handle(&self, command: DisableCommand) -> Result<(), AdminError> {
+        let Some(bus) = self.command_bus() else {
+            return Ok(());
+        };
+
+        let (id, name) = bus
+            .resolve(&command.name)
+            .ok_or_else(|| AdminError::UnknownCommand(bus.missing_named(command.name)))?;
+        bus.switchboard().disable_id(id, name);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<EnableCommand> for AdminHandler {
+    async fn handle(&self, command: EnableCommand) -> Result<(), AdminError> {
+        let Some(bus) = self.command_bus() else {
+            return Ok(());
+        };
+
+        let (id, _) = bus
+            .resolve(&command.name)
+            .ok_or_else(|| AdminError::UnknownCommand(bus.missing_named(command.name)))?;
+        bus.switchboard().enable_id(id);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<DrainBus> for AdminHandler {
+    async fn handle(&self, _command: DrainBus) -> Result<(), Infallible> {
+        let Some(bus) = self.command_bus() else {
+            return Ok(());
+        };
+
+        bus.switchboard().enter_maintenance();
+
+        // The drain commands themselves are in flight, and must not be waited for.
+        let _draining = Draining::new(&self.draining);
+        while bus.stats().in_flight > self.draining.load(Ordering::SeqCst) {
+            self.runtime.sleep(DRAIN_INTERVAL).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandHandler<ResumeBus> for AdminHandler {
+    async fn handle(&self, _command: ResumeBus) -> Result<(), Infallible> {
+        if let Some(bus) = self.command_bus() {
+            bus.switchboard().exit_maintenance();
+        }
+
+        Ok(())
+    }
+}
+
+/// A guard counting a [DrainBus] command as draining, until dropped.
+#[doc(hidden)]
+struct Draining<'a>(&'a AtomicUsize);
+
+impl<'a> Draining<'a> {
+    fn new(draining: &'a AtomicUsize) -> Self {
+        draining.fetch_add(1, Ordering::SeqCst);
+
+        Self(draining)
+    }
+}
+
+impl Drop for Draining<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,164 @@
+//! The `authorization` module provides declarative permission requirements on commands.
+//!
+//! Checking permissions inside every handler scatters the security policy of an application across its code base,
+//! and makes it hard to review. Instead, commands declare the permissions they require by implementing
+//! [RequiredPermissions], usually using `#[command(requires = "...")]`, next to their definition, and the
+//! [AuthorizationMiddleware] checks them, for every command dispatched through a
+//! [CommandBus](crate::command::CommandBus), before its handler is invoked.
+//!
+//! - [RequiredPermissions]: Declares the permissions required to dispatch a command.
+//! - [AuthorizationMiddleware]: A middleware rejecting commands whose required permissions are not granted.
+
+use alloc::boxed::Box;
+use alloc::format;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+use crate::async_trait;
+use crate::context::Context;
+use crate::middleware::CommandMiddleware;
+use crate::middleware::Next;
+use crate::middleware::Outcome;
+
+/// The `RequiredPermissions` trait declares the permissions required to dispatch a command.
+///
+/// The `Command` derive macro implements this trait when given the `requires` argument, e.g.
+/// `#[command(requires = "users:write")]`, which can be repeated. Commands implementing `RequiredPermissions`
+/// manually should also return [RequiredPermissions::PERMISSIONS] from their
+/// [required_permissions](crate::command::Command::required_permissions) function, which is what the
+/// [AuthorizationMiddleware] consults.
+///
+/// # Example
+///
+/// ```
+/// use discern::authorization::RequiredPermissions;
+/// use discern::command::Command;
+///
+/// #[derive(Debug)]
+/// struct DeleteUserCommand {
+///     user_id: u64,
+/// }
+///
+/// impl RequiredPermissions for DeleteUserCommand {
+///     const PERMISSIONS: &'static [&'static str] = &["users:write", "users:delete"];
+/// }
+///
+/// impl Command for DeleteUserCommand {
+///     type Metadata = ();
+///     type Error = std::io::Error;
+///
+///     fn required_permissions() -> &'static [&'static str] {
+///         Self::PERMISSIONS
+///     }
+/// }
+///
+/// assert_eq!(DeleteUserCommand::required_permissions(), ["users:write", "users:delete"]);
+/// ```
+pub trait RequiredPermissions {
+    /// The permissions required to dispatch the command, all of which must be granted.
+    const PERMISSIONS: &'static [&'static str];
+}
+
+/// A middleware rejecting commands whose required permissions are not granted, see [RequiredPermissions].
+///
+/// Each permission required by a command is checked using the given function, along with the context of the
+/// dispatch, which usually holds the authenticated principal, attached by the caller using
+/// [CommandBus::dispatch_with](crate::command::CommandBus::dispatch_with), or by a middleware running before this
+/// one. Commands missing any of their permissions are rejected with
+/// [DispatchError::Rejected](crate::error::DispatchError::Rejected), without being handled. Commands requiring no
+/// permissions are always handled.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl RequiredPermissions for DeleteUserCommand {
+/// #     const PERMISSIONS: &'static [&'static str] = &["users:write"];
+/// # }
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// #
+/// #     fn required_permissions() -> &'static [&'static str] {
+/// #         Self::PERMISSIONS
+/// #     }
+/// # }
+/// use discern::authorization::AuthorizationMiddleware;
+/// use discern::authorization::RequiredPermissions;
+/// use discern::command_bus;
+/// use discern::context::Context;
+/// use discern::error::DispatchError;
+///
+/// struct Grants(Vec<&'static str>);
+///
+/// let command_bus = command_bus! {
+///     middleware: [AuthorizationMiddleware::new(|context: &Context, permission: &str| {
+///         context.get::<Grants>().is_some_and(|grants| grants.0.contains(&permission))
+///     })],
+///     DeleteUserCommand => |_| async { Ok(()) },
+/// };
+///
+/// let context = Context::new().with(Grants(vec!["users:read"]));
+/// let result = command_bus.dispatch_with(DeleteUserCommand { user_id: 1 }, context).await;
+/// assert!(matches!(result, Err(DispatchError::Rejected(_))));
+///
+/// let context = Context::new().with(Grants(vec!["users:read", "users:write"]));
+/// let result = command_bus.dispatch_with(DeleteUserCommand { user_id: 1 }, context).await;
+/// assert!(result.is_ok());
+/// # });
+/// ```
+pub struct AuthorizationMiddleware<F> {
+    #[doc(hidden)]
+    check: F,
+}
+
+/// The `AuthorizationMiddleware` implementation.
+impl<F> AuthorizationMiddleware<F>
+where
+    F: Fn(&Context, &str) -> bool + Send + Sync,
+{
+    /// Creates a new `AuthorizationMiddleware`.
+    ///
+    /// # Arguments
+    ///
+    /// * `check` - A function returning whether the given permission is granted, within the given context.
+    pub fn new(check: F) -> Self {
+        Self { check }
+    }
+}
+
+#[async_trait]
+impl<F> CommandMiddleware for AuthorizationMiddleware<F>
+where
+    F: Fn(&Context, &str) -> bool + Send + Sync,
+{
+    async fn handle(&self, next: Next<'_>) -> Outcome {
+        let missing = next
+            .required_permissions()
+            .iter()
+            .find(|permission| !(self.check)(next.context(), permission));
+
+        if let Some(permission) = missing {
+            return Outcome::Rejected(format!("missing the {:?} permission", permission));
+        }
+
+        next.run().await
+    }
+}
+
+/// Debug implementation for `AuthorizationMiddleware`
+impl<F> Debug for AuthorizationMiddleware<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("AuthorizationMiddleware").finish()
+    }
+}
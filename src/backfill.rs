@@ -0,0 +1,337 @@
+//! The `backfill` module provides a framework for migrations, and backfills, over keyed ranges.
+//!
+//! Backfills, e.g. recomputing a field for every user, are usually expressed as a command handling a range of keys.
+//! A [Backfill] splits a range of keys into chunks, dispatches a command per chunk, persists its progress so that it
+//! can resume after a restart, can be paused and resumed at runtime, and throttles itself against the load of the bus.
+//!
+//! - [Backfill]: Dispatches a command per chunk of a keyed range.
+//! - [BackfillControl]: Pauses, and resumes, a running backfill, and reports its progress.
+//! - [BackfillProgress]: The progress of a backfill.
+//! - [BackfillError]: The error returned when a backfill fails.
+
+use std::error::Error;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bulk::Checkpoint;
+use crate::command::Command;
+use crate::command::CommandBus;
+use crate::error::DispatchError;
+use crate::runtime::Runtime;
+
+/// The interval at which a paused, or throttled, backfill checks whether it can continue.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Dispatches a command per chunk of a keyed range.
+///
+/// Chunks are dispatched one at a time, in order, and the progress is saved to the [Checkpoint], if any, after every
+/// chunk. When a chunk fails, the backfill stops, and returns the error; running it again with the same checkpoint
+/// resumes from the failed chunk.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::async_trait;
+/// # use discern::command::CommandHandler;
+/// #
+/// # struct RecomputeUserScoresCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<RecomputeUserScoresCommand> for RecomputeUserScoresCommandHandler {
+/// #    async fn handle(&self, command: RecomputeUserScoresCommand) -> Result<(), std::io::Error> {
+/// #       Ok(())
+/// #   }
+/// # }
+/// use std::ops::Range;
+///
+/// use discern::backfill::Backfill;
+/// use discern::command_bus;
+/// use discern::runtime::TokioRuntime;
+///
+/// #[derive(Debug)]
+/// struct RecomputeUserScoresCommand {
+///     user_ids: Range<u64>,
+/// }
+///
+/// impl Command for RecomputeUserScoresCommand {
+///     type Metadata = ();
+///     type Error = std::io::Error;
+/// }
+///
+/// let command_bus = command_bus! {
+///     RecomputeUserScoresCommand => RecomputeUserScoresCommandHandler,
+/// };
+///
+/// let backfill = Backfill::new(command_bus, TokioRuntime, 0..10_000, 1_000, |user_ids| {
+///     RecomputeUserScoresCommand { user_ids }
+/// })
+/// // Wait while the bus is busy with 32 commands or more.
+/// .with_max_in_flight(32);
+///
+/// let control = backfill.control();
+///
+/// backfill.run().await.unwrap();
+///
+/// assert_eq!(control.progress().completed(), 10_000);
+/// # });
+/// ```
+pub struct Backfill<C> {
+    #[doc(hidden)]
+    command_bus: CommandBus,
+    #[doc(hidden)]
+    runtime: Box<dyn Runtime>,
+    #[doc(hidden)]
+    range: Range<u64>,
+    #[doc(hidden)]
+    chunk_size: u64,
+    #[doc(hidden)]
+    command: Box<dyn Fn(Range<u64>) -> C + Send + Sync>,
+    #[doc(hidden)]
+    checkpoint: Option<Box<dyn Checkpoint>>,
+    #[doc(hidden)]
+    max_in_flight: Option<usize>,
+    #[doc(hidden)]
+    control: BackfillControl,
+}
+
+/// The `Backfill` implementation.
+impl<C: Command> Backfill<C> {
+    /// Creates a new `Backfill`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_bus` - The command bus to dispatch the commands to.
+    /// * `runtime` - The runtime used to wait while the backfill is paused, or throttled.
+    /// * `range` - The range of keys to backfill.
+    /// * `chunk_size` - The number of keys per chunk.
+    /// * `command` - A function creating the command handling a chunk of keys.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `chunk_size` is zero.
+    pub fn new(
+        command_bus: CommandBus,
+        runtime: impl Runtime,
+        range: Range<u64>,
+        chunk_size: u64,
+        command: impl Fn(Range<u64>) -> C + Send + Sync + 'static,
+    ) -> Self {
+        assert!(chunk_size > 0, "the chunk size must be greater than zero");
+
+        Self {
+            command_bus,
+            runtime: Box::new(runtime),
+            control: BackfillControl {
+                state: Arc::new(ControlState {
+                    paused: AtomicBool::new(false),
+                    position: AtomicU64::new(range.start),
+                    start: range.start,
+                    end: range.end,
+                }),
+            },
+            range,
+            chunk_size,
+            command: Box::new(command),
+            checkpoint: None,
+            max_in_flight: None,
+        }
+    }
+
+    /// Records the progress of the backfill to the given checkpoint, and resumes from it.
+    pub fn with_checkpoint(mut self, checkpoint: impl Checkpoint + 'static) -> Self {
+        self.checkpoint = Some(Box::new(checkpoint));
+        self
+    }
+
+    /// Waits before dispatching a chunk while the bus has at least `max_in_flight` commands in flight.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    /// Returns a handle to pause, and resume, this backfill, and to follow its progress.
+    pub fn control(&self) -> BackfillControl {
+        self.control.clone()
+    }
+
+    /// Runs the backfill until all chunks were dispatched, or a chunk failed.
+    pub async fn run(&self) -> Result<BackfillProgress, BackfillError<C::Error>> {
+        let mut position = match &self.checkpoint {
+            Some(checkpoint) => checkpoint
+                .load()
+                .map_err(BackfillError::Checkpoint)?
+                .map_or(self.range.start, |position| position.max(self.range.start)),
+            None => self.range.start,
+        };
+
+        self.control
+            .state
+            .position
+            .store(position, Ordering::SeqCst);
+
+        while position < self.range.end {
+            self.wait().await;
+
+            let chunk = position..position.saturating_add(self.chunk_size).min(self.range.end);
+            if let Err(error) = self
+                .command_bus
+                .try_dispatch((self.command)(chunk.clone()))
+                .await
+            {
+                return Err(BackfillError::Chunk { chunk, error });
+            }
+
+            position = chunk.end;
+            self.control
+                .state
+                .position
+                .store(position, Ordering::SeqCst);
+
+            if let Some(checkpoint) = &self.checkpoint {
+                checkpoint
+                    .save(position)
+                    .map_err(BackfillError::Checkpoint)?;
+            }
+        }
+
+        Ok(self.control.progress())
+    }
+
+    /// Waits while the backfill is paused, or the bus is busy.
+    async fn wait(&self) {
+        loop {
+            let busy = self
+                .max_in_flight
+                .is_some_and(|max_in_flight| self.command_bus.stats().in_flight >= max_in_flight);
+
+            if !busy && !self.control.is_paused() {
+                return;
+            }
+
+            self.runtime.sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Debug implementation for `Backfill`
+impl<C> Debug for Backfill<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Backfill")
+            .field("range", &self.range)
+            .field("chunk_size", &self.chunk_size)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("control", &self.control)
+            .finish()
+    }
+}
+
+/// Pauses, and resumes, a running [Backfill], and reports its progress.
+///
+/// A paused backfill completes the chunk it is dispatching, and waits before dispatching the next one.
+#[derive(Debug, Clone)]
+pub struct BackfillControl {
+    #[doc(hidden)]
+    state: Arc<ControlState>,
+}
+
+/// The state shared between a [Backfill], and its controls.
+#[doc(hidden)]
+#[derive(Debug)]
+struct ControlState {
+    paused: AtomicBool,
+    position: AtomicU64,
+    start: u64,
+    end: u64,
+}
+
+/// The `BackfillControl` implementation.
+impl BackfillControl {
+    /// Pauses the backfill.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resumes the backfill.
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns whether the backfill is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::SeqCst)
+    }
+
+    /// Returns the progress of the backfill.
+    pub fn progress(&self) -> BackfillProgress {
+        BackfillProgress {
+            range: self.state.start..self.state.end,
+            position: self.state.position.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// The progress of a [Backfill].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BackfillProgress {
+    /// The range of keys to backfill.
+    pub range: Range<u64>,
+    /// The first key that was not backfilled yet.
+    pub position: u64,
+}
+
+/// The `BackfillProgress` implementation.
+impl BackfillProgress {
+    /// Returns the number of keys that were backfilled.
+    pub fn completed(&self) -> u64 {
+        self.position.saturating_sub(self.range.start)
+    }
+
+    /// Returns the number of keys that remain to be backfilled.
+    pub fn remaining(&self) -> u64 {
+        self.range.end.saturating_sub(self.position)
+    }
+
+    /// Returns whether all keys were backfilled.
+    pub fn is_complete(&self) -> bool {
+        self.position >= self.range.end
+    }
+}
+
+/// The error returned when a [Backfill] fails.
+#[derive(Debug)]
+pub enum BackfillError<E> {
+    /// The checkpoint could not be loaded, or saved.
+    Checkpoint(io::Error),
+    /// The command of a chunk failed.
+    Chunk {
+        /// The keys of the chunk.
+        chunk: Range<u64>,
+        /// The error.
+        error: DispatchError<E>,
+    },
+}
+
+impl<E: Debug> Display for BackfillError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::Checkpoint(error) => write!(f, "backfill checkpoint failed: {}", error),
+            Self::Chunk { chunk, error } => {
+                write!(f, "backfill of chunk {:?} failed: {:?}", chunk, error)
+            }
+        }
+    }
+}
+
+impl<E: Debug> Error for BackfillError<E> {}
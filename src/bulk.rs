@@ -0,0 +1,372 @@
+//! The `bulk` module provides a helper to dispatch large numbers of commands, e.g. in data migrations.
+//!
+//! Driving a data migration through the command bus keeps the business rules enforced by the handlers, but
+//! dispatching millions of commands one by one is slow, and a crash halfway through should not require starting
+//! over. The [BulkDispatcher] dispatches commands with bounded concurrency, in batches, records its progress to a
+//! [Checkpoint] after every batch, and collects the errors into a final [BulkReport].
+//!
+//! - [BulkDispatcher]: Dispatches commands in bulk.
+//! - [Checkpoint]: Stores the progress of a bulk dispatch.
+//! - [FileCheckpoint]: A [Checkpoint] stored in a file.
+//! - [BulkReport]: The report of a bulk dispatch.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::Poll;
+
+use crate::command::Command;
+use crate::command::CommandBus;
+use crate::error::DispatchError;
+
+/// The `Checkpoint` trait stores the progress of a bulk dispatch, or of a [Backfill](crate::backfill::Backfill).
+///
+/// The progress is a position, e.g. the number of commands, from the start of the input, that were dispatched.
+pub trait Checkpoint: Send + Sync {
+    /// Returns the stored position, or `None` if none was stored.
+    fn load(&self) -> io::Result<Option<u64>>;
+
+    /// Stores the position.
+    fn save(&self, position: u64) -> io::Result<()>;
+}
+
+/// A [Checkpoint] stored in a file.
+///
+/// The file is replaced atomically, so a crash while saving leaves the previous progress intact.
+#[derive(Debug, Clone)]
+pub struct FileCheckpoint {
+    #[doc(hidden)]
+    path: PathBuf,
+}
+
+/// The `FileCheckpoint` implementation.
+impl FileCheckpoint {
+    /// Creates a new `FileCheckpoint` stored at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Checkpoint for FileCheckpoint {
+    fn load(&self) -> io::Result<Option<u64>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => content
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn save(&self, position: u64) -> io::Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        fs::write(&temporary, position.to_string())?;
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+/// A command that failed during a bulk dispatch.
+#[derive(Debug)]
+pub struct BulkFailure<E> {
+    /// The position of the command in the input.
+    pub position: usize,
+    /// The error.
+    pub error: DispatchError<E>,
+}
+
+/// The report of a bulk dispatch.
+#[derive(Debug)]
+pub struct BulkReport<E> {
+    /// The number of commands skipped, because a previous run already dispatched them.
+    pub skipped: usize,
+    /// The number of commands dispatched.
+    pub dispatched: usize,
+    /// The number of commands that succeeded.
+    pub succeeded: usize,
+    /// The commands that failed.
+    pub failures: Vec<BulkFailure<E>>,
+    /// Whether the dispatch stopped before the end of the input, because too many commands failed.
+    pub aborted: bool,
+}
+
+/// Dispatches commands in bulk.
+///
+/// Commands are dispatched in batches of `batch_size`, with at most `concurrency` commands in flight. Once a batch
+/// completes, the progress is saved to the [Checkpoint], if any. When the dispatch is restarted with the same
+/// checkpoint, and the same input, the commands that were already dispatched are skipped. Commands of a batch that
+/// was interrupted are dispatched again, they should therefore be idempotent.
+///
+/// Commands are dispatched using [CommandBus::try_dispatch], so missing handlers, and disabled commands, are
+/// reported as failures.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::async_trait;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct ImportUserCommand {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Command for ImportUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct ImportUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<ImportUserCommand> for ImportUserCommandHandler {
+/// #    async fn handle(&self, command: ImportUserCommand) -> Result<(), std::io::Error> {
+/// #       if command.user_id % 10 == 0 {
+/// #           return Err(std::io::Error::other("invalid user"));
+/// #       }
+/// #
+/// #       Ok(())
+/// #   }
+/// # }
+/// use discern::bulk::BulkDispatcher;
+/// use discern::bulk::FileCheckpoint;
+/// use discern::command_bus;
+///
+/// let command_bus = command_bus! {
+///     ImportUserCommand => ImportUserCommandHandler,
+/// };
+///
+/// # let directory = std::env::temp_dir().join(format!("discern-bulk-{}", std::process::id()));
+/// # std::fs::create_dir_all(&directory).unwrap();
+/// # let path = directory.join("import-users.checkpoint");
+/// let dispatcher = BulkDispatcher::new(command_bus)
+///     .with_concurrency(8)
+///     .with_batch_size(25)
+///     .with_checkpoint(FileCheckpoint::new(&path));
+///
+/// let commands = (1..=100).map(|user_id| ImportUserCommand { user_id });
+/// let report = dispatcher.dispatch(commands).await.unwrap();
+///
+/// assert_eq!(report.dispatched, 100);
+/// assert_eq!(report.succeeded, 90);
+/// assert_eq!(report.failures.len(), 10);
+///
+/// // Running the import again resumes from the checkpoint.
+/// let commands = (1..=100).map(|user_id| ImportUserCommand { user_id });
+/// let report = dispatcher.dispatch(commands).await.unwrap();
+///
+/// assert_eq!(report.skipped, 100);
+/// assert_eq!(report.dispatched, 0);
+/// # std::fs::remove_dir_all(&directory).unwrap();
+/// # });
+/// ```
+pub struct BulkDispatcher {
+    #[doc(hidden)]
+    command_bus: CommandBus,
+    #[doc(hidden)]
+    concurrency: usize,
+    #[doc(hidden)]
+    batch_size: usize,
+    #[doc(hidden)]
+    max_failures: Option<usize>,
+    #[doc(hidden)]
+    checkpoint: Option<Box<dyn Checkpoint>>,
+}
+
+/// The `BulkDispatcher` implementation.
+impl BulkDispatcher {
+    /// Creates a new `BulkDispatcher`, dispatching one command at a time, in batches of 100 commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_bus` - The command bus to dispatch the commands to.
+    pub fn new(command_bus: CommandBus) -> Self {
+        Self {
+            command_bus,
+            concurrency: 1,
+            batch_size: 100,
+            max_failures: None,
+            checkpoint: None,
+        }
+    }
+
+    /// Sets the maximum number of commands in flight.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `concurrency` is zero.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "the concurrency must be greater than zero");
+
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Sets the number of commands dispatched between checkpoints.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `batch_size` is zero.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "the batch size must be greater than zero");
+
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Stops the dispatch after the batch in which the number of failed commands exceeded `max_failures`.
+    pub fn with_max_failures(mut self, max_failures: usize) -> Self {
+        self.max_failures = Some(max_failures);
+        self
+    }
+
+    /// Records the progress of the dispatch to the given checkpoint.
+    pub fn with_checkpoint(mut self, checkpoint: impl Checkpoint + 'static) -> Self {
+        self.checkpoint = Some(Box::new(checkpoint));
+        self
+    }
+
+    /// Dispatches the given commands.
+    ///
+    /// # Returns
+    ///
+    /// The report of the dispatch, or an error if the checkpoint could not be loaded, or saved.
+    pub async fn dispatch<C, I>(&self, commands: I) -> io::Result<BulkReport<C::Error>>
+    where
+        C: Command,
+        I: IntoIterator<Item = C>,
+    {
+        let skipped = match &self.checkpoint {
+            Some(checkpoint) => checkpoint.load()?.unwrap_or(0) as usize,
+            None => 0,
+        };
+
+        let mut report = BulkReport {
+            skipped: 0,
+            dispatched: 0,
+            succeeded: 0,
+            failures: Vec::new(),
+            aborted: false,
+        };
+
+        let mut commands = commands.into_iter().enumerate();
+        report.skipped = commands.by_ref().take(skipped).count();
+
+        loop {
+            let batch: Vec<(usize, C)> = commands.by_ref().take(self.batch_size).collect();
+            if batch.is_empty() {
+                break;
+            }
+
+            let position = batch[batch.len() - 1].0 + 1;
+            for (index, result) in self.dispatch_batch(batch).await {
+                report.dispatched += 1;
+                match result {
+                    Ok(_) => report.succeeded += 1,
+                    Err(error) => report.failures.push(BulkFailure {
+                        position: index,
+                        error,
+                    }),
+                }
+            }
+
+            if let Some(checkpoint) = &self.checkpoint {
+                checkpoint.save(position as u64)?;
+            }
+
+            if self
+                .max_failures
+                .is_some_and(|max_failures| report.failures.len() > max_failures)
+            {
+                report.aborted = commands.next().is_some();
+
+                break;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Dispatches a batch of commands, with at most `concurrency` commands in flight.
+    async fn dispatch_batch<C: Command>(
+        &self,
+        batch: Vec<(usize, C)>,
+    ) -> Vec<(usize, Result<C::Metadata, DispatchError<C::Error>>)> {
+        type Dispatch<'a, C> = Pin<
+            Box<
+                dyn Future<
+                        Output = (
+                            usize,
+                            Result<<C as Command>::Metadata, DispatchError<<C as Command>::Error>>,
+                        ),
+                    > + Send
+                    + 'a,
+            >,
+        >;
+
+        let mut results = Vec::with_capacity(batch.len());
+        let mut pending = batch.into_iter();
+        let mut in_flight: Vec<Dispatch<'_, C>> = Vec::with_capacity(self.concurrency);
+
+        std::future::poll_fn(|cx| {
+            loop {
+                while in_flight.len() < self.concurrency {
+                    let Some((index, command)) = pending.next() else {
+                        break;
+                    };
+
+                    in_flight.push(Box::pin(async move {
+                        (index, self.command_bus.try_dispatch(command).await)
+                    }));
+                }
+
+                let before = in_flight.len();
+                let mut i = 0;
+                while i < in_flight.len() {
+                    match in_flight[i].as_mut().poll(cx) {
+                        Poll::Ready(result) => {
+                            results.push(result);
+                            drop(in_flight.swap_remove(i));
+                        }
+                        Poll::Pending => i += 1,
+                    }
+                }
+
+                if in_flight.is_empty() && pending.len() == 0 {
+                    return Poll::Ready(());
+                }
+
+                // Only keep polling if completed dispatches made room for new ones.
+                if in_flight.len() == before {
+                    return Poll::Pending;
+                }
+            }
+        })
+        .await;
+
+        results.sort_by_key(|(index, _)| *index);
+        results
+    }
+}
+
+/// Debug implementation for `BulkDispatcher`
+impl Debug for BulkDispatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("BulkDispatcher")
+            .field("concurrency", &self.concurrency)
+            .field("batch_size", &self.batch_size)
+            .field("max_failures", &self.max_failures)
+            .finish()
+    }
+}
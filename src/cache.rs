@@ -0,0 +1,1175 @@
+//! The `cache` module provides handler wrappers that cache the results of queries.
+//!
+//! Repeated lookups of keys that don't exist are common, e.g. a client polling for a resource that was never
+//! created, and each of them reaches the database, only to fail again. An [ErrorCache] remembers such failures
+//! for a short duration, and the [NegativeCachingHandler] returns them without invoking the wrapped handler.
+//!
+//! - [CacheKey]: Identifies queries whose results can be cached.
+//! - [NegativeCache]: Marks the errors of a query that can be cached.
+//! - [ErrorCache]: Stores the cacheable errors of a query type.
+//! - [NegativeCachingHandler]: A handler that caches the cacheable errors of a query.
+//!
+//! Expensive read-model queries can also have their results cached by the [QueryBus](crate::query::QueryBus)
+//! itself, instead of inside each handler. A [QueryCache] added to the bus using
+//! [QueryBus::with_cache](crate::query::QueryBus::with_cache) serves the successful results of the queries
+//! implementing [CachedQuery], for the duration each of them declares.
+//!
+//! - [CachedQuery]: Marks queries whose results are cached by a [QueryCache].
+//! - [QueryCache]: Caches the results of queries at the bus level.
+//! - [CacheStore]: Stores the cached results of a query type.
+//! - [MemoryStore]: The in-memory [CacheStore], used by default.
+//!
+//! Cached results become stale once a command changes the state they were read from. Commands implementing
+//! [InvalidatesCache] declare the results they make stale, which are removed from the cache once the command
+//! succeeded, when the cache is also added to the [CommandBus](crate::command::CommandBus) using
+//! [CommandBus::with_cache](crate::command::CommandBus::with_cache).
+//!
+//! - [InvalidatesCache]: Declares the cached query results made stale by a command.
+//! - [Invalidation]: The cached query results made stale by a command.
+
+use std::any::Any;
+use std::any::TypeId;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::evict::EvictingMap;
+use crate::query::Query;
+use crate::query::QueryHandler;
+
+/// The `CacheKey` trait identifies queries whose results can be cached.
+///
+/// Queries with equal keys are considered identical, and share the same cached result.
+///
+/// # Example
+///
+/// ```
+/// use discern::cache::CacheKey;
+/// use discern::query::Query;
+///
+/// #[derive(Debug)]
+/// struct GetUserQuery {
+///     user_id: u64,
+/// }
+///
+/// impl Query for GetUserQuery {
+///     type Output = String;
+///     type Error = String;
+/// }
+///
+/// impl CacheKey for GetUserQuery {
+///     type Key = u64;
+///
+///     fn cache_key(&self) -> u64 {
+///         self.user_id
+///     }
+/// }
+/// ```
+pub trait CacheKey: Query {
+    /// The key identifying identical queries.
+    type Key: Hash + Eq + Clone + Send + Sync + 'static;
+
+    /// Returns the key identifying this query.
+    fn cache_key(&self) -> Self::Key;
+}
+
+/// The `NegativeCache` trait marks the errors of a query that can be cached.
+///
+/// Only errors that are expected to persist for a while, e.g. "not found", should be cached. Transient errors,
+/// e.g. a timeout, must not be, as they would be returned until the cached entry expires.
+///
+/// # Example
+///
+/// ```
+/// # use discern::cache::CacheKey;
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUserQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #     type Output = String;
+/// #     type Error = GetUserError;
+/// # }
+/// #
+/// # impl CacheKey for GetUserQuery {
+/// #     type Key = u64;
+/// #
+/// #     fn cache_key(&self) -> u64 {
+/// #         self.user_id
+/// #     }
+/// # }
+/// use discern::cache::NegativeCache;
+///
+/// #[derive(Debug, Clone)]
+/// enum GetUserError {
+///     NotFound,
+///     Timeout,
+/// }
+///
+/// impl NegativeCache for GetUserQuery {
+///     fn is_cacheable(error: &GetUserError) -> bool {
+///         matches!(error, GetUserError::NotFound)
+///     }
+/// }
+/// ```
+pub trait NegativeCache: CacheKey {
+    /// Returns whether the given error can be cached.
+    fn is_cacheable(error: &Self::Error) -> bool;
+}
+
+/// Stores the cacheable errors of a query type.
+///
+/// When a handler wrapped using [ErrorCache::cache] returns an error marked as cacheable by [NegativeCache], the
+/// error is returned to every dispatch of an identical query, until `ttl` has elapsed, without invoking the handler.
+/// Successful results are never cached, and therefore, the error type of the query must implement `Clone`.
+///
+/// Expired errors are no longer returned, but are only evicted once enough new errors were cached, which keeps
+/// caching an error cheap, so [ErrorCache::len] may count some of them.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::cache::CacheKey;
+/// # use discern::cache::NegativeCache;
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUserQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # #[derive(Debug, Clone)]
+/// # enum GetUserError {
+/// #     NotFound,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #     type Output = String;
+/// #     type Error = GetUserError;
+/// # }
+/// #
+/// # impl CacheKey for GetUserQuery {
+/// #     type Key = u64;
+/// #
+/// #     fn cache_key(&self) -> u64 {
+/// #         self.user_id
+/// #     }
+/// # }
+/// #
+/// # impl NegativeCache for GetUserQuery {
+/// #     fn is_cacheable(error: &GetUserError) -> bool {
+/// #         matches!(error, GetUserError::NotFound)
+/// #     }
+/// # }
+/// use std::sync::atomic::AtomicUsize;
+/// use std::sync::atomic::Ordering;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use discern::async_trait;
+/// use discern::cache::ErrorCache;
+/// use discern::query::QueryHandler;
+/// use discern::query_bus;
+///
+/// struct GetUserQueryHandler {
+///     lookups: Arc<AtomicUsize>,
+/// }
+///
+/// #[async_trait]
+/// impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+///     async fn handle(&self, query: GetUserQuery) -> Result<String, GetUserError> {
+///         self.lookups.fetch_add(1, Ordering::SeqCst);
+///
+///         Err(GetUserError::NotFound)
+///     }
+/// }
+///
+/// let lookups = Arc::new(AtomicUsize::new(0));
+/// let errors = Arc::new(ErrorCache::new(Duration::from_secs(30)));
+///
+/// let query_bus = query_bus! {
+///     GetUserQuery => errors.cache(GetUserQueryHandler { lookups: lookups.clone() }),
+/// };
+///
+/// assert!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.is_err());
+/// assert!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.is_err());
+///
+/// // The second lookup was served from the cache.
+/// assert_eq!(lookups.load(Ordering::SeqCst), 1);
+///
+/// // Once the user is created, its cached error is removed.
+/// errors.invalidate(&1);
+/// assert!(errors.is_empty());
+/// # });
+/// ```
+pub struct ErrorCache<Q: NegativeCache> {
+    #[doc(hidden)]
+    ttl: Duration,
+    #[doc(hidden)]
+    entries: Mutex<EvictingMap<Q::Key, Entry<Q::Error>>>,
+}
+
+/// A cached error.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+struct Entry<E> {
+    cached_at: Instant,
+    error: E,
+}
+
+/// The `ErrorCache` implementation.
+impl<Q: NegativeCache> ErrorCache<Q> {
+    /// Creates a new `ErrorCache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - The duration for which a cacheable error is cached.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(EvictingMap::new()),
+        }
+    }
+
+    /// Wraps the given handler, caching its cacheable errors.
+    pub fn cache<H: QueryHandler<Q>>(self: &Arc<Self>, handler: H) -> NegativeCachingHandler<Q, H> {
+        NegativeCachingHandler {
+            handler,
+            cache: self.clone(),
+        }
+    }
+
+    /// Removes the cached error of the query with the given key, if any.
+    ///
+    /// This should be called when the missing resource is created, so that it can be found before the cached
+    /// error expires.
+    pub fn invalidate(&self, key: &Q::Key) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+
+    /// Removes all cached errors.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Returns the number of cached errors, including expired ones that were not evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns whether no errors are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The `ErrorCache` implementation for queries with cloneable errors.
+impl<Q: NegativeCache> ErrorCache<Q>
+where
+    Q::Error: Clone,
+{
+    /// Returns the cached error of the query with the given key, if it did not expire.
+    fn get(&self, key: &Q::Key) -> Option<Q::Error> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        entries
+            .get(key)
+            .filter(|entry| entry.cached_at.elapsed() < self.ttl)
+            .map(|entry| entry.error.clone())
+    }
+
+    /// Records the result of the query with the given key.
+    fn record<T>(&self, key: Q::Key, result: &Result<T, Q::Error>) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match result {
+            Err(error) if Q::is_cacheable(error) => {
+                let now = Instant::now();
+
+                entries.evict(|_, entry| now.duration_since(entry.cached_at) < self.ttl);
+                entries.insert(
+                    key,
+                    Entry {
+                        cached_at: now,
+                        error: error.clone(),
+                    },
+                );
+            }
+            _ => {
+                entries.remove(&key);
+            }
+        }
+    }
+}
+
+/// Debug implementation for `ErrorCache`
+impl<Q: NegativeCache> Debug for ErrorCache<Q> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("ErrorCache")
+            .field("ttl", &self.ttl)
+            .field("entries", &self.len())
+            .finish()
+    }
+}
+
+/// A handler that caches the cacheable errors of a query.
+///
+/// `NegativeCachingHandler` is created using [ErrorCache::cache].
+pub struct NegativeCachingHandler<Q: NegativeCache, H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    cache: Arc<ErrorCache<Q>>,
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for NegativeCachingHandler<Q, H>
+where
+    Q: NegativeCache,
+    Q::Error: Clone,
+    H: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        let key = query.cache_key();
+        if let Some(error) = self.cache.get(&key) {
+            return Err(error);
+        }
+
+        let result = self.handler.handle(query).await;
+        self.cache.record(key, &result);
+
+        result
+    }
+}
+
+/// Debug implementation for `NegativeCachingHandler`
+impl<Q: NegativeCache, H> Debug for NegativeCachingHandler<Q, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("NegativeCachingHandler")
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+/// The `CachedQuery` trait marks queries whose results are cached by a [QueryCache].
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use discern::cache::CacheKey;
+/// use discern::cache::CachedQuery;
+/// use discern::query::Query;
+///
+/// #[derive(Debug)]
+/// struct GetUserQuery {
+///     user_id: u64,
+/// }
+///
+/// impl Query for GetUserQuery {
+///     type Output = String;
+///     type Error = std::io::Error;
+/// }
+///
+/// impl CacheKey for GetUserQuery {
+///     type Key = u64;
+///
+///     fn cache_key(&self) -> u64 {
+///         self.user_id
+///     }
+/// }
+///
+/// impl CachedQuery for GetUserQuery {
+///     const TTL: Duration = Duration::from_secs(60);
+/// }
+/// ```
+pub trait CachedQuery: CacheKey {
+    /// The duration for which a successful result is cached.
+    const TTL: Duration;
+
+    /// Returns the duration for which the given result is cached.
+    ///
+    /// Defaults to [CachedQuery::TTL]. Queries whose result may be absent, see
+    /// [MaybeQuery](crate::maybe::MaybeQuery), usually cache absent results for a shorter duration, so that a
+    /// newly created thing is found quickly:
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use discern::cache::CacheKey;
+    /// # use discern::cache::CachedQuery;
+    /// # use discern::query::Query;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct FindUserQuery {
+    /// #     user_id: u64,
+    /// # }
+    /// #
+    /// # impl Query for FindUserQuery {
+    /// #     type Output = Option<String>;
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # impl CacheKey for FindUserQuery {
+    /// #     type Key = u64;
+    /// #
+    /// #     fn cache_key(&self) -> u64 {
+    /// #         self.user_id
+    /// #     }
+    /// # }
+    /// impl CachedQuery for FindUserQuery {
+    ///     const TTL: Duration = Duration::from_secs(60);
+    ///
+    ///     fn ttl(output: &Option<String>) -> Duration {
+    ///         match output {
+    ///             Some(_) => Self::TTL,
+    ///             None => Duration::from_secs(5),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(FindUserQuery::ttl(&None), Duration::from_secs(5));
+    /// ```
+    fn ttl(output: &Self::Output) -> Duration {
+        let _ = output;
+
+        Self::TTL
+    }
+}
+
+/// The `CacheStore` trait stores the cached results of a query type, see [QueryCache::cache_in].
+///
+/// Implementations backed by a shared store, e.g. Redis, let several processes share the same cache.
+pub trait CacheStore<Q: CachedQuery>: Send + Sync + 'static {
+    /// Returns the cached output of the query with the given key, if it did not expire.
+    fn get(&self, key: &Q::Key) -> Option<Q::Output>;
+
+    /// Caches the output of the query with the given key, for the given duration.
+    fn insert(&self, key: Q::Key, output: Q::Output, ttl: Duration);
+
+    /// Removes the cached output of the query with the given key, if any.
+    fn remove(&self, key: &Q::Key);
+
+    /// Removes all the cached outputs.
+    fn clear(&self);
+}
+
+/// The in-memory [CacheStore], used by default.
+///
+/// Expired outputs are evicted as new outputs are cached, once the number of cached outputs doubled since the
+/// previous eviction.
+pub struct MemoryStore<Q: CachedQuery> {
+    #[doc(hidden)]
+    entries: Mutex<EvictingMap<Q::Key, Expiring<Q::Output>>>,
+}
+
+/// A cached output, along with its expiration.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+struct Expiring<T> {
+    expires_at: Instant,
+    output: T,
+}
+
+/// The `MemoryStore` implementation.
+impl<Q: CachedQuery> MemoryStore<Q> {
+    /// Creates a new, empty, `MemoryStore`.
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(EvictingMap::new()),
+        }
+    }
+
+    /// Returns the number of cached outputs, including expired ones that were not evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns whether no outputs are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<Q: CachedQuery> CacheStore<Q> for MemoryStore<Q>
+where
+    Q::Output: Clone,
+{
+    fn get(&self, key: &Q::Key) -> Option<Q::Output> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        entries
+            .get(key)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map(|entry| entry.output.clone())
+    }
+
+    fn insert(&self, key: Q::Key, output: Q::Output, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+
+        entries.evict(|_, entry| entry.expires_at > now);
+        entries.insert(
+            key,
+            Expiring {
+                expires_at: now + ttl,
+                output,
+            },
+        );
+    }
+
+    fn remove(&self, key: &Q::Key) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+
+    fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl<Q: CachedQuery> Default for MemoryStore<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debug implementation for `MemoryStore`
+impl<Q: CachedQuery> Debug for MemoryStore<Q> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("MemoryStore")
+            .field("entries", &self.len())
+            .finish()
+    }
+}
+
+/// Caches the results of queries at the bus level.
+///
+/// Once added to a [QueryBus](crate::query::QueryBus) using
+/// [QueryBus::with_cache](crate::query::QueryBus::with_cache), the successful results of the query types
+/// registered in the cache are served from it, without invoking their handler, until their
+/// [TTL](CachedQuery::TTL) has elapsed. Errors are never cached, see [ErrorCache] for that. Clones of a
+/// `QueryCache` share the same cached results.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use std::time::Duration;
+/// # use discern::cache::CacheKey;
+/// # use discern::cache::CachedQuery;
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUserQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #     type Output = String;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # impl CacheKey for GetUserQuery {
+/// #     type Key = u64;
+/// #
+/// #     fn cache_key(&self) -> u64 {
+/// #         self.user_id
+/// #     }
+/// # }
+/// #
+/// # impl CachedQuery for GetUserQuery {
+/// #     const TTL: Duration = Duration::from_secs(60);
+/// # }
+/// use std::sync::atomic::AtomicUsize;
+/// use std::sync::atomic::Ordering;
+/// use std::sync::Arc;
+///
+/// use discern::async_trait;
+/// use discern::cache::QueryCache;
+/// use discern::query::QueryBus;
+/// use discern::query::QueryHandler;
+/// use discern::query_registry;
+///
+/// struct GetUserQueryHandler {
+///     lookups: Arc<AtomicUsize>,
+/// }
+///
+/// #[async_trait]
+/// impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+///     async fn handle(&self, query: GetUserQuery) -> Result<String, std::io::Error> {
+///         self.lookups.fetch_add(1, Ordering::SeqCst);
+///
+///         Ok(format!("user #{}", query.user_id))
+///     }
+/// }
+///
+/// let lookups = Arc::new(AtomicUsize::new(0));
+///
+/// let registry = query_registry! {
+///     GetUserQuery => GetUserQueryHandler { lookups: lookups.clone() },
+/// };
+///
+/// let cache = QueryCache::new().cache::<GetUserQuery>();
+/// let query_bus = QueryBus::new(registry).with_cache(cache.clone());
+///
+/// assert_eq!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap(), "user #1");
+/// assert_eq!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap(), "user #1");
+///
+/// // The second dispatch was served from the cache.
+/// assert_eq!(lookups.load(Ordering::SeqCst), 1);
+///
+/// // Once the user is updated, its cached result is removed.
+/// cache.invalidate::<GetUserQuery>(&1);
+/// query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap();
+///
+/// assert_eq!(lookups.load(Ordering::SeqCst), 2);
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct QueryCache {
+    #[doc(hidden)]
+    slots: Arc<BTreeMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    #[doc(hidden)]
+    invalidators: Arc<BTreeMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+/// The `QueryCache` implementation.
+impl QueryCache {
+    /// Creates a new `QueryCache`, caching no query types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caches the results of the query type `Q` in memory, see [MemoryStore].
+    pub fn cache<Q: CachedQuery>(self) -> Self
+    where
+        Q::Output: Clone,
+    {
+        self.cache_in::<Q>(MemoryStore::new())
+    }
+
+    /// Caches the results of the query type `Q` in the given store.
+    ///
+    /// Caching a query type replaces its previous store, and the results cached in it. Clones of this
+    /// `QueryCache` made before are not affected, so query types should be cached before the cache is shared.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store of the results of the query type `Q`.
+    pub fn cache_in<Q: CachedQuery>(mut self, store: impl CacheStore<Q>) -> Self
+    where
+        Q::Output: Clone,
+    {
+        let tracked = Arc::new(Tracked::new(Arc::new(store)));
+
+        Arc::make_mut(&mut self.slots).insert(TypeId::of::<Q>(), Arc::new(Slot::new(tracked)));
+        self
+    }
+
+    /// Returns whether the results of the query type `Q` are cached.
+    pub fn is_cached<Q: Query>(&self) -> bool {
+        self.slots.contains_key(&TypeId::of::<Q>())
+    }
+
+    /// Returns whether the result of the query of type `Q` with the given key is cached, and did not expire.
+    pub fn contains<Q: CachedQuery>(&self, key: &Q::Key) -> bool {
+        self.tracked::<Q>()
+            .is_some_and(|tracked| tracked.store.get(key).is_some())
+    }
+
+    /// Removes the cached result of the query of type `Q` with the given key, if any.
+    ///
+    /// Queries with the same key that missed the cache before the invalidation, and are still being handled, do not
+    /// cache their result, since it may have been read before the state changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use std::time::Duration;
+    /// # use discern::cache::CacheKey;
+    /// # use discern::cache::CachedQuery;
+    /// # use discern::query::Query;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct GetUserQuery {
+    /// #     user_id: u64,
+    /// # }
+    /// #
+    /// # impl Query for GetUserQuery {
+    /// #     type Output = String;
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # impl CacheKey for GetUserQuery {
+    /// #     type Key = u64;
+    /// #
+    /// #     fn cache_key(&self) -> u64 {
+    /// #         self.user_id
+    /// #     }
+    /// # }
+    /// #
+    /// # impl CachedQuery for GetUserQuery {
+    /// #     const TTL: Duration = Duration::from_secs(60);
+    /// # }
+    /// use discern::async_trait;
+    /// use discern::cache::QueryCache;
+    /// use discern::query::QueryBus;
+    /// use discern::query::QueryHandler;
+    /// use discern::query_registry;
+    ///
+    /// struct GetUserQueryHandler {
+    ///     cache: QueryCache,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+    ///     async fn handle(&self, query: GetUserQuery) -> Result<String, std::io::Error> {
+    ///         let username = format!("user #{}", query.user_id);
+    ///
+    ///         // A command updates the user after it was read, but before the query returns.
+    ///         self.cache.invalidate::<GetUserQuery>(&query.user_id);
+    ///
+    ///         Ok(username)
+    ///     }
+    /// }
+    ///
+    /// let cache = QueryCache::new().cache::<GetUserQuery>();
+    ///
+    /// let query_bus = QueryBus::new(query_registry! {
+    ///     GetUserQuery => GetUserQueryHandler { cache: cache.clone() },
+    /// })
+    /// .with_cache(cache.clone());
+    ///
+    /// query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap();
+    ///
+    /// // The stale result was not cached.
+    /// assert!(!cache.contains::<GetUserQuery>(&1));
+    /// # });
+    /// ```
+    pub fn invalidate<Q: CachedQuery>(&self, key: &Q::Key) {
+        if let Some(tracked) = self.tracked::<Q>() {
+            tracked.invalidate(key);
+        }
+    }
+
+    /// Removes all the cached results of the query type `Q`.
+    pub fn clear<Q: Query>(&self) {
+        if let Some(slot) = self.slot::<Q>() {
+            (slot.clear)();
+        }
+    }
+
+    /// Removes the cached results declared stale by the commands of type `C`, once they succeeded, see
+    /// [InvalidatesCache].
+    ///
+    /// The cache must also be added to the [CommandBus](crate::command::CommandBus) dispatching the commands, using
+    /// [CommandBus::with_cache](crate::command::CommandBus::with_cache).
+    pub fn invalidated_by<C: InvalidatesCache>(mut self) -> Self {
+        let invalidator: fn(&C) -> Invalidation = invalidation_of::<C>;
+
+        Arc::make_mut(&mut self.invalidators).insert(TypeId::of::<C>(), Arc::new(invalidator));
+        self
+    }
+
+    /// Returns the cached results made stale by the given command, if its type invalidates the cache.
+    pub(crate) fn invalidation<C: Command>(&self, command: &C) -> Option<Invalidation> {
+        self.invalidators
+            .get(&TypeId::of::<C>())
+            .and_then(|invalidator| invalidator.downcast_ref::<fn(&C) -> Invalidation>())
+            .map(|invalidator| invalidator(command))
+    }
+
+    /// Removes the given stale results from the cache.
+    pub(crate) fn apply(&self, invalidation: Invalidation) {
+        for action in invalidation.actions {
+            action(self);
+        }
+    }
+
+    /// Looks up the cached result of the given query, if its type is cached.
+    pub(crate) fn lookup<Q: Query>(&self, query: &Q) -> Option<Lookup<Q>> {
+        self.slot::<Q>().map(|slot| (slot.lookup)(query))
+    }
+
+    /// Returns the tracked store of the query type `Q`, if it is cached.
+    fn tracked<Q: CachedQuery>(&self) -> Option<&Arc<Tracked<Q>>> {
+        self.slot::<Q>()
+            .and_then(|slot| slot.tracked.downcast_ref::<Arc<Tracked<Q>>>())
+    }
+
+    /// Returns the slot of the query type `Q`, if it is cached.
+    fn slot<Q: Query>(&self) -> Option<&Slot<Q>> {
+        self.slots
+            .get(&TypeId::of::<Q>())
+            .and_then(|slot| slot.downcast_ref::<Slot<Q>>())
+    }
+}
+
+/// Debug implementation for `QueryCache`
+impl Debug for QueryCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("QueryCache")
+            .field("queries", &self.slots.len())
+            .field("invalidators", &self.invalidators.len())
+            .finish()
+    }
+}
+
+/// The `InvalidatesCache` trait declares the cached query results made stale by a command.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use std::time::Duration;
+/// # use discern::cache::CacheKey;
+/// # use discern::cache::CachedQuery;
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUserQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #     type Output = String;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # impl CacheKey for GetUserQuery {
+/// #     type Key = u64;
+/// #
+/// #     fn cache_key(&self) -> u64 {
+/// #         self.user_id
+/// #     }
+/// # }
+/// #
+/// # impl CachedQuery for GetUserQuery {
+/// #     const TTL: Duration = Duration::from_secs(60);
+/// # }
+/// use discern::cache::InvalidatesCache;
+/// use discern::cache::Invalidation;
+/// use discern::cache::QueryCache;
+/// use discern::command::Command;
+/// use discern::command::CommandBus;
+/// use discern::command_registry;
+/// use discern::query::QueryBus;
+/// use discern::query_registry;
+///
+/// #[derive(Debug)]
+/// struct UpdateUserCommand {
+///     user_id: u64,
+///     username: String,
+/// }
+///
+/// impl Command for UpdateUserCommand {
+///     type Metadata = ();
+///     type Error = std::io::Error;
+/// }
+///
+/// impl InvalidatesCache for UpdateUserCommand {
+///     fn invalidate(&self, invalidation: &mut Invalidation) {
+///         invalidation.query::<GetUserQuery>(self.user_id);
+///     }
+/// }
+///
+/// let cache = QueryCache::new()
+///     .cache::<GetUserQuery>()
+///     .invalidated_by::<UpdateUserCommand>();
+///
+/// let query_bus = QueryBus::new(query_registry! {
+///     GetUserQuery => |query| async move { Ok(format!("user #{}", query.user_id)) },
+/// })
+/// .with_cache(cache.clone());
+///
+/// let command_bus = CommandBus::new(command_registry! {
+///     UpdateUserCommand => |_command| async move { Ok(()) },
+/// })
+/// .with_cache(cache.clone());
+///
+/// query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap();
+/// assert!(cache.contains::<GetUserQuery>(&1));
+///
+/// let command = UpdateUserCommand { user_id: 1, username: "bob".to_string() };
+/// command_bus.dispatch(command).await.unwrap();
+///
+/// // The cached user was removed, since the command succeeded.
+/// assert!(!cache.contains::<GetUserQuery>(&1));
+/// # });
+/// ```
+pub trait InvalidatesCache: Command {
+    /// Declares the cached query results made stale by this command, which are removed once it succeeded.
+    fn invalidate(&self, invalidation: &mut Invalidation);
+}
+
+/// The cached query results made stale by a command, see [InvalidatesCache].
+#[derive(Default)]
+pub struct Invalidation {
+    #[doc(hidden)]
+    actions: Vec<InvalidateFn>,
+}
+
+/// Removes stale results from a [QueryCache].
+type InvalidateFn = Box<dyn FnOnce(&QueryCache) + Send>;
+
+/// The `Invalidation` implementation.
+impl Invalidation {
+    /// Creates a new, empty, `Invalidation`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares the cached result of the query of type `Q` with the given key stale.
+    pub fn query<Q: CachedQuery>(&mut self, key: Q::Key) -> &mut Self {
+        self.actions
+            .push(Box::new(move |cache| cache.invalidate::<Q>(&key)));
+        self
+    }
+
+    /// Declares all the cached results of the query type `Q` stale.
+    pub fn all<Q: Query>(&mut self) -> &mut Self {
+        self.actions.push(Box::new(|cache| cache.clear::<Q>()));
+        self
+    }
+
+    /// Returns the number of declarations.
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Returns whether nothing was declared stale.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+/// Debug implementation for `Invalidation`
+impl Debug for Invalidation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Invalidation")
+            .field("actions", &self.actions.len())
+            .finish()
+    }
+}
+
+/// Returns the cached results made stale by the given command.
+fn invalidation_of<C: InvalidatesCache>(command: &C) -> Invalidation {
+    let mut invalidation = Invalidation::new();
+    command.invalidate(&mut invalidation);
+
+    invalidation
+}
+
+/// The result of looking up a query in a [QueryCache].
+#[doc(hidden)]
+pub(crate) enum Lookup<Q: Query> {
+    /// The output of the query was cached.
+    Hit(Q::Output),
+    /// The output of the query was not cached, and should be recorded once the handler returns it.
+    Miss(RecordFn<Q>),
+}
+
+/// Records the output of a query missing from the cache.
+type RecordFn<Q> = Box<dyn FnOnce(&<Q as Query>::Output) + Send>;
+
+/// Looks up the cached result of a query, or returns a function recording it.
+type LookupFn<Q> = Box<dyn Fn(&Q) -> Lookup<Q> + Send + Sync>;
+
+/// The type-erased store of a query type, callable without the [CachedQuery] bound.
+#[doc(hidden)]
+struct Slot<Q: Query> {
+    lookup: LookupFn<Q>,
+    clear: Box<dyn Fn() + Send + Sync>,
+    tracked: Box<dyn Any + Send + Sync>,
+}
+
+impl<Q: CachedQuery> Slot<Q>
+where
+    Q::Output: Clone,
+{
+    /// Creates the slot of the given tracked store.
+    fn new(tracked: Arc<Tracked<Q>>) -> Self {
+        Self {
+            lookup: Box::new({
+                let tracked = tracked.clone();
+
+                move |query| tracked.lookup(query.cache_key())
+            }),
+            clear: Box::new({
+                let tracked = tracked.clone();
+
+                move || tracked.clear()
+            }),
+            tracked: Box::new(tracked),
+        }
+    }
+}
+
+/// The store of a query type, along with the generations of the keys missing from it.
+///
+/// A result read before its key was invalidated must not be cached after the invalidation. Each key missing from
+/// the store gets a generation, captured on the miss, which invalidating the key bumps, and clearing the store
+/// bumps the epoch of all the keys, so that the result is only recorded if neither changed in the meantime.
+#[doc(hidden)]
+struct Tracked<Q: CachedQuery> {
+    store: Arc<dyn CacheStore<Q>>,
+    generations: Mutex<Generations<Q::Key>>,
+}
+
+/// The generations of the keys missing from a store, while their queries are being handled.
+#[doc(hidden)]
+struct Generations<K> {
+    epoch: u64,
+    keys: HashMap<K, Generation>,
+}
+
+/// The generation of a key missing from a store, and the number of queries being handled for it.
+///
+/// Keys are only tracked while queries are being handled for them, and forgotten once the last of them completed.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+struct Generation {
+    value: u64,
+    pending: usize,
+}
+
+impl<Q: CachedQuery> Tracked<Q> {
+    /// Tracks the given store.
+    fn new(store: Arc<dyn CacheStore<Q>>) -> Self {
+        Self {
+            store,
+            generations: Mutex::new(Generations {
+                epoch: 0,
+                keys: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Removes the cached output of the given key, and bumps its generation if queries are being handled for it.
+    fn invalidate(&self, key: &Q::Key) {
+        let mut generations = self.lock();
+        if let Some(generation) = generations.keys.get_mut(key) {
+            generation.value += 1;
+        }
+
+        self.store.remove(key);
+    }
+
+    /// Removes all the cached outputs, and bumps the epoch of all the keys.
+    fn clear(&self) {
+        let mut generations = self.lock();
+        generations.epoch += 1;
+
+        self.store.clear();
+    }
+
+    /// Locks the generations of the keys.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Generations<Q::Key>> {
+        self.generations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<Q: CachedQuery> Tracked<Q>
+where
+    Q::Output: Clone,
+{
+    /// Looks up the cached output of the given key, or captures its generation to record it.
+    fn lookup(self: &Arc<Self>, key: Q::Key) -> Lookup<Q> {
+        if let Some(output) = self.store.get(&key) {
+            return Lookup::Hit(output);
+        }
+
+        let mut generations = self.lock();
+        let epoch = generations.epoch;
+        let generation = generations.keys.entry(key.clone()).or_default();
+        generation.pending += 1;
+
+        let ticket = Ticket {
+            tracked: self.clone(),
+            generation: (epoch, generation.value),
+            key,
+        };
+
+        Lookup::Miss(Box::new(move |output: &Q::Output| ticket.record(output)))
+    }
+}
+
+/// The generation of a key captured on a miss, released once the query was handled.
+#[doc(hidden)]
+struct Ticket<Q: CachedQuery> {
+    tracked: Arc<Tracked<Q>>,
+    generation: (u64, u64),
+    key: Q::Key,
+}
+
+impl<Q: CachedQuery> Ticket<Q>
+where
+    Q::Output: Clone,
+{
+    /// Caches the given output, unless its key was invalidated since the miss.
+    fn record(&self, output: &Q::Output) {
+        let generations = self.tracked.lock();
+        let current = generations
+            .keys
+            .get(&self.key)
+            .map(|generation| (generations.epoch, generation.value));
+
+        // The lock is held while inserting, so that an invalidation can't slip in between the check and the insert.
+        if current == Some(self.generation) {
+            self.tracked
+                .store
+                .insert(self.key.clone(), output.clone(), Q::ttl(output));
+        }
+    }
+}
+
+impl<Q: CachedQuery> Drop for Ticket<Q> {
+    fn drop(&mut self) {
+        let mut generations = self.tracked.lock();
+        if let Some(generation) = generations.keys.get_mut(&self.key) {
+            generation.pending -= 1;
+            if generation.pending == 0 {
+                generations.keys.remove(&self.key);
+            }
+        }
+    }
+}
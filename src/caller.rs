@@ -0,0 +1,607 @@
+//! The `caller` module provides caller attribution, and per-caller quotas.
+//!
+//! Platforms shared by multiple teams need to know which consumer of the bus is responsible for which load,
+//! and to keep a single consumer from exhausting the resources of the others. A [CallerId] is attached to the
+//! [Context](crate::context::Context) of a command, using
+//! [CommandBus::dispatch_with](crate::command::CommandBus::dispatch_with), where it is visible to the middleware,
+//! and to contextual handlers. Queries don't carry a context, so their caller is attached using [CallerId::scope]
+//! instead, and is visible to the handlers, and to any query they dispatch inline.
+//!
+//! - [CallerId]: Identifies the caller on whose behalf a dispatch runs.
+//! - [CallerQuota]: Enforces per-caller rate and concurrency quotas, and maintains per-caller counters.
+//! - [QuotaMiddleware]: A middleware that enforces a [CallerQuota] on commands.
+//! - [QuotaHandler]: A handler that enforces a [CallerQuota] on queries.
+//! - [QuotaExceeded]: The error returned when a caller exceeds its quota.
+
+use std::error::Error;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::evict::EvictingMap;
+use crate::middleware::CommandMiddleware;
+use crate::middleware::Next;
+use crate::middleware::Outcome;
+use crate::query::Query;
+use crate::query::QueryHandler;
+use crate::scope;
+
+/// The default duration after which the state of an idle caller is forgotten, see [CallerQuota::with_idle_timeout].
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Identifies the caller on whose behalf a dispatch runs, e.g. a team, a service, or a tenant.
+///
+/// Commands carry their caller as an extension of their [Context](crate::context::Context), see [QuotaMiddleware],
+/// while queries are dispatched within [CallerId::scope].
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::query::Query;
+/// # use discern::async_trait;
+/// #
+/// # #[derive(Debug)]
+/// # struct WhoAmIQuery;
+/// #
+/// # impl Query for WhoAmIQuery {
+/// #   type Output = Option<CallerId>;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::caller::CallerId;
+/// use discern::query::QueryHandler;
+/// use discern::query_bus;
+///
+/// struct WhoAmIQueryHandler;
+///
+/// #[async_trait]
+/// impl QueryHandler<WhoAmIQuery> for WhoAmIQueryHandler {
+///     async fn handle(&self, _query: WhoAmIQuery) -> Result<Option<CallerId>, std::io::Error> {
+///         Ok(CallerId::current())
+///     }
+/// }
+///
+/// let query_bus = query_bus! {
+///     WhoAmIQuery => WhoAmIQueryHandler,
+/// };
+///
+/// let caller = CallerId::new("billing");
+/// let result = caller.scope(query_bus.dispatch(WhoAmIQuery)).await;
+///
+/// assert_eq!(result.unwrap(), Some(CallerId::new("billing")));
+/// assert_eq!(query_bus.dispatch(WhoAmIQuery).await.unwrap(), None);
+/// # });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CallerId(Arc<str>);
+
+/// The `CallerId` implementation.
+impl CallerId {
+    /// Creates a new `CallerId`.
+    pub fn new(id: impl Into<Arc<str>>) -> Self {
+        Self(id.into())
+    }
+
+    /// Returns the `CallerId` used for dispatches that are not attributed to any caller.
+    pub fn anonymous() -> Self {
+        Self::new("anonymous")
+    }
+
+    /// Returns the caller on whose behalf the current query runs, if any.
+    ///
+    /// The caller is only available while the future passed to [CallerId::scope] is being polled,
+    /// tasks spawned from it are not attributed to the caller. The caller of a command is read from its
+    /// [Context](crate::context::Context) instead.
+    pub fn current() -> Option<Self> {
+        scope::caller()
+    }
+
+    /// Returns the identifier of the caller.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Runs the given future on behalf of this caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - The future to run, usually the dispatch of a query.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut caller = Some(self);
+
+        std::future::poll_fn(|cx| scope::with_caller(&mut caller, || future.as_mut().poll(cx)))
+            .await
+    }
+}
+
+impl Display for CallerId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.write_str(&self.0)
+    }
+}
+
+/// The error returned when a caller exceeds its quota.
+///
+/// Queries handled by a [QuotaHandler] must have an error type that implements `From<QuotaExceeded>`, while
+/// commands rejected by a [QuotaMiddleware] fail with
+/// [DispatchError::Rejected](crate::error::DispatchError::Rejected), holding the message of this error.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum QuotaExceeded {
+    /// The caller reached the maximum number of messages in flight.
+    Concurrency {
+        /// The caller that exceeded its quota.
+        caller: CallerId,
+        /// The maximum number of messages in flight.
+        limit: usize,
+    },
+    /// The caller reached the maximum number of messages within the current window.
+    Rate {
+        /// The caller that exceeded its quota.
+        caller: CallerId,
+        /// The maximum number of messages within a window.
+        limit: usize,
+        /// The duration after which the window of the caller is reset.
+        retry_after: Duration,
+    },
+}
+
+impl Display for QuotaExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::Concurrency { caller, limit } => {
+                write!(
+                    f,
+                    "caller {:?} exceeded its quota of {} messages in flight",
+                    caller.as_str(),
+                    limit
+                )
+            }
+            Self::Rate {
+                caller,
+                limit,
+                retry_after,
+            } => write!(
+                f,
+                "caller {:?} exceeded its quota of {} messages, retry after {:?}",
+                caller.as_str(),
+                limit,
+                retry_after
+            ),
+        }
+    }
+}
+
+impl Error for QuotaExceeded {}
+
+/// The counters maintained by a [CallerQuota] for a caller.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallerStats {
+    /// The number of messages of the caller that were accepted.
+    pub dispatched: usize,
+    /// The number of messages of the caller that were rejected.
+    pub rejected: usize,
+    /// The number of messages of the caller currently being handled.
+    pub in_flight: usize,
+}
+
+/// Enforces per-caller rate and concurrency quotas, and maintains per-caller counters.
+///
+/// Commands are subject to the quota once it is added to the command bus using [CallerQuota::middleware], and
+/// queries once their handler is wrapped using [CallerQuota::enforce]. Dispatches that are not attributed to a
+/// caller are attributed to [CallerId::anonymous]. A `CallerQuota` can be shared between buses, and between
+/// handlers of different query types, in which case the quota applies to all of them combined.
+///
+/// The state of a caller, including its counters, is forgotten once it has no message in flight, and dispatched
+/// nothing for a while, see [CallerQuota::with_idle_timeout].
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct SendEmailCommand;
+/// #
+/// # impl Command for SendEmailCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct CountEmailsQuery;
+/// #
+/// # impl Query for CountEmailsQuery {
+/// #     type Output = usize;
+/// #     type Error = QuotaExceeded;
+/// # }
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use discern::caller::CallerId;
+/// use discern::caller::CallerQuota;
+/// use discern::caller::QuotaExceeded;
+/// use discern::command_bus;
+/// use discern::context::Context;
+/// use discern::error::DispatchError;
+/// use discern::query::QueryHandlerFn;
+/// use discern::query_bus;
+///
+/// // Each caller can dispatch up to 2 messages per minute, and 1 at a time.
+/// let quota = Arc::new(
+///     CallerQuota::new()
+///         .with_rate(2, Duration::from_secs(60))
+///         .with_max_in_flight(1),
+/// );
+///
+/// let command_bus = command_bus! {
+///     middleware: [quota.middleware()],
+///     SendEmailCommand => |_| async { Ok(()) },
+/// };
+///
+/// let query_bus = query_bus! {
+///     CountEmailsQuery => quota.enforce(QueryHandlerFn::new(|_| async { Ok(1) })),
+/// };
+///
+/// let marketing = CallerId::new("marketing");
+/// for _ in 0..2 {
+///     let context = Context::new().with(marketing.clone());
+///     command_bus.dispatch_with(SendEmailCommand, context).await.unwrap();
+/// }
+///
+/// let context = Context::new().with(marketing.clone());
+/// let result = command_bus.dispatch_with(SendEmailCommand, context).await;
+/// assert!(matches!(result, Err(DispatchError::Rejected(_))));
+///
+/// // The quota is shared with the queries of the caller.
+/// let result = marketing.clone().scope(query_bus.dispatch(CountEmailsQuery)).await;
+/// assert!(matches!(result, Err(DispatchError::Handler(QuotaExceeded::Rate { .. }))));
+///
+/// let stats = quota.stats(&marketing);
+/// assert_eq!(stats.dispatched, 2);
+/// assert_eq!(stats.rejected, 2);
+///
+/// // Other callers have their own quota.
+/// let context = Context::new().with(CallerId::new("billing"));
+/// assert!(command_bus.dispatch_with(SendEmailCommand, context).await.is_ok());
+/// # });
+/// ```
+pub struct CallerQuota {
+    #[doc(hidden)]
+    max_in_flight: Option<usize>,
+    #[doc(hidden)]
+    rate: Option<(usize, Duration)>,
+    #[doc(hidden)]
+    idle_timeout: Duration,
+    #[doc(hidden)]
+    callers: Mutex<EvictingMap<CallerId, CallerState>>,
+}
+
+/// The state of a caller within a [CallerQuota].
+#[doc(hidden)]
+#[derive(Debug)]
+struct CallerState {
+    last_seen: Instant,
+    window_start: Instant,
+    window_count: usize,
+    stats: CallerStats,
+}
+
+/// The `CallerQuota` implementation.
+impl CallerQuota {
+    /// Creates a new `CallerQuota`, without any limit.
+    pub fn new() -> Self {
+        Self {
+            max_in_flight: None,
+            rate: None,
+            idle_timeout: IDLE_TIMEOUT,
+            callers: Mutex::new(EvictingMap::new()),
+        }
+    }
+
+    /// Limits the number of messages each caller can have in flight.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_in_flight` - The maximum number of messages in flight.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    /// Limits the number of messages each caller can dispatch within a time window.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of messages within a window.
+    /// * `window` - The duration of a window, which starts with the first message of the caller.
+    pub fn with_rate(mut self, limit: usize, window: Duration) -> Self {
+        self.rate = Some((limit, window));
+        self
+    }
+
+    /// Sets the duration after which the state of a caller without messages in flight is forgotten, along with its
+    /// counters, so that the quota doesn't grow with every caller ever seen.
+    ///
+    /// Defaults to 5 minutes, and is extended to the rate window, if longer, so that forgetting a caller never
+    /// resets its rate quota early. Idle callers are forgotten as new callers are admitted, once the number of
+    /// callers doubled since they were last forgotten, so that admitting a message doesn't scan every caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `idle_timeout` - The duration since the last message of a caller after which it is forgotten.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct SendEmailCommand;
+    /// #
+    /// # impl Command for SendEmailCommand {
+    /// #     type Metadata = ();
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// use discern::caller::CallerId;
+    /// use discern::caller::CallerQuota;
+    /// use discern::command_bus;
+    /// use discern::context::Context;
+    ///
+    /// let quota = Arc::new(CallerQuota::new().with_idle_timeout(Duration::ZERO));
+    ///
+    /// let command_bus = command_bus! {
+    ///     middleware: [quota.middleware()],
+    ///     SendEmailCommand => |_| async { Ok(()) },
+    /// };
+    ///
+    /// for team in 0..64 {
+    ///     let context = Context::new().with(CallerId::new(format!("team-{}", team)));
+    ///     command_bus.dispatch_with(SendEmailCommand, context).await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(quota.callers().len(), 64);
+    ///
+    /// let context = Context::new().with(CallerId::new("billing"));
+    /// command_bus.dispatch_with(SendEmailCommand, context).await.unwrap();
+    ///
+    /// // The idle callers were forgotten when the billing caller was admitted.
+    /// assert_eq!(quota.callers().len(), 1);
+    /// assert_eq!(quota.stats(&CallerId::new("team-0")).dispatched, 0);
+    /// # });
+    /// ```
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Creates a middleware enforcing this quota on the commands dispatched through a command bus, see
+    /// [QuotaMiddleware].
+    pub fn middleware(self: &Arc<Self>) -> QuotaMiddleware {
+        QuotaMiddleware {
+            quota: self.clone(),
+        }
+    }
+
+    /// Wraps the given query handler, enforcing this quota, see [QuotaHandler].
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to wrap.
+    pub fn enforce<H>(self: &Arc<Self>, handler: H) -> QuotaHandler<H> {
+        QuotaHandler {
+            handler,
+            quota: self.clone(),
+        }
+    }
+
+    /// Returns the counters of the given caller.
+    pub fn stats(&self, caller: &CallerId) -> CallerStats {
+        self.lock()
+            .get(caller)
+            .map(|state| state.stats)
+            .unwrap_or_default()
+    }
+
+    /// Returns the counters of every caller seen by this quota.
+    pub fn callers(&self) -> Vec<(CallerId, CallerStats)> {
+        self.lock()
+            .iter()
+            .map(|(caller, state)| (caller.clone(), state.stats))
+            .collect()
+    }
+
+    /// Admits a message of the given caller, or returns [QuotaExceeded] if the caller exceeded its quota.
+    fn admit(&self, caller: CallerId) -> Result<Admitted<'_>, QuotaExceeded> {
+        let now = Instant::now();
+
+        let idle_timeout = match self.rate {
+            Some((_, window)) => self.idle_timeout.max(window),
+            None => self.idle_timeout,
+        };
+
+        let mut callers = self.lock();
+        if !callers.contains_key(&caller) {
+            callers.evict(|_, state| {
+                state.stats.in_flight > 0 || now.duration_since(state.last_seen) < idle_timeout
+            });
+        }
+
+        let state = callers
+            .entry(caller.clone())
+            .or_insert_with(|| CallerState {
+                last_seen: now,
+                window_start: now,
+                window_count: 0,
+                stats: CallerStats::default(),
+            });
+
+        state.last_seen = now;
+
+        if let Some(limit) = self.max_in_flight {
+            if state.stats.in_flight >= limit {
+                state.stats.rejected += 1;
+
+                return Err(QuotaExceeded::Concurrency { caller, limit });
+            }
+        }
+
+        if let Some((limit, window)) = self.rate {
+            let elapsed = now.duration_since(state.window_start);
+            if elapsed >= window {
+                state.window_start = now;
+                state.window_count = 0;
+            } else if state.window_count >= limit {
+                state.stats.rejected += 1;
+
+                return Err(QuotaExceeded::Rate {
+                    caller,
+                    limit,
+                    retry_after: window - elapsed,
+                });
+            }
+
+            state.window_count += 1;
+        }
+
+        state.stats.dispatched += 1;
+        state.stats.in_flight += 1;
+
+        Ok(Admitted {
+            quota: self,
+            caller,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, EvictingMap<CallerId, CallerState>> {
+        self.callers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for CallerQuota {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debug implementation for `CallerQuota`
+impl Debug for CallerQuota {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CallerQuota")
+            .field("max_in_flight", &self.max_in_flight)
+            .field("rate", &self.rate)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+/// A message admitted by a [CallerQuota], counted as in flight until dropped.
+#[doc(hidden)]
+struct Admitted<'a> {
+    quota: &'a CallerQuota,
+    caller: CallerId,
+}
+
+impl Drop for Admitted<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.quota.lock().get_mut(&self.caller) {
+            state.stats.in_flight -= 1;
+        }
+    }
+}
+
+/// A middleware that enforces a [CallerQuota] on commands.
+///
+/// `QuotaMiddleware` is created using [CallerQuota::middleware]. The caller of a command is read from the
+/// [Context](crate::context::Context) of its dispatch, where it is attached using
+/// [CommandBus::dispatch_with](crate::command::CommandBus::dispatch_with), or by a middleware running before this
+/// one. Commands whose caller exceeded its quota are rejected with
+/// [DispatchError::Rejected](crate::error::DispatchError::Rejected), without being handled, see the example of
+/// [CallerQuota].
+pub struct QuotaMiddleware {
+    #[doc(hidden)]
+    quota: Arc<CallerQuota>,
+}
+
+#[async_trait]
+impl CommandMiddleware for QuotaMiddleware {
+    async fn handle(&self, next: Next<'_>) -> Outcome {
+        let caller = next
+            .context()
+            .get::<CallerId>()
+            .cloned()
+            .unwrap_or_else(CallerId::anonymous);
+
+        let _admitted = match self.quota.admit(caller) {
+            Ok(admitted) => admitted,
+            Err(error) => return Outcome::Rejected(error.to_string()),
+        };
+
+        next.run().await
+    }
+}
+
+/// Debug implementation for `QuotaMiddleware`
+impl Debug for QuotaMiddleware {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("QuotaMiddleware")
+            .field("quota", &self.quota)
+            .finish()
+    }
+}
+
+/// A handler that enforces a [CallerQuota] on queries.
+///
+/// `QuotaHandler` is created using [CallerQuota::enforce], and implements [QueryHandler] for every query the
+/// wrapped handler implements it for. Queries don't carry a [Context](crate::context::Context), so their caller is
+/// the one of the [CallerId::scope] they are dispatched within. Queries whose caller exceeded its quota are rejected with
+/// [QuotaExceeded], converted into the error type of the query, without being handled.
+pub struct QuotaHandler<H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    quota: Arc<CallerQuota>,
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for QuotaHandler<H>
+where
+    Q: Query,
+    Q::Error: From<QuotaExceeded>,
+    H: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        let caller = CallerId::current().unwrap_or_else(CallerId::anonymous);
+        let _admitted = self.quota.admit(caller)?;
+
+        self.handler.handle(query).await
+    }
+}
+
+/// Debug implementation for `QuotaHandler`
+impl<H> Debug for QuotaHandler<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("QuotaHandler")
+            .field("quota", &self.quota)
+            .finish()
+    }
+}
@@ -0,0 +1,234 @@
+//! The `coalesce` module provides a handler wrapper that coalesces identical in-flight commands.
+//!
+//! Some idempotent commands, e.g. "refresh the cache for X", are frequently dispatched again while a previous
+//! dispatch is still being handled. Handling each of them separately wastes resources, since they all produce the
+//! same outcome. The [CoalescingHandler] coalesces such dispatches onto a single execution, and shares its result.
+//!
+//! - [Coalesce]: Identifies commands that can be coalesced.
+//! - [CoalescingHandler]: A handler that coalesces identical in-flight commands.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use async_lock::OnceCell;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::command::Idempotent;
+
+/// The `Coalesce` trait identifies commands that can be coalesced.
+///
+/// Commands with equal keys are considered identical: while one of them is being handled, dispatching
+/// another one waits for the first to complete, and returns its result instead of being handled.
+///
+/// Only idempotent commands can be coalesced, so the [CoalescingHandler] only handles commands implementing
+/// [Idempotent] as well.
+///
+/// # Example
+///
+/// ```
+/// use discern::coalesce::Coalesce;
+/// use discern::command::Command;
+/// use discern::command::Idempotent;
+///
+/// #[derive(Debug)]
+/// struct RefreshUserCacheCommand {
+///     user_id: u64,
+/// }
+///
+/// impl Command for RefreshUserCacheCommand {
+///     type Metadata = ();
+///     type Error = String;
+/// }
+///
+/// impl Idempotent for RefreshUserCacheCommand {}
+///
+/// impl Coalesce for RefreshUserCacheCommand {
+///     type Key = u64;
+///
+///     fn coalesce_key(&self) -> u64 {
+///         self.user_id
+///     }
+/// }
+/// ```
+pub trait Coalesce: Command {
+    /// The key identifying identical commands.
+    type Key: Hash + Eq + Clone + Send + Sync + 'static;
+
+    /// Returns the key identifying this command.
+    fn coalesce_key(&self) -> Self::Key;
+}
+
+/// A handler that coalesces identical in-flight commands.
+///
+/// The result of the command is shared with every coalesced dispatch, and therefore, both the metadata
+/// and the error types of the command must implement `Clone`. Coalescing assumes that handling the command once
+/// has the same effect as handling each of the coalesced dispatches, so the command must implement [Idempotent].
+///
+/// If the dispatch handling the command is cancelled, one of the coalesced dispatches takes over,
+/// and handles its own command instead.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::coalesce::Coalesce;
+/// # use discern::command::Command;
+/// # use discern::command::Idempotent;
+/// #
+/// # #[derive(Debug)]
+/// # struct RefreshUserCacheCommand {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Command for RefreshUserCacheCommand {
+/// #     type Metadata = ();
+/// #     type Error = String;
+/// # }
+/// #
+/// # impl Idempotent for RefreshUserCacheCommand {}
+/// #
+/// # impl Coalesce for RefreshUserCacheCommand {
+/// #     type Key = u64;
+/// #
+/// #     fn coalesce_key(&self) -> u64 {
+/// #         self.user_id
+/// #     }
+/// # }
+/// use std::sync::atomic::AtomicUsize;
+/// use std::sync::atomic::Ordering;
+/// use std::sync::Arc;
+///
+/// use discern::async_trait;
+/// use discern::coalesce::CoalescingHandler;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+///
+/// struct RefreshUserCacheCommandHandler {
+///     refreshes: Arc<AtomicUsize>,
+/// }
+///
+/// #[async_trait]
+/// impl CommandHandler<RefreshUserCacheCommand> for RefreshUserCacheCommandHandler {
+///     async fn handle(&self, _command: RefreshUserCacheCommand) -> Result<(), String> {
+///         self.refreshes.fetch_add(1, Ordering::SeqCst);
+///         // Refresh the cache...
+///         # tokio::task::yield_now().await;
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let refreshes = Arc::new(AtomicUsize::new(0));
+/// let handler = CoalescingHandler::new(RefreshUserCacheCommandHandler {
+///     refreshes: refreshes.clone(),
+/// });
+///
+/// let command_bus = command_bus! {
+///     RefreshUserCacheCommand => handler,
+/// };
+///
+/// let (first, second) = tokio::join!(
+///     command_bus.dispatch(RefreshUserCacheCommand { user_id: 1 }),
+///     command_bus.dispatch(RefreshUserCacheCommand { user_id: 1 }),
+/// );
+///
+/// assert!(first.is_ok());
+/// assert!(second.is_ok());
+///
+/// // Both dispatches were handled by a single execution.
+/// assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+/// # assert_eq!(command_bus.stats().dispatched, 2);
+/// # });
+/// ```
+pub struct CoalescingHandler<C: Coalesce, H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    in_flight: Mutex<HashMap<C::Key, Arc<Execution<C>>>>,
+}
+
+/// The shared result of a coalesced execution.
+#[doc(hidden)]
+type Execution<C> = OnceCell<Result<<C as Command>::Metadata, <C as Command>::Error>>;
+
+/// The `CoalescingHandler` implementation.
+impl<C: Coalesce, H: CommandHandler<C>> CoalescingHandler<C, H> {
+    /// Creates a new `CoalescingHandler` wrapping the given handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler handling the coalesced commands.
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the number of distinct commands currently being handled.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+#[async_trait]
+impl<C, H> CommandHandler<C> for CoalescingHandler<C, H>
+where
+    C: Coalesce + Idempotent,
+    C::Metadata: Clone,
+    C::Error: Clone,
+    H: CommandHandler<C>,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        let key = command.coalesce_key();
+        let execution = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        execution
+            .get_or_init(|| async {
+                let result = self.handler.handle(command).await;
+
+                // Stop coalescing onto this execution, dispatches waiting for it already hold a reference to it.
+                let mut in_flight = self
+                    .in_flight
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                if in_flight
+                    .get(&key)
+                    .is_some_and(|current| Arc::ptr_eq(current, &execution))
+                {
+                    in_flight.remove(&key);
+                }
+
+                result
+            })
+            .await
+            .clone()
+    }
+}
+
+/// Debug implementation for `CoalescingHandler`
+impl<C: Coalesce, H: CommandHandler<C>> Debug for CoalescingHandler<C, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CoalescingHandler")
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
@@ -6,20 +6,39 @@
 //! The `CommandBus` is responsible for dispatching commands to their respective handlers. It utilizes the
 //! `CommandHandlerRegistry` from the [registry](crate::registry) module to manage and retrieve the appropriate handlers.
 //!
-//! - [Command]: Represents a command in the system.
+//! - [Command]: Represents a command in the system, and derives it when the `derive` feature is enabled.
+//! - [SimpleCommand]: Represents a command that returns no metadata.
+//! - [Idempotent]: Marks commands that can safely be handled more than once.
 //! - [CommandHandler]: Trait for handling commands.
+//! - [CommandHandlerFn]: A command handler defined by a closure.
 //! - [CommandBus]: Dispatches commands to the appropriate handlers.
 //!
 //! # See Also
 //!
 //! - [CommandHandlerRegistry]: Manages command handlers.
 
-use std::any::Any;
-use std::fmt::Debug;
-use std::sync::Arc;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::any::Any;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::future::Future;
 
 use crate::async_trait;
+use crate::context::Context;
+use crate::diagnostics::MissingHandler;
+use crate::error::DispatchError;
+use crate::middleware::CommandMiddleware;
+use crate::middleware::Endpoint;
+use crate::middleware::Outcome;
+use crate::middleware::Pipeline;
 use crate::registry::CommandHandlerRegistry;
+use crate::registry::SharedRegistry;
+use crate::stats::BusStats;
+use crate::stats::Counters;
+#[cfg(feature = "std")]
+use crate::switchboard::Switchboard;
 
 /// The `Command` trait represents a command that changes the state of the system.
 ///
@@ -58,6 +77,238 @@ pub trait Command: Send + Sync + Any + Debug {
     ///
     /// This type must implement the `Debug`, `Send`, and `Sync` traits.
     type Error: Debug + Send + Sync;
+
+    /// Returns the name of the command, used to identify it in logs, metrics, and when routing by name.
+    ///
+    /// Defaults to the type name of the command, which changes whenever the type is moved, or renamed. Commands
+    /// implementing [MessageName](crate::message::MessageName) should return its name instead, which
+    /// `#[derive(Command)]` does.
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        core::any::type_name::<Self>()
+    }
+
+    /// Returns the descriptor of the command, see [MessageDescriptor](crate::message::MessageDescriptor).
+    ///
+    /// Defaults to the name of the command, at version `1`, with an unknown schema hash. Commands implementing
+    /// [MessageName](crate::message::MessageName) should return
+    /// [MessageDescriptor::of](crate::message::MessageDescriptor::of) instead, which `#[derive(Command)]` does.
+    fn descriptor() -> crate::message::MessageDescriptor
+    where
+        Self: Sized,
+    {
+        crate::message::MessageDescriptor::new(Self::name(), crate::message::MessageKind::Command)
+    }
+
+    /// Returns the permissions required to dispatch the command, see
+    /// [AuthorizationMiddleware](crate::authorization::AuthorizationMiddleware).
+    ///
+    /// Defaults to no permissions. Commands implementing
+    /// [RequiredPermissions](crate::authorization::RequiredPermissions) should return its permissions instead,
+    /// which `#[derive(Command)]` does.
+    fn required_permissions() -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Takes the follow-up commands out of the metadata of the command, see [Followups](crate::followup::Followups).
+    ///
+    /// Once the command succeeded, the [CommandBus] dispatches the returned commands, before returning the metadata
+    /// of the command. Follow-up commands are dispatched with an empty [Context], and their failures don't affect the
+    /// result of the command, see [CommandBus::failed_followups], and [Chain](crate::followup::Chain).
+    ///
+    /// Defaults to no follow-up commands.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use std::sync::atomic::AtomicU64;
+    /// # use std::sync::atomic::Ordering;
+    /// # use std::sync::Arc;
+    /// use discern::async_trait;
+    /// use discern::command::Command;
+    /// use discern::command::CommandHandler;
+    /// use discern::command_bus;
+    /// use discern::followup::Followups;
+    ///
+    /// #[derive(Debug)]
+    /// struct SendWelcomeEmailCommand {
+    ///     user_id: u64,
+    /// }
+    ///
+    /// impl Command for SendWelcomeEmailCommand {
+    ///     type Metadata = ();
+    ///     type Error = std::io::Error;
+    /// }
+    ///
+    /// #[derive(Debug)]
+    /// struct CreateUserCommand {
+    ///     username: String,
+    /// }
+    ///
+    /// #[derive(Debug)]
+    /// struct CreateUserMetadata {
+    ///     user_id: u64,
+    ///     followups: Followups,
+    /// }
+    ///
+    /// impl Command for CreateUserCommand {
+    ///     type Metadata = CreateUserMetadata;
+    ///     type Error = std::io::Error;
+    ///
+    ///     fn followups(metadata: &mut CreateUserMetadata) -> Followups {
+    ///         std::mem::take(&mut metadata.followups)
+    ///     }
+    /// }
+    ///
+    /// struct CreateUserCommandHandler;
+    ///
+    /// #[async_trait]
+    /// impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+    ///     async fn handle(&self, command: CreateUserCommand) -> Result<CreateUserMetadata, std::io::Error> {
+    ///         let user_id = 1;
+    ///
+    ///         let mut followups = Followups::new();
+    ///         followups.push(SendWelcomeEmailCommand { user_id });
+    ///
+    ///         Ok(CreateUserMetadata { user_id, followups })
+    ///     }
+    /// }
+    ///
+    /// struct SendWelcomeEmailCommandHandler {
+    ///     sent: Arc<AtomicU64>,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl CommandHandler<SendWelcomeEmailCommand> for SendWelcomeEmailCommandHandler {
+    ///     async fn handle(&self, command: SendWelcomeEmailCommand) -> Result<(), std::io::Error> {
+    ///         self.sent.store(command.user_id, Ordering::SeqCst);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let sent = Arc::new(AtomicU64::new(0));
+    /// let command_bus = command_bus! {
+    ///     CreateUserCommand => CreateUserCommandHandler,
+    ///     SendWelcomeEmailCommand => SendWelcomeEmailCommandHandler { sent: sent.clone() },
+    /// };
+    ///
+    /// let metadata = command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+    ///
+    /// assert_eq!(metadata.user_id, 1);
+    /// assert_eq!(sent.load(Ordering::SeqCst), 1);
+    /// assert_eq!(command_bus.failed_followups(), 0);
+    /// # });
+    /// ```
+    fn followups(_metadata: &mut Self::Metadata) -> crate::followup::Followups
+    where
+        Self: Sized,
+    {
+        crate::followup::Followups::new()
+    }
+}
+
+/// Derives the [Command] trait, using the `#[command(metadata = ..., error = ...)]` attribute.
+#[cfg(feature = "derive")]
+pub use discern_derive::Command;
+
+/// The `Idempotent` trait marks commands that can safely be handled more than once.
+///
+/// Handling an idempotent command several times has the same effect as handling it once, e.g. "set the email of
+/// user X to Y", as opposed to "charge customer X". Handler wrappers that may handle a command more than once,
+/// such as [HedgedHandler](crate::hedge::HedgedHandler), only accept commands implementing this trait.
+///
+/// # Example
+///
+/// ```
+/// use discern::command::Command;
+/// use discern::command::Idempotent;
+///
+/// #[derive(Debug, Clone)]
+/// struct SetUserEmailCommand {
+///    user_id: u64,
+///    email: String,
+/// }
+///
+/// impl Command for SetUserEmailCommand {
+///   type Metadata = ();
+///   type Error = std::io::Error;
+/// }
+///
+/// impl Idempotent for SetUserEmailCommand {}
+/// ```
+pub trait Idempotent: Command {}
+
+/// The `SimpleCommand` trait represents a command that returns no metadata.
+///
+/// Many commands only report whether they succeeded. Implementing `SimpleCommand` instead of [Command] only requires
+/// declaring the error type, the metadata type is `()`.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::async_trait;
+/// use discern::command::CommandHandler;
+/// use discern::command::SimpleCommand;
+/// use discern::command_bus;
+///
+/// #[derive(Debug)]
+/// struct DeleteUserCommand {
+///    user_id: u64,
+/// }
+///
+/// impl SimpleCommand for DeleteUserCommand {
+///   type Error = std::io::Error;
+/// }
+///
+/// struct DeleteUserCommandHandler;
+///
+/// #[async_trait]
+/// impl CommandHandler<DeleteUserCommand> for DeleteUserCommandHandler {
+///    async fn handle(&self, command: DeleteUserCommand) -> Result<(), std::io::Error> {
+///       // Delete the user.
+///       Ok(())
+///   }
+/// }
+///
+/// let command_bus = command_bus! {
+///     DeleteUserCommand => DeleteUserCommandHandler,
+/// };
+///
+/// assert!(command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await.is_ok());
+/// # });
+/// ```
+pub trait SimpleCommand: Send + Sync + Any + Debug {
+    /// The error type that is returned if the command fails.
+    ///
+    /// This type must implement the `Debug`, `Send`, and `Sync` traits.
+    type Error: Debug + Send + Sync;
+
+    /// Returns the name of the command, see [Command::name].
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        core::any::type_name::<Self>()
+    }
+}
+
+impl<C: SimpleCommand> Command for C {
+    type Metadata = ();
+    type Error = <C as SimpleCommand>::Error;
+
+    fn name() -> &'static str {
+        <C as SimpleCommand>::name()
+    }
 }
 
 /// The `CommandHandler` trait represents a handler that processes a command.
@@ -103,6 +354,205 @@ pub trait CommandHandler<C: Command>: Send + Sync {
     async fn handle(&self, command: C) -> Result<C::Metadata, C::Error>;
 }
 
+/// A command handler defined by a closure.
+///
+/// The closure receives the command, and returns a future resolving to the result of the command. Closures can also
+/// be registered directly using the [command_bus](crate::command_bus) and
+/// [command_registry](crate::command_registry) macros.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #    username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #   type Metadata = u64;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::command::CommandBus;
+/// use discern::command::CommandHandlerFn;
+/// use discern::registry::CommandHandlerRegistry;
+///
+/// let mut registry = CommandHandlerRegistry::new();
+/// registry.register(CommandHandlerFn::new(|command: CreateUserCommand| async move {
+///     Ok(command.username.len() as u64)
+/// }));
+///
+/// let command_bus = CommandBus::new(registry);
+///
+/// let user_id = command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+///
+/// assert_eq!(user_id, 5);
+/// # });
+/// ```
+pub struct CommandHandlerFn<F> {
+    #[doc(hidden)]
+    handler: F,
+}
+
+/// The `CommandHandlerFn` implementation.
+impl<F> CommandHandlerFn<F> {
+    /// Creates a new `CommandHandlerFn` from the given closure.
+    pub fn new<C, Fut>(handler: F) -> Self
+    where
+        C: Command,
+        F: Fn(C) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<C::Metadata, C::Error>> + Send,
+    {
+        Self { handler }
+    }
+}
+
+#[async_trait]
+impl<C, F, Fut> CommandHandler<C> for CommandHandlerFn<F>
+where
+    C: Command,
+    F: Fn(C) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<C::Metadata, C::Error>> + Send,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        (self.handler)(command).await
+    }
+}
+
+/// Debug implementation for `CommandHandlerFn`
+impl<F> Debug for CommandHandlerFn<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CommandHandlerFn").finish()
+    }
+}
+
+/// The `SyncCommandHandler` trait represents a handler that processes a command synchronously.
+///
+/// Synchronous handlers are useful for simple handlers ( e.g. in-memory maps, pure computation ) that
+/// do not need to await anything. They are registered using
+/// [CommandHandlerRegistry::register_sync], and are invoked directly by the `CommandBus`, without
+/// allocating a future for each dispatch.
+///
+/// Synchronous handlers don't implement [CommandHandler], as a blanket implementation would overlap with every
+/// other implementation of it, use the [SyncHandler](crate::sync::SyncHandler) adapter where an asynchronous handler
+/// is required.
+///
+/// # Example
+///
+/// ```
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct IncrementCounterCommand;
+/// #
+/// # impl Command for IncrementCounterCommand {
+/// #   type Metadata = u64;
+/// #   type Error = std::io::Error;
+/// # }
+/// use std::sync::atomic::AtomicU64;
+/// use std::sync::atomic::Ordering;
+///
+/// use discern::command::SyncCommandHandler;
+///
+/// struct IncrementCounterCommandHandler {
+///     counter: AtomicU64,
+/// }
+///
+/// impl SyncCommandHandler<IncrementCounterCommand> for IncrementCounterCommandHandler {
+///     fn handle(&self, _command: IncrementCounterCommand) -> Result<u64, std::io::Error> {
+///         Ok(self.counter.fetch_add(1, Ordering::SeqCst) + 1)
+///     }
+/// }
+///
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::command::CommandBus;
+/// use discern::registry::CommandHandlerRegistry;
+///
+/// let mut registry = CommandHandlerRegistry::new();
+/// registry.register_sync(IncrementCounterCommandHandler {
+///     counter: AtomicU64::new(0),
+/// });
+///
+/// let command_bus = CommandBus::new(registry);
+///
+/// assert_eq!(command_bus.dispatch(IncrementCounterCommand).await.unwrap(), 1);
+/// assert_eq!(command_bus.dispatch(IncrementCounterCommand).await.unwrap(), 2);
+/// # });
+/// ```
+#[cfg(feature = "sync")]
+pub trait SyncCommandHandler<C: Command>: Send + Sync {
+    /// Handles the processing of a command.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to be processed.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, which includes the metadata or an error.
+    fn handle(&self, command: C) -> Result<C::Metadata, C::Error>;
+}
+
+/// The `ContextualCommandHandler` trait represents a handler that processes a command along with the
+/// [Context] it was dispatched with.
+///
+/// Contextual handlers are registered using [CommandHandlerRegistry::register_contextual], and receive the values
+/// attached to the dispatch by the caller, using [CommandBus::dispatch_with], and by middleware, using
+/// [Next::context_mut](crate::middleware::Next::context_mut). Commands dispatched without a context are handled
+/// with an empty one.
+///
+/// # Example
+///
+/// ```
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateProjectCommand {
+/// #    name: String,
+/// # }
+/// #
+/// # impl Command for CreateProjectCommand {
+/// #   type Metadata = String;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::command::ContextualCommandHandler;
+/// use discern::context::Context;
+///
+/// struct TenantId(u64);
+///
+/// struct CreateProjectCommandHandler;
+///
+/// #[async_trait]
+/// impl ContextualCommandHandler<CreateProjectCommand> for CreateProjectCommandHandler {
+///     async fn handle(&self, command: CreateProjectCommand, context: &Context) -> Result<String, std::io::Error> {
+///         let Some(TenantId(tenant)) = context.get::<TenantId>() else {
+///             return Err(std::io::ErrorKind::PermissionDenied.into());
+///         };
+///
+///         Ok(format!("{}/{}", tenant, command.name))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait ContextualCommandHandler<C: Command>: Send + Sync {
+    /// Handles the processing of a command.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to be processed.
+    /// * `context` - The context the command was dispatched with.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, which includes the metadata or an error.
+    async fn handle(&self, command: C, context: &Context) -> Result<C::Metadata, C::Error>;
+}
+
 /// The `CommandBus` is responsible for dispatching commands to their respective handlers.
 ///
 /// # Example
@@ -172,7 +622,23 @@ pub trait CommandHandler<C: Command>: Send + Sync {
 #[derive(Clone, Debug)]
 pub struct CommandBus {
     #[doc(hidden)]
-    registry: Arc<CommandHandlerRegistry>,
+    registry: Arc<SharedRegistry<CommandHandlerRegistry>>,
+    #[doc(hidden)]
+    counters: Arc<Counters>,
+    #[doc(hidden)]
+    middleware: Arc<Pipeline>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    switchboard: Arc<Switchboard>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    permits: Option<Arc<async_lock::Semaphore>>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    cache: Option<crate::cache::QueryCache>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    events: Option<crate::event::EventBus>,
 }
 
 /// The `CommandBus` implementation.
@@ -192,23 +658,70 @@ impl CommandBus {
     /// ```
     pub fn new(registry: CommandHandlerRegistry) -> Self {
         Self {
-            registry: Arc::new(registry),
+            registry: Arc::new(SharedRegistry::new(registry)),
+            counters: Arc::new(Counters::default()),
+            middleware: Arc::new(Pipeline::default()),
+            #[cfg(feature = "std")]
+            switchboard: Arc::new(Switchboard::new()),
+            #[cfg(feature = "std")]
+            permits: None,
+            #[cfg(feature = "std")]
+            cache: None,
+            #[cfg(feature = "std")]
+            events: None,
         }
     }
 
-    /// Dispatches a command to its respective handler.
+    /// Creates a new `CommandBus` instance, with the handlers registered in the inventory.
+    ///
+    /// Handlers are registered in the inventory using the
+    /// [command_handler](crate::inventory::command_handler) attribute, see the [inventory](crate::inventory) module.
+    #[cfg(feature = "inventory")]
+    pub fn from_inventory() -> Self {
+        Self::new(CommandHandlerRegistry::from_inventory())
+    }
+
+    /// Adds a middleware wrapping the handling of every command, see [CommandMiddleware].
+    ///
+    /// Middleware are run in the order they were added, the first one wrapping all the others. Clones of this
+    /// `CommandBus` made before adding a middleware are not affected by it, so middleware should be added before
+    /// the bus is shared.
     ///
     /// # Arguments
     ///
-    /// * `command` - The command to dispatch.
+    /// * `middleware` - The middleware to add.
+    pub fn with_middleware(mut self, middleware: impl CommandMiddleware + 'static) -> Self {
+        Arc::make_mut(&mut self.middleware).push(Arc::new(middleware));
+        self
+    }
+
+    /// Records the time spent in each middleware, and in the handler, of every dispatch in the given timeline.
     ///
-    /// # Returns
+    /// See the [timeline](crate::timeline) module.
     ///
-    /// The result of the command handler.
+    /// # Arguments
+    ///
+    /// * `timeline` - The timeline recording the dispatches.
+    #[cfg(feature = "std")]
+    pub fn with_timeline(mut self, timeline: crate::timeline::Timeline) -> Self {
+        Arc::make_mut(&mut self.middleware).set_timeline(timeline);
+        self
+    }
+
+    /// Limits the number of commands handled concurrently by this `CommandBus`.
+    ///
+    /// Once `max_in_flight` commands are being handled, further dispatches wait for one of them to complete
+    /// before their handler is called, which protects shared resources, e.g. a database connection pool, from
+    /// load spikes. Middleware run before the wait, and clones of this `CommandBus` made afterwards share
+    /// the limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_in_flight` - The maximum number of commands handled concurrently.
     ///
     /// # Panics
     ///
-    /// This method will panic if the command handler is not found.
+    /// This method will panic if `max_in_flight` is zero.
     ///
     /// # Example
     ///
@@ -216,38 +729,426 @@ impl CommandBus {
     /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
     /// # rt.block_on(async {
     /// # use discern::command::Command;
-    /// # use discern::async_trait;
-    /// # use discern::command::CommandHandler;
-    /// #
-    /// # #[derive(Debug)]
-    /// # enum CreateUserError {
-    /// #    UsernameAlreadyExists,
-    /// #    EmailAlreadyExists,
-    /// # }
     /// #
     /// # #[derive(Debug)]
-    /// # struct CreateUserCommand {
-    /// #    username: String,
-    /// #    email: String,
+    /// # struct ChargeCardCommand {
+    /// #     amount: u64,
     /// # }
     /// #
-    /// # impl Command for CreateUserCommand {
-    /// #   // The identifier of the newly created user.
-    /// #   type Metadata = u64;
-    /// #   // The error type that is returned if the command fails.
-    /// #   type Error = CreateUserError;
-    /// # }
-    /// #
-    /// # struct CreateUserCommandHandler;
-    /// #
-    /// # #[async_trait]
-    /// # impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
-    /// #    async fn handle(&self, command: CreateUserCommand) -> Result<u64, CreateUserError> {
-    /// #       // Create a new user.
-    /// #       Ok(1)
-    /// #   }
+    /// # impl Command for ChargeCardCommand {
+    /// #     type Metadata = u64;
+    /// #     type Error = std::io::Error;
     /// # }
-    /// use discern::command_registry;
+    /// use discern::command::CommandBus;
+    /// use discern::command_registry;
+    ///
+    /// let registry = command_registry! {
+    ///     ChargeCardCommand => |command| async move { Ok(command.amount) },
+    /// };
+    /// let command_bus = CommandBus::new(registry).with_max_in_flight(8);
+    ///
+    /// let (first, second) = tokio::join!(
+    ///     command_bus.dispatch(ChargeCardCommand { amount: 10 }),
+    ///     command_bus.dispatch(ChargeCardCommand { amount: 20 }),
+    /// );
+    ///
+    /// assert_eq!(first.unwrap() + second.unwrap(), 30);
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        assert!(
+            max_in_flight > 0,
+            "the maximum number of commands in flight must be greater than zero"
+        );
+
+        self.permits = Some(Arc::new(async_lock::Semaphore::new(max_in_flight)));
+        self
+    }
+
+    /// Removes the query results made stale by the commands dispatched through this `CommandBus` from the given
+    /// cache, see [InvalidatesCache](crate::cache::InvalidatesCache).
+    ///
+    /// Only the command types registered using
+    /// [QueryCache::invalidated_by](crate::cache::QueryCache::invalidated_by) invalidate the cache, once their
+    /// handler succeeded.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache of the query results, usually shared with the [QueryBus](crate::query::QueryBus).
+    #[cfg(feature = "std")]
+    pub fn with_cache(mut self, cache: crate::cache::QueryCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Delivers the events published by handlers using [Context::publish] to the given event bus, once their
+    /// command succeeded.
+    ///
+    /// Events whose subscribers fail are counted, see [CommandBus::undelivered_events].
+    ///
+    /// # Arguments
+    ///
+    /// * `event_bus` - The event bus the events are published to.
+    #[cfg(feature = "std")]
+    pub fn with_events(mut self, event_bus: crate::event::EventBus) -> Self {
+        self.events = Some(event_bus);
+        self
+    }
+
+    /// Waits for a permit to handle a command, if the number of commands in flight is limited.
+    #[cfg(feature = "std")]
+    async fn permit(&self) -> Option<async_lock::SemaphoreGuardArc> {
+        match &self.permits {
+            Some(permits) => Some(permits.acquire_arc().await),
+            None => None,
+        }
+    }
+
+    /// Returns the number of events published using [Context::publish] by succeeded commands, which were not
+    /// delivered to every subscriber, across this `CommandBus`, and its clones.
+    ///
+    /// An event is undelivered when any of its subscribers fails, or when the `CommandBus` has no event bus, see
+    /// [CommandBus::with_events].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// # use discern::event::Event;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct CreateUserCommand;
+    /// #
+    /// # impl Command for CreateUserCommand {
+    /// #     type Metadata = ();
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct UserCreatedEvent;
+    /// #
+    /// # impl Event for UserCreatedEvent {
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// use discern::async_trait;
+    /// use discern::command::CommandBus;
+    /// use discern::command::ContextualCommandHandler;
+    /// use discern::context::Context;
+    /// use discern::event::EventHandler;
+    /// use discern::event_bus;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// struct CreateUserCommandHandler;
+    ///
+    /// #[async_trait]
+    /// impl ContextualCommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+    ///     async fn handle(&self, _command: CreateUserCommand, context: &Context) -> Result<(), std::io::Error> {
+    ///         context.publish(UserCreatedEvent);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// struct SendWelcomeEmail;
+    ///
+    /// #[async_trait]
+    /// impl EventHandler<UserCreatedEvent> for SendWelcomeEmail {
+    ///     async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+    ///         Err(std::io::ErrorKind::ConnectionRefused.into())
+    ///     }
+    /// }
+    ///
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.register_contextual(CreateUserCommandHandler);
+    ///
+    /// let command_bus = CommandBus::new(registry).with_events(event_bus! {
+    ///     UserCreatedEvent => SendWelcomeEmail,
+    /// });
+    ///
+    /// // The command took effect, even though the welcome email was not sent.
+    /// assert!(command_bus.dispatch(CreateUserCommand).await.is_ok());
+    /// assert_eq!(command_bus.undelivered_events(), 1);
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn undelivered_events(&self) -> usize {
+        self.counters.undelivered()
+    }
+
+    /// Returns the number of follow-up commands that failed, or were skipped, since the bus was created, see
+    /// [Command::followups].
+    ///
+    /// Follow-up commands don't affect the result of the command that returned them, so their failures are counted
+    /// instead, and traced when the `tracing` feature is enabled. Use a [Chain](crate::followup::Chain) to get the
+    /// failures of the follow-up commands of a given command.
+    pub fn failed_followups(&self) -> usize {
+        self.counters.failed_followups()
+    }
+
+    /// Returns a snapshot of the counters maintained by the `CommandBus`.
+    ///
+    /// Clones of a `CommandBus` share the same counters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::command::CommandBus;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// let command_bus = CommandBus::new(CommandHandlerRegistry::new());
+    ///
+    /// assert_eq!(command_bus.stats().dispatched, 0);
+    /// ```
+    pub fn stats(&self) -> BusStats {
+        self.counters.snapshot()
+    }
+
+    /// Returns the names of the commands that have a registered handler, in no particular order, see [Command::name].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::command::CommandBus;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// let command_bus = CommandBus::new(CommandHandlerRegistry::new());
+    ///
+    /// assert!(command_bus.handlers().is_empty());
+    /// ```
+    pub fn handlers(&self) -> alloc::vec::Vec<&'static str> {
+        self.registry.with(|registry| registry.names().collect())
+    }
+
+    /// Returns the descriptors of the commands that have a registered handler, in no particular order, see
+    /// [MessageDescriptor](crate::message::MessageDescriptor).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::command::CommandBus;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// let command_bus = CommandBus::new(CommandHandlerRegistry::new());
+    ///
+    /// assert!(command_bus.descriptors().is_empty());
+    /// ```
+    pub fn descriptors(&self) -> alloc::vec::Vec<crate::message::MessageDescriptor> {
+        self.registry
+            .with(|registry| registry.descriptors().collect())
+    }
+
+    /// Returns diagnostics explaining why the command type `C` cannot be dispatched, if no handler is registered for it.
+    ///
+    /// The diagnostics list the commands with a registered handler, and suggest the ones whose name is close to
+    /// the name of `C`, along with where they were registered, see [MissingHandler].
+    pub fn missing_handler<C: Command>(&self) -> Option<MissingHandler> {
+        self.registry.with(|registry| {
+            registry
+                .entry::<C>()
+                .is_none()
+                .then(|| MissingHandler::new(C::name(), registry.registrations()))
+        })
+    }
+
+    /// Returns the type id, and the name, of the command with the given name, if it has a registered handler.
+    #[cfg(feature = "admin")]
+    pub(crate) fn resolve(&self, name: &str) -> Option<(core::any::TypeId, &'static str)> {
+        self.registry.with(|registry| registry.resolve(name))
+    }
+
+    /// Returns diagnostics explaining why no command with the given name has a registered handler.
+    #[cfg(feature = "admin")]
+    pub(crate) fn missing_named(&self, name: alloc::string::String) -> MissingHandler {
+        self.registry
+            .with(|registry| MissingHandler::new(name, registry.registrations()))
+    }
+
+    /// Returns a weak reference to this `CommandBus`, which does not keep its registry alive.
+    ///
+    /// This is used by handlers that control the bus they are registered in.
+    #[cfg(feature = "admin")]
+    pub(crate) fn downgrade(&self) -> WeakCommandBus {
+        WeakCommandBus {
+            registry: Arc::downgrade(&self.registry),
+            counters: Arc::downgrade(&self.counters),
+            middleware: self.middleware.clone(),
+            switchboard: Arc::downgrade(&self.switchboard),
+            permits: self.permits.clone(),
+            cache: self.cache.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// Returns the [Switchboard] of the `CommandBus`, used to disable command types at runtime.
+    ///
+    /// Clones of a `CommandBus` share the same switchboard.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::command::CommandBus;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// let command_bus = CommandBus::new(CommandHandlerRegistry::new());
+    ///
+    /// command_bus.switchboard().enter_maintenance();
+    ///
+    /// assert!(command_bus.switchboard().status().maintenance);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn switchboard(&self) -> &Switchboard {
+        &self.switchboard
+    }
+
+    /// Registers a command handler for a specific command type, at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to be registered for the command type `C`.
+    ///
+    /// The registry is replaced using copy-on-write, which keeps dispatching lock-free. Since the whole registry
+    /// is copied, this is intended for occasional registrations, not for the hot path.
+    ///
+    /// The handler is visible to all clones of this `CommandBus`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// # use discern::async_trait;
+    /// # use discern::command::CommandHandler;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct DeleteUserCommand {
+    /// #    user_id: u64,
+    /// # }
+    /// #
+    /// # impl Command for DeleteUserCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # struct DeleteUserCommandHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl CommandHandler<DeleteUserCommand> for DeleteUserCommandHandler {
+    /// #    async fn handle(&self, command: DeleteUserCommand) -> Result<(), std::io::Error> {
+    /// #       Ok(())
+    /// #   }
+    /// # }
+    /// use discern::command_bus;
+    ///
+    /// let command_bus = command_bus! {};
+    ///
+    /// command_bus.register(DeleteUserCommandHandler { /* ... */ });
+    ///
+    /// assert!(command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await.is_ok());
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub fn register<C: Command>(&self, handler: impl CommandHandler<C> + 'static) {
+        let mut registry = CommandHandlerRegistry::new();
+        registry.register(handler);
+
+        self.merge(registry);
+    }
+
+    /// Merges the handlers of a registry into the registry of this `CommandBus`, at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry containing the handlers to add, or replace.
+    ///
+    /// See [CommandBus::register] for details.
+    #[cfg(feature = "std")]
+    pub fn merge(&self, registry: CommandHandlerRegistry) {
+        self.registry.update(|current| {
+            let mut updated = current.clone();
+            updated.merge(registry.clone());
+
+            updated
+        });
+    }
+
+    /// Atomically replaces the registry of this `CommandBus`, at runtime.
+    ///
+    /// Unlike [CommandBus::merge], handlers that are not part of the given registry are removed. Dispatches that
+    /// already started complete using the previous handlers, while later ones use the new handlers.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry replacing the current one.
+    ///
+    /// # Returns
+    ///
+    /// The previous registry, which can be used to roll back the replacement.
+    ///
+    /// See [Standby](crate::standby::Standby) for verifying a registry before switching to it.
+    #[cfg(feature = "std")]
+    pub fn replace(&self, registry: CommandHandlerRegistry) -> CommandHandlerRegistry {
+        self.registry.replace(registry)
+    }
+
+    /// Dispatches a command to its respective handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, or a [DispatchError] if the command is disabled using the [Switchboard],
+    /// if a middleware rejects it, or if the rate limit of the command type is exceeded.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the command handler is not found, since it is a programming error. Use
+    /// [CommandBus::try_dispatch] to handle this case gracefully.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// # use discern::async_trait;
+    /// # use discern::command::CommandHandler;
+    /// #
+    /// # #[derive(Debug)]
+    /// # enum CreateUserError {
+    /// #    UsernameAlreadyExists,
+    /// #    EmailAlreadyExists,
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct CreateUserCommand {
+    /// #    username: String,
+    /// #    email: String,
+    /// # }
+    /// #
+    /// # impl Command for CreateUserCommand {
+    /// #   // The identifier of the newly created user.
+    /// #   type Metadata = u64;
+    /// #   // The error type that is returned if the command fails.
+    /// #   type Error = CreateUserError;
+    /// # }
+    /// #
+    /// # struct CreateUserCommandHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+    /// #    async fn handle(&self, command: CreateUserCommand) -> Result<u64, CreateUserError> {
+    /// #       // Create a new user.
+    /// #       Ok(1)
+    /// #   }
+    /// # }
+    /// use discern::command_registry;
     /// use discern::command::CommandBus;
     ///
     /// let registry = command_registry! {
@@ -274,15 +1175,538 @@ impl CommandBus {
     /// }
     /// # });
     /// ```
-    pub async fn dispatch<C: Command>(&self, command: C) -> Result<C::Metadata, C::Error> {
-        match self.registry.get_handler::<C>() {
+    pub async fn dispatch<C: Command>(
+        &self,
+        command: C,
+    ) -> Result<C::Metadata, DispatchError<C::Error>> {
+        let result = self.try_dispatch(command).await;
+
+        self.expect_handler::<C, _>(result)
+    }
+
+    /// Dispatches a command to its respective handler, without panicking when no handler is registered for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, or a [DispatchError] if the command was rejected by the bus.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct DeleteUserCommand {
+    /// #    user_id: u64,
+    /// # }
+    /// #
+    /// # impl Command for DeleteUserCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::command_bus;
+    /// use discern::error::DispatchError;
+    ///
+    /// let command_bus = command_bus! {};
+    ///
+    /// let result = command_bus.try_dispatch(DeleteUserCommand { user_id: 1 }).await;
+    ///
+    /// assert!(matches!(result, Err(DispatchError::HandlerNotFound)));
+    /// # });
+    /// ```
+    pub async fn try_dispatch<C: Command>(
+        &self,
+        command: C,
+    ) -> Result<C::Metadata, DispatchError<C::Error>> {
+        self.try_dispatch_with(command, Context::new()).await
+    }
+
+    /// Dispatches a command, along with a context, to its respective handler.
+    ///
+    /// The context is visible to the middleware of the bus, and to the handler, if it was registered using
+    /// [CommandHandlerRegistry::register_contextual].
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    /// * `context` - The context of the dispatch, e.g. the authenticated user, or the tenant.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, or a [DispatchError], see [CommandBus::dispatch].
+    ///
+    /// # Panics
+    ///
+    /// This method will panic under the same conditions as [CommandBus::dispatch].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct CreateProjectCommand {
+    /// #    name: String,
+    /// # }
+    /// #
+    /// # impl Command for CreateProjectCommand {
+    /// #   type Metadata = String;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::async_trait;
+    /// use discern::command::CommandBus;
+    /// use discern::command::ContextualCommandHandler;
+    /// use discern::context::Context;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// struct TenantId(u64);
+    ///
+    /// struct CreateProjectCommandHandler;
+    ///
+    /// #[async_trait]
+    /// impl ContextualCommandHandler<CreateProjectCommand> for CreateProjectCommandHandler {
+    ///     async fn handle(&self, command: CreateProjectCommand, context: &Context) -> Result<String, std::io::Error> {
+    ///         let tenant = context.get::<TenantId>().map_or(0, |tenant| tenant.0);
+    ///
+    ///         Ok(format!("{}/{}", tenant, command.name))
+    ///     }
+    /// }
+    ///
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.register_contextual(CreateProjectCommandHandler);
+    ///
+    /// let command_bus = CommandBus::new(registry);
+    ///
+    /// let command = CreateProjectCommand { name: "discern".to_string() };
+    /// let project = command_bus.dispatch_with(command, Context::new().with(TenantId(7))).await.unwrap();
+    /// assert_eq!(project, "7/discern");
+    ///
+    /// let command = CreateProjectCommand { name: "discern".to_string() };
+    /// let project = command_bus.dispatch(command).await.unwrap();
+    /// assert_eq!(project, "0/discern");
+    /// # });
+    /// ```
+    pub async fn dispatch_with<C: Command>(
+        &self,
+        command: C,
+        context: Context,
+    ) -> Result<C::Metadata, DispatchError<C::Error>> {
+        let result = self.try_dispatch_with(command, context).await;
+
+        self.expect_handler::<C, _>(result)
+    }
+
+    /// Panics if no handler is registered for the command type `C`, returning the given result otherwise.
+    fn expect_handler<C: Command, T>(
+        &self,
+        result: Result<T, DispatchError<C::Error>>,
+    ) -> Result<T, DispatchError<C::Error>> {
+        if let Err(DispatchError::HandlerNotFound) = result {
+            match self.missing_handler::<C>() {
+                Some(missing) => panic!("No handler registered for command: {}", missing),
+                None => panic!("No handler registered for command: {:?}", C::name()),
+            }
+        }
+
+        result
+    }
+
+    /// Dispatches a command, along with a context, to its respective handler, without panicking when no handler is
+    /// registered for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    /// * `context` - The context of the dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, or a [DispatchError] if the command was rejected by the bus.
+    pub async fn try_dispatch_with<C: Command>(
+        &self,
+        command: C,
+        context: Context,
+    ) -> Result<C::Metadata, DispatchError<C::Error>> {
+        let (result, _) = self
+            .dispatch_chained(command, context, 0, crate::followup::MAX_DEPTH)
+            .await;
+
+        result
+    }
+
+    /// Dispatches a command at the given depth, then its follow-up commands once it succeeded, see
+    /// [Command::followups].
+    ///
+    /// Follow-up commands failing are counted once the whole chain completed, i.e. at the depth of `0`.
+    pub(crate) async fn dispatch_chained<C: Command>(
+        &self,
+        command: C,
+        context: Context,
+        depth: usize,
+        max_depth: usize,
+    ) -> (
+        Result<C::Metadata, DispatchError<C::Error>>,
+        crate::followup::ChainReport,
+    ) {
+        let dispatch = self.route(command, context);
+        #[cfg(feature = "tracing")]
+        let dispatch = crate::instrument::dispatch("command", C::name(), dispatch);
+
+        let mut result = dispatch.await;
+        let report = match &mut result {
+            Ok(metadata) => {
+                C::followups(metadata)
+                    .dispatch(self, depth + 1, max_depth)
+                    .await
+            }
+            Err(_) => crate::followup::ChainReport::default(),
+        };
+
+        if depth == 0 && !report.failures.is_empty() {
+            self.counters.record_failed_followups(report.failures.len());
+
+            #[cfg(feature = "tracing")]
+            for failure in &report.failures {
+                tracing::warn!(
+                    command = failure.command,
+                    depth = failure.depth,
+                    error = ?failure.error,
+                    "a follow-up command failed"
+                );
+            }
+        }
+
+        (result, report)
+    }
+
+    /// Runs a command through the switchboard, and the middleware, up to its handler.
+    async fn route<C: Command>(
+        &self,
+        command: C,
+        context: Context,
+    ) -> Result<C::Metadata, DispatchError<C::Error>> {
+        #[cfg(feature = "std")]
+        crate::scope::assert_writable(C::name());
+
+        #[cfg(feature = "std")]
+        if self
+            .switchboard
+            .is_disabled_id(core::any::TypeId::of::<C>())
+        {
+            return Err(DispatchError::Disabled);
+        }
+
+        if self.middleware.is_empty() {
+            let result = self.handle(command, &context).await;
+            #[cfg(feature = "std")]
+            self.deliver(&context, &result).await;
+
+            return result;
+        }
+
+        let mut endpoint = CommandEndpoint {
+            command_bus: self,
+            command: Some(command),
+            context,
+            result: None,
+        };
+
+        let outcome = self.middleware.run(&mut endpoint).await;
+
+        let result = match (endpoint.result, outcome) {
+            (Some(Ok(_)), Outcome::Rejected(reason)) => {
+                Err(DispatchError::RejectedAfterHandling(reason))
+            }
+            (None, Outcome::Rejected(reason)) => Err(DispatchError::Rejected(reason)),
+            (Some(result), _) => result,
+            (None, _) => Err(DispatchError::Rejected(
+                "the command was not handled by the middleware".into(),
+            )),
+        };
+
+        #[cfg(feature = "std")]
+        self.deliver(&endpoint.context, &result).await;
+
+        result
+    }
+
+    /// Publishes the events of the given context to the event bus, if the command succeeded, see
+    /// [Context::publish].
+    #[cfg(feature = "std")]
+    async fn deliver<T, E>(&self, context: &Context, result: &Result<T, E>) {
+        let events = context.take_events();
+        if events.is_empty() || result.is_err() {
+            return;
+        }
+
+        debug_assert!(
+            self.events.is_some(),
+            "events were published using `Context::publish`, but the `CommandBus` has no event bus, see `CommandBus::with_events`"
+        );
+
+        let Some(event_bus) = &self.events else {
+            self.counters.record_undelivered(events.len());
+
+            return;
+        };
+
+        for event in events {
+            if let Err(_failure) = event(event_bus.clone()).await {
+                self.counters.record_undelivered(1);
+
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_failure, "an event published by a succeeded command was not delivered");
+            }
+        }
+    }
+
+    /// Dispatches a command to its respective handler, returning its metadata along with the events published
+    /// while it was handled, see [CommandOutcome](crate::outcome::CommandOutcome).
+    ///
+    /// The events published through any [EventBus](crate::event::EventBus) while the handler is polled are
+    /// reported, including the events published by the commands it dispatches, and by the subscribers of its
+    /// events. Events published by tasks spawned from the handler are not reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the command, or a [DispatchError] if the command was rejected by the bus.
+    #[cfg(feature = "std")]
+    pub async fn try_dispatch_outcome<C: Command>(
+        &self,
+        command: C,
+    ) -> Result<crate::outcome::CommandOutcome<C>, DispatchError<C::Error>> {
+        let (result, events) = crate::outcome::EventLog::capture(self.try_dispatch(command)).await;
+
+        result.map(|metadata| crate::outcome::CommandOutcome { metadata, events })
+    }
+
+    /// Looks up the handler of a command, and calls it.
+    async fn handle<C: Command>(
+        &self,
+        command: C,
+        context: &Context,
+    ) -> Result<C::Metadata, DispatchError<C::Error>> {
+        let Some(handler) = self.registry.with(|registry| registry.entry::<C>()) else {
+            return Err(DispatchError::HandlerNotFound);
+        };
+
+        #[cfg(feature = "std")]
+        if let Some(limiter) = self.registry.with(|registry| registry.limiter::<C>()) {
+            if !limiter.acquire().await {
+                return Err(DispatchError::RateLimited);
+            }
+        }
+
+        #[cfg(feature = "std")]
+        let _permit = self.permit().await;
+
+        #[cfg(feature = "std")]
+        let invalidation = self
+            .cache
+            .as_ref()
+            .and_then(|cache| Some((cache, cache.invalidation(&command)?)));
+
+        let in_flight = self.counters.start();
+        let result = handler.handle(command, context).await;
+        in_flight.finish(&result);
+
+        #[cfg(feature = "std")]
+        if let (Some((cache, invalidation)), Ok(_)) = (invalidation, &result) {
+            cache.apply(invalidation);
+        }
+
+        result.map_err(DispatchError::Handler)
+    }
+}
+
+/// The innermost step of the middleware pipeline of a [CommandBus].
+#[doc(hidden)]
+struct CommandEndpoint<'a, C: Command> {
+    command_bus: &'a CommandBus,
+    command: Option<C>,
+    context: Context,
+    result: Option<Result<C::Metadata, DispatchError<C::Error>>>,
+}
+
+impl<C: Command> Endpoint for CommandEndpoint<'_, C> {
+    fn name(&self) -> &'static str {
+        C::name()
+    }
+
+    fn required_permissions(&self) -> &'static [&'static str] {
+        C::required_permissions()
+    }
+
+    fn command(&self) -> Option<&(dyn Any + Send + Sync)> {
+        self.command.as_ref().map(|command| command as _)
+    }
+
+    fn debug(&self) -> Option<&dyn Debug> {
+        self.command.as_ref().map(|command| command as _)
+    }
+
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    fn call(&mut self) -> crate::runtime::BoxFuture<'_, Outcome> {
+        Box::pin(async move {
+            let Some(command) = self.command.take() else {
+                return Outcome::Rejected("the command was already handled".into());
+            };
+
+            let result = self.command_bus.handle(command, &self.context).await;
+            let outcome = match &result {
+                Ok(_) => Outcome::Succeeded,
+                Err(DispatchError::HandlerNotFound) | Err(DispatchError::NotBorrowable) => {
+                    Outcome::HandlerNotFound
+                }
+                Err(DispatchError::Handler(error)) => {
+                    Outcome::Failed(alloc::format!("{:?}", error))
+                }
+                Err(DispatchError::Rejected(reason))
+                | Err(DispatchError::RejectedAfterHandling(reason)) => {
+                    Outcome::Rejected(reason.clone())
+                }
+                Err(DispatchError::Disabled) => Outcome::Rejected("the command is disabled".into()),
+                Err(DispatchError::RateLimited) => {
+                    Outcome::Rejected("the command was rate limited".into())
+                }
+            };
+
+            self.result = Some(result);
+
+            outcome
+        })
+    }
+}
+
+/// A weak reference to a [CommandBus], created using `CommandBus::downgrade`.
+#[cfg(feature = "admin")]
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+pub(crate) struct WeakCommandBus {
+    registry: alloc::sync::Weak<SharedRegistry<CommandHandlerRegistry>>,
+    counters: alloc::sync::Weak<Counters>,
+    middleware: Arc<Pipeline>,
+    switchboard: alloc::sync::Weak<Switchboard>,
+    permits: Option<Arc<async_lock::Semaphore>>,
+    cache: Option<crate::cache::QueryCache>,
+    events: Option<crate::event::EventBus>,
+}
+
+#[cfg(feature = "admin")]
+impl WeakCommandBus {
+    /// Returns the referenced `CommandBus`, or `None` if it was dropped.
+    pub(crate) fn upgrade(&self) -> Option<CommandBus> {
+        Some(CommandBus {
+            registry: self.registry.upgrade()?,
+            counters: self.counters.upgrade()?,
+            middleware: self.middleware.clone(),
+            switchboard: self.switchboard.upgrade()?,
+            permits: self.permits.clone(),
+            cache: self.cache.clone(),
+            events: self.events.clone(),
+        })
+    }
+}
+
+/// The `SyncCommandBus` is a simplified, synchronous variant of the `CommandBus`.
+///
+/// It only dispatches commands to handlers registered using
+/// [CommandHandlerRegistry::register_sync], and does not require an async runtime,
+/// which makes it suitable for `no_std` environments.
+///
+/// # Example
+///
+/// ```
+/// # use discern::command::Command;
+/// # use discern::command::SyncCommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct DoubleCommand(u64);
+/// #
+/// # impl Command for DoubleCommand {
+/// #   type Metadata = u64;
+/// #   type Error = ();
+/// # }
+/// #
+/// # struct DoubleCommandHandler;
+/// #
+/// # impl SyncCommandHandler<DoubleCommand> for DoubleCommandHandler {
+/// #   fn handle(&self, command: DoubleCommand) -> Result<u64, ()> {
+/// #     Ok(command.0 * 2)
+/// #   }
+/// # }
+/// use discern::command::SyncCommandBus;
+/// use discern::registry::CommandHandlerRegistry;
+///
+/// let mut registry = CommandHandlerRegistry::new();
+/// registry.register_sync(DoubleCommandHandler);
+///
+/// let command_bus = SyncCommandBus::new(registry);
+///
+/// assert_eq!(command_bus.dispatch(DoubleCommand(21)), Ok(42));
+/// ```
+#[cfg(feature = "sync")]
+#[derive(Clone, Debug)]
+pub struct SyncCommandBus {
+    #[doc(hidden)]
+    registry: Arc<CommandHandlerRegistry>,
+}
+
+/// The `SyncCommandBus` implementation.
+#[cfg(feature = "sync")]
+impl SyncCommandBus {
+    /// Creates a new `SyncCommandBus` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The command handler registry.
+    pub fn new(registry: CommandHandlerRegistry) -> Self {
+        Self {
+            registry: Arc::new(registry),
+        }
+    }
+
+    /// Dispatches a command to its respective synchronous handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if no synchronous command handler is found.
+    pub fn dispatch<C: Command>(&self, command: C) -> Result<C::Metadata, C::Error> {
+        match self.registry.handle_sync(command) {
+            Some(result) => result,
             None => {
                 panic!(
-                    "No handler registered for command: {:?}",
-                    std::any::type_name::<C>()
+                    "No synchronous handler registered for command: {:?}",
+                    C::name()
                 );
             }
-            Some(handler) => handler.handle(command).await,
         }
     }
 }
@@ -0,0 +1,282 @@
+//! The `context` module provides request-scoped data attached to a dispatch.
+//!
+//! Data such as the authenticated user, the tenant, or a request id, is needed by many handlers, but is not part of
+//! what the command expresses, and adding it to every command struct is repetitive. A [Context] carries such data
+//! alongside a command dispatched using [CommandBus::dispatch_with](crate::command::CommandBus::dispatch_with), and
+//! is visible to [middleware](crate::middleware), and to handlers implementing
+//! [ContextualCommandHandler](crate::command::ContextualCommandHandler).
+//!
+//! - [Context]: A type map of values attached to a dispatch.
+//!
+//! Handlers also use the context to publish the events of a command, using [Context::publish], which are only
+//! delivered once the command succeeded, so that subscribers never observe the events of a command that failed,
+//! or whose transaction was rolled back.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::any::Any;
+use core::any::TypeId;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+/// A type map of values attached to a dispatch, holding at most one value per type.
+///
+/// # Example
+///
+/// ```
+/// use discern::context::Context;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct TenantId(u64);
+///
+/// let mut context = Context::new().with(TenantId(1));
+///
+/// assert_eq!(context.get::<TenantId>(), Some(&TenantId(1)));
+/// assert_eq!(context.insert(TenantId(2)), Some(TenantId(1)));
+/// assert_eq!(context.remove::<TenantId>(), Some(TenantId(2)));
+/// assert!(context.is_empty());
+/// ```
+#[derive(Default)]
+pub struct Context {
+    #[doc(hidden)]
+    values: BTreeMap<TypeId, Box<dyn Any + Send + Sync>>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    events: std::sync::Mutex<Vec<DeferredEvent>>,
+}
+
+/// Delivers an event published using [Context::publish] to an event bus, and describes the failure of its
+/// subscribers, if any.
+#[cfg(feature = "std")]
+pub(crate) type DeferredEvent = Box<
+    dyn FnOnce(crate::event::EventBus) -> crate::runtime::BoxFuture<'static, Result<(), String>>
+        + Send,
+>;
+
+/// The `Context` implementation.
+impl Context {
+    /// Creates a new, empty `Context`.
+    pub fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            #[cfg(feature = "std")]
+            events: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns this `Context`, with the given value inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to insert, replacing the value of the same type, if any.
+    pub fn with<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.insert(value);
+        self
+    }
+
+    /// Inserts a value, replacing the value of the same type, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to insert.
+    ///
+    /// # Returns
+    ///
+    /// The replaced value, if any.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns a reference to the value of type `T`, if any.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns a mutable reference to the value of type `T`, if any.
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Removes, and returns, the value of type `T`, if any.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())?
+            .downcast()
+            .ok()
+            .map(|value| *value)
+    }
+
+    /// Returns whether this `Context` holds a value of type `T`.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the number of values in this `Context`.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether this `Context` holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Publishes an event once the command this `Context` was dispatched with succeeded.
+    ///
+    /// The event is delivered to the event bus of the [CommandBus](crate::command::CommandBus), see
+    /// [CommandBus::with_events](crate::command::CommandBus::with_events), once the handler, and the middleware
+    /// wrapping it, e.g. one committing a transaction, succeeded. Events are discarded if the command fails, or is
+    /// rejected.
+    ///
+    /// Subscriber failures are not returned to the caller, since the command already took effect. Instead, they
+    /// are counted by the `CommandBus`, see
+    /// [CommandBus::undelivered_events](crate::command::CommandBus::undelivered_events), and logged when the
+    /// `tracing` feature is enabled. Publishing events on a `CommandBus` without an event bus is a programming
+    /// error, which fails a debug assertion, and counts the events as undelivered in release builds.
+    ///
+    /// The event is stamped with the [CorrelationId](crate::metadata::CorrelationId), the
+    /// [CausationId](crate::metadata::CausationId), and the [CallerId](crate::caller::CallerId) held by this context
+    /// when it is published, see [EventMetadata](crate::metadata::EventMetadata).
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to publish.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// # use discern::event::Event;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct CreateUserCommand {
+    /// #     username: String,
+    /// # }
+    /// #
+    /// # impl Command for CreateUserCommand {
+    /// #     type Metadata = u64;
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct UserCreatedEvent {
+    /// #     user_id: u64,
+    /// # }
+    /// #
+    /// # impl Event for UserCreatedEvent {
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// use std::sync::atomic::AtomicU64;
+    /// use std::sync::atomic::Ordering;
+    /// use std::sync::Arc;
+    ///
+    /// use discern::async_trait;
+    /// use discern::command::CommandBus;
+    /// use discern::command::ContextualCommandHandler;
+    /// use discern::context::Context;
+    /// use discern::event::EventHandler;
+    /// use discern::event_bus;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// struct CreateUserCommandHandler;
+    ///
+    /// #[async_trait]
+    /// impl ContextualCommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+    ///     async fn handle(&self, command: CreateUserCommand, context: &Context) -> Result<u64, std::io::Error> {
+    ///         context.publish(UserCreatedEvent { user_id: 1 });
+    ///
+    ///         if command.username.is_empty() {
+    ///             // The event published above is discarded.
+    ///             return Err(std::io::ErrorKind::InvalidInput.into());
+    ///         }
+    ///
+    ///         Ok(1)
+    ///     }
+    /// }
+    ///
+    /// struct CountUsers {
+    ///     count: Arc<AtomicU64>,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl EventHandler<UserCreatedEvent> for CountUsers {
+    ///     async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+    ///         self.count.fetch_add(1, Ordering::SeqCst);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let count = Arc::new(AtomicU64::new(0));
+    /// let event_bus = event_bus! {
+    ///     UserCreatedEvent => CountUsers { count: count.clone() },
+    /// };
+    ///
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.register_contextual(CreateUserCommandHandler);
+    ///
+    /// let command_bus = CommandBus::new(registry).with_events(event_bus);
+    ///
+    /// let command = CreateUserCommand { username: "".to_string() };
+    /// assert!(command_bus.dispatch(command).await.is_err());
+    /// assert_eq!(count.load(Ordering::SeqCst), 0);
+    ///
+    /// let command = CreateUserCommand { username: "alice".to_string() };
+    /// command_bus.dispatch(command).await.unwrap();
+    /// assert_eq!(count.load(Ordering::SeqCst), 1);
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn publish<E: crate::event::Event>(&self, event: E) {
+        let metadata = crate::metadata::EventMetadata::of(self);
+
+        self.lock_events().push(Box::new(move |event_bus| {
+            Box::pin(async move {
+                event_bus
+                    .publish_stamped(event, metadata)
+                    .await
+                    .map(|_| ())
+                    .map_err(|error| {
+                        format!("failed to deliver the {} event: {:?}", E::name(), error)
+                    })
+            })
+        }));
+    }
+
+    /// Returns the number of events published using [Context::publish], and not delivered yet.
+    #[cfg(feature = "std")]
+    pub fn pending_events(&self) -> usize {
+        self.lock_events().len()
+    }
+
+    /// Takes the events published using [Context::publish], in the order they were published.
+    #[cfg(feature = "std")]
+    pub(crate) fn take_events(&self) -> Vec<DeferredEvent> {
+        core::mem::take(&mut *self.lock_events())
+    }
+
+    /// Locks the events published using [Context::publish].
+    #[cfg(feature = "std")]
+    fn lock_events(&self) -> std::sync::MutexGuard<'_, Vec<DeferredEvent>> {
+        self.events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Debug implementation for `Context`
+impl Debug for Context {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Context")
+            .field("values", &self.values.len())
+            .finish()
+    }
+}
@@ -0,0 +1,327 @@
+//! The `cost` module provides cost based limiting for queries.
+//!
+//! Not all queries are equal: a single analytical query can cost as much as thousands of interactive ones.
+//! Limiting the number of queries is therefore not enough to keep expensive queries from starving cheap ones.
+//! Instead, queries declare a cost using the [Cost] trait, and a [CostBudget] limits the total cost each caller
+//! can spend within a time window.
+//!
+//! - [Cost]: Declares the cost of a query.
+//! - [CostBudget]: Tracks the cost spent by each caller within a time window.
+//! - [BudgetedHandler]: A handler that charges the cost of each query to a [CostBudget].
+//! - [BudgetExceeded]: The error returned when a caller exceeds its budget.
+
+use std::error::Error;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::evict::EvictingMap;
+use crate::query::Query;
+use crate::query::QueryHandler;
+
+/// The `Cost` trait declares the cost of a query.
+///
+/// The cost is an arbitrary unit, e.g. the estimated number of rows a query scans, or a complexity score
+/// computed by a GraphQL gateway.
+///
+/// # Example
+///
+/// ```
+/// use discern::cost::Cost;
+/// use discern::query::Query;
+///
+/// #[derive(Debug)]
+/// struct SearchOrdersQuery {
+///     customer: String,
+///     limit: u64,
+/// }
+///
+/// impl Query for SearchOrdersQuery {
+///     type Output = Vec<u64>;
+///     type Error = std::io::Error;
+/// }
+///
+/// impl Cost for SearchOrdersQuery {
+///     fn cost(&self) -> u64 {
+///         self.limit
+///     }
+/// }
+/// ```
+pub trait Cost {
+    /// Returns the cost of this query.
+    fn cost(&self) -> u64;
+}
+
+/// The error returned when a caller exceeds its budget.
+///
+/// Queries handled by a [BudgetedHandler] must have an error type that implements `From<BudgetExceeded>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BudgetExceeded {
+    /// The caller that exceeded its budget.
+    pub caller: String,
+    /// The cost of the rejected query.
+    pub cost: u64,
+    /// The budget remaining for the caller within the current window.
+    pub remaining: u64,
+    /// The duration after which the budget of the caller is reset.
+    pub retry_after: Duration,
+}
+
+impl Display for BudgetExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        write!(
+            f,
+            "caller {:?} exceeded its budget: query costs {}, {} remaining, retry after {:?}",
+            self.caller, self.cost, self.remaining, self.retry_after
+        )
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// Tracks the cost spent by each caller within a time window.
+///
+/// Each caller can spend up to `budget` within a window, which starts when the caller is first charged, and
+/// is reset once `window` has elapsed. A `CostBudget` can be shared between handlers of different query types,
+/// in which case the cost of all of them is charged to the same budget.
+///
+/// The cost of a query is charged before it is handled, and is not refunded if the handler fails, or the query
+/// is cancelled, since the query may have consumed the resources the budget protects by then, e.g. a report timing
+/// out after scanning a whole table.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::async_trait;
+/// # use discern::cost::Cost;
+/// # use discern::query::Query;
+/// # use discern::query::QueryHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct SearchOrdersQuery {
+/// #     customer: String,
+/// #     limit: u64,
+/// # }
+/// #
+/// # impl Cost for SearchOrdersQuery {
+/// #     fn cost(&self) -> u64 {
+/// #         self.limit
+/// #     }
+/// # }
+/// #
+/// # struct SearchOrdersQueryHandler;
+/// #
+/// # #[async_trait]
+/// # impl QueryHandler<SearchOrdersQuery> for SearchOrdersQueryHandler {
+/// #     async fn handle(&self, query: SearchOrdersQuery) -> Result<Vec<u64>, SearchOrdersError> {
+/// #         Ok((0..query.limit).collect())
+/// #     }
+/// # }
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use discern::cost::BudgetExceeded;
+/// use discern::cost::CostBudget;
+/// use discern::query_bus;
+///
+/// #[derive(Debug)]
+/// enum SearchOrdersError {
+///     BudgetExceeded(BudgetExceeded),
+/// }
+///
+/// impl From<BudgetExceeded> for SearchOrdersError {
+///     fn from(error: BudgetExceeded) -> Self {
+///         SearchOrdersError::BudgetExceeded(error)
+///     }
+/// }
+///
+/// impl Query for SearchOrdersQuery {
+///     type Output = Vec<u64>;
+///     type Error = SearchOrdersError;
+/// }
+///
+/// // Each customer can fetch up to 100 orders per minute.
+/// let budget = Arc::new(CostBudget::new(100, Duration::from_secs(60)));
+///
+/// let query_bus = query_bus! {
+///     SearchOrdersQuery => budget.enforce(SearchOrdersQueryHandler, |query: &SearchOrdersQuery| {
+///         query.customer.clone()
+///     }),
+/// };
+///
+/// let query = SearchOrdersQuery { customer: "alice".to_string(), limit: 80 };
+/// assert!(query_bus.dispatch(query).await.is_ok());
+///
+/// let query = SearchOrdersQuery { customer: "alice".to_string(), limit: 80 };
+/// assert!(query_bus.dispatch(query).await.is_err());
+///
+/// // Other customers have their own budget.
+/// let query = SearchOrdersQuery { customer: "bob".to_string(), limit: 80 };
+/// assert!(query_bus.dispatch(query).await.is_ok());
+///
+/// assert_eq!(budget.remaining("alice"), 20);
+/// # });
+/// ```
+pub struct CostBudget {
+    #[doc(hidden)]
+    budget: u64,
+    #[doc(hidden)]
+    window: Duration,
+    #[doc(hidden)]
+    spent: Mutex<EvictingMap<String, Spent>>,
+}
+
+/// The cost spent by a caller within its current window.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+struct Spent {
+    since: Instant,
+    cost: u64,
+}
+
+/// The `CostBudget` implementation.
+impl CostBudget {
+    /// Creates a new `CostBudget`.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - The total cost each caller can spend within a window.
+    /// * `window` - The duration of a window.
+    pub fn new(budget: u64, window: Duration) -> Self {
+        Self {
+            budget,
+            window,
+            spent: Mutex::new(EvictingMap::new()),
+        }
+    }
+
+    /// Wraps the given handler, charging the cost of each query to the caller returned by `caller`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to wrap.
+    /// * `caller` - A function returning the caller of a query, which can also be the [CallerId](crate::caller::CallerId)
+    ///   of the dispatch, e.g. `|_| CallerId::current().unwrap_or_else(CallerId::anonymous).to_string()`.
+    pub fn enforce<Q, H>(
+        self: &Arc<Self>,
+        handler: H,
+        caller: impl Fn(&Q) -> String + Send + Sync + 'static,
+    ) -> BudgetedHandler<Q, H> {
+        BudgetedHandler {
+            handler,
+            budget: self.clone(),
+            caller: Box::new(caller),
+        }
+    }
+
+    /// Returns the budget remaining for the given caller within its current window.
+    pub fn remaining(&self, caller: &str) -> u64 {
+        let now = Instant::now();
+        let spent = self.spent.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match spent.get(caller) {
+            Some(spent) if now.duration_since(spent.since) < self.window => {
+                self.budget.saturating_sub(spent.cost)
+            }
+            _ => self.budget,
+        }
+    }
+
+    /// Charges the given cost to the caller, or returns [BudgetExceeded] if the caller can't afford it.
+    ///
+    /// Rejected queries are not charged, and charged costs are never refunded.
+    ///
+    /// # Arguments
+    ///
+    /// * `caller` - The caller to charge.
+    /// * `cost` - The cost to charge.
+    pub fn charge(&self, caller: &str, cost: u64) -> Result<(), BudgetExceeded> {
+        let now = Instant::now();
+        let mut spent = self.spent.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if !spent.contains_key(caller) {
+            spent.evict(|_, spent| now.duration_since(spent.since) < self.window);
+        }
+
+        let entry = spent.entry(caller.to_string()).or_insert(Spent {
+            since: now,
+            cost: 0,
+        });
+
+        if now.duration_since(entry.since) >= self.window {
+            *entry = Spent {
+                since: now,
+                cost: 0,
+            };
+        }
+
+        let remaining = self.budget.saturating_sub(entry.cost);
+        if cost > remaining {
+            return Err(BudgetExceeded {
+                caller: caller.to_string(),
+                cost,
+                remaining,
+                retry_after: self.window.saturating_sub(now.duration_since(entry.since)),
+            });
+        }
+
+        entry.cost += cost;
+
+        Ok(())
+    }
+}
+
+/// Debug implementation for `CostBudget`
+impl Debug for CostBudget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CostBudget")
+            .field("budget", &self.budget)
+            .field("window", &self.window)
+            .finish()
+    }
+}
+
+/// A handler that charges the cost of each query to a [CostBudget].
+///
+/// `BudgetedHandler` is created using [CostBudget::enforce]. Queries whose caller exceeded its budget are
+/// rejected with [BudgetExceeded], converted into the error type of the query, without being handled.
+pub struct BudgetedHandler<Q, H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    budget: Arc<CostBudget>,
+    #[doc(hidden)]
+    caller: Box<dyn Fn(&Q) -> String + Send + Sync>,
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for BudgetedHandler<Q, H>
+where
+    Q: Query + Cost,
+    Q::Error: From<BudgetExceeded>,
+    H: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        self.budget.charge(&(self.caller)(&query), query.cost())?;
+
+        self.handler.handle(query).await
+    }
+}
+
+/// Debug implementation for `BudgetedHandler`
+impl<Q, H> Debug for BudgetedHandler<Q, H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("BudgetedHandler")
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
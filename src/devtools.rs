@@ -0,0 +1,486 @@
+//! The `devtools` module provides an inspector server exposing the live state of the buses ( requires the
+//! `devtools` feature ).
+//!
+//! Wiring mistakes, e.g. a handler registered in the wrong registry, or a middleware rejecting every command, are
+//! easier to spot by looking at the running application than by reading its setup code. The [Inspector] records
+//! the recent dispatches of a [CommandBus], and serves, along with the registered handlers, and the counters of
+//! both buses, as JSON over a tiny HTTP server meant for local development:
+//!
+//! - `GET /`: A page polling the other endpoints.
+//! - `GET /handlers`: The descriptors of the registered commands, and queries.
+//! - `GET /stats`: The counters of both buses, see [BusStats], and the status of the command bus switchboard.
+//! - `GET /dispatches`: The recent dispatches of commands, most recent last.
+//!
+//! The server is not hardened, and must not be exposed outside of the development machine.
+//!
+//! - [Inspector]: Records recent dispatches, and serves the state of the buses.
+//! - [DispatchRecorder]: The middleware recording the dispatches of a [CommandBus].
+//! - [DispatchRecord]: A recorded dispatch.
+//! - [DevtoolsServer]: A running inspector server.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::fmt::Write as _;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::command::CommandBus;
+use crate::message::MessageDescriptor;
+use crate::middleware::CommandMiddleware;
+use crate::middleware::Next;
+use crate::middleware::Outcome;
+use crate::query::QueryBus;
+use crate::stats::BusStats;
+
+/// The number of dispatches recorded by default.
+const DEFAULT_CAPACITY: usize = 100;
+
+/// The page served at `/`, polling the JSON endpoints.
+const INDEX: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>discern devtools</title></head>
+<body>
+<h1>discern devtools</h1>
+<h2>Stats</h2><pre id="stats"></pre>
+<h2>Handlers</h2><pre id="handlers"></pre>
+<h2>Recent dispatches</h2><pre id="dispatches"></pre>
+<script>
+async function refresh() {
+  for (const id of ["stats", "handlers", "dispatches"]) {
+    const response = await fetch("/" + id);
+    document.getElementById(id).textContent = JSON.stringify(await response.json(), null, 2);
+  }
+}
+refresh();
+setInterval(refresh, 1000);
+</script>
+</body>
+</html>
+"#;
+
+/// A recorded dispatch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DispatchRecord {
+    /// The name of the command, see [Command::name](crate::command::Command::name).
+    pub name: &'static str,
+    /// The outcome of the dispatch.
+    pub outcome: Outcome,
+    /// The time spent handling the command, including the middleware after the recorder.
+    pub duration: Duration,
+}
+
+/// Records recent dispatches, and serves the state of the buses.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// use std::io::Read;
+/// use std::io::Write;
+/// use std::net::TcpStream;
+///
+/// use discern::command::CommandBus;
+/// use discern::command_registry;
+/// use discern::devtools::Inspector;
+/// use discern::query_bus;
+///
+/// let inspector = Inspector::new();
+///
+/// let registry = command_registry! {
+///     CreateUserCommand => |command| async move { Ok(command.username.len() as u64) },
+/// };
+/// let command_bus = CommandBus::new(registry).with_middleware(inspector.recorder());
+/// let query_bus = query_bus! {};
+///
+/// command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+///
+/// let server = inspector.serve("127.0.0.1:0", &command_bus, &query_bus).unwrap();
+///
+/// let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+/// stream.write_all(b"GET /dispatches HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+///
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+///
+/// assert!(response.starts_with("HTTP/1.1 200 OK"));
+/// assert!(response.contains("CreateUserCommand"));
+/// assert!(response.contains(r#""outcome":"succeeded""#));
+///
+/// server.shutdown();
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct Inspector {
+    #[doc(hidden)]
+    dispatches: Arc<Mutex<VecDeque<DispatchRecord>>>,
+    #[doc(hidden)]
+    capacity: usize,
+}
+
+/// The `Inspector` implementation.
+impl Inspector {
+    /// Creates a new `Inspector`, recording the last 100 dispatches.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new `Inspector`, recording the given number of dispatches.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity must be greater than zero");
+
+        Self {
+            dispatches: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Returns the middleware recording the dispatches of the [CommandBus] it is added to.
+    ///
+    /// The recorder should be added first, so that it observes the commands rejected by the other middleware.
+    pub fn recorder(&self) -> DispatchRecorder {
+        DispatchRecorder {
+            inspector: self.clone(),
+        }
+    }
+
+    /// Returns the recent dispatches, most recent last.
+    pub fn dispatches(&self) -> Vec<DispatchRecord> {
+        self.dispatches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Starts serving the state of the buses, on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to listen on, e.g. `127.0.0.1:9090`, or `127.0.0.1:0` for any available port.
+    /// * `command_bus` - The command bus to inspect.
+    /// * `query_bus` - The query bus to inspect.
+    ///
+    /// # Returns
+    ///
+    /// The running server, which stops when shut down, or dropped, or an error if the address can't be bound.
+    pub fn serve(
+        &self,
+        address: impl ToSocketAddrs,
+        command_bus: &CommandBus,
+        query_bus: &QueryBus,
+    ) -> std::io::Result<DevtoolsServer> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let state = State {
+            inspector: self.clone(),
+            command_bus: command_bus.clone(),
+            query_bus: query_bus.clone(),
+        };
+
+        let thread = std::thread::Builder::new()
+            .name("discern-devtools".into())
+            .spawn({
+                let stopped = stopped.clone();
+
+                move || {
+                    for stream in listener.incoming() {
+                        if stopped.load(Ordering::Acquire) {
+                            break;
+                        }
+
+                        if let Ok(stream) = stream {
+                            // A failing client must not stop the server.
+                            let _ = state.respond(stream);
+                        }
+                    }
+                }
+            })?;
+
+        Ok(DevtoolsServer {
+            address,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    /// Records a dispatch, evicting the oldest one if the capacity is reached.
+    fn record(&self, record: DispatchRecord) {
+        let mut dispatches = self
+            .dispatches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if dispatches.len() == self.capacity {
+            dispatches.pop_front();
+        }
+
+        dispatches.push_back(record);
+    }
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debug implementation for `Inspector`
+impl Debug for Inspector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Inspector")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// The middleware recording the dispatches of a [CommandBus], created using [Inspector::recorder].
+#[derive(Debug, Clone)]
+pub struct DispatchRecorder {
+    #[doc(hidden)]
+    inspector: Inspector,
+}
+
+#[async_trait]
+impl CommandMiddleware for DispatchRecorder {
+    async fn handle(&self, next: Next<'_>) -> Outcome {
+        let name = next.name();
+        let start = Instant::now();
+        let outcome = next.run().await;
+
+        self.inspector.record(DispatchRecord {
+            name,
+            outcome: outcome.clone(),
+            duration: start.elapsed(),
+        });
+
+        outcome
+    }
+}
+
+/// A running inspector server, created using [Inspector::serve].
+///
+/// The server stops when shut down, or dropped.
+pub struct DevtoolsServer {
+    #[doc(hidden)]
+    address: SocketAddr,
+    #[doc(hidden)]
+    stopped: Arc<AtomicBool>,
+    #[doc(hidden)]
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The `DevtoolsServer` implementation.
+impl DevtoolsServer {
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Stops the server, and waits for its thread to exit.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    /// Stops the server, waking up its thread by connecting to it.
+    fn stop(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+
+        self.stopped.store(true, Ordering::Release);
+        if TcpStream::connect(self.address).is_ok() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for DevtoolsServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Debug implementation for `DevtoolsServer`
+impl Debug for DevtoolsServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("DevtoolsServer")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+/// The state served by the inspector server.
+#[doc(hidden)]
+struct State {
+    inspector: Inspector,
+    command_bus: CommandBus,
+    query_bus: QueryBus,
+}
+
+impl State {
+    /// Reads a request from the stream, and writes the response.
+    fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+
+        let mut parts = request.split_whitespace();
+        let (status, content_type, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/")) => ("200 OK", "text/html", INDEX.to_string()),
+            (Some("GET"), Some("/handlers")) => ("200 OK", "application/json", self.handlers()),
+            (Some("GET"), Some("/stats")) => ("200 OK", "application/json", self.stats()),
+            (Some("GET"), Some("/dispatches")) => ("200 OK", "application/json", self.dispatches()),
+            _ => (
+                "404 Not Found",
+                "application/json",
+                r#"{"error":"not found"}"#.to_string(),
+            ),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )?;
+
+        stream.flush()
+    }
+
+    /// Returns the descriptors of the registered commands, and queries, as JSON.
+    fn handlers(&self) -> String {
+        let commands = descriptors(&self.command_bus.descriptors());
+        let queries = descriptors(&self.query_bus.descriptors());
+
+        format!(r#"{{"commands":{},"queries":{}}}"#, commands, queries)
+    }
+
+    /// Returns the counters of both buses, as JSON.
+    fn stats(&self) -> String {
+        let status = self.command_bus.switchboard().status();
+        let disabled: Vec<String> = status.disabled.iter().map(|name| string(name)).collect();
+
+        format!(
+            r#"{{"commands":{},"queries":{},"switchboard":{{"maintenance":{},"disabled":[{}]}}}}"#,
+            stats(&self.command_bus.stats()),
+            stats(&self.query_bus.stats()),
+            status.maintenance,
+            disabled.join(",")
+        )
+    }
+
+    /// Returns the recent dispatches, as JSON.
+    fn dispatches(&self) -> String {
+        let mut json = String::from("[");
+        for (index, record) in self.inspector.dispatches().iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+
+            let (outcome, reason) = match &record.outcome {
+                Outcome::Succeeded => ("succeeded", None),
+                Outcome::HandlerNotFound => ("handler_not_found", None),
+                Outcome::Failed(error) => ("failed", Some(error)),
+                Outcome::Rejected(reason) => ("rejected", Some(reason)),
+            };
+
+            let _ = write!(
+                json,
+                r#"{{"name":{},"outcome":"{}","reason":{},"duration_us":{}}}"#,
+                string(record.name),
+                outcome,
+                reason.map_or_else(|| "null".to_string(), |reason| string(reason)),
+                record.duration.as_micros()
+            );
+        }
+
+        json.push(']');
+        json
+    }
+}
+
+/// Returns the given descriptors, as a JSON array.
+fn descriptors(descriptors: &[MessageDescriptor]) -> String {
+    let descriptors: Vec<String> = descriptors
+        .iter()
+        .map(|descriptor| {
+            format!(
+                r#"{{"name":{},"version":{},"schema_hash":{}}}"#,
+                string(descriptor.name),
+                descriptor.version,
+                descriptor
+                    .schema_hash
+                    .map_or_else(|| "null".to_string(), |hash| format!(r#""{:016x}""#, hash))
+            )
+        })
+        .collect();
+
+    format!("[{}]", descriptors.join(","))
+}
+
+/// Returns the given counters, as a JSON object.
+fn stats(stats: &BusStats) -> String {
+    format!(
+        r#"{{"dispatched":{},"succeeded":{},"failed":{},"in_flight":{}}}"#,
+        stats.dispatched, stats.succeeded, stats.failed, stats.in_flight
+    )
+}
+
+/// Returns the given value, as a JSON string.
+fn string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            character if character.is_control() => {
+                let _ = write!(json, "\\u{:04x}", character as u32);
+            }
+            character => json.push(character),
+        }
+    }
+
+    json.push('"');
+    json
+}
@@ -0,0 +1,195 @@
+//! The `diagnostics` module explains why a message could not be routed to a handler.
+//!
+//! A missing handler is usually caused by a forgotten registration, a typo in a message name when routing by string,
+//! or two message types sharing the same name in different modules. A [MissingHandler] lists the messages that do
+//! have a registered handler, along with the registrations whose name is close to the requested one, and where
+//! they were registered.
+//!
+//! - [MissingHandler]: Diagnostics of a message without a registered handler.
+//! - [Registration]: A message with a registered handler, and where it was registered.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::panic::Location;
+
+use crate::message::MessageDescriptor;
+
+/// The maximum number of registered messages listed when displaying a [MissingHandler].
+const LISTED_REGISTRATIONS: usize = 16;
+
+/// A message with a registered handler, and where it was registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Registration {
+    /// The name of the message.
+    pub name: &'static str,
+    /// The descriptor of the message.
+    pub descriptor: MessageDescriptor,
+    /// The location of the code that registered the handler.
+    pub location: &'static Location<'static>,
+}
+
+/// Diagnostics of a message without a registered handler.
+///
+/// # Example
+///
+/// ```
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand;
+/// #
+/// # impl Command for CreateUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUsersCommand;
+/// #
+/// # impl Command for CreateUsersCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::command_bus;
+///
+/// let command_bus = command_bus! {
+///     CreateUsersCommand => |_command| async move { Ok(()) },
+/// };
+///
+/// let missing = command_bus.missing_handler::<CreateUserCommand>().unwrap();
+///
+/// assert_eq!(missing.registered.len(), 1);
+/// assert_eq!(missing.suggestions.len(), 1);
+/// assert!(missing.suggestions[0].name.ends_with("CreateUsersCommand"));
+/// assert!(missing.to_string().contains("did you mean"));
+///
+/// assert!(command_bus.missing_handler::<CreateUsersCommand>().is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MissingHandler {
+    /// The name of the message without a registered handler.
+    pub message: String,
+    /// The messages with a registered handler.
+    pub registered: Vec<Registration>,
+    /// The registered messages whose name is close to the requested one, closest first.
+    pub suggestions: Vec<Registration>,
+}
+
+/// The `MissingHandler` implementation.
+impl MissingHandler {
+    /// Creates the diagnostics of the given message, against the given registrations.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The name of the message without a registered handler, as returned by its `name`
+    ///   function, or as used for routing by string.
+    /// * `registered` - The messages with a registered handler.
+    pub fn new(message: impl Into<String>, registered: Vec<Registration>) -> Self {
+        let message = message.into();
+
+        let mut suggestions: Vec<(usize, Registration)> = registered
+            .iter()
+            .filter_map(|registration| {
+                similarity(&message, registration.name).map(|distance| (distance, *registration))
+            })
+            .collect();
+        suggestions.sort_by_key(|(distance, registration)| (*distance, registration.name));
+
+        Self {
+            message,
+            registered,
+            suggestions: suggestions
+                .into_iter()
+                .map(|(_, registration)| registration)
+                .collect(),
+        }
+    }
+}
+
+impl Display for MissingHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        write!(f, "no handler is registered for {:?}", self.message)?;
+
+        for suggestion in &self.suggestions {
+            write!(
+                f,
+                "\n  did you mean {:?}, registered at {}?",
+                suggestion.name, suggestion.location
+            )?;
+        }
+
+        if self.registered.is_empty() {
+            return write!(
+                f,
+                "\n  no handlers are registered, is the bus built from the right registry?"
+            );
+        }
+
+        write!(f, "\n  registered: ")?;
+        for (index, registration) in self
+            .registered
+            .iter()
+            .take(LISTED_REGISTRATIONS)
+            .enumerate()
+        {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{}", registration.name)?;
+        }
+
+        if self.registered.len() > LISTED_REGISTRATIONS {
+            write!(
+                f,
+                ", and {} more",
+                self.registered.len() - LISTED_REGISTRATIONS
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the last segment of a type name, ignoring its generic arguments, e.g. `CreateUser` for
+/// `app::users::CreateUser<T>`.
+fn short_name(name: &str) -> &str {
+    let name = name.split('<').next().unwrap_or(name);
+
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Returns how close the two names are, lower being closer, or `None` if they are unrelated.
+fn similarity(requested: &str, registered: &str) -> Option<usize> {
+    let requested = short_name(requested).to_lowercase();
+    let registered = short_name(registered).to_lowercase();
+
+    let distance = levenshtein(&requested, &registered);
+    let threshold = (requested.chars().count().max(registered.chars().count()) / 3).max(1);
+
+    (distance <= threshold).then_some(distance)
+}
+
+/// Returns the number of single character edits needed to turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = Vec::with_capacity(b.len() + 1);
+
+    for (i, a) in a.chars().enumerate() {
+        current.clear();
+        current.push(i + 1);
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        core::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,270 @@
+//! The `effect` module provides a ledger of the side effects performed by handlers.
+//!
+//! Handlers often perform side effects besides changing state, e.g. sending an email, or charging a card. Such
+//! effects are hard to audit, since each handler logs them differently, if at all, and hard to test, since they
+//! must be mocked in every handler. Handlers perform them through [perform] instead, which records them in the
+//! [EffectLedger] of the current dispatch. In dry-run mode, the effects are only recorded, and never performed.
+//!
+//! - [perform]: Performs a side effect, recording it in the current ledger.
+//! - [EffectLedger]: Records the side effects performed on behalf of a dispatch.
+//! - [EffectMode]: Whether the side effects are performed, or only recorded.
+//! - [EffectRecord]: A side effect recorded in a ledger.
+//! - [EffectStatus]: The outcome of a recorded side effect.
+
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::scope;
+
+/// Whether the side effects are performed, or only recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EffectMode {
+    /// The side effects are performed, and recorded along with their outcome.
+    Live,
+    /// The side effects are only recorded, and never performed.
+    DryRun,
+}
+
+/// The outcome of a recorded side effect.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EffectStatus {
+    /// The side effect was recorded in dry-run mode, and not performed.
+    Captured,
+    /// The side effect was performed successfully.
+    Performed,
+    /// The side effect failed, with the given error.
+    Failed(String),
+}
+
+/// A side effect recorded in a ledger.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EffectRecord {
+    /// The kind of the side effect, e.g. `"email"`, or `"charge"`.
+    pub kind: &'static str,
+    /// A description of the side effect, e.g. the recipient of an email.
+    pub description: String,
+    /// The outcome of the side effect.
+    pub status: EffectStatus,
+}
+
+/// Records the side effects performed on behalf of a dispatch.
+///
+/// An `EffectLedger` is a handle, cloning it returns a handle to the same ledger. Side effects performed using
+/// [perform], while the future passed to [EffectLedger::scope] is being polled, are recorded in the ledger. Side
+/// effects performed outside of any ledger are performed, but not recorded.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     email: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+/// use discern::effect;
+/// use discern::effect::EffectLedger;
+/// use discern::effect::EffectStatus;
+///
+/// struct CreateUserCommandHandler;
+///
+/// #[async_trait]
+/// impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+///     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+///         effect::perform("email", format!("welcome email to {}", command.email), async {
+///             // Send the email...
+///             Ok::<(), std::io::Error>(())
+///         })
+///         .await?;
+///
+///         Ok(1)
+///     }
+/// }
+///
+/// let command_bus = command_bus! {
+///     CreateUserCommand => CreateUserCommandHandler,
+/// };
+///
+/// let ledger = EffectLedger::dry_run();
+/// let command = CreateUserCommand { email: "alice@example.com".to_string() };
+///
+/// ledger.scope(command_bus.dispatch(command)).await.unwrap();
+///
+/// let records = ledger.records();
+///
+/// assert_eq!(records.len(), 1);
+/// assert_eq!(records[0].kind, "email");
+/// assert_eq!(records[0].description, "welcome email to alice@example.com");
+/// assert_eq!(records[0].status, EffectStatus::Captured);
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct EffectLedger {
+    #[doc(hidden)]
+    inner: Arc<Inner>,
+}
+
+/// The state shared between the handles of an [EffectLedger].
+#[doc(hidden)]
+struct Inner {
+    mode: EffectMode,
+    records: Mutex<Vec<EffectRecord>>,
+}
+
+/// The `EffectLedger` implementation.
+impl EffectLedger {
+    /// Creates a new `EffectLedger`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Whether the side effects are performed, or only recorded.
+    pub fn new(mode: EffectMode) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                mode,
+                records: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Creates a new `EffectLedger`, performing the side effects, and recording their outcome.
+    pub fn live() -> Self {
+        Self::new(EffectMode::Live)
+    }
+
+    /// Creates a new `EffectLedger`, only recording the side effects.
+    pub fn dry_run() -> Self {
+        Self::new(EffectMode::DryRun)
+    }
+
+    /// Returns the ledger of the current dispatch, if it runs within one.
+    pub fn current() -> Option<EffectLedger> {
+        scope::effect_ledger()
+    }
+
+    /// Returns the mode of this ledger.
+    pub fn mode(&self) -> EffectMode {
+        self.inner.mode
+    }
+
+    /// Runs the given future within this ledger.
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - The future to run, usually a dispatch.
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut current = Some(self.clone());
+
+        std::future::poll_fn(|cx| {
+            scope::with_effect_ledger(&mut current, || future.as_mut().poll(cx))
+        })
+        .await
+    }
+
+    /// Returns the side effects recorded in this ledger, in the order they were performed.
+    pub fn records(&self) -> Vec<EffectRecord> {
+        self.inner
+            .records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Removes, and returns, the side effects recorded in this ledger.
+    pub fn take(&self) -> Vec<EffectRecord> {
+        std::mem::take(
+            &mut *self
+                .inner
+                .records
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    /// Records a side effect.
+    fn record(&self, kind: &'static str, description: String, status: EffectStatus) {
+        self.inner
+            .records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(EffectRecord {
+                kind,
+                description,
+                status,
+            });
+    }
+}
+
+/// Debug implementation for `EffectLedger`
+impl Debug for EffectLedger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("EffectLedger")
+            .field("mode", &self.inner.mode)
+            .field("records", &self.records())
+            .finish()
+    }
+}
+
+/// Performs a side effect, recording it in the ledger of the current dispatch.
+///
+/// Within a dry-run ledger, the side effect is recorded, but not performed, and `Ok(())` is returned. Within a live
+/// ledger, the side effect is performed, and recorded along with its outcome. Outside of any ledger, the side effect
+/// is performed, but not recorded.
+///
+/// # Arguments
+///
+/// * `kind` - The kind of the side effect, e.g. `"email"`, or `"charge"`.
+/// * `description` - A description of the side effect, e.g. the recipient of an email.
+/// * `effect` - The future performing the side effect.
+///
+/// # Returns
+///
+/// The result of the side effect, or `Ok(())` in dry-run mode.
+pub async fn perform<E, F>(
+    kind: &'static str,
+    description: impl Display,
+    effect: F,
+) -> Result<(), E>
+where
+    E: Debug,
+    F: Future<Output = Result<(), E>>,
+{
+    let Some(ledger) = EffectLedger::current() else {
+        return effect.await;
+    };
+
+    let description = description.to_string();
+    if ledger.mode() == EffectMode::DryRun {
+        ledger.record(kind, description, EffectStatus::Captured);
+
+        return Ok(());
+    }
+
+    let result = effect.await;
+    let status = match &result {
+        Ok(()) => EffectStatus::Performed,
+        Err(error) => EffectStatus::Failed(format!("{:?}", error)),
+    };
+
+    ledger.record(kind, description, status);
+
+    result
+}
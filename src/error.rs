@@ -0,0 +1,377 @@
+//! The `error` module provides the errors returned by the buses.
+//!
+//! - [DispatchError]: The error returned when dispatching a message fails.
+//! - [BoxedError]: A general purpose error type, for messages that don't need a dedicated one.
+//! - [ErrorClass]: Classifies errors, so that they are only retried when it could help.
+//! - [ErrorCategory]: The category of an error.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::ops::Deref;
+
+/// The error returned when dispatching a message fails.
+///
+/// Besides the error returned by the handler itself, dispatching a message can be rejected by the bus,
+/// before any handler is invoked.
+///
+/// # Example
+///
+/// ```
+/// use discern::error::DispatchError;
+///
+/// let error: DispatchError<std::io::Error> = DispatchError::Disabled;
+///
+/// assert!(error.handler_error().is_none());
+/// assert_eq!(error.to_string(), "the message is disabled");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DispatchError<E> {
+    /// No handler is registered for the message type.
+    HandlerNotFound,
+    /// The handler registered for the query takes it by value, so the query cannot be dispatched by reference, see
+    /// [QueryBus::dispatch_ref](crate::query::QueryBus::dispatch_ref).
+    NotBorrowable,
+    /// The message type is disabled, or the bus is in maintenance mode.
+    Disabled,
+    /// The message was rejected by a middleware, with the given reason.
+    Rejected(String),
+    /// The handler succeeded, but a middleware wrapping it rejected the command afterwards, with the given reason,
+    /// e.g. when a transaction failed to commit.
+    ///
+    /// Unlike [DispatchError::Rejected], the handler already ran, and its side effects outside of the rejected
+    /// scope took place, so the command must not be retried blindly.
+    RejectedAfterHandling(String),
+    /// The rate limit of the message type was exceeded, see [RateLimit](crate::ratelimit::RateLimit).
+    RateLimited,
+    /// The handler returned an error.
+    Handler(E),
+}
+
+/// The `DispatchError` implementation.
+impl<E> DispatchError<E> {
+    /// Returns the error returned by the handler, if any.
+    pub fn handler_error(&self) -> Option<&E> {
+        match self {
+            Self::Handler(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Returns the error returned by the handler, if any, consuming the `DispatchError`.
+    pub fn into_handler_error(self) -> Option<E> {
+        match self {
+            Self::Handler(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<E: Display> Display for DispatchError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::HandlerNotFound => write!(f, "no handler is registered for the message"),
+            Self::NotBorrowable => write!(f, "the handler of the message does not borrow it"),
+            Self::Disabled => write!(f, "the message is disabled"),
+            Self::Rejected(reason) => write!(f, "the message was rejected: {}", reason),
+            Self::RejectedAfterHandling(reason) => {
+                write!(f, "the message was handled, then rejected: {}", reason)
+            }
+            Self::RateLimited => write!(f, "the message was rate limited"),
+            Self::Handler(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for DispatchError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Handler(error) => error.source(),
+            _ => None,
+        }
+    }
+}
+
+/// The category of an error, see [ErrorClass].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// The failure is temporary, e.g. a timeout, or a dropped connection, and the same attempt may succeed later.
+    Transient,
+    /// The failure will happen again, e.g. an invalid input, or a missing permission.
+    Permanent,
+    /// The attempt conflicted with a concurrent change, e.g. an optimistic concurrency check, and may succeed once
+    /// attempted again against the new state.
+    Conflict,
+    /// The target of the message does not exist.
+    NotFound,
+}
+
+/// The `ErrorCategory` implementation.
+impl ErrorCategory {
+    /// Returns whether errors of this category may succeed when attempted again, i.e. transient errors, and
+    /// conflicts.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Transient | Self::Conflict)
+    }
+}
+
+/// Classifies errors, so that they are only retried when it could help.
+///
+/// Handler error types implement `ErrorClass` to tell the machinery wrapping handlers, e.g. a
+/// [RetryPolicy](crate::retry::RetryPolicy) created using
+/// [RetryPolicy::classified](crate::retry::RetryPolicy::classified), which of their errors are worth retrying,
+/// instead of retrying every error blindly.
+///
+/// # Example
+///
+/// ```
+/// use discern::error::ErrorCategory;
+/// use discern::error::ErrorClass;
+///
+/// #[derive(Debug)]
+/// enum CreateUserError {
+///     DatabaseUnavailable,
+///     UsernameTaken,
+///     InvalidUsername,
+/// }
+///
+/// impl ErrorClass for CreateUserError {
+///     fn category(&self) -> ErrorCategory {
+///         match self {
+///             CreateUserError::DatabaseUnavailable => ErrorCategory::Transient,
+///             CreateUserError::UsernameTaken => ErrorCategory::Conflict,
+///             CreateUserError::InvalidUsername => ErrorCategory::Permanent,
+///         }
+///     }
+/// }
+///
+/// assert!(CreateUserError::DatabaseUnavailable.is_retryable());
+/// assert!(!CreateUserError::InvalidUsername.is_retryable());
+/// ```
+pub trait ErrorClass {
+    /// Returns the category of the error.
+    fn category(&self) -> ErrorCategory;
+
+    /// Returns whether attempting again may succeed, see [ErrorCategory::is_retryable].
+    fn is_retryable(&self) -> bool {
+        self.category().is_retryable()
+    }
+}
+
+/// The errors returned by the handler keep their own category, a missing handler is not found, a rate limited
+/// message is transient, and a disabled message, a handler that cannot borrow the message, or a rejection,
+/// including one after handling, is permanent.
+///
+/// A disabled message stays disabled until an operator enables it again, which retrying within a dispatch can't
+/// wait for, so it is not retried.
+///
+/// # Example
+///
+/// ```
+/// use discern::error::DispatchError;
+/// use discern::error::ErrorClass;
+///
+/// assert!(DispatchError::<std::io::Error>::RateLimited.is_retryable());
+/// assert!(!DispatchError::<std::io::Error>::Disabled.is_retryable());
+/// ```
+impl<E: ErrorClass> ErrorClass for DispatchError<E> {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            Self::HandlerNotFound => ErrorCategory::NotFound,
+            Self::RateLimited => ErrorCategory::Transient,
+            Self::Disabled
+            | Self::NotBorrowable
+            | Self::Rejected(_)
+            | Self::RejectedAfterHandling(_) => ErrorCategory::Permanent,
+            Self::Handler(error) => error.category(),
+        }
+    }
+}
+
+/// I/O errors are classified by their kind: timeouts, interruptions, and dropped connections are transient,
+/// [NotFound](std::io::ErrorKind::NotFound) is not found, [AlreadyExists](std::io::ErrorKind::AlreadyExists) is a
+/// conflict, and every other kind is permanent.
+#[cfg(feature = "std")]
+impl ErrorClass for std::io::Error {
+    fn category(&self) -> ErrorCategory {
+        use std::io::ErrorKind;
+
+        match self.kind() {
+            ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe => ErrorCategory::Transient,
+            ErrorKind::NotFound => ErrorCategory::NotFound,
+            ErrorKind::AlreadyExists => ErrorCategory::Conflict,
+            _ => ErrorCategory::Permanent,
+        }
+    }
+}
+
+/// A general purpose error type, for messages that don't need a dedicated one.
+///
+/// Any error can be converted into a `BoxedError`, which makes the `?` operator work within handlers regardless
+/// of the errors they encounter. Errors that are just a message can be created using [BoxedError::msg].
+///
+/// `BoxedError` dereferences to the underlying `dyn Error`, but does not implement `Error` itself, as that would
+/// conflict with the conversion from any error. Use [BoxedError::into_inner] to retrieve a `Box<dyn Error>`.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::async_trait;
+/// use discern::command::Command;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+/// use discern::error::BoxedError;
+///
+/// #[derive(Debug)]
+/// struct SetUserAgeCommand {
+///    user_id: u64,
+///    age: String,
+/// }
+///
+/// impl Command for SetUserAgeCommand {
+///   type Metadata = u8;
+///   type Error = BoxedError;
+/// }
+///
+/// struct SetUserAgeCommandHandler;
+///
+/// #[async_trait]
+/// impl CommandHandler<SetUserAgeCommand> for SetUserAgeCommandHandler {
+///    async fn handle(&self, command: SetUserAgeCommand) -> Result<u8, BoxedError> {
+///       let age: u8 = command.age.parse()?;
+///       if age < 18 {
+///           return Err(BoxedError::msg("the user must be an adult"));
+///       }
+///
+///       Ok(age)
+///   }
+/// }
+///
+/// let command_bus = command_bus! {
+///     SetUserAgeCommand => SetUserAgeCommandHandler,
+/// };
+///
+/// let error = command_bus.dispatch(SetUserAgeCommand { user_id: 1, age: "old".to_string() }).await.unwrap_err();
+/// assert!(error.handler_error().unwrap().is::<std::num::ParseIntError>());
+///
+/// let error = command_bus.dispatch(SetUserAgeCommand { user_id: 1, age: "12".to_string() }).await.unwrap_err();
+/// assert_eq!(error.to_string(), "the user must be an adult");
+/// # });
+/// ```
+pub struct BoxedError {
+    #[doc(hidden)]
+    inner: Box<dyn Error + Send + Sync + 'static>,
+}
+
+/// The `BoxedError` implementation.
+impl BoxedError {
+    /// Creates a new `BoxedError` from the given error.
+    pub fn new(error: impl Error + Send + Sync + 'static) -> Self {
+        Self {
+            inner: Box::new(error),
+        }
+    }
+
+    /// Creates a new `BoxedError` from the given message.
+    pub fn msg(message: impl Display + Debug + Send + Sync + 'static) -> Self {
+        Self::new(Message(message))
+    }
+
+    /// Returns whether the underlying error is of type `E`.
+    pub fn is<E: Error + 'static>(&self) -> bool {
+        self.inner.is::<E>()
+    }
+
+    /// Returns a reference to the underlying error, if it is of type `E`.
+    pub fn downcast_ref<E: Error + 'static>(&self) -> Option<&E> {
+        self.inner.downcast_ref::<E>()
+    }
+
+    /// Returns the underlying error, if it is of type `E`, or the `BoxedError` itself otherwise.
+    pub fn downcast<E: Error + 'static>(self) -> Result<E, Self> {
+        self.inner
+            .downcast::<E>()
+            .map(|error| *error)
+            .map_err(|inner| Self { inner })
+    }
+
+    /// Returns the underlying error.
+    pub fn into_inner(self) -> Box<dyn Error + Send + Sync + 'static> {
+        self.inner
+    }
+}
+
+impl<E: Error + Send + Sync + 'static> From<E> for BoxedError {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+impl From<BoxedError> for Box<dyn Error + Send + Sync + 'static> {
+    fn from(error: BoxedError) -> Self {
+        error.inner
+    }
+}
+
+impl From<BoxedError> for Box<dyn Error + 'static> {
+    fn from(error: BoxedError) -> Self {
+        error.inner
+    }
+}
+
+impl Deref for BoxedError {
+    type Target = dyn Error + Send + Sync + 'static;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.inner
+    }
+}
+
+impl AsRef<dyn Error + Send + Sync + 'static> for BoxedError {
+    fn as_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.inner
+    }
+}
+
+/// Debug implementation for `BoxedError`
+impl Debug for BoxedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        Debug::fmt(&self.inner, f)
+    }
+}
+
+impl Display for BoxedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+/// An error that is just a message.
+#[doc(hidden)]
+struct Message<M>(M);
+
+impl<M: Debug> Debug for Message<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+impl<M: Display> Display for Message<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl<M: Display + Debug> Error for Message<M> {}
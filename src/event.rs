@@ -0,0 +1,542 @@
+//! The `event` module defines the abstractions and components related to publishing events.
+//!
+//! Events are facts that happened in the system, e.g. "user created". Unlike a command, which is handled by exactly
+//! one handler, an event can have any number of handlers, called subscribers, each reacting to it independently,
+//! e.g. sending a welcome email, or updating a read model.
+//!
+//! The `EventBus` is responsible for publishing events to all of their subscribers. It utilizes the
+//! `EventHandlerRegistry` from the [registry](crate::registry) module to manage the subscribers.
+//!
+//! - [Event]: Represents an event in the system.
+//! - [EventHandler]: Trait for handling events.
+//! - [EventBus]: Publishes events to their subscribers.
+//! - [PublishError]: The error returned when some subscribers fail to handle an event.
+//!
+//! # See Also
+//!
+//! - [EventHandlerRegistry]: Manages event handlers.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::error::Error;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::task::Poll;
+
+use crate::async_trait;
+use crate::registry::EventHandlerRegistry;
+use crate::registry::SharedRegistry;
+use crate::runtime::BoxFuture;
+
+/// The metadata an event is stamped with when published, which is only tracked when the `std` feature is enabled.
+#[cfg(feature = "std")]
+pub(crate) type Stamp = crate::metadata::EventMetadata;
+#[cfg(not(feature = "std"))]
+pub(crate) type Stamp = ();
+
+/// The `Event` trait represents a fact that happened in the system.
+///
+/// # Example
+///
+/// ```
+/// use discern::event::Event;
+///
+/// #[derive(Debug)]
+/// struct UserCreatedEvent {
+///    user_id: u64,
+/// }
+///
+/// impl Event for UserCreatedEvent {
+///   // The error type that is returned if a subscriber fails.
+///   type Error = std::io::Error;
+/// }
+/// ```
+pub trait Event: Send + Sync + Any + Debug {
+    /// The error type that is returned if a subscriber fails to handle the event.
+    ///
+    /// This type must implement the `Debug`, `Send`, and `Sync` traits.
+    type Error: Debug + Send + Sync;
+
+    /// Returns the name of the event, used to identify it in logs, metrics, and when routing by name.
+    ///
+    /// Defaults to the type name of the event, which changes whenever the type is moved, or renamed. Events
+    /// implementing [MessageName](crate::message::MessageName) should return its name instead.
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        core::any::type_name::<Self>()
+    }
+
+    /// Returns the descriptor of the event, see [MessageDescriptor](crate::message::MessageDescriptor).
+    ///
+    /// Defaults to the name of the event, at version `1`, with an unknown schema hash. Events implementing
+    /// [MessageName](crate::message::MessageName) should return
+    /// [MessageDescriptor::of](crate::message::MessageDescriptor::of) instead.
+    fn descriptor() -> crate::message::MessageDescriptor
+    where
+        Self: Sized,
+    {
+        crate::message::MessageDescriptor::new(Self::name(), crate::message::MessageKind::Event)
+    }
+}
+
+/// The `EventHandler` trait represents a subscriber reacting to an event.
+///
+/// Since an event is handled by all of its subscribers, handlers receive a reference to it.
+///
+/// # Example
+///
+/// ```
+/// # use discern::event::Event;
+/// #
+/// # #[derive(Debug)]
+/// # struct UserCreatedEvent {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Event for UserCreatedEvent {
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::event::EventHandler;
+///
+/// struct SendWelcomeEmail;
+///
+/// #[async_trait]
+/// impl EventHandler<UserCreatedEvent> for SendWelcomeEmail {
+///    async fn handle(&self, event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+///       // Send the welcome email.
+///       Ok(())
+///   }
+/// }
+/// ```
+#[async_trait]
+pub trait EventHandler<E: Event>: Send + Sync {
+    async fn handle(&self, event: &E) -> Result<(), E::Error>;
+}
+
+/// The error of a subscriber that failed to handle an event.
+#[derive(Debug)]
+pub struct SubscriberError<E> {
+    /// The type name of the subscriber.
+    pub subscriber: &'static str,
+    /// The error returned by the subscriber.
+    pub error: E,
+}
+
+/// The error returned when some subscribers fail to handle an event.
+///
+/// A failing subscriber does not prevent the others from handling the event, the errors of all the failing
+/// subscribers are collected, in the order they were subscribed.
+#[derive(Debug)]
+pub struct PublishError<E> {
+    /// The number of subscribers the event was published to.
+    pub subscribers: usize,
+    /// The errors of the subscribers that failed.
+    pub failures: Vec<SubscriberError<E>>,
+}
+
+impl<E: Display> Display for PublishError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        write!(
+            f,
+            "{} of {} subscribers failed to handle the event",
+            self.failures.len(),
+            self.subscribers
+        )?;
+
+        for failure in &self.failures {
+            write!(f, "; {}: {}", failure.subscriber, failure.error)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: Debug + Display> Error for PublishError<E> {}
+
+/// The `EventBus` struct is responsible for publishing events to their subscribers.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::sync::atomic::AtomicU64;
+/// use std::sync::atomic::Ordering;
+/// use std::sync::Arc;
+///
+/// use discern::async_trait;
+/// use discern::event::Event;
+/// use discern::event::EventHandler;
+/// use discern::event_bus;
+///
+/// #[derive(Debug)]
+/// struct UserCreatedEvent {
+///    user_id: u64,
+/// }
+///
+/// impl Event for UserCreatedEvent {
+///   type Error = std::io::Error;
+/// }
+///
+/// struct SendWelcomeEmail;
+///
+/// #[async_trait]
+/// impl EventHandler<UserCreatedEvent> for SendWelcomeEmail {
+///    async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+///       Err(std::io::Error::other("mail server is down"))
+///   }
+/// }
+///
+/// struct CountUsers {
+///    count: Arc<AtomicU64>,
+/// }
+///
+/// #[async_trait]
+/// impl EventHandler<UserCreatedEvent> for CountUsers {
+///    async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+///       self.count.fetch_add(1, Ordering::SeqCst);
+///       Ok(())
+///   }
+/// }
+///
+/// let count = Arc::new(AtomicU64::new(0));
+///
+/// let event_bus = event_bus! {
+///     UserCreatedEvent => SendWelcomeEmail,
+///     UserCreatedEvent => CountUsers { count: count.clone() },
+/// };
+///
+/// let error = event_bus.publish(UserCreatedEvent { user_id: 1 }).await.unwrap_err();
+///
+/// // The failing subscriber did not prevent the other one from handling the event.
+/// assert_eq!(count.load(Ordering::SeqCst), 1);
+/// assert_eq!(error.subscribers, 2);
+/// assert_eq!(error.failures.len(), 1);
+/// # });
+/// ```
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    #[doc(hidden)]
+    registry: Arc<SharedRegistry<EventHandlerRegistry>>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    position: Arc<core::sync::atomic::AtomicU64>,
+}
+
+/// The `EventBus` implementation.
+impl EventBus {
+    /// Creates a new `EventBus` instance.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::event::EventBus;
+    /// use discern::registry::EventHandlerRegistry;
+    ///
+    /// let registry = EventHandlerRegistry::new();
+    /// let event_bus = EventBus::new(registry);
+    ///
+    /// # assert!(true);
+    /// ```
+    pub fn new(registry: EventHandlerRegistry) -> Self {
+        Self {
+            registry: Arc::new(SharedRegistry::new(registry)),
+            #[cfg(feature = "std")]
+            position: Arc::new(core::sync::atomic::AtomicU64::new(0)),
+        }
+    }
+
+    /// Subscribes an event handler at runtime.
+    ///
+    /// The handler is added to the registry shared by this `EventBus` and its clones, and receives the events
+    /// published after it was subscribed.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to subscribe to the event type `E`.
+    #[cfg(feature = "std")]
+    pub fn subscribe<E: Event>(&self, handler: impl EventHandler<E> + 'static) {
+        let mut registry = EventHandlerRegistry::new();
+        registry.subscribe(handler);
+
+        self.registry.update(|current| {
+            let mut updated = current.clone();
+            updated.merge(registry.clone());
+
+            updated
+        });
+    }
+
+    /// Returns the position of the last event published through this `EventBus`, and its clones, i.e. the number
+    /// of events published so far, including the events without subscribers.
+    ///
+    /// Events published while handling a command dispatched using
+    /// [CommandBus::try_dispatch_outcome](crate::command::CommandBus::try_dispatch_outcome) are reported along with
+    /// their position, see [EmittedEvent](crate::outcome::EmittedEvent).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::event::Event;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct UserCreatedEvent;
+    /// #
+    /// # impl Event for UserCreatedEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::event::EventBus;
+    /// use discern::registry::EventHandlerRegistry;
+    ///
+    /// let event_bus = EventBus::new(EventHandlerRegistry::new());
+    ///
+    /// // The event has no subscribers, but is published nonetheless.
+    /// assert_eq!(event_bus.publish(UserCreatedEvent).await.unwrap(), 0);
+    /// assert_eq!(event_bus.position(), 1);
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn position(&self) -> u64 {
+        self.position.load(core::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Returns the number of handlers subscribed to the event type `E`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::async_trait;
+    /// # use discern::event::Event;
+    /// # use discern::event::EventHandler;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct UserCreatedEvent;
+    /// #
+    /// # impl Event for UserCreatedEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # struct SendWelcomeEmail;
+    /// #
+    /// # #[async_trait]
+    /// # impl EventHandler<UserCreatedEvent> for SendWelcomeEmail {
+    /// #    async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+    /// #       Ok(())
+    /// #   }
+    /// # }
+    /// use discern::event::EventBus;
+    /// use discern::registry::EventHandlerRegistry;
+    ///
+    /// let event_bus = EventBus::new(EventHandlerRegistry::new());
+    /// assert_eq!(event_bus.subscribers::<UserCreatedEvent>(), 0);
+    ///
+    /// event_bus.subscribe(SendWelcomeEmail);
+    /// assert_eq!(event_bus.subscribers::<UserCreatedEvent>(), 1);
+    /// ```
+    pub fn subscribers<E: Event>(&self) -> usize {
+        self.registry.with(|registry| registry.subscribers::<E>())
+    }
+
+    /// Publishes an event to all of its subscribers.
+    ///
+    /// The subscribers handle the event concurrently, and the errors of the failing subscribers are collected,
+    /// instead of aborting on the first error. Publishing an event without subscribers succeeds.
+    ///
+    /// Every published event is a fact that happened, whether or not anything reacts to it, so events without
+    /// subscribers still advance the [position](EventBus::position), and are still reported to
+    /// [CommandBus::try_dispatch_outcome](crate::command::CommandBus::try_dispatch_outcome).
+    ///
+    /// When the `std` feature is enabled, the event is stamped with its position, and with the correlation id, and
+    /// the principal, of the event being handled by the current subscriber, if any, see
+    /// [EventMetadata](crate::metadata::EventMetadata). Use [EventBus::publish_with] to stamp the metadata of a
+    /// dispatch context instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to publish.
+    ///
+    /// # Returns
+    ///
+    /// The number of subscribers that handled the event, or a [PublishError] if any of them failed.
+    pub async fn publish<E: Event>(&self, event: E) -> Result<usize, PublishError<E::Error>> {
+        #[cfg(feature = "std")]
+        return self
+            .publish_stamped(event, crate::metadata::EventMetadata::inherited())
+            .await;
+        #[cfg(not(feature = "std"))]
+        self.publish_stamped(event, ()).await
+    }
+
+    /// Publishes an event to all of its subscribers, stamped with the metadata found in the given context.
+    ///
+    /// The subscribers read the [CorrelationId](crate::metadata::CorrelationId), the
+    /// [CausationId](crate::metadata::CausationId), and the [CallerId](crate::caller::CallerId) of the context,
+    /// along with the position of the event, using [EventMetadata::current](crate::metadata::EventMetadata::current).
+    /// Events published using [Context::publish](crate::context::Context::publish) are stamped the same way.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to publish.
+    /// * `context` - The context of the dispatch publishing the event.
+    ///
+    /// # Returns
+    ///
+    /// The number of subscribers that handled the event, or a [PublishError] if any of them failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::event::Event;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct UserCreatedEvent;
+    /// #
+    /// # impl Event for UserCreatedEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use std::sync::Arc;
+    /// use std::sync::Mutex;
+    ///
+    /// use discern::async_trait;
+    /// use discern::context::Context;
+    /// use discern::event::EventHandler;
+    /// use discern::event_bus;
+    /// use discern::metadata::CorrelationId;
+    /// use discern::metadata::EventMetadata;
+    ///
+    /// struct RecordCorrelation {
+    ///     correlations: Arc<Mutex<Vec<Option<CorrelationId>>>>,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl EventHandler<UserCreatedEvent> for RecordCorrelation {
+    ///     async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+    ///         let metadata = EventMetadata::current().unwrap();
+    ///         self.correlations.lock().unwrap().push(metadata.correlation_id);
+    ///
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let correlations = Arc::new(Mutex::new(Vec::new()));
+    /// let event_bus = event_bus! {
+    ///     UserCreatedEvent => RecordCorrelation { correlations: correlations.clone() },
+    /// };
+    ///
+    /// let context = Context::new().with(CorrelationId::new("signup-42"));
+    /// event_bus.publish_with(UserCreatedEvent, &context).await.unwrap();
+    /// event_bus.publish(UserCreatedEvent).await.unwrap();
+    ///
+    /// assert_eq!(*correlations.lock().unwrap(), vec![Some(CorrelationId::new("signup-42")), None]);
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub async fn publish_with<E: Event>(
+        &self,
+        event: E,
+        context: &crate::context::Context,
+    ) -> Result<usize, PublishError<E::Error>> {
+        self.publish_stamped(event, crate::metadata::EventMetadata::of(context))
+            .await
+    }
+
+    /// Publishes an event to all of its subscribers, stamped with the given metadata, and the position of the event.
+    pub(crate) async fn publish_stamped<E: Event>(
+        &self,
+        event: E,
+        #[cfg_attr(not(feature = "std"), allow(unused_mut, unused_variables))] mut metadata: Stamp,
+    ) -> Result<usize, PublishError<E::Error>> {
+        type Delivery<'a, E> = BoxFuture<'a, Result<(), <E as Event>::Error>>;
+
+        let entries = self.registry.with(|registry| registry.entries::<E>());
+        let subscribers = entries.len();
+
+        #[cfg(feature = "std")]
+        {
+            let position = self
+                .position
+                .fetch_add(1, core::sync::atomic::Ordering::AcqRel)
+                + 1;
+
+            crate::outcome::EventLog::record(E::descriptor(), position);
+            metadata.position = position;
+        }
+
+        let mut in_flight: Vec<Option<Delivery<'_, E>>> = entries
+            .iter()
+            .map(|entry| Some(entry.handle(&event)))
+            .collect();
+        let mut results: Vec<Option<Result<(), E::Error>>> =
+            (0..subscribers).map(|_| None).collect();
+
+        #[cfg(feature = "std")]
+        let mut metadata = Some(metadata);
+
+        core::future::poll_fn(|cx| {
+            #[allow(unused_mut)]
+            let mut poll = || {
+                let mut pending = false;
+                for (index, slot) in in_flight.iter_mut().enumerate() {
+                    let Some(future) = slot else {
+                        continue;
+                    };
+
+                    match future.as_mut().poll(cx) {
+                        Poll::Ready(result) => {
+                            results[index] = Some(result);
+                            *slot = None;
+                        }
+                        Poll::Pending => pending = true,
+                    }
+                }
+
+                if pending {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(())
+                }
+            };
+
+            // The subscribers read the metadata of the event while they are polled.
+            #[cfg(feature = "std")]
+            return crate::scope::with_event_metadata(&mut metadata, poll);
+            #[cfg(not(feature = "std"))]
+            poll()
+        })
+        .await;
+
+        drop(in_flight);
+
+        let failures: Vec<SubscriberError<E::Error>> = entries
+            .iter()
+            .zip(results)
+            .filter_map(|(entry, result)| match result {
+                Some(Err(error)) => Some(SubscriberError {
+                    subscriber: entry.name,
+                    error,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(subscribers)
+        } else {
+            Err(PublishError {
+                subscribers,
+                failures,
+            })
+        }
+    }
+}
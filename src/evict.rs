@@ -0,0 +1,60 @@
+//! The `evict` module provides maps evicting their stale entries as they grow.
+//!
+//! Caches, and per-caller state, are keyed by values coming from the outside, so their maps would grow with every
+//! key ever seen unless stale entries are evicted. Scanning the whole map on every insert evicts them as early as
+//! possible, but makes each insert cost as much as the size of the map, while holding its lock. Instead, an
+//! [EvictingMap] scans the map once it doubled in size since the previous scan, so that evicting stale entries costs
+//! amortized constant time per insert, and the map holds at most about twice as many entries as there are live
+//! ones.
+
+use core::hash::Hash;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use std::collections::HashMap;
+
+/// The number of entries below which a map is never scanned.
+const MIN_THRESHOLD: usize = 64;
+
+/// A map evicting its stale entries as it grows, see the [module documentation](self).
+#[doc(hidden)]
+#[derive(Debug)]
+pub(crate) struct EvictingMap<K, V> {
+    entries: HashMap<K, V>,
+    threshold: usize,
+}
+
+impl<K: Eq + Hash, V> EvictingMap<K, V> {
+    /// Creates a new, empty, map.
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            threshold: MIN_THRESHOLD,
+        }
+    }
+
+    /// Evicts the entries for which `is_live` returns `false`, if the map doubled in size since the previous scan.
+    ///
+    /// This method should be called before inserting an entry.
+    pub(crate) fn evict(&mut self, is_live: impl FnMut(&K, &mut V) -> bool) {
+        if self.entries.len() < self.threshold {
+            return;
+        }
+
+        self.entries.retain(is_live);
+        self.threshold = MIN_THRESHOLD.max(self.entries.len() * 2);
+    }
+}
+
+impl<K, V> Deref for EvictingMap<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl<K, V> DerefMut for EvictingMap<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.entries
+    }
+}
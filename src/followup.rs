@@ -0,0 +1,292 @@
+//! The `followup` module provides chained dispatch of follow-up commands.
+//!
+//! Orchestrating handlers often need to trigger further commands once they succeed, e.g. sending a welcome email
+//! after creating a user. Instead of holding a handle to the bus, such handlers return the follow-up commands as
+//! part of their metadata, and the [CommandBus] dispatches them once the handler succeeded, see
+//! [Command::followups].
+//!
+//! - [Followups]: A list of follow-up commands.
+//! - [Chain]: Dispatches commands, and reports the outcome of their follow-up commands.
+//! - [ChainReport]: The report of the follow-up commands dispatched along with a command.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+use crate::command::Command;
+use crate::command::CommandBus;
+use crate::context::Context;
+use crate::error::DispatchError;
+use crate::runtime::BoxFuture;
+
+/// The maximum depth of follow-up commands, unless configured otherwise using [Chain::with_max_depth].
+pub(crate) const MAX_DEPTH: usize = 8;
+
+/// A list of follow-up commands.
+///
+/// Follow-up commands are dispatched in the order they were added, each one followed by its own follow-up commands,
+/// if any, see [Command::followups].
+#[derive(Default)]
+pub struct Followups {
+    #[doc(hidden)]
+    followups: Vec<Followup>,
+}
+
+/// Dispatches a follow-up command at the given depth, returning its result, and the report of its own follow-up
+/// commands.
+type Dispatch = Box<
+    dyn for<'a> FnOnce(
+            &'a CommandBus,
+            usize,
+            usize,
+        ) -> BoxFuture<'a, (Result<(), FollowupError>, ChainReport)>
+        + Send
+        + Sync,
+>;
+
+/// A follow-up command, waiting to be dispatched.
+#[doc(hidden)]
+struct Followup {
+    command: &'static str,
+    dispatch: Dispatch,
+}
+
+/// The `Followups` implementation.
+impl Followups {
+    /// Creates an empty list of follow-up commands.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a follow-up command, whose own follow-up commands are dispatched once it succeeded.
+    pub fn push<C: Command>(&mut self, command: C) {
+        self.followups.push(Followup {
+            command: C::name(),
+            dispatch: Box::new(|command_bus, depth, max_depth| {
+                Box::pin(async move {
+                    let (result, report) = command_bus
+                        .dispatch_chained(command, Context::new(), depth, max_depth)
+                        .await;
+
+                    (
+                        result.map(|_| ()).map_err(FollowupError::from_dispatch),
+                        report,
+                    )
+                })
+            }),
+        });
+    }
+
+    /// Returns the number of follow-up commands.
+    pub fn len(&self) -> usize {
+        self.followups.len()
+    }
+
+    /// Returns whether there are no follow-up commands.
+    pub fn is_empty(&self) -> bool {
+        self.followups.is_empty()
+    }
+
+    /// Dispatches the follow-up commands, in order, at the given depth, along with their own follow-up commands.
+    ///
+    /// Commands nested deeper than the maximum depth are skipped, and reported as failures.
+    pub(crate) async fn dispatch(
+        self,
+        command_bus: &CommandBus,
+        depth: usize,
+        max_depth: usize,
+    ) -> ChainReport {
+        let mut report = ChainReport::default();
+        for followup in self.followups {
+            if depth > max_depth {
+                report.failures.push(FollowupFailure {
+                    command: followup.command,
+                    depth,
+                    error: FollowupError::DepthExceeded,
+                });
+
+                continue;
+            }
+
+            let (result, nested) = (followup.dispatch)(command_bus, depth, max_depth).await;
+            match result {
+                Ok(()) => report.succeeded += 1,
+                Err(error) => report.failures.push(FollowupFailure {
+                    command: followup.command,
+                    depth,
+                    error,
+                }),
+            }
+
+            report.succeeded += nested.succeeded;
+            report.failures.extend(nested.failures);
+        }
+
+        report
+    }
+}
+
+/// Debug implementation for `Followups`
+impl Debug for Followups {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_list()
+            .entries(self.followups.iter().map(|followup| followup.command))
+            .finish()
+    }
+}
+
+/// The reason a follow-up command failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowupError {
+    /// The command was rejected by the bus, or its handler failed.
+    Dispatch(String),
+    /// The command is nested deeper than the maximum depth.
+    DepthExceeded,
+}
+
+/// The `FollowupError` implementation.
+impl FollowupError {
+    /// Creates a `FollowupError` from the error returned by the bus.
+    fn from_dispatch<E: Debug>(error: DispatchError<E>) -> Self {
+        Self::Dispatch(format!("{:?}", error))
+    }
+}
+
+/// A follow-up command that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowupFailure {
+    /// The type name of the command.
+    pub command: &'static str,
+    /// The depth of the command, follow-ups of the dispatched command have a depth of `1`.
+    pub depth: usize,
+    /// The reason the command failed.
+    pub error: FollowupError,
+}
+
+/// The report of the follow-up commands dispatched along with a command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainReport {
+    /// The number of follow-up commands that succeeded.
+    pub succeeded: usize,
+    /// The follow-up commands that failed, or were skipped.
+    pub failures: Vec<FollowupFailure>,
+}
+
+/// Dispatches commands, and reports the outcome of their follow-up commands.
+///
+/// The [CommandBus] dispatches follow-up commands on its own, counting the ones that failed, see
+/// [CommandBus::failed_followups]. A `Chain` dispatches commands the same way, but returns the [ChainReport] of their
+/// follow-up commands along with their metadata, and allows configuring the maximum depth of follow-up commands.
+///
+/// Once the dispatched command succeeded, its follow-up commands are dispatched one at a time, in order, each one
+/// followed by its own follow-up commands. A follow-up command failing doesn't prevent the others from being
+/// dispatched, the failures are collected into the [ChainReport] instead. Commands are free to follow up with
+/// commands of their own type, e.g. to import the next page of a listing, so to prevent runaway chains, follow-up
+/// commands nested deeper than the maximum depth are skipped, and reported as failures.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::async_trait;
+/// use discern::command::Command;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+/// use discern::followup::Chain;
+/// use discern::followup::FollowupError;
+/// use discern::followup::Followups;
+///
+/// #[derive(Debug)]
+/// struct ImportPageCommand {
+///     page: u64,
+/// }
+///
+/// impl Command for ImportPageCommand {
+///     type Metadata = Followups;
+///     type Error = std::io::Error;
+///
+///     fn followups(metadata: &mut Followups) -> Followups {
+///         std::mem::take(metadata)
+///     }
+/// }
+///
+/// struct ImportPageCommandHandler;
+///
+/// #[async_trait]
+/// impl CommandHandler<ImportPageCommand> for ImportPageCommandHandler {
+///     async fn handle(&self, command: ImportPageCommand) -> Result<Followups, std::io::Error> {
+///         let mut followups = Followups::new();
+///         if command.page < 3 {
+///             followups.push(ImportPageCommand { page: command.page + 1 });
+///         }
+///
+///         Ok(followups)
+///     }
+/// }
+///
+/// let command_bus = command_bus! {
+///     ImportPageCommand => ImportPageCommandHandler,
+/// };
+///
+/// let (_, report) = Chain::new(command_bus.clone()).dispatch(ImportPageCommand { page: 0 }).await.unwrap();
+///
+/// assert_eq!(report.succeeded, 3);
+/// assert!(report.failures.is_empty());
+///
+/// let (_, report) = Chain::new(command_bus).with_max_depth(2).dispatch(ImportPageCommand { page: 0 }).await.unwrap();
+///
+/// assert_eq!(report.succeeded, 2);
+/// assert_eq!(report.failures[0].depth, 3);
+/// assert_eq!(report.failures[0].error, FollowupError::DepthExceeded);
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chain {
+    #[doc(hidden)]
+    command_bus: CommandBus,
+    #[doc(hidden)]
+    max_depth: usize,
+}
+
+/// The `Chain` implementation.
+impl Chain {
+    /// Creates a new `Chain`, dispatching follow-up commands up to a depth of 8, like the [CommandBus] does.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_bus` - The command bus to dispatch the commands to.
+    pub fn new(command_bus: CommandBus) -> Self {
+        Self {
+            command_bus,
+            max_depth: MAX_DEPTH,
+        }
+    }
+
+    /// Sets the maximum depth of follow-up commands.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Dispatches the given command, and its follow-up commands once it succeeded.
+    ///
+    /// # Returns
+    ///
+    /// The metadata of the command, and the report of its follow-up commands, or the error of the command.
+    pub async fn dispatch<C: Command>(
+        &self,
+        command: C,
+    ) -> Result<(C::Metadata, ChainReport), DispatchError<C::Error>> {
+        let (result, report) = self
+            .command_bus
+            .dispatch_chained(command, Context::new(), 0, self.max_depth)
+            .await;
+
+        Ok((result?, report))
+    }
+}
@@ -0,0 +1,367 @@
+//! The `hedge` module provides a handler wrapper that hedges slow queries, and idempotent commands.
+//!
+//! A few slow requests, e.g. caused by a garbage collection pause, or a cold cache on one replica, dominate the
+//! tail latency of a service. Hedging sends a second attempt, to a replica, when the first one did not complete
+//! within the usual latency of the handler, and uses whichever completes first successfully.
+//!
+//! - [HedgedHandler]: A handler that hedges slow attempts using a replica handler.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::command::Idempotent;
+use crate::query::Query;
+use crate::query::QueryHandler;
+use crate::runtime::Runtime;
+
+/// An attempt at handling a message.
+type Attempt<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>;
+
+/// The minimum number of samples required before deriving the hedging delay from the observed latencies.
+const MIN_SAMPLES: usize = 10;
+
+/// A handler that hedges slow attempts using a replica handler.
+///
+/// Each query is first sent to the primary handler. If the primary did not complete once the hedging delay
+/// elapsed, or if it failed, the query is also sent to the replica handler. The first successful result is
+/// returned, and the other attempt is cancelled. If both attempts fail, the error of the last one is returned.
+///
+/// The hedging delay is the given percentile of the latencies recently observed for the primary handler, e.g.
+/// with the default percentile of `0.95`, about 5% of the queries are hedged. Until enough latencies are observed,
+/// the initial delay is used instead.
+///
+/// Since the query is sent twice, it must implement `Clone`. Commands can be hedged as well, as long as they
+/// implement [Idempotent], hedging a command that is not idempotent fails to compile:
+///
+/// ```compile_fail
+/// # use discern::async_trait;
+/// # use discern::command::Command;
+/// # use discern::command::CommandHandler;
+/// # use discern::hedge::HedgedHandler;
+/// # use discern::runtime::TokioRuntime;
+/// # use discern::command_bus;
+/// #
+/// #[derive(Debug, Clone)]
+/// struct ChargeCustomerCommand {
+///     amount: u64,
+/// }
+///
+/// impl Command for ChargeCustomerCommand {
+///     type Metadata = ();
+///     type Error = std::io::Error;
+/// }
+/// #
+/// # struct ChargeCustomerCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<ChargeCustomerCommand> for ChargeCustomerCommandHandler {
+/// #     async fn handle(&self, command: ChargeCustomerCommand) -> Result<(), std::io::Error> {
+/// #         Ok(())
+/// #     }
+/// # }
+///
+/// let command_bus = command_bus! {
+///     ChargeCustomerCommand => HedgedHandler::new(
+///         ChargeCustomerCommandHandler,
+///         ChargeCustomerCommandHandler,
+///         TokioRuntime,
+///     ),
+/// };
+/// ```
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug, Clone)]
+/// # struct GetUserQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #     type Output = String;
+/// #     type Error = std::io::Error;
+/// # }
+/// use std::time::Duration;
+///
+/// use discern::async_trait;
+/// use discern::hedge::HedgedHandler;
+/// use discern::query::QueryHandler;
+/// use discern::query_bus;
+/// use discern::runtime::TokioRuntime;
+///
+/// struct GetUserQueryHandler {
+///     replica: &'static str,
+///     latency: Duration,
+/// }
+///
+/// #[async_trait]
+/// impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+///     async fn handle(&self, query: GetUserQuery) -> Result<String, std::io::Error> {
+///         tokio::time::sleep(self.latency).await;
+///
+///         Ok(format!("user-{} from {}", query.user_id, self.replica))
+///     }
+/// }
+///
+/// let primary = GetUserQueryHandler { replica: "primary", latency: Duration::from_secs(5) };
+/// let replica = GetUserQueryHandler { replica: "replica", latency: Duration::from_millis(1) };
+///
+/// let query_bus = query_bus! {
+///     GetUserQuery => HedgedHandler::new(primary, replica, TokioRuntime)
+///         .with_initial_delay(Duration::from_millis(10)),
+/// };
+///
+/// let user = query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap();
+///
+/// assert_eq!(user, "user-1 from replica");
+/// # });
+/// ```
+pub struct HedgedHandler<H, R> {
+    #[doc(hidden)]
+    primary: H,
+    #[doc(hidden)]
+    replica: R,
+    #[doc(hidden)]
+    runtime: Box<dyn Runtime>,
+    #[doc(hidden)]
+    percentile: f64,
+    #[doc(hidden)]
+    initial_delay: Duration,
+    #[doc(hidden)]
+    window: usize,
+    #[doc(hidden)]
+    latencies: Mutex<VecDeque<Duration>>,
+    #[doc(hidden)]
+    hedged: AtomicU64,
+}
+
+/// The `HedgedHandler` implementation.
+impl<H, R> HedgedHandler<H, R> {
+    /// Creates a new `HedgedHandler`, hedging at the 95th percentile of the last 100 latencies, with an initial
+    /// delay of 100 milliseconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `primary` - The handler each query is sent to first.
+    /// * `replica` - The handler slow, or failed, queries are sent to.
+    /// * `runtime` - The runtime used to wait for the hedging delay.
+    pub fn new(primary: H, replica: R, runtime: impl Runtime) -> Self {
+        Self {
+            primary,
+            replica,
+            runtime: Box::new(runtime),
+            percentile: 0.95,
+            initial_delay: Duration::from_millis(100),
+            window: 100,
+            latencies: Mutex::new(VecDeque::new()),
+            hedged: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the percentile of the observed latencies used as the hedging delay.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `percentile` is not between `0.0` and `1.0`.
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&percentile),
+            "the percentile must be between 0.0 and 1.0"
+        );
+
+        self.percentile = percentile;
+        self
+    }
+
+    /// Sets the hedging delay used until enough latencies are observed.
+    pub fn with_initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Sets the number of recent latencies the hedging delay is derived from.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `window` is zero.
+    pub fn with_window(mut self, window: usize) -> Self {
+        assert!(window > 0, "the window must be greater than zero");
+
+        self.window = window;
+        self
+    }
+
+    /// Returns the current hedging delay.
+    pub fn delay(&self) -> Duration {
+        let latencies = self
+            .latencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if latencies.len() < MIN_SAMPLES {
+            return self.initial_delay;
+        }
+
+        let mut latencies: Vec<Duration> = latencies.iter().copied().collect();
+        latencies.sort_unstable();
+
+        let index = ((latencies.len() - 1) as f64 * self.percentile).round() as usize;
+
+        latencies[index]
+    }
+
+    /// Returns the number of attempts sent to the replica handler.
+    pub fn hedged(&self) -> u64 {
+        self.hedged.load(Ordering::Relaxed)
+    }
+
+    /// Records a latency observed for the primary handler.
+    fn observe(&self, latency: Duration) {
+        let mut latencies = self
+            .latencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if latencies.len() == self.window {
+            latencies.pop_front();
+        }
+
+        latencies.push_back(latency);
+    }
+
+    /// Sends the message to the primary attempt, and to the replica attempt once the hedging delay elapsed, or the
+    /// primary attempt failed.
+    async fn race<'a, M, T, E>(
+        &'a self,
+        message: M,
+        primary: impl FnOnce(M) -> Attempt<'a, T, E>,
+        replica: impl FnOnce(M) -> Attempt<'a, T, E>,
+    ) -> Result<T, E>
+    where
+        M: Clone,
+    {
+        let start = Instant::now();
+
+        let mut primary = Some(primary(message.clone()));
+        let mut timer = Some(self.runtime.sleep(self.delay()));
+        let mut replica = Some(replica);
+        let mut hedge = None;
+        let mut message = Some(message);
+        let mut failure = None;
+
+        let mut launch = |hedge: &mut Option<Attempt<'a, T, E>>| {
+            if let (Some(replica), Some(message)) = (replica.take(), message.take()) {
+                *hedge = Some(replica(message));
+                self.hedged.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+
+        std::future::poll_fn(|cx| {
+            if let Some(attempt) = primary.as_mut() {
+                if let Poll::Ready(result) = attempt.as_mut().poll(cx) {
+                    primary = None;
+                    self.observe(start.elapsed());
+
+                    match result {
+                        Ok(output) => return Poll::Ready(Ok(output)),
+                        Err(error) => {
+                            failure = Some(error);
+                            // Don't wait for the delay to elapse, the replica is the only attempt left.
+                            timer = None;
+                            launch(&mut hedge);
+                        }
+                    }
+                }
+            }
+
+            if let Some(sleep) = timer.as_mut() {
+                if sleep.as_mut().poll(cx).is_ready() {
+                    timer = None;
+                    launch(&mut hedge);
+                }
+            }
+
+            if let Some(attempt) = hedge.as_mut() {
+                if let Poll::Ready(result) = attempt.as_mut().poll(cx) {
+                    hedge = None;
+
+                    match result {
+                        Ok(output) => return Poll::Ready(Ok(output)),
+                        Err(error) => failure = Some(error),
+                    }
+                }
+            }
+
+            if primary.is_none() && hedge.is_none() {
+                if let Some(error) = failure.take() {
+                    return Poll::Ready(Err(error));
+                }
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl<Q, H, R> QueryHandler<Q> for HedgedHandler<H, R>
+where
+    Q: Query + Clone,
+    H: QueryHandler<Q>,
+    R: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        self.race(
+            query,
+            |query| QueryHandler::handle(&self.primary, query),
+            |query| QueryHandler::handle(&self.replica, query),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<C, H, R> CommandHandler<C> for HedgedHandler<H, R>
+where
+    C: Command + Idempotent + Clone,
+    H: CommandHandler<C>,
+    R: CommandHandler<C>,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        self.race(
+            command,
+            |command| CommandHandler::handle(&self.primary, command),
+            |command| CommandHandler::handle(&self.replica, command),
+        )
+        .await
+    }
+}
+
+/// Debug implementation for `HedgedHandler`
+impl<H, R> Debug for HedgedHandler<H, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("HedgedHandler")
+            .field("percentile", &self.percentile)
+            .field("initial_delay", &self.initial_delay)
+            .field("window", &self.window)
+            .field("hedged", &self.hedged())
+            .finish()
+    }
+}
@@ -0,0 +1,132 @@
+//! The `instrument` module wraps the dispatch, and the handling, of messages in `tracing` spans.
+//!
+//! Every dispatch through a [CommandBus](crate::command::CommandBus), or a [QueryBus](crate::query::QueryBus),
+//! is run inside a `dispatch` span, at the `INFO` level, with the following fields:
+//!
+//! - `kind`: `"command"`, or `"query"`.
+//! - `name`: The name of the message, see [Command::name](crate::command::Command::name).
+//! - `outcome`: `"succeeded"`, `"failed"`, `"handler_not_found"`, `"disabled"`, `"rejected"`, or `"rate_limited"`.
+//! - `error`: The debug representation of the handler error, or the reason of the rejection, if any.
+//!
+//! Since the handler is polled inside the span, the spans, and events, it creates are nested under it.
+//!
+//! Teams not routing every dispatch through a bus, or wanting telemetry per handler, annotate the handler
+//! implementation with the `#[discern::instrument]` attribute, when the `derive` feature is enabled as well. The
+//! handler is then run inside a `handler` span, at the `INFO` level, with the following fields:
+//!
+//! - `kind`: `"command"`, `"query"`, or `"event"`.
+//! - `handler`: The name of the handler type, or the `name` given to the attribute.
+//! - `message`: The name of the message, see [Command::name](crate::command::Command::name).
+//! - `outcome`: `"succeeded"`, or `"failed"`.
+//! - `elapsed`: The time spent handling the message, requires the `std` feature.
+//! - `error`: The debug representation of the handler error, if any.
+//!
+//! Failures are also logged as `ERROR` events, so that they are reported even when spans are not collected.
+//!
+//! See [discern::instrument](macro@crate::instrument) for an example.
+
+use core::fmt::Debug;
+use core::future::Future;
+
+use tracing::field;
+use tracing::Instrument;
+
+use crate::error::DispatchError;
+
+/// Runs the dispatch of a message inside a `dispatch` span, recording its outcome.
+pub(crate) async fn dispatch<T, E: Debug>(
+    kind: &'static str,
+    name: &'static str,
+    future: impl Future<Output = Result<T, DispatchError<E>>>,
+) -> Result<T, DispatchError<E>> {
+    let span = tracing::info_span!(
+        "dispatch",
+        kind,
+        name,
+        outcome = field::Empty,
+        error = field::Empty,
+    );
+
+    let result = future.instrument(span.clone()).await;
+
+    match &result {
+        Ok(_) => {
+            span.record("outcome", "succeeded");
+        }
+        Err(DispatchError::Handler(error)) => {
+            span.record("outcome", "failed");
+            span.record("error", field::debug(error));
+        }
+        Err(DispatchError::HandlerNotFound) => {
+            span.record("outcome", "handler_not_found");
+        }
+        Err(DispatchError::NotBorrowable) => {
+            span.record("outcome", "not_borrowable");
+        }
+        Err(DispatchError::Disabled) => {
+            span.record("outcome", "disabled");
+        }
+        Err(DispatchError::RateLimited) => {
+            span.record("outcome", "rate_limited");
+        }
+        Err(DispatchError::Rejected(reason)) => {
+            span.record("outcome", "rejected");
+            span.record("error", field::display(reason));
+        }
+        Err(DispatchError::RejectedAfterHandling(reason)) => {
+            span.record("outcome", "rejected_after_handling");
+            span.record("error", field::display(reason));
+        }
+    }
+
+    result
+}
+
+/// Runs the handling of a message inside a `handler` span, recording its outcome, and logging its error, if any.
+///
+/// This function is called by handlers annotated with the `#[discern::instrument]` attribute.
+///
+/// # Arguments
+///
+/// * `kind` - The kind of the message, `"command"`, `"query"`, or `"event"`.
+/// * `handler` - The name of the handler.
+/// * `message` - The name of the message.
+/// * `future` - The handling of the message.
+pub async fn handler<T, E: Debug>(
+    kind: &'static str,
+    handler: &'static str,
+    message: &'static str,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let span = tracing::info_span!(
+        "handler",
+        kind,
+        handler,
+        message,
+        outcome = field::Empty,
+        elapsed = field::Empty,
+        error = field::Empty,
+    );
+
+    #[cfg(feature = "std")]
+    let start = std::time::Instant::now();
+
+    let result = future.instrument(span.clone()).await;
+
+    #[cfg(feature = "std")]
+    span.record("elapsed", field::debug(start.elapsed()));
+
+    match &result {
+        Ok(_) => {
+            span.record("outcome", "succeeded");
+        }
+        Err(error) => {
+            span.record("outcome", "failed");
+            span.record("error", field::debug(error));
+
+            tracing::error!(parent: &span, kind, handler, message, ?error, "handler failed");
+        }
+    }
+
+    result
+}
@@ -0,0 +1,184 @@
+//! The `inventory` module provides the automatic registration of handlers.
+//!
+//! Large applications have hundreds of handlers, and registering all of them in a single `command_bus!` invocation
+//! results in a file that every change touches. Instead, handler implementations can be annotated with the
+//! [command_handler] and [query_handler] attributes, which register them in a global inventory, at link time, from
+//! wherever they are defined. The buses are then built from the inventory using
+//! [CommandBus::from_inventory](crate::command::CommandBus::from_inventory), and
+//! [QueryBus::from_inventory](crate::query::QueryBus::from_inventory).
+//!
+//! Annotated handlers are constructed using their `Default` implementation, or using the function given to the
+//! `constructor` argument of the attribute, e.g. `#[command_handler(constructor = CreateUserCommandHandler::new)]`.
+//!
+//! - [command_handler]: Registers a command handler in the inventory.
+//! - [query_handler]: Registers a query handler in the inventory.
+//! - [CommandHandlerRegistration]: A command handler registered in the inventory.
+//! - [QueryHandlerRegistration]: A query handler registered in the inventory.
+//!
+//! # Example
+//!
+//! ```
+//! # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+//! # rt.block_on(async {
+//! use discern::async_trait;
+//! use discern::command::Command;
+//! use discern::command::CommandBus;
+//! use discern::command::CommandHandler;
+//! use discern::inventory::command_handler;
+//! use discern::inventory::query_handler;
+//! use discern::query::Query;
+//! use discern::query::QueryBus;
+//! use discern::query::QueryHandler;
+//!
+//! #[derive(Debug, Command)]
+//! #[command(metadata = u64, error = std::io::Error)]
+//! struct CreateUserCommand {
+//!     username: String,
+//! }
+//!
+//! #[derive(Default)]
+//! struct CreateUserCommandHandler;
+//!
+//! #[command_handler]
+//! #[async_trait]
+//! impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+//!     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+//!         Ok(command.username.len() as u64)
+//!     }
+//! }
+//!
+//! #[derive(Debug, Query)]
+//! #[query(output = String, error = std::io::Error)]
+//! struct GetUsernameQuery {
+//!     user_id: u64,
+//! }
+//!
+//! struct GetUsernameQueryHandler {
+//!     prefix: String,
+//! }
+//!
+//! impl GetUsernameQueryHandler {
+//!     fn new() -> Self {
+//!         Self { prefix: "user-".to_string() }
+//!     }
+//! }
+//!
+//! #[query_handler(constructor = GetUsernameQueryHandler::new)]
+//! #[async_trait]
+//! impl QueryHandler<GetUsernameQuery> for GetUsernameQueryHandler {
+//!     async fn handle(&self, query: GetUsernameQuery) -> Result<String, std::io::Error> {
+//!         Ok(format!("{}{}", self.prefix, query.user_id))
+//!     }
+//! }
+//!
+//! let command_bus = CommandBus::from_inventory();
+//! let query_bus = QueryBus::from_inventory();
+//!
+//! let user_id = command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+//! let username = query_bus.dispatch(GetUsernameQuery { user_id }).await.unwrap();
+//!
+//! assert_eq!(username, "user-5");
+//! # });
+//! ```
+
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+use crate::registry::CommandHandlerRegistry;
+use crate::registry::QueryHandlerRegistry;
+
+/// Registers a command handler in the inventory, see the [inventory](crate::inventory) module.
+///
+/// The attribute is placed on the `impl CommandHandler<C> for H` block, or on the `impl SyncCommandHandler<C> for H`
+/// block when the `sync` feature is enabled.
+pub use discern_derive::command_handler;
+/// Registers a query handler in the inventory, see the [inventory](crate::inventory) module.
+///
+/// The attribute is placed on the `impl QueryHandler<Q> for H` block, the `impl BorrowedQueryHandler<Q> for H`
+/// block, or the `impl SyncQueryHandler<Q> for H` block when the `sync` feature is enabled.
+pub use discern_derive::query_handler;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use ::inventory::submit;
+}
+
+/// A command handler registered in the inventory.
+///
+/// Registrations are submitted by the [command_handler] attribute, and should not be created manually.
+pub struct CommandHandlerRegistration {
+    #[doc(hidden)]
+    register: fn(&mut CommandHandlerRegistry),
+}
+
+/// The `CommandHandlerRegistration` implementation.
+impl CommandHandlerRegistration {
+    /// Creates a new `CommandHandlerRegistration`.
+    ///
+    /// # Arguments
+    ///
+    /// * `register` - The function registering the handler.
+    #[doc(hidden)]
+    pub const fn new(register: fn(&mut CommandHandlerRegistry)) -> Self {
+        Self { register }
+    }
+}
+
+/// Debug implementation for `CommandHandlerRegistration`
+impl Debug for CommandHandlerRegistration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CommandHandlerRegistration").finish()
+    }
+}
+
+/// A query handler registered in the inventory.
+///
+/// Registrations are submitted by the [query_handler] attribute, and should not be created manually.
+pub struct QueryHandlerRegistration {
+    #[doc(hidden)]
+    register: fn(&mut QueryHandlerRegistry),
+}
+
+/// The `QueryHandlerRegistration` implementation.
+impl QueryHandlerRegistration {
+    /// Creates a new `QueryHandlerRegistration`.
+    ///
+    /// # Arguments
+    ///
+    /// * `register` - The function registering the handler.
+    #[doc(hidden)]
+    pub const fn new(register: fn(&mut QueryHandlerRegistry)) -> Self {
+        Self { register }
+    }
+}
+
+/// Debug implementation for `QueryHandlerRegistration`
+impl Debug for QueryHandlerRegistration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("QueryHandlerRegistration").finish()
+    }
+}
+
+::inventory::collect!(CommandHandlerRegistration);
+::inventory::collect!(QueryHandlerRegistration);
+
+/// Returns a registry containing all the command handlers registered in the inventory.
+pub(crate) fn command_registry() -> CommandHandlerRegistry {
+    let mut registry = CommandHandlerRegistry::new();
+    for registration in ::inventory::iter::<CommandHandlerRegistration> {
+        (registration.register)(&mut registry);
+    }
+
+    registry
+}
+
+/// Returns a registry containing all the query handlers registered in the inventory.
+pub(crate) fn query_registry() -> QueryHandlerRegistry {
+    let mut registry = QueryHandlerRegistry::new();
+    for registration in ::inventory::iter::<QueryHandlerRegistration> {
+        (registration.register)(&mut registry);
+    }
+
+    registry
+}
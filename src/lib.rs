@@ -13,6 +13,7 @@
 //!
 //! - [CommandBus](crate::command::CommandBus): Dispatches commands to their respective handlers.
 //! - [QueryBus](crate::query::QueryBus): Dispatches queries to their respective handlers.
+//! - [EventBus](crate::event::EventBus): Publishes events to all of their subscribers.
 //!
 //! # Example: Handling Commands
 //!
@@ -150,11 +151,114 @@
 //! }
 //! # });
 //! ```
+//!
+//! # Feature Flags
+//!
+//! - `std` ( enabled by default ): Links against the Rust standard library. When disabled, the crate only
+//!   depends on `core` and `alloc`, which allows reusing the same command and query definitions in `no_std` environments.
+//! - `sync`: Enables synchronous command and query handlers, see
+//!   [SyncCommandHandler](crate::command::SyncCommandHandler) and [SyncQueryHandler](crate::query::SyncQueryHandler),
+//!   as well as the simplified [SyncCommandBus](crate::command::SyncCommandBus) and [SyncQueryBus](crate::query::SyncQueryBus),
+//!   and the [SyncHandler](crate::sync::SyncHandler) adapter.
+//! - `tokio` ( enabled by default ): Provides the [TokioRuntime](crate::runtime::TokioRuntime) implementation of the
+//!   [Runtime](crate::runtime::Runtime) trait.
+//! - `admin`: Provides built-in administrative commands and queries, see the [admin](crate::admin) module.
+//! - `smol`: Provides the [SmolRuntime](crate::runtime::SmolRuntime) implementation of the
+//!   [Runtime](crate::runtime::Runtime) trait, for applications running on `smol` or `async-std`.
+//! - `derive`: Provides the [Command](derive@crate::command::Command) and [Query](derive@crate::query::Query) derive
+//!   macros, which implement the [Command](crate::command::Command) and [Query](crate::query::Query) traits from a
+//!   `#[command(metadata = ..., error = ...)]`, or `#[query(output = ..., error = ...)]`, attribute.
+//!   The [MessageName](derive@crate::message::MessageName) derive macro gives other messages, e.g. events, a stable name.
+//! - `inventory`: Provides the automatic registration of handlers annotated with the `#[command_handler]` and
+//!   `#[query_handler]` attributes, see the [inventory module](crate::inventory).
+//! - `devtools`: Provides an inspector server exposing the registered handlers, the counters, and the recent
+//!   dispatches, of the buses as JSON, for local development, see the [devtools](crate::devtools) module.
+//! - `tracing`: Runs every dispatch through the [CommandBus](crate::command::CommandBus), and the
+//!   [QueryBus](crate::query::QueryBus), inside a `dispatch` span of the `tracing` crate, with the `kind`, and
+//!   `name` of the message, as fields, and records its `outcome`, and `error`, if any. Handlers are polled inside the span,
+//!   so the spans, and events, they create are nested under it. Along with the `derive` feature, handlers
+//!   annotated with the `#[discern::instrument]` attribute run inside their own `handler` span, see the
+//!   [instrument module](mod@crate::instrument).
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
+pub mod accounting;
+#[cfg(feature = "admin")]
+pub mod admin;
+pub mod authorization;
+#[cfg(feature = "std")]
+pub mod backfill;
+#[cfg(feature = "std")]
+pub mod bulk;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod caller;
+#[cfg(feature = "std")]
+pub mod coalesce;
 pub mod command;
+pub mod context;
+#[cfg(feature = "std")]
+pub mod cost;
+#[cfg(feature = "devtools")]
+pub mod devtools;
+pub mod diagnostics;
+#[cfg(feature = "std")]
+pub mod effect;
+pub mod error;
+pub mod event;
+#[cfg(feature = "std")]
+mod evict;
+pub mod followup;
+#[cfg(feature = "std")]
+pub mod hedge;
+#[cfg(feature = "tracing")]
+pub mod instrument;
+#[cfg(feature = "inventory")]
+pub mod inventory;
 pub mod macros;
+pub mod maybe;
+pub mod message;
+#[cfg(feature = "std")]
+pub mod metadata;
+pub mod middleware;
+#[cfg(feature = "std")]
+pub mod monitor;
+#[cfg(feature = "std")]
+pub mod notify;
+#[cfg(feature = "std")]
+pub mod outcome;
+pub mod plugin;
+#[cfg(feature = "std")]
+pub mod policy;
+#[cfg(feature = "std")]
+pub mod pool;
 pub mod query;
+#[cfg(feature = "std")]
+pub mod ratelimit;
 pub mod registry;
+#[cfg(feature = "std")]
+pub mod retry;
+pub mod runtime;
+#[cfg(feature = "std")]
+mod scope;
+#[cfg(feature = "std")]
+pub mod spawn;
+#[cfg(feature = "std")]
+pub mod standby;
+pub mod stats;
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod switchboard;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(feature = "std")]
+pub mod timeline;
+pub mod timeout;
+pub mod typed;
 
 /// Re-exports the `async_trait` crate.
 ///
@@ -162,3 +266,48 @@ pub mod registry;
 ///
 /// For more details on `#[async_trait]`, see [mod@async_trait]
 pub use async_trait::async_trait;
+
+/// Runs a handler inside a `handler` span, see the [instrument module](mod@crate::instrument).
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::async_trait;
+/// use discern::command::Command;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+///
+/// #[derive(Debug, Command)]
+/// #[command(metadata = u64, error = std::io::Error)]
+/// struct CreateUserCommand {
+///     username: String,
+/// }
+///
+/// struct CreateUserCommandHandler;
+///
+/// #[discern::instrument]
+/// #[async_trait]
+/// impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+///     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+///         if command.username.is_empty() {
+///             return Err(std::io::ErrorKind::InvalidInput.into());
+///         }
+///
+///         let length = u64::try_from(command.username.len()).map_err(std::io::Error::other)?;
+///
+///         Ok(length)
+///     }
+/// }
+///
+/// let command_bus = command_bus! {
+///     CreateUserCommand => CreateUserCommandHandler,
+/// };
+///
+/// let user_id = command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await;
+/// assert_eq!(user_id.unwrap(), 5);
+/// # });
+/// ```
+#[cfg(all(feature = "derive", feature = "tracing"))]
+pub use discern_derive::instrument;
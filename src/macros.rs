@@ -9,6 +9,7 @@
 //! - [command_registry](crate::command_registry): Creates a `CommandHandlerRegistry` and registers handlers.
 //! - [query_bus](crate::query_bus): Creates a `QueryBus` and registers handlers.
 //! - [query_registry](crate::query_registry): Creates a `QueryHandlerRegistry` and registers handlers.
+//! - [event_bus](crate::event_bus): Creates an `EventBus` and subscribes handlers.
 
 /// A macro for creating a `CommandBus` instance.
 ///
@@ -146,11 +147,106 @@
 /// ```
 /// This explicitly specifies the command type associated with each handler.
 ///
+/// 3. **Providing type-closure pairs:**
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #    username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #   type Metadata = u64;
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::command_bus;
+///
+/// let command_bus = command_bus! {
+///    CreateUserCommand => |command| async move {
+///        Ok(command.username.len() as u64)
+///    },
+///    DeleteUserCommand => |_| async move { Ok(()) },
+/// };
+///
+/// let user_id = command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+/// assert_eq!(user_id, 5);
+///
+/// assert!(command_bus.dispatch(DeleteUserCommand { user_id }).await.is_ok());
+/// # });
+/// ```
+///
+/// Closures are wrapped in a [CommandHandlerFn](crate::command::CommandHandlerFn), and can be mixed with handlers.
+/// Their argument can be any irrefutable pattern, e.g. `_` to ignore the command.
+///
+/// 4. **Providing middleware:**
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::command_bus;
+/// use discern::middleware::CommandMiddleware;
+/// use discern::middleware::Next;
+/// use discern::middleware::Outcome;
+///
+/// struct TracingMiddleware;
+///
+/// #[async_trait]
+/// impl CommandMiddleware for TracingMiddleware {
+///     async fn handle(&self, next: Next<'_>) -> Outcome {
+///         println!("Handling {:?}", next.debug());
+///
+///         next.run().await
+///     }
+/// }
+///
+/// let command_bus = command_bus! {
+///    middleware: [TracingMiddleware],
+///    DeleteUserCommand => |_command| async move { Ok(()) },
+/// };
+///
+/// assert!(command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await.is_ok());
+/// # });
+/// ```
+///
+/// The `middleware` section must come first, the middleware wrap the handling of every command in the given order,
+/// see [CommandMiddleware](crate::middleware::CommandMiddleware).
+///
 /// # See Also
 ///
 /// - [CommandBus](crate::command::CommandBus)
 #[macro_export]
 macro_rules! command_bus {
+        (middleware: [$($middleware:expr),* $(,)?] $(, $($rest:tt)*)?) => {{
+            let command_bus = $crate::command_bus!($($($rest)*)?);
+            $(let command_bus = command_bus.with_middleware($middleware);)*
+            command_bus
+        }};
         () => {{
             $crate::command::CommandBus::new($crate::registry::CommandHandlerRegistry::new())
         }};
@@ -159,10 +255,8 @@ macro_rules! command_bus {
             $(command_handler_registry.register($handler);)*
             $crate::command::CommandBus::new(command_handler_registry)
         }};
-        ($($command:ty => $handler:expr),*$(,)?) => {{
-            let mut command_handler_registry = $crate::registry::CommandHandlerRegistry::new();
-            $(command_handler_registry.register::<$command>($handler);)*
-            $crate::command::CommandBus::new(command_handler_registry)
+        ($command:ty => $($entries:tt)+) => {{
+            $crate::command::CommandBus::new($crate::command_registry!($command => $($entries)+))
         }};
     }
 
@@ -268,11 +362,130 @@ macro_rules! command_bus {
 /// ```
 /// This explicitly specifies the command type associated with each handler.
 ///
+/// # Registration options
+///
+/// Type-handler pairs can be followed by options, keeping the policy of a handler next to its registration:
+///
+/// - `timeout: 5s`: Fails commands that are not handled within the given duration, an integer followed by `ms`,
+///   `s`, `m`, or `h`, see [TimeoutHandler](crate::timeout::TimeoutHandler). The error type of the command must
+///   implement `From<Elapsed>`.
+/// - `retries: 3`: Retries failed attempts up to the given number of times, using the default
+///   [RetryPolicy](crate::retry::RetryPolicy), see [RetryingHandler](crate::retry::RetryingHandler). The command
+///   must implement `Clone`, and [Idempotent](crate::command::Idempotent).
+///
+/// Options wrap the handler in the given order, `{ timeout: 5s, retries: 3 }` limits each attempt to 5 seconds,
+/// while `{ retries: 3, timeout: 5s }` limits all attempts combined. Both use the
+/// [DefaultRuntime](crate::runtime::DefaultRuntime), handlers needing another runtime, or a custom policy, should
+/// be wrapped explicitly instead.
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::sync::atomic::AtomicU32;
+/// use std::sync::atomic::Ordering;
+/// use std::time::Duration;
+///
+/// use discern::command::Command;
+/// use discern::command::Idempotent;
+/// use discern::command_bus;
+/// use discern::runtime::Elapsed;
+/// use discern::runtime::Runtime;
+/// use discern::runtime::TokioRuntime;
+///
+/// #[derive(Debug, Clone)]
+/// struct SetUserEmailCommand {
+///     user_id: u64,
+/// }
+///
+/// #[derive(Debug)]
+/// struct TimedOut;
+///
+/// impl From<Elapsed> for TimedOut {
+///     fn from(_: Elapsed) -> Self {
+///         TimedOut
+///     }
+/// }
+///
+/// impl Command for SetUserEmailCommand {
+///     type Metadata = ();
+///     type Error = TimedOut;
+/// }
+///
+/// impl Idempotent for SetUserEmailCommand {}
+///
+/// static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+///
+/// let command_bus = command_bus! {
+///     SetUserEmailCommand => |_| async {
+///         // The first attempt stalls, and is cancelled after 10 milliseconds.
+///         if ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+///             TokioRuntime.sleep(Duration::from_secs(60)).await;
+///         }
+///
+///         Ok(())
+///     }, { timeout: 10ms, retries: 1 },
+/// };
+///
+/// assert!(command_bus.dispatch(SetUserEmailCommand { user_id: 1 }).await.is_ok());
+/// assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+/// # });
+/// ```
+///
 /// # See Also
 ///
 /// - [CommandHandlerRegistry](crate::registry::CommandHandlerRegistry)
 #[macro_export]
 macro_rules! command_registry {
+        (@register $registry:ident;) => {};
+        (@register $registry:ident; $command:ty => $(move)? |$argument:pat_param| $body:expr, { $($options:tt)* } $(, $($rest:tt)*)?) => {
+            $registry.register::<$command>($crate::command_registry!(@options $command; $crate::command::CommandHandlerFn::new::<$command, _>(move |$argument| $body); $($options)*));
+            $crate::command_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $command:ty => $(move)? |$argument:tt : $argument_type:ty| $body:expr, { $($options:tt)* } $(, $($rest:tt)*)?) => {
+            $registry.register::<$command>($crate::command_registry!(@options $command; $crate::command::CommandHandlerFn::new::<$command, _>(move |$argument: $argument_type| $body); $($options)*));
+            $crate::command_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $command:ty => $handler:expr, { $($options:tt)* } $(, $($rest:tt)*)?) => {
+            $registry.register::<$command>($crate::command_registry!(@options $command; $handler; $($options)*));
+            $crate::command_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $command:ty => $(move)? |$argument:pat_param| $body:expr $(, $($rest:tt)*)?) => {
+            $registry.register::<$command>($crate::command::CommandHandlerFn::new::<$command, _>(move |$argument| $body));
+            $crate::command_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $command:ty => $(move)? |$argument:tt : $argument_type:ty| $body:expr $(, $($rest:tt)*)?) => {
+            $registry.register::<$command>($crate::command::CommandHandlerFn::new::<$command, _>(move |$argument: $argument_type| $body));
+            $crate::command_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $command:ty => $handler:expr $(, $($rest:tt)*)?) => {
+            $registry.register::<$command>($handler);
+            $crate::command_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@options $command:ty; $handler:expr; $($option:ident : $value:tt),* $(,)?) => {{
+            let handler = $handler;
+            $(let handler = $crate::command_registry!(@option $command; handler; $option: $value);)*
+            handler
+        }};
+        (@option $command:ty; $handler:ident; timeout: $value:tt) => {
+            $crate::timeout::TimeoutHandler::new(
+                $handler,
+                {
+                    const TIMEOUT: ::core::time::Duration = $crate::timeout::duration(stringify!($value));
+                    TIMEOUT
+                },
+                $crate::runtime::DefaultRuntime::default(),
+            )
+        };
+        (@option $command:ty; $handler:ident; retries: $value:tt) => {
+            $crate::retry::RetryingHandler::new(
+                $handler,
+                $crate::retry::RetryPolicy::<<$command as $crate::command::Command>::Error>::new($value + 1),
+                $crate::runtime::DefaultRuntime::default(),
+            )
+        };
+        (@option $command:ty; $handler:ident; $option:ident: $value:tt) => {
+            compile_error!(concat!("unknown registration option `", stringify!($option), "`, expected `timeout` or `retries`"))
+        };
         () => {{
             $crate::command::CommandHandlerRegistry::new()
         }};
@@ -281,9 +494,9 @@ macro_rules! command_registry {
             $(command_handler_registry.register($handler);)*
             command_handler_registry
         }};
-        ($($command:ty => $handler:expr),*$(,)?) => {{
+        ($command:ty => $($entries:tt)+) => {{
             let mut command_handler_registry = $crate::registry::CommandHandlerRegistry::new();
-            $(command_handler_registry.register::<$command>($handler);)*
+            $crate::command_registry!(@register command_handler_registry; $command => $($entries)+);
             command_handler_registry
         }};
     }
@@ -384,6 +597,37 @@ macro_rules! command_registry {
 ///
 /// This explicitly specifies the query type associated with each handler.
 ///
+/// 3. **Providing type-closure pairs:**
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUserQuery {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #   type Output = String;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::query_bus;
+///
+/// let query_bus = query_bus! {
+///    GetUserQuery => |GetUserQuery { user_id }| async move {
+///        Ok(format!("user-{}", user_id))
+///    },
+/// };
+///
+/// assert_eq!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap(), "user-1");
+/// # });
+/// ```
+///
+/// Closures are wrapped in a [QueryHandlerFn](crate::query::QueryHandlerFn), and can be mixed with handlers.
+/// Their argument can be any irrefutable pattern, e.g. to destructure the query.
+///
 /// # See Also
 ///
 /// - [QueryBus](crate::query::QueryBus)
@@ -397,10 +641,8 @@ macro_rules! query_bus {
             $(query_handler_registry.register($handler);)*
             $crate::query::QueryBus::new(query_handler_registry)
         }};
-        ($($query:ty => $handler:expr),*$(,)?) => {{
-            let mut query_handler_registry = $crate::registry::QueryHandlerRegistry::new();
-            $(query_handler_registry.register::<$query>($handler);)*
-            $crate::query::QueryBus::new(query_handler_registry)
+        ($query:ty => $($entries:tt)+) => {{
+            $crate::query::QueryBus::new($crate::query_registry!($query => $($entries)+))
         }};
     }
 
@@ -487,11 +729,95 @@ macro_rules! query_bus {
 ///
 /// This explicitly specifies the query type associated with each handler.
 ///
+/// # Registration options
+///
+/// Type-handler pairs accept the same `timeout` and `retries` options as the
+/// [command_registry](crate::command_registry) macro, retried queries must implement `Clone`.
+///
+/// ```
+/// # use discern::query::Query;
+/// # use discern::runtime::Elapsed;
+/// #
+/// # #[derive(Debug, Clone)]
+/// # struct GetUserQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct TimedOut;
+/// #
+/// # impl From<Elapsed> for TimedOut {
+/// #     fn from(_: Elapsed) -> Self {
+/// #         TimedOut
+/// #     }
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #     type Output = String;
+/// #     type Error = TimedOut;
+/// # }
+/// use discern::query_registry;
+///
+/// let query_registry = query_registry! {
+///     GetUserQuery => |query| async move { Ok(format!("user #{}", query.user_id)) }, { timeout: 500ms, retries: 2 },
+/// };
+/// ```
+///
 /// # See Also
 ///
 /// - [QueryHandlerRegistry](crate::registry::QueryHandlerRegistry)
 #[macro_export]
 macro_rules! query_registry {
+        (@register $registry:ident;) => {};
+        (@register $registry:ident; $query:ty => $(move)? |$argument:pat_param| $body:expr, { $($options:tt)* } $(, $($rest:tt)*)?) => {
+            $registry.register::<$query>($crate::query_registry!(@options $query; $crate::query::QueryHandlerFn::new::<$query, _>(move |$argument| $body); $($options)*));
+            $crate::query_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $query:ty => $(move)? |$argument:tt : $argument_type:ty| $body:expr, { $($options:tt)* } $(, $($rest:tt)*)?) => {
+            $registry.register::<$query>($crate::query_registry!(@options $query; $crate::query::QueryHandlerFn::new::<$query, _>(move |$argument: $argument_type| $body); $($options)*));
+            $crate::query_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $query:ty => $handler:expr, { $($options:tt)* } $(, $($rest:tt)*)?) => {
+            $registry.register::<$query>($crate::query_registry!(@options $query; $handler; $($options)*));
+            $crate::query_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $query:ty => $(move)? |$argument:pat_param| $body:expr $(, $($rest:tt)*)?) => {
+            $registry.register::<$query>($crate::query::QueryHandlerFn::new::<$query, _>(move |$argument| $body));
+            $crate::query_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $query:ty => $(move)? |$argument:tt : $argument_type:ty| $body:expr $(, $($rest:tt)*)?) => {
+            $registry.register::<$query>($crate::query::QueryHandlerFn::new::<$query, _>(move |$argument: $argument_type| $body));
+            $crate::query_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@register $registry:ident; $query:ty => $handler:expr $(, $($rest:tt)*)?) => {
+            $registry.register::<$query>($handler);
+            $crate::query_registry!(@register $registry; $($($rest)*)?);
+        };
+        (@options $query:ty; $handler:expr; $($option:ident : $value:tt),* $(,)?) => {{
+            let handler = $handler;
+            $(let handler = $crate::query_registry!(@option $query; handler; $option: $value);)*
+            handler
+        }};
+        (@option $query:ty; $handler:ident; timeout: $value:tt) => {
+            $crate::timeout::TimeoutHandler::new(
+                $handler,
+                {
+                    const TIMEOUT: ::core::time::Duration = $crate::timeout::duration(stringify!($value));
+                    TIMEOUT
+                },
+                $crate::runtime::DefaultRuntime::default(),
+            )
+        };
+        (@option $query:ty; $handler:ident; retries: $value:tt) => {
+            $crate::retry::RetryingHandler::new(
+                $handler,
+                $crate::retry::RetryPolicy::<<$query as $crate::query::Query>::Error>::new($value + 1),
+                $crate::runtime::DefaultRuntime::default(),
+            )
+        };
+        (@option $query:ty; $handler:ident; $option:ident: $value:tt) => {
+            compile_error!(concat!("unknown registration option `", stringify!($option), "`, expected `timeout` or `retries`"))
+        };
         () => {{
             $crate::query::QueryHandlerRegistry::new()
         }};
@@ -500,9 +826,121 @@ macro_rules! query_registry {
             $(query_handler_registry.register($handler);)*
             query_handler_registry
         }};
-        ($($query:ty => $handler:expr),*$(,)?) => {{
+        ($query:ty => $($entries:tt)+) => {{
             let mut query_handler_registry = $crate::registry::QueryHandlerRegistry::new();
-            $(query_handler_registry.register::<$query>($handler);)*
+            $crate::query_registry!(@register query_handler_registry; $query => $($entries)+);
             query_handler_registry
         }};
     }
+
+/// A macro for creating an `EventBus` instance.
+///
+/// This macro provides a convenient way to initialize an `EventBus` and subscribe multiple
+/// event handlers at once.
+///
+/// # Usage
+///
+/// You can use this macro in two ways:
+///
+/// 1. **Providing only handlers:**
+///
+/// ```
+/// # use discern::event::Event;
+/// # use discern::async_trait;
+/// # use discern::event::EventHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct UserCreatedEvent {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Event for UserCreatedEvent {
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct SendWelcomeEmail;
+/// #
+/// # #[async_trait]
+/// # impl EventHandler<UserCreatedEvent> for SendWelcomeEmail {
+/// #    async fn handle(&self, event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+/// #       Ok(())
+/// #   }
+/// # }
+/// use discern::event_bus;
+///
+/// let event_bus = event_bus! {
+///    SendWelcomeEmail { /* ... */ },
+/// };
+/// #
+/// # assert_eq!(event_bus.subscribers::<UserCreatedEvent>(), 1);
+/// ```
+/// This infers the event type from the handler, which requires the handler to handle a single event type.
+///
+/// 2. **Providing type-handler pairs:**
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::event::Event;
+/// # use discern::async_trait;
+/// # use discern::event::EventHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct UserCreatedEvent {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Event for UserCreatedEvent {
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct SendWelcomeEmail;
+/// #
+/// # #[async_trait]
+/// # impl EventHandler<UserCreatedEvent> for SendWelcomeEmail {
+/// #    async fn handle(&self, event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+/// #       Ok(())
+/// #   }
+/// # }
+/// #
+/// # struct UpdateUserList;
+/// #
+/// # #[async_trait]
+/// # impl EventHandler<UserCreatedEvent> for UpdateUserList {
+/// #    async fn handle(&self, event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+/// #       Ok(())
+/// #   }
+/// # }
+/// use discern::event_bus;
+///
+/// let event_bus = event_bus! {
+///    UserCreatedEvent => SendWelcomeEmail { /* ... */ },
+///    UserCreatedEvent => UpdateUserList { /* ... */ },
+/// };
+///
+/// let subscribers = event_bus.publish(UserCreatedEvent { user_id: 1 }).await.unwrap();
+/// assert_eq!(subscribers, 2);
+/// # });
+/// ```
+/// This explicitly specifies the event type associated with each handler, the same event type can be repeated
+/// to subscribe several handlers.
+///
+/// # See Also
+///
+/// - [EventBus](crate::event::EventBus)
+#[macro_export]
+macro_rules! event_bus {
+        () => {{
+            $crate::event::EventBus::new($crate::registry::EventHandlerRegistry::new())
+        }};
+        ($($handler:expr),*$(,)?) => {{
+            let mut event_handler_registry = $crate::registry::EventHandlerRegistry::new();
+            $(event_handler_registry.subscribe($handler);)*
+            $crate::event::EventBus::new(event_handler_registry)
+        }};
+        ($($event:ty => $handler:expr),+ $(,)?) => {{
+            let mut event_handler_registry = $crate::registry::EventHandlerRegistry::new();
+            $(event_handler_registry.subscribe::<$event>($handler);)+
+            $crate::event::EventBus::new(event_handler_registry)
+        }};
+    }
@@ -0,0 +1,128 @@
+//! The `maybe` module provides conventions for queries whose result may be absent.
+//!
+//! Many queries look up a thing that may not exist, e.g. a user by id. Modelling its absence as an error forces
+//! every caller to tell "not found" apart from actual failures, and makes absence an error-path hack. Such queries
+//! should return `Option<T>` instead, which makes them a [MaybeQuery], and callers that do require the thing to
+//! exist can dispatch them using [QueryBus::dispatch_required](crate::query::QueryBus::dispatch_required), which
+//! turns absence into a [RequiredError::NotFound].
+//!
+//! Absent results are cached like any other result by a [QueryCache](crate::cache::QueryCache), usually for a
+//! shorter duration, see [CachedQuery::ttl](crate::cache::CachedQuery::ttl).
+//!
+//! - [MaybeQuery]: A query whose result may be absent.
+//! - [RequiredError]: The error returned when a required result is absent, or the query fails.
+
+use core::error::Error;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+use crate::error::ErrorCategory;
+use crate::error::ErrorClass;
+use crate::query::Query;
+
+/// The `MaybeQuery` trait represents a query whose result may be absent.
+///
+/// `MaybeQuery` is implemented for every query whose output is an `Option`.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::maybe::MaybeQuery;
+/// use discern::maybe::RequiredError;
+/// use discern::query::Query;
+/// use discern::query_bus;
+///
+/// #[derive(Debug)]
+/// struct FindUserQuery {
+///     user_id: u64,
+/// }
+///
+/// impl Query for FindUserQuery {
+///     type Output = Option<String>;
+///     type Error = std::io::Error;
+/// }
+///
+/// fn item_of<Q: MaybeQuery>(output: Q::Output) -> Option<Q::Item> {
+///     output
+/// }
+///
+/// let query_bus = query_bus! {
+///     FindUserQuery => |query| async move {
+///         Ok((query.user_id == 1).then(|| "alice".to_string()))
+///     },
+/// };
+///
+/// assert_eq!(query_bus.dispatch(FindUserQuery { user_id: 2 }).await.unwrap(), None);
+/// assert_eq!(item_of::<FindUserQuery>(Some("bob".to_string())), Some("bob".to_string()));
+///
+/// let user = query_bus.dispatch_required(FindUserQuery { user_id: 1 }).await.unwrap();
+/// assert_eq!(user, "alice");
+///
+/// let error = query_bus.dispatch_required(FindUserQuery { user_id: 2 }).await.unwrap_err();
+/// assert!(matches!(error, RequiredError::NotFound));
+/// # });
+/// ```
+pub trait MaybeQuery: Query<Output = Option<<Self as MaybeQuery>::Item>> {
+    /// The type of the result, when present.
+    type Item: Send + Sync;
+}
+
+impl<Q, T> MaybeQuery for Q
+where
+    Q: Query<Output = Option<T>>,
+    T: Send + Sync,
+{
+    type Item = T;
+}
+
+/// The error returned when a required result is absent, or the query fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequiredError<E> {
+    /// The query succeeded, but its result is absent.
+    NotFound,
+    /// The query failed.
+    Query(E),
+}
+
+/// The `RequiredError` implementation.
+impl<E> RequiredError<E> {
+    /// Returns the error returned by the query, if any.
+    pub fn query_error(&self) -> Option<&E> {
+        match self {
+            Self::Query(error) => Some(error),
+            Self::NotFound => None,
+        }
+    }
+}
+
+impl<E: Display> Display for RequiredError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::NotFound => write!(f, "the result of the query is absent"),
+            Self::Query(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for RequiredError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Query(error) => error.source(),
+            Self::NotFound => None,
+        }
+    }
+}
+
+/// An absent result is not found, and the errors returned by the query keep their own category.
+impl<E: ErrorClass> ErrorClass for RequiredError<E> {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            Self::NotFound => ErrorCategory::NotFound,
+            Self::Query(error) => error.category(),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! The `message` module provides the stable identity of commands, queries, and events.
+//!
+//! By default, messages are identified by their type name, e.g. `app::users::CreateUserCommand`, which changes
+//! whenever the type is moved, or renamed, breaking the dashboards, and the routing, that depend on it. A
+//! [MessageName] gives a message a stable name, independent of its Rust type path, which is then returned by
+//! [Command::name](crate::command::Command::name), [Query::name](crate::query::Query::name), and
+//! [Event::name](crate::event::Event::name), and used by the buses wherever they identify a message, e.g. in
+//! [CommandBus::handlers](crate::command::CommandBus::handlers), diagnostics, and middleware.
+//!
+//! A [MessageDescriptor] extends the name with the version, the kind, and the schema hash of the message, forming
+//! a uniform description of the messages a bus knows about, returned by
+//! [CommandBus::descriptors](crate::command::CommandBus::descriptors), and the registries.
+//!
+//! - [MessageName]: The stable name of a message.
+//! - [MessageDescriptor]: The description of a message.
+//! - [MessageKind]: The kind of a message.
+
+/// The `MessageName` trait gives a message a stable name, independent of its Rust type path.
+///
+/// The `Command`, and `Query`, derive macros implement this trait, using the name of the type, e.g.
+/// `CreateUserCommand`, unless overridden using the `name` argument, e.g. `#[command(name = "users.create")]`.
+/// Other types can derive it using `#[derive(MessageName)]`, and `#[message(name = "...")]`, when the `derive`
+/// feature is enabled.
+///
+/// Commands, queries, and events implementing `MessageName` manually should also return [MessageName::NAME] from
+/// their `name` function.
+///
+/// # Example
+///
+/// ```
+/// use discern::command::Command;
+/// use discern::message::MessageName;
+///
+/// #[derive(Debug)]
+/// struct CreateUserCommand {
+///     username: String,
+/// }
+///
+/// impl MessageName for CreateUserCommand {
+///     const NAME: &'static str = "users.create";
+/// }
+///
+/// impl Command for CreateUserCommand {
+///     type Metadata = u64;
+///     type Error = std::io::Error;
+///
+///     fn name() -> &'static str {
+///         Self::NAME
+///     }
+/// }
+///
+/// assert_eq!(CreateUserCommand::name(), "users.create");
+/// ```
+pub trait MessageName {
+    /// The stable name of the message.
+    const NAME: &'static str;
+
+    /// The version of the message, incremented whenever its schema changes incompatibly.
+    ///
+    /// Defaults to `1`, and is given to the derive macros using the `version` argument, e.g.
+    /// `#[command(version = 2)]`.
+    const VERSION: u32 = 1;
+
+    /// The hash of the schema of the message, if known.
+    ///
+    /// The derive macros compute it from the names, and types, of the fields of the message, so that it changes
+    /// whenever a field is added, removed, renamed, or retyped.
+    const SCHEMA_HASH: Option<u64> = None;
+}
+
+/// The kind of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MessageKind {
+    /// A command, handled by a single handler of a [CommandBus](crate::command::CommandBus).
+    Command,
+    /// A query, handled by a single handler of a [QueryBus](crate::query::QueryBus).
+    Query,
+    /// An event, published to all the subscribers of an [EventBus](crate::event::EventBus).
+    Event,
+}
+
+/// The description of a message: its name, version, kind, and schema hash.
+///
+/// Descriptors are returned by [Command::descriptor](crate::command::Command::descriptor),
+/// [Query::descriptor](crate::query::Query::descriptor), and [Event::descriptor](crate::event::Event::descriptor),
+/// and listed by the registries for the messages with a registered handler.
+///
+/// # Example
+///
+/// ```
+/// use discern::command::Command;
+/// use discern::message::MessageDescriptor;
+/// use discern::message::MessageKind;
+/// use discern::message::MessageName;
+///
+/// #[derive(Debug)]
+/// struct CreateUserCommand {
+///     username: String,
+/// }
+///
+/// impl MessageName for CreateUserCommand {
+///     const NAME: &'static str = "users.create";
+///     const VERSION: u32 = 2;
+/// }
+///
+/// impl Command for CreateUserCommand {
+///     type Metadata = u64;
+///     type Error = std::io::Error;
+///
+///     fn name() -> &'static str {
+///         Self::NAME
+///     }
+///
+///     fn descriptor() -> MessageDescriptor {
+///         MessageDescriptor::of::<Self>(MessageKind::Command)
+///     }
+/// }
+///
+/// let descriptor = CreateUserCommand::descriptor();
+///
+/// assert_eq!(descriptor.name, "users.create");
+/// assert_eq!(descriptor.version, 2);
+/// assert_eq!(descriptor.kind, MessageKind::Command);
+/// assert_eq!(descriptor.schema_hash, None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageDescriptor {
+    /// The name of the message.
+    pub name: &'static str,
+    /// The version of the message.
+    pub version: u32,
+    /// The kind of the message.
+    pub kind: MessageKind,
+    /// The hash of the schema of the message, if known.
+    pub schema_hash: Option<u64>,
+}
+
+/// The `MessageDescriptor` implementation.
+impl MessageDescriptor {
+    /// Creates a new `MessageDescriptor`, at version `1`, with an unknown schema hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the message.
+    /// * `kind` - The kind of the message.
+    pub const fn new(name: &'static str, kind: MessageKind) -> Self {
+        Self {
+            name,
+            version: 1,
+            kind,
+            schema_hash: None,
+        }
+    }
+
+    /// Creates the `MessageDescriptor` of a message implementing [MessageName].
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of the message.
+    pub const fn of<M: MessageName>(kind: MessageKind) -> Self {
+        Self {
+            name: M::NAME,
+            version: M::VERSION,
+            kind,
+            schema_hash: M::SCHEMA_HASH,
+        }
+    }
+}
+
+/// Derives the [MessageName] trait, using the `#[message(name = "...", version = ...)]` attribute.
+#[cfg(feature = "derive")]
+pub use discern_derive::MessageName;
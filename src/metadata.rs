@@ -0,0 +1,221 @@
+//! The `metadata` module stamps published events with the metadata of the dispatch that published them.
+//!
+//! Subscribers often need to know more about an event than what it expresses: which request it belongs to, what
+//! caused it, on whose behalf it was published, and where it is in the stream of events. Rather than adding these
+//! fields to every event struct, the [EventBus](crate::event::EventBus) stamps every event it publishes with an
+//! [EventMetadata], which its subscribers read using [EventMetadata::current].
+//!
+//! Events published using [Context::publish](crate::context::Context::publish), or
+//! [EventBus::publish_with](crate::event::EventBus::publish_with), are stamped with the [CorrelationId], the
+//! [CausationId], and the [CallerId] found in the [Context](crate::context::Context) of the dispatch. Events
+//! published while a subscriber handles another event inherit the correlation id, and the principal, of that event.
+//!
+//! - [CorrelationId]: Identifies the request, or the workflow, a dispatch belongs to.
+//! - [CausationId]: Identifies the message that caused a dispatch.
+//! - [EventMetadata]: The metadata an event was published with.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::sync::Arc;
+
+use crate::caller::CallerId;
+use crate::context::Context;
+use crate::scope;
+
+/// Identifies the request, or the workflow, a dispatch belongs to, shared by every message it leads to.
+///
+/// The correlation id is attached to the [Context] of a command, and is stamped on the events it publishes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CorrelationId(Arc<str>);
+
+/// The `CorrelationId` implementation.
+impl CorrelationId {
+    /// Creates a new `CorrelationId`.
+    pub fn new(id: impl Into<Arc<str>>) -> Self {
+        Self(id.into())
+    }
+
+    /// Returns the identifier of the correlation.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for CorrelationId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.write_str(&self.0)
+    }
+}
+
+/// Identifies the message that caused a dispatch, e.g. the id of the request, or of the message consumed from a
+/// queue.
+///
+/// The causation id is attached to the [Context] of a command, and is stamped on the events it publishes. Unlike the
+/// [CorrelationId], it is not inherited by the events published by subscribers, since it names their direct cause.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CausationId(Arc<str>);
+
+/// The `CausationId` implementation.
+impl CausationId {
+    /// Creates a new `CausationId`.
+    pub fn new(id: impl Into<Arc<str>>) -> Self {
+        Self(id.into())
+    }
+
+    /// Returns the identifier of the cause.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for CausationId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.write_str(&self.0)
+    }
+}
+
+/// The metadata an event was published with.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::event::Event;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand;
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct UserCreatedEvent;
+/// #
+/// # impl Event for UserCreatedEvent {
+/// #     type Error = std::io::Error;
+/// # }
+/// use std::sync::Arc;
+/// use std::sync::Mutex;
+///
+/// use discern::async_trait;
+/// use discern::caller::CallerId;
+/// use discern::command::CommandBus;
+/// use discern::command::ContextualCommandHandler;
+/// use discern::context::Context;
+/// use discern::event::EventHandler;
+/// use discern::event_bus;
+/// use discern::metadata::CausationId;
+/// use discern::metadata::CorrelationId;
+/// use discern::metadata::EventMetadata;
+/// use discern::registry::CommandHandlerRegistry;
+///
+/// struct CreateUserCommandHandler;
+///
+/// #[async_trait]
+/// impl ContextualCommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+///     async fn handle(&self, _command: CreateUserCommand, context: &Context) -> Result<(), std::io::Error> {
+///         context.publish(UserCreatedEvent);
+///
+///         Ok(())
+///     }
+/// }
+///
+/// struct AuditTrail {
+///     entries: Arc<Mutex<Vec<EventMetadata>>>,
+/// }
+///
+/// #[async_trait]
+/// impl EventHandler<UserCreatedEvent> for AuditTrail {
+///     async fn handle(&self, _event: &UserCreatedEvent) -> Result<(), std::io::Error> {
+///         self.entries.lock().unwrap().extend(EventMetadata::current());
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let entries = Arc::new(Mutex::new(Vec::new()));
+/// let event_bus = event_bus! {
+///     UserCreatedEvent => AuditTrail { entries: entries.clone() },
+/// };
+///
+/// let mut registry = CommandHandlerRegistry::new();
+/// registry.register_contextual(CreateUserCommandHandler);
+///
+/// let command_bus = CommandBus::new(registry).with_events(event_bus.clone());
+///
+/// let context = Context::new()
+///     .with(CorrelationId::new("signup-42"))
+///     .with(CausationId::new("request-7"))
+///     .with(CallerId::new("web"));
+///
+/// command_bus.dispatch_with(CreateUserCommand, context).await.unwrap();
+///
+/// // Events published outside of a dispatch are only stamped with their position.
+/// event_bus.publish(UserCreatedEvent).await.unwrap();
+///
+/// let entries = entries.lock().unwrap();
+/// assert_eq!(entries[0].position, 1);
+/// assert_eq!(entries[0].correlation_id, Some(CorrelationId::new("signup-42")));
+/// assert_eq!(entries[0].causation_id, Some(CausationId::new("request-7")));
+/// assert_eq!(entries[0].principal, Some(CallerId::new("web")));
+/// assert_eq!(entries[1], EventMetadata { position: 2, ..EventMetadata::default() });
+/// # });
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct EventMetadata {
+    /// The position of the event in the stream of events published through the bus, see
+    /// [EventBus::position](crate::event::EventBus::position).
+    pub position: u64,
+    /// The correlation id of the dispatch that published the event, if any.
+    pub correlation_id: Option<CorrelationId>,
+    /// The causation id of the dispatch that published the event, if any.
+    pub causation_id: Option<CausationId>,
+    /// The caller on whose behalf the event was published, if any.
+    pub principal: Option<CallerId>,
+}
+
+/// The `EventMetadata` implementation.
+impl EventMetadata {
+    /// Returns the metadata of the event being handled, if any.
+    ///
+    /// The metadata is only available while a subscriber is being polled by the
+    /// [EventBus](crate::event::EventBus), tasks spawned from it don't see the metadata of the event.
+    pub fn current() -> Option<Self> {
+        scope::event_metadata()
+    }
+
+    /// Returns the metadata stamped on events published outside of a dispatch, inherited from the event being
+    /// handled, if any.
+    pub(crate) fn inherited() -> Self {
+        let current = Self::current().unwrap_or_default();
+
+        Self {
+            position: 0,
+            correlation_id: current.correlation_id,
+            causation_id: None,
+            principal: current.principal,
+        }
+    }
+
+    /// Returns the metadata stamped on events published by a dispatch with the given context.
+    ///
+    /// The values missing from the context are inherited from the event being handled, if any.
+    pub(crate) fn of(context: &Context) -> Self {
+        let inherited = Self::inherited();
+
+        Self {
+            position: 0,
+            correlation_id: context
+                .get::<CorrelationId>()
+                .cloned()
+                .or(inherited.correlation_id),
+            causation_id: context.get::<CausationId>().cloned(),
+            principal: context.get::<CallerId>().cloned().or(inherited.principal),
+        }
+    }
+}
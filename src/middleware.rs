@@ -0,0 +1,438 @@
+//! The `middleware` module provides a pipeline wrapping the handling of commands.
+//!
+//! Concerns such as logging, metrics, validation, or transactions apply to every command, and implementing them
+//! in each handler is repetitive, and easy to forget. A [CommandMiddleware] wraps the handling of every command
+//! dispatched through a [CommandBus](crate::command::CommandBus): it inspects the command, decides whether to
+//! continue by running the [Next] step of the pipeline, and observes the [Outcome]. Middleware can also attach
+//! values, such as the authenticated user, to the [Context](crate::context::Context) of the dispatch, which is
+//! visible to the middleware after them, and to
+//! [ContextualCommandHandler](crate::command::ContextualCommandHandler)s.
+//!
+//! - [CommandMiddleware]: Wraps the handling of commands.
+//! - [Next]: The remaining steps of the pipeline.
+//! - [Outcome]: The outcome of handling a command, as observed by a middleware.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+use crate::async_trait;
+use crate::context::Context;
+use crate::runtime::BoxFuture;
+#[cfg(feature = "std")]
+use crate::timeline::Recording;
+#[cfg(feature = "std")]
+use crate::timeline::Timeline;
+
+/// The outcome of handling a command, as observed by a middleware.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// The handler succeeded.
+    Succeeded,
+    /// No handler is registered for the command.
+    HandlerNotFound,
+    /// The handler failed, with the debug representation of its error.
+    Failed(String),
+    /// A middleware rejected the command, with the given reason.
+    Rejected(String),
+}
+
+/// The `Outcome` implementation.
+impl Outcome {
+    /// Returns whether the handler succeeded.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Succeeded)
+    }
+}
+
+/// The `CommandMiddleware` trait wraps the handling of commands.
+///
+/// Middleware are run in the order they were added to the bus, the first one wrapping all the others, and the
+/// handler lookup, and call, being the innermost step. A middleware continues the pipeline by running [Next::run],
+/// or rejects the command by returning [Outcome::Rejected] without running it, in which case the dispatch fails
+/// with [DispatchError::Rejected](crate::error::DispatchError::Rejected). Returning [Outcome::Rejected] after the
+/// handler succeeded, e.g. when a transaction fails to commit, fails the dispatch with
+/// [DispatchError::RejectedAfterHandling](crate::error::DispatchError::RejectedAfterHandling) instead, so that
+/// callers can tell that the handler already ran.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # struct CreateUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+/// #     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+/// #         Ok(1)
+/// #     }
+/// # }
+/// use std::sync::Arc;
+/// use std::sync::Mutex;
+///
+/// use discern::async_trait;
+/// use discern::command_bus;
+/// use discern::error::DispatchError;
+/// use discern::middleware::CommandMiddleware;
+/// use discern::middleware::Next;
+/// use discern::middleware::Outcome;
+///
+/// struct LoggingMiddleware {
+///     log: Arc<Mutex<Vec<String>>>,
+/// }
+///
+/// #[async_trait]
+/// impl CommandMiddleware for LoggingMiddleware {
+///     async fn handle(&self, next: Next<'_>) -> Outcome {
+///         let name = next.name();
+///         let outcome = next.run().await;
+///
+///         self.log.lock().unwrap().push(format!("{}: {:?}", name, outcome));
+///
+///         outcome
+///     }
+/// }
+///
+/// struct ValidationMiddleware;
+///
+/// #[async_trait]
+/// impl CommandMiddleware for ValidationMiddleware {
+///     async fn handle(&self, next: Next<'_>) -> Outcome {
+///         if let Some(command) = next.command::<CreateUserCommand>() {
+///             if command.username.is_empty() {
+///                 return Outcome::Rejected("the username must not be empty".to_string());
+///             }
+///         }
+///
+///         next.run().await
+///     }
+/// }
+///
+/// struct TransactionMiddleware;
+///
+/// #[async_trait]
+/// impl CommandMiddleware for TransactionMiddleware {
+///     async fn handle(&self, next: Next<'_>) -> Outcome {
+///         let reserved = next.command::<CreateUserCommand>().is_some_and(|command| command.username == "root");
+///         let outcome = next.run().await;
+///
+///         if outcome.is_success() && reserved {
+///             // The commit fails, after the handler ran.
+///             return Outcome::Rejected("the transaction failed to commit".to_string());
+///         }
+///
+///         outcome
+///     }
+/// }
+///
+/// let log = Arc::new(Mutex::new(Vec::new()));
+///
+/// let command_bus = command_bus! {
+///     middleware: [LoggingMiddleware { log: log.clone() }, ValidationMiddleware, TransactionMiddleware],
+///     CreateUserCommand => CreateUserCommandHandler,
+/// };
+///
+/// let result = command_bus.try_dispatch(CreateUserCommand { username: "alice".to_string() }).await;
+/// assert_eq!(result.unwrap(), 1);
+///
+/// let result = command_bus.try_dispatch(CreateUserCommand { username: "".to_string() }).await;
+/// assert!(matches!(result, Err(DispatchError::Rejected(_))));
+///
+/// let result = command_bus.try_dispatch(CreateUserCommand { username: "root".to_string() }).await;
+/// assert!(matches!(result, Err(DispatchError::RejectedAfterHandling(_))));
+///
+/// assert_eq!(log.lock().unwrap().len(), 3);
+/// # });
+/// ```
+#[async_trait]
+pub trait CommandMiddleware: Send + Sync {
+    /// Handles a command, usually by running the next step of the pipeline.
+    ///
+    /// # Arguments
+    ///
+    /// * `next` - The remaining steps of the pipeline, which also provides access to the command.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of handling the command.
+    async fn handle(&self, next: Next<'_>) -> Outcome;
+
+    /// Returns the name of the middleware, used to identify it in timelines, see the [timeline](crate::timeline)
+    /// module.
+    ///
+    /// Defaults to the type name of the middleware.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+/// The innermost step of the pipeline, which looks up the handler of the command, and calls it.
+#[doc(hidden)]
+pub(crate) trait Endpoint: Send {
+    /// Returns the name of the command.
+    fn name(&self) -> &'static str;
+
+    /// Returns the permissions required to dispatch the command.
+    fn required_permissions(&self) -> &'static [&'static str];
+
+    /// Returns the command, as long as it wasn't handled yet.
+    fn command(&self) -> Option<&(dyn Any + Send + Sync)>;
+
+    /// Returns the debug representation of the command, as long as it wasn't handled yet.
+    fn debug(&self) -> Option<&dyn Debug>;
+
+    /// Returns the context of the dispatch.
+    fn context(&self) -> &Context;
+
+    /// Returns the context of the dispatch, mutably.
+    fn context_mut(&mut self) -> &mut Context;
+
+    /// Looks up the handler of the command, and calls it.
+    fn call(&mut self) -> BoxFuture<'_, Outcome>;
+}
+
+/// The remaining steps of the pipeline.
+///
+/// Running the next steps consumes the `Next`, so the command is handled at most once.
+pub struct Next<'a> {
+    #[doc(hidden)]
+    middleware: &'a [Arc<dyn CommandMiddleware>],
+    #[doc(hidden)]
+    endpoint: &'a mut dyn Endpoint,
+    #[doc(hidden)]
+    #[cfg(feature = "std")]
+    recording: Option<&'a Recording>,
+    #[doc(hidden)]
+    #[cfg(feature = "std")]
+    depth: usize,
+}
+
+/// The `Next` implementation.
+impl<'a> Next<'a> {
+    /// Creates the pipeline running the given middleware around the endpoint.
+    pub(crate) fn new(
+        middleware: &'a [Arc<dyn CommandMiddleware>],
+        endpoint: &'a mut dyn Endpoint,
+    ) -> Self {
+        Self {
+            middleware,
+            endpoint,
+            #[cfg(feature = "std")]
+            recording: None,
+            #[cfg(feature = "std")]
+            depth: 0,
+        }
+    }
+
+    /// Returns the name of the command, see [Command::name](crate::command::Command::name).
+    pub fn name(&self) -> &'static str {
+        self.endpoint.name()
+    }
+
+    /// Returns the permissions required to dispatch the command, see
+    /// [Command::required_permissions](crate::command::Command::required_permissions).
+    pub fn required_permissions(&self) -> &'static [&'static str] {
+        self.endpoint.required_permissions()
+    }
+
+    /// Returns the command, if it is of type `C`.
+    pub fn command<C: Any>(&self) -> Option<&C> {
+        self.endpoint.command()?.downcast_ref::<C>()
+    }
+
+    /// Returns the debug representation of the command.
+    pub fn debug(&self) -> &dyn Debug {
+        self.endpoint.debug().unwrap_or(&"<handled>")
+    }
+
+    /// Returns the context of the dispatch, see [CommandBus::dispatch_with](crate::command::CommandBus::dispatch_with).
+    pub fn context(&self) -> &Context {
+        self.endpoint.context()
+    }
+
+    /// Returns the context of the dispatch, mutably, allowing values to be attached for the next steps.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct DeleteUserCommand {
+    /// #     user_id: u64,
+    /// # }
+    /// #
+    /// # impl Command for DeleteUserCommand {
+    /// #     type Metadata = ();
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// use discern::async_trait;
+    /// use discern::command::CommandBus;
+    /// use discern::command::ContextualCommandHandler;
+    /// use discern::context::Context;
+    /// use discern::middleware::CommandMiddleware;
+    /// use discern::middleware::Next;
+    /// use discern::middleware::Outcome;
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// struct Principal(&'static str);
+    ///
+    /// struct AuthenticationMiddleware;
+    ///
+    /// #[async_trait]
+    /// impl CommandMiddleware for AuthenticationMiddleware {
+    ///     async fn handle(&self, mut next: Next<'_>) -> Outcome {
+    ///         next.context_mut().insert(Principal("alice"));
+    ///
+    ///         next.run().await
+    ///     }
+    /// }
+    ///
+    /// struct DeleteUserCommandHandler;
+    ///
+    /// #[async_trait]
+    /// impl ContextualCommandHandler<DeleteUserCommand> for DeleteUserCommandHandler {
+    ///     async fn handle(&self, _command: DeleteUserCommand, context: &Context) -> Result<(), std::io::Error> {
+    ///         match context.get::<Principal>() {
+    ///             Some(Principal("alice")) => Ok(()),
+    ///             _ => Err(std::io::ErrorKind::PermissionDenied.into()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.register_contextual(DeleteUserCommandHandler);
+    ///
+    /// let command_bus = CommandBus::new(registry).with_middleware(AuthenticationMiddleware);
+    ///
+    /// assert!(command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await.is_ok());
+    /// # });
+    /// ```
+    pub fn context_mut(&mut self) -> &mut Context {
+        self.endpoint.context_mut()
+    }
+
+    /// Runs the remaining steps of the pipeline.
+    pub async fn run(self) -> Outcome {
+        #[cfg(feature = "std")]
+        if let Some(recording) = self.recording {
+            let start = recording.now();
+            let (name, outcome) = match self.middleware.split_first() {
+                Some((middleware, rest)) => {
+                    let next = Next {
+                        middleware: rest,
+                        endpoint: self.endpoint,
+                        recording: Some(recording),
+                        depth: self.depth + 1,
+                    };
+
+                    (middleware.name(), middleware.handle(next).await)
+                }
+                None => (crate::timeline::HANDLER_SEGMENT, self.endpoint.call().await),
+            };
+
+            recording.segment(name, self.depth, start);
+
+            return outcome;
+        }
+
+        match self.middleware.split_first() {
+            Some((middleware, rest)) => middleware.handle(Next::new(rest, self.endpoint)).await,
+            None => self.endpoint.call().await,
+        }
+    }
+}
+
+/// Debug implementation for `Next`
+impl Debug for Next<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Next")
+            .field("name", &self.name())
+            .field("remaining", &self.middleware.len())
+            .finish()
+    }
+}
+
+/// The middleware of a bus, in the order they were added.
+#[doc(hidden)]
+#[derive(Clone, Default)]
+pub(crate) struct Pipeline {
+    middleware: Vec<Arc<dyn CommandMiddleware>>,
+    #[cfg(feature = "std")]
+    timeline: Option<Timeline>,
+}
+
+impl Pipeline {
+    /// Adds a middleware, wrapped by the middleware added before it.
+    pub(crate) fn push(&mut self, middleware: Arc<dyn CommandMiddleware>) {
+        self.middleware.push(middleware);
+    }
+
+    /// Records the timing of every dispatch in the given timeline.
+    #[cfg(feature = "std")]
+    pub(crate) fn set_timeline(&mut self, timeline: Timeline) {
+        self.timeline = Some(timeline);
+    }
+
+    /// Returns whether the pipeline has no middleware, and no timeline.
+    pub(crate) fn is_empty(&self) -> bool {
+        #[cfg(feature = "std")]
+        if self.timeline.is_some() {
+            return false;
+        }
+
+        self.middleware.is_empty()
+    }
+
+    /// Runs the middleware around the given endpoint.
+    pub(crate) async fn run(&self, endpoint: &mut dyn Endpoint) -> Outcome {
+        #[cfg(feature = "std")]
+        if let Some(timeline) = &self.timeline {
+            let name = endpoint.name();
+            let recording = timeline.start();
+
+            let mut next = Next::new(&self.middleware, endpoint);
+            next.recording = Some(&recording);
+            let outcome = next.run().await;
+
+            timeline.finish(name, recording);
+
+            return outcome;
+        }
+
+        Next::new(&self.middleware, endpoint).run().await
+    }
+}
+
+/// Debug implementation for `Pipeline`
+impl Debug for Pipeline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        let mut debug = f.debug_struct("Pipeline");
+        debug.field("middleware", &self.middleware.len());
+        #[cfg(feature = "std")]
+        debug.field("timeline", &self.timeline.is_some());
+
+        debug.finish()
+    }
+}
@@ -0,0 +1,310 @@
+//! The `monitor` module provides failure rate monitoring for handlers.
+//!
+//! A [FailureRateMonitor] tracks the outcome of every message handled by the handlers it observes, per message
+//! type, over a sliding window. When the failure rate of a message type exceeds the configured threshold, the
+//! monitor invokes a user callback, which can be used to alert, or to implement self-healing patterns such as
+//! pausing a faulty source by dispatching a command.
+//!
+//! - [FailureRateMonitor]: Tracks failure rates per message type, and invokes a callback when a threshold is exceeded.
+//! - [MonitoredHandler]: A handler that reports its outcomes to a [FailureRateMonitor].
+//! - [FailureRateAlert]: The alert passed to the callback.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+
+/// The alert passed to the [FailureRateMonitor] callback when the failure rate of a message type exceeds the threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FailureRateAlert {
+    /// The name of the message, as returned by [Command::name] or [Query::name].
+    pub message: &'static str,
+    /// The number of failed messages within the window.
+    pub failures: usize,
+    /// The number of handled messages within the window.
+    pub samples: usize,
+    /// The failure rate within the window, between `0.0` and `1.0`.
+    pub rate: f64,
+}
+
+/// Tracks failure rates per message type over a sliding window, and invokes a callback when a threshold is exceeded.
+///
+/// The callback is invoked once when the failure rate of a message type reaches the threshold, and is not invoked
+/// again for that message type until its failure rate drops back below the threshold.
+///
+/// The callback is invoked synchronously from within the dispatch that triggered it, and must therefore not block,
+/// work that needs to be awaited, such as dispatching a command, should be spawned instead.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::async_trait;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct ImportOrderCommand {
+/// #    order_id: u64,
+/// # }
+/// #
+/// # impl Command for ImportOrderCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct ImportOrderCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<ImportOrderCommand> for ImportOrderCommandHandler {
+/// #    async fn handle(&self, _command: ImportOrderCommand) -> Result<(), std::io::Error> {
+/// #       Err(std::io::Error::other("upstream unavailable"))
+/// #   }
+/// # }
+/// use std::sync::atomic::AtomicBool;
+/// use std::sync::atomic::Ordering;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use discern::command_bus;
+/// use discern::monitor::FailureRateMonitor;
+///
+/// let paused = Arc::new(AtomicBool::new(false));
+/// let monitor = {
+///     let paused = paused.clone();
+///
+///     // Pause the import when at least half of the orders failed within the last minute.
+///     FailureRateMonitor::new(Duration::from_secs(60), 0.5, move |alert| {
+///         println!("{} failed {} out of {} times", alert.message, alert.failures, alert.samples);
+///
+///         paused.store(true, Ordering::SeqCst);
+///     })
+///     .with_min_samples(3)
+/// };
+/// let monitor = Arc::new(monitor);
+///
+/// let command_bus = command_bus! {
+///     ImportOrderCommand => monitor.observe(ImportOrderCommandHandler),
+/// };
+///
+/// for order_id in 0..3 {
+///     let _ = command_bus.dispatch(ImportOrderCommand { order_id }).await;
+/// }
+///
+/// assert!(paused.load(Ordering::SeqCst));
+/// assert_eq!(monitor.failure_rate(ImportOrderCommand::name()), Some(1.0));
+/// # });
+/// ```
+pub struct FailureRateMonitor {
+    #[doc(hidden)]
+    window: Duration,
+    #[doc(hidden)]
+    threshold: f64,
+    #[doc(hidden)]
+    min_samples: usize,
+    #[doc(hidden)]
+    callback: Box<dyn Fn(FailureRateAlert) + Send + Sync>,
+    #[doc(hidden)]
+    windows: Mutex<HashMap<&'static str, Window>>,
+}
+
+/// The `FailureRateMonitor` implementation.
+impl FailureRateMonitor {
+    /// Creates a new `FailureRateMonitor`.
+    ///
+    /// By default, a single failed message is enough to exceed the threshold, use [FailureRateMonitor::with_min_samples]
+    /// to require a minimum number of handled messages within the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The duration of the sliding window.
+    /// * `threshold` - The failure rate, between `0.0` and `1.0`, at which the callback is invoked.
+    /// * `callback` - The function invoked when the threshold is exceeded.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `threshold` is not between `0.0` and `1.0`.
+    pub fn new(
+        window: Duration,
+        threshold: f64,
+        callback: impl Fn(FailureRateAlert) + Send + Sync + 'static,
+    ) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&threshold),
+            "the threshold must be between 0.0 and 1.0"
+        );
+
+        Self {
+            window,
+            threshold,
+            min_samples: 1,
+            callback: Box::new(callback),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Requires a minimum number of handled messages within the window before the callback can be invoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_samples` - The minimum number of handled messages.
+    pub fn with_min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples.max(1);
+        self
+    }
+
+    /// Wraps the given handler, reporting its outcomes to this monitor.
+    ///
+    /// The monitor is shared between all the handlers it observes, and must therefore be wrapped in an [Arc].
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to observe.
+    pub fn observe<H>(self: &Arc<Self>, handler: H) -> MonitoredHandler<H> {
+        MonitoredHandler {
+            handler,
+            monitor: self.clone(),
+        }
+    }
+
+    /// Returns the current failure rate of the given message type, or `None` if no message of
+    /// that type was handled within the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The name of the message type, as returned by [Command::name] or [Query::name].
+    pub fn failure_rate(&self, message: &str) -> Option<f64> {
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        let window = windows.get_mut(message)?;
+        window.prune(Instant::now(), self.window);
+
+        window.rate()
+    }
+
+    /// Records the outcome of a handled message.
+    fn record(&self, message: &'static str, failed: bool) {
+        let now = Instant::now();
+
+        let alert = {
+            let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+            let window = windows.entry(message).or_default();
+            window.prune(now, self.window);
+            window.outcomes.push_back((now, failed));
+            window.failures += usize::from(failed);
+
+            let samples = window.outcomes.len();
+            let rate = window.rate().unwrap_or(0.0);
+            let exceeded = samples >= self.min_samples && rate >= self.threshold;
+
+            let alert = (exceeded && !window.alerting).then_some(FailureRateAlert {
+                message,
+                failures: window.failures,
+                samples,
+                rate,
+            });
+            window.alerting = exceeded;
+
+            alert
+        };
+
+        // The callback is invoked without holding the lock, so that it can query the monitor.
+        if let Some(alert) = alert {
+            (self.callback)(alert);
+        }
+    }
+}
+
+/// Debug implementation for `FailureRateMonitor`
+impl Debug for FailureRateMonitor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("FailureRateMonitor")
+            .field("window", &self.window)
+            .field("threshold", &self.threshold)
+            .field("min_samples", &self.min_samples)
+            .finish()
+    }
+}
+
+/// The outcomes of a message type within the sliding window.
+#[doc(hidden)]
+#[derive(Default)]
+struct Window {
+    outcomes: VecDeque<(Instant, bool)>,
+    failures: usize,
+    alerting: bool,
+}
+
+impl Window {
+    /// Removes the outcomes that are older than the window.
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some(&(at, failed)) = self.outcomes.front() {
+            if now.duration_since(at) < window {
+                break;
+            }
+
+            self.outcomes.pop_front();
+            self.failures -= usize::from(failed);
+        }
+    }
+
+    fn rate(&self) -> Option<f64> {
+        if self.outcomes.is_empty() {
+            return None;
+        }
+
+        Some(self.failures as f64 / self.outcomes.len() as f64)
+    }
+}
+
+/// A handler that reports its outcomes to a [FailureRateMonitor].
+///
+/// `MonitoredHandler` is created using [FailureRateMonitor::observe], and implements [CommandHandler] and
+/// [QueryHandler] for every command, and query, the observed handler implements them for.
+pub struct MonitoredHandler<H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    monitor: Arc<FailureRateMonitor>,
+}
+
+#[async_trait]
+impl<C: Command, H: CommandHandler<C>> CommandHandler<C> for MonitoredHandler<H> {
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        let result = self.handler.handle(command).await;
+        self.monitor.record(C::name(), result.is_err());
+
+        result
+    }
+}
+
+#[async_trait]
+impl<Q: Query, H: QueryHandler<Q>> QueryHandler<Q> for MonitoredHandler<H> {
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        let result = self.handler.handle(query).await;
+        self.monitor.record(Q::name(), result.is_err());
+
+        result
+    }
+}
+
+/// Debug implementation for `MonitoredHandler`
+impl<H> Debug for MonitoredHandler<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("MonitoredHandler")
+            .field("monitor", &self.monitor)
+            .finish()
+    }
+}
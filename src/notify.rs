@@ -0,0 +1,397 @@
+//! The `notify` module provides an outbound adapter, sending notifications for integration events.
+//!
+//! Integration events are the events other systems, or people, are told about, e.g. an email welcoming a new user,
+//! or a webhook calling a partner back when an order ships. Events designated as integration events implement
+//! [Notify], exposing the variables their notifications are rendered from. A [NotificationHandler] subscribes to
+//! such an event, renders a [Notification] from its [Template]s, and hands it to a [Notifier], which delivers it
+//! using a provider, e.g. SMTP, SES, or an HTTP client posting to a webhook.
+//!
+//! Deliveries failing transiently are retried by wrapping the handler in a
+//! [RetryingHandler](crate::retry::RetryingHandler). Notifications carry the
+//! [EventMetadata](crate::metadata::EventMetadata) of their event, whose position identifies the event, and makes
+//! a suitable idempotency key for providers receiving the same notification more than once.
+//!
+//! - [Notify]: Designates an event as an integration event, exposing its template variables.
+//! - [Template]: A template rendering the variables of an event.
+//! - [Notification]: A notification rendered for an event.
+//! - [Notifier]: Delivers notifications using a provider.
+//! - [NotificationHandler]: A subscriber sending a notification for each event it handles.
+//! - [TemplateError]: The error returned when a template refers to a variable an event does not have.
+
+use std::error::Error;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+
+use crate::async_trait;
+use crate::event::Event;
+use crate::event::EventHandler;
+use crate::metadata::EventMetadata;
+
+/// Designates an event as an integration event, exposing the variables its notifications are rendered from.
+///
+/// # Example
+///
+/// ```
+/// use discern::event::Event;
+/// use discern::notify::Notify;
+///
+/// #[derive(Debug)]
+/// struct UserCreatedEvent {
+///     email: String,
+///     name: String,
+/// }
+///
+/// impl Event for UserCreatedEvent {
+///     type Error = std::io::Error;
+/// }
+///
+/// impl Notify for UserCreatedEvent {
+///     fn variable(&self, name: &str) -> Option<String> {
+///         match name {
+///             "email" => Some(self.email.clone()),
+///             "name" => Some(self.name.clone()),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait Notify: Event {
+    /// Returns the value of the given template variable, or `None` if the event has no such variable.
+    fn variable(&self, name: &str) -> Option<String>;
+}
+
+/// A template rendering the variables of an event, written as `{{ name }}`.
+///
+/// Text that is not a variable is rendered as is, including unterminated `{{`.
+///
+/// # Example
+///
+/// ```
+/// # use discern::event::Event;
+/// # use discern::notify::Notify;
+/// #
+/// # #[derive(Debug)]
+/// # struct UserCreatedEvent {
+/// #     name: String,
+/// # }
+/// #
+/// # impl Event for UserCreatedEvent {
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # impl Notify for UserCreatedEvent {
+/// #     fn variable(&self, name: &str) -> Option<String> {
+/// #         (name == "name").then(|| self.name.clone())
+/// #     }
+/// # }
+/// use discern::notify::Template;
+///
+/// let event = UserCreatedEvent { name: "Alice".to_string() };
+///
+/// let template = Template::new("Welcome, {{ name }}!");
+/// assert_eq!(template.render(&event).unwrap(), "Welcome, Alice!");
+///
+/// let template = Template::new("Your email is {{email}}");
+/// assert_eq!(template.render(&event).unwrap_err().variable, "email");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    #[doc(hidden)]
+    segments: Vec<Segment>,
+}
+
+/// A part of a [Template].
+#[doc(hidden)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+/// The `Template` implementation.
+impl Template {
+    /// Creates a new `Template`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The text of the template, with variables written as `{{ name }}`.
+    pub fn new(source: &str) -> Self {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        while let Some((text, tail)) = rest.split_once("{{") {
+            let Some((variable, tail)) = tail.split_once("}}") else {
+                break;
+            };
+
+            if !text.is_empty() {
+                segments.push(Segment::Text(text.to_string()));
+            }
+
+            segments.push(Segment::Variable(variable.trim().to_string()));
+            rest = tail;
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Self { segments }
+    }
+
+    /// Renders the template with the variables of the given event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event providing the variables.
+    ///
+    /// # Returns
+    ///
+    /// The rendered text, or a [TemplateError] naming the first variable the event does not have.
+    pub fn render<E: Notify>(&self, event: &E) -> Result<String, TemplateError> {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => rendered.push_str(text),
+                Segment::Variable(name) => match event.variable(name) {
+                    Some(value) => rendered.push_str(&value),
+                    None => {
+                        return Err(TemplateError {
+                            event: E::name(),
+                            variable: name.clone(),
+                        })
+                    }
+                },
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+/// The error returned when a template refers to a variable an event does not have.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TemplateError {
+    /// The name of the event.
+    pub event: &'static str,
+    /// The name of the missing variable.
+    pub variable: String,
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        write!(
+            f,
+            "the {} event has no template variable named {:?}",
+            self.event, self.variable
+        )
+    }
+}
+
+impl Error for TemplateError {}
+
+/// A notification rendered for an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// The recipient of the notification, e.g. an email address, or the URL of a webhook.
+    pub recipient: String,
+    /// The subject of the notification, e.g. the subject of an email, or the type of a webhook payload.
+    pub subject: String,
+    /// The body of the notification.
+    pub body: String,
+    /// The metadata of the event the notification was rendered for.
+    pub metadata: EventMetadata,
+}
+
+/// Delivers notifications using a provider, e.g. SMTP, SES, or an HTTP client posting to a webhook.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// The error returned when the provider fails to deliver a notification.
+    type Error: Debug + Send + Sync;
+
+    /// Delivers a notification.
+    ///
+    /// # Arguments
+    ///
+    /// * `notification` - The notification to deliver.
+    async fn send(&self, notification: Notification) -> Result<(), Self::Error>;
+}
+
+/// A subscriber sending a notification for each event it handles.
+///
+/// The error type of the event must be convertible from the [TemplateError] returned when rendering the
+/// notification fails, and from the error of the [Notifier].
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::sync::Arc;
+/// use std::sync::Mutex;
+/// use std::time::Duration;
+///
+/// use discern::async_trait;
+/// use discern::event::Event;
+/// use discern::event_bus;
+/// use discern::notify::Notification;
+/// use discern::notify::NotificationHandler;
+/// use discern::notify::Notifier;
+/// use discern::notify::Notify;
+/// use discern::notify::Template;
+/// use discern::notify::TemplateError;
+/// use discern::retry::RetryPolicy;
+/// use discern::retry::RetryingHandler;
+/// use discern::runtime::TokioRuntime;
+///
+/// #[derive(Debug)]
+/// enum NotificationFailure {
+///     Template(TemplateError),
+///     Provider(std::io::Error),
+/// }
+///
+/// impl From<TemplateError> for NotificationFailure {
+///     fn from(error: TemplateError) -> Self {
+///         Self::Template(error)
+///     }
+/// }
+///
+/// impl From<std::io::Error> for NotificationFailure {
+///     fn from(error: std::io::Error) -> Self {
+///         Self::Provider(error)
+///     }
+/// }
+///
+/// #[derive(Debug)]
+/// struct OrderShippedEvent {
+///     order_id: u64,
+/// }
+///
+/// impl Event for OrderShippedEvent {
+///     type Error = NotificationFailure;
+/// }
+///
+/// impl Notify for OrderShippedEvent {
+///     fn variable(&self, name: &str) -> Option<String> {
+///         (name == "order_id").then(|| self.order_id.to_string())
+///     }
+/// }
+///
+/// /// Stands in for an HTTP client, posting notifications to a webhook, which is down at first.
+/// struct Webhook {
+///     attempts: Mutex<u32>,
+///     delivered: Arc<Mutex<Vec<Notification>>>,
+/// }
+///
+/// #[async_trait]
+/// impl Notifier for Webhook {
+///     type Error = std::io::Error;
+///
+///     async fn send(&self, notification: Notification) -> Result<(), std::io::Error> {
+///         let mut attempts = self.attempts.lock().unwrap();
+///         *attempts += 1;
+///         if *attempts == 1 {
+///             return Err(std::io::ErrorKind::ConnectionRefused.into());
+///         }
+///
+///         self.delivered.lock().unwrap().push(notification);
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let delivered = Arc::new(Mutex::new(Vec::new()));
+/// let webhook = Webhook { attempts: Mutex::new(0), delivered: delivered.clone() };
+///
+/// let handler = NotificationHandler::new(
+///     webhook,
+///     Template::new("https://partner.localhost/hooks/orders"),
+///     Template::new("order.shipped"),
+///     Template::new(r#"{"order_id":{{ order_id }}}"#),
+/// );
+///
+/// let policy = RetryPolicy::new(3)
+///     .with_backoff(Duration::from_millis(1), Duration::from_millis(10))
+///     .retry_if(|error| matches!(error, NotificationFailure::Provider(_)));
+///
+/// let event_bus = event_bus! {
+///     OrderShippedEvent => RetryingHandler::new(handler, policy, TokioRuntime),
+/// };
+///
+/// event_bus.publish(OrderShippedEvent { order_id: 7 }).await.unwrap();
+///
+/// let delivered = delivered.lock().unwrap();
+/// assert_eq!(delivered.len(), 1);
+/// assert_eq!(delivered[0].recipient, "https://partner.localhost/hooks/orders");
+/// assert_eq!(delivered[0].body, r#"{"order_id":7}"#);
+/// assert_eq!(delivered[0].metadata.position, 1);
+/// # });
+/// ```
+pub struct NotificationHandler<N> {
+    #[doc(hidden)]
+    notifier: N,
+    #[doc(hidden)]
+    recipient: Template,
+    #[doc(hidden)]
+    subject: Template,
+    #[doc(hidden)]
+    body: Template,
+}
+
+/// The `NotificationHandler` implementation.
+impl<N> NotificationHandler<N> {
+    /// Creates a new `NotificationHandler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `notifier` - The notifier delivering the notifications.
+    /// * `recipient` - The template of the recipient of the notifications.
+    /// * `subject` - The template of the subject of the notifications.
+    /// * `body` - The template of the body of the notifications.
+    pub fn new(notifier: N, recipient: Template, subject: Template, body: Template) -> Self {
+        Self {
+            notifier,
+            recipient,
+            subject,
+            body,
+        }
+    }
+
+    /// Returns the notifier of this handler.
+    pub fn notifier(&self) -> &N {
+        &self.notifier
+    }
+}
+
+#[async_trait]
+impl<E, N> EventHandler<E> for NotificationHandler<N>
+where
+    E: Notify,
+    E::Error: From<TemplateError> + From<N::Error>,
+    N: Notifier,
+{
+    async fn handle(&self, event: &E) -> Result<(), E::Error> {
+        let notification = Notification {
+            recipient: self.recipient.render(event)?,
+            subject: self.subject.render(event)?,
+            body: self.body.render(event)?,
+            metadata: EventMetadata::current().unwrap_or_default(),
+        };
+
+        self.notifier.send(notification).await?;
+
+        Ok(())
+    }
+}
+
+/// Debug implementation for `NotificationHandler`
+impl<N> Debug for NotificationHandler<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("NotificationHandler")
+            .field("recipient", &self.recipient)
+            .field("subject", &self.subject)
+            .field("body", &self.body)
+            .finish()
+    }
+}
@@ -0,0 +1,185 @@
+//! The `outcome` module provides result envelopes describing the events emitted while handling a command.
+//!
+//! Callers dispatching a command often need to know what happened downstream of it, e.g. to wait until the read
+//! model caught up with the events it emitted, before querying it. Instead of querying for them afterwards, the
+//! caller dispatches the command using [CommandBus::try_dispatch_outcome](crate::command::CommandBus::try_dispatch_outcome),
+//! which returns a [CommandOutcome] carrying the metadata of the handler, along with the events published through
+//! any [EventBus](crate::event::EventBus) while it was handled.
+//!
+//! - [CommandOutcome]: The metadata of a handled command, and the events it emitted.
+//! - [EmittedEvent]: An event published while handling a command.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::command::Command;
+use crate::message::MessageDescriptor;
+use crate::scope;
+
+/// An event published while handling a command.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmittedEvent {
+    /// The descriptor of the event, see [Event::descriptor](crate::event::Event::descriptor).
+    pub descriptor: MessageDescriptor,
+    /// The position of the event among the events published through the same
+    /// [EventBus](crate::event::EventBus), and its clones, starting at `1`, see
+    /// [EventBus::position](crate::event::EventBus::position).
+    pub position: u64,
+}
+
+/// The metadata of a handled command, and the events it emitted.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::event::Event;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct UserCreatedEvent {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Event for UserCreatedEvent {
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+/// use discern::event::EventBus;
+/// use discern::event_bus;
+///
+/// struct CreateUserCommandHandler {
+///     event_bus: EventBus,
+/// }
+///
+/// #[async_trait]
+/// impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+///     async fn handle(&self, _command: CreateUserCommand) -> Result<u64, std::io::Error> {
+///         self.event_bus.publish(UserCreatedEvent { user_id: 1 }).await.unwrap();
+///
+///         Ok(1)
+///     }
+/// }
+///
+/// let event_bus = event_bus! {};
+/// let command_bus = command_bus! {
+///     CreateUserCommand => CreateUserCommandHandler { event_bus: event_bus.clone() },
+/// };
+///
+/// let command = CreateUserCommand { username: "alice".to_string() };
+/// let outcome = command_bus.try_dispatch_outcome(command).await.unwrap();
+///
+/// assert_eq!(outcome.metadata, 1);
+/// assert_eq!(outcome.events.len(), 1);
+/// assert!(outcome.events[0].descriptor.name.ends_with("UserCreatedEvent"));
+/// assert_eq!(outcome.events[0].position, event_bus.position());
+/// # });
+/// ```
+pub struct CommandOutcome<C: Command> {
+    /// The metadata returned by the handler.
+    pub metadata: C::Metadata,
+    /// The events published while handling the command, in the order they were published.
+    pub events: Vec<EmittedEvent>,
+}
+
+/// The `CommandOutcome` implementation.
+impl<C: Command> CommandOutcome<C> {
+    /// Returns the position of the last event published while handling the command, if any.
+    ///
+    /// Waiting until a projection reached this position guarantees that it observed every event of the command.
+    pub fn last_position(&self) -> Option<u64> {
+        self.events.iter().map(|event| event.position).max()
+    }
+}
+
+impl<C: Command> Clone for CommandOutcome<C>
+where
+    C::Metadata: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            metadata: self.metadata.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+/// Debug implementation for `CommandOutcome`
+impl<C: Command> Debug for CommandOutcome<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CommandOutcome")
+            .field("metadata", &self.metadata)
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+/// Records the events published while handling a command.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EventLog {
+    events: Arc<Mutex<Vec<EmittedEvent>>>,
+}
+
+impl EventLog {
+    /// Records an event, if the current dispatch runs within an event log.
+    pub(crate) fn record(descriptor: MessageDescriptor, position: u64) {
+        if let Some(log) = scope::event_log() {
+            log.push(EmittedEvent {
+                descriptor,
+                position,
+            });
+        }
+    }
+
+    /// Runs the given future within a new event log, returning its output, and the events it published.
+    ///
+    /// The events are also recorded in the enclosing event log, if any, since they are downstream of the
+    /// enclosing dispatch as well.
+    pub(crate) async fn capture<F: Future>(future: F) -> (F::Output, Vec<EmittedEvent>) {
+        let log = EventLog::default();
+        let mut future = pin!(future);
+        let mut current = Some(log.clone());
+
+        let output = std::future::poll_fn(|cx| {
+            scope::with_event_log(&mut current, || future.as_mut().poll(cx))
+        })
+        .await;
+
+        let events = std::mem::take(&mut *log.lock());
+        if let Some(enclosing) = scope::event_log() {
+            enclosing.lock().extend(events.iter().cloned());
+        }
+
+        (output, events)
+    }
+
+    /// Records an emitted event.
+    fn push(&self, event: EmittedEvent) {
+        self.lock().push(event);
+    }
+
+    /// Locks the recorded events.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<EmittedEvent>> {
+        self.events.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
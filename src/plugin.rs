@@ -0,0 +1,106 @@
+//! The `plugin` module provides bundles of handlers that extend a host application.
+//!
+//! Extensible applications ship features as plugins, each contributing its own commands, queries, and their
+//! handlers. A [Plugin] registers its handlers into the registries of the host, either before the buses are built,
+//! or into running buses, using [install].
+//!
+//! - [Plugin]: A bundle of command, and query, handlers.
+//! - [install]: Registers the handlers of a plugin into running buses.
+
+#[cfg(feature = "std")]
+use crate::command::CommandBus;
+#[cfg(feature = "std")]
+use crate::query::QueryBus;
+use crate::registry::CommandHandlerRegistry;
+use crate::registry::QueryHandlerRegistry;
+
+/// The `Plugin` trait represents a bundle of command, and query, handlers.
+///
+/// Both registration methods default to registering nothing, so that a plugin only implements the ones it needs.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::async_trait;
+/// # use discern::query::Query;
+/// # use discern::query::QueryHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetInvoiceQuery {
+/// #     invoice_id: u64,
+/// # }
+/// #
+/// # impl Query for GetInvoiceQuery {
+/// #     type Output = String;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # struct GetInvoiceQueryHandler;
+/// #
+/// # #[async_trait]
+/// # impl QueryHandler<GetInvoiceQuery> for GetInvoiceQueryHandler {
+/// #     async fn handle(&self, query: GetInvoiceQuery) -> Result<String, std::io::Error> {
+/// #         Ok(format!("invoice-{}", query.invoice_id))
+/// #     }
+/// # }
+/// use discern::command_bus;
+/// use discern::plugin::Plugin;
+/// use discern::query_bus;
+/// use discern::registry::QueryHandlerRegistry;
+///
+/// struct InvoicingPlugin;
+///
+/// impl Plugin for InvoicingPlugin {
+///     fn name(&self) -> &'static str {
+///         "invoicing"
+///     }
+///
+///     fn register_queries(&self, registry: &mut QueryHandlerRegistry) {
+///         registry.register(GetInvoiceQueryHandler);
+///     }
+/// }
+///
+/// let command_bus = command_bus! {};
+/// let query_bus = query_bus! {};
+///
+/// discern::plugin::install(&InvoicingPlugin, &command_bus, &query_bus);
+///
+/// assert_eq!(query_bus.dispatch(GetInvoiceQuery { invoice_id: 1 }).await.unwrap(), "invoice-1");
+/// # });
+/// ```
+pub trait Plugin {
+    /// Returns the name of the plugin.
+    fn name(&self) -> &'static str;
+
+    /// Registers the command handlers of the plugin.
+    fn register_commands(&self, registry: &mut CommandHandlerRegistry) {
+        let _ = registry;
+    }
+
+    /// Registers the query handlers of the plugin.
+    fn register_queries(&self, registry: &mut QueryHandlerRegistry) {
+        let _ = registry;
+    }
+}
+
+/// Registers the handlers of a plugin into running buses.
+///
+/// Handlers of the plugin replace the handlers already registered for the same message types.
+///
+/// # Arguments
+///
+/// * `plugin` - The plugin to install.
+/// * `command_bus` - The command bus to register the command handlers into.
+/// * `query_bus` - The query bus to register the query handlers into.
+#[cfg(feature = "std")]
+pub fn install(plugin: &dyn Plugin, command_bus: &CommandBus, query_bus: &QueryBus) {
+    let mut commands = CommandHandlerRegistry::new();
+    plugin.register_commands(&mut commands);
+    command_bus.merge(commands);
+
+    let mut queries = QueryHandlerRegistry::new();
+    plugin.register_queries(&mut queries);
+    query_bus.merge(queries);
+}
@@ -0,0 +1,210 @@
+//! The `policy` module provides row-level security for query results.
+//!
+//! In multi-tenant applications, every query must only return the rows the caller is allowed to see. Relying on
+//! each handler to filter its results is fragile: a single forgotten `WHERE` clause leaks the data of another
+//! tenant. A [RowLevelPolicy] is enforced by the [FilteredHandler], which removes the rows the current
+//! [CallerId] is not allowed to see from the output of the wrapped handler, before it is returned.
+//!
+//! - [RowLevelPolicy]: Decides which rows of a query output the caller is allowed to see.
+//! - [Rows]: A query output made of rows, which can be filtered.
+//! - [FilteredHandler]: A handler that enforces a [RowLevelPolicy] on the output of a query.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+
+use crate::async_trait;
+use crate::caller::CallerId;
+use crate::query::Query;
+use crate::query::QueryHandler;
+
+/// The `Rows` trait represents a query output made of rows, which can be filtered.
+///
+/// It is implemented for `Vec<T>`, `VecDeque<T>`, and `Option<T>`, whose rows are their elements.
+pub trait Rows {
+    /// The type of the rows.
+    type Row;
+
+    /// Retains only the rows for which `keep` returns `true`.
+    fn retain_rows(&mut self, keep: impl FnMut(&Self::Row) -> bool);
+}
+
+impl<T> Rows for Vec<T> {
+    type Row = T;
+
+    fn retain_rows(&mut self, keep: impl FnMut(&T) -> bool) {
+        self.retain(keep);
+    }
+}
+
+impl<T> Rows for VecDeque<T> {
+    type Row = T;
+
+    fn retain_rows(&mut self, keep: impl FnMut(&T) -> bool) {
+        self.retain(keep);
+    }
+}
+
+impl<T> Rows for Option<T> {
+    type Row = T;
+
+    fn retain_rows(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        if self.as_ref().is_some_and(|row| !keep(row)) {
+            *self = None;
+        }
+    }
+}
+
+/// The `RowLevelPolicy` trait decides which rows of a query output the caller is allowed to see.
+///
+/// The policy is called for every row of the output, with the [CallerId] of the current dispatch, if any.
+///
+/// # Example
+///
+/// ```
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct ListInvoicesQuery;
+/// #
+/// # impl Query for ListInvoicesQuery {
+/// #     type Output = Vec<Invoice>;
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::caller::CallerId;
+/// use discern::policy::RowLevelPolicy;
+///
+/// #[derive(Debug)]
+/// struct Invoice {
+///     tenant: String,
+///     amount: u64,
+/// }
+///
+/// struct TenantPolicy;
+///
+/// impl RowLevelPolicy<ListInvoicesQuery> for TenantPolicy {
+///     fn allows(&self, caller: Option<&CallerId>, invoice: &Invoice) -> bool {
+///         caller.is_some_and(|caller| caller.as_str() == invoice.tenant)
+///     }
+/// }
+/// ```
+pub trait RowLevelPolicy<Q: Query>: Send + Sync
+where
+    Q::Output: Rows,
+{
+    /// Returns whether the caller is allowed to see the given row.
+    fn allows(&self, caller: Option<&CallerId>, row: &<Q::Output as Rows>::Row) -> bool;
+}
+
+/// A handler that enforces a [RowLevelPolicy] on the output of a query.
+///
+/// Rows the current caller is not allowed to see are removed from the output of the wrapped handler. Dispatches
+/// that do not run within a [CallerId::scope] are passed `None`, so the policy decides whether anonymous callers
+/// can see anything.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::caller::CallerId;
+/// # use discern::policy::RowLevelPolicy;
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct ListInvoicesQuery;
+/// #
+/// # impl Query for ListInvoicesQuery {
+/// #     type Output = Vec<Invoice>;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct Invoice {
+/// #     tenant: String,
+/// #     amount: u64,
+/// # }
+/// #
+/// # struct TenantPolicy;
+/// #
+/// # impl RowLevelPolicy<ListInvoicesQuery> for TenantPolicy {
+/// #     fn allows(&self, caller: Option<&CallerId>, invoice: &Invoice) -> bool {
+/// #         caller.is_some_and(|caller| caller.as_str() == invoice.tenant)
+/// #     }
+/// # }
+/// use discern::async_trait;
+/// use discern::policy::FilteredHandler;
+/// use discern::query::QueryHandler;
+/// use discern::query_bus;
+///
+/// struct ListInvoicesQueryHandler;
+///
+/// #[async_trait]
+/// impl QueryHandler<ListInvoicesQuery> for ListInvoicesQueryHandler {
+///     async fn handle(&self, query: ListInvoicesQuery) -> Result<Vec<Invoice>, std::io::Error> {
+///         // Oops, the invoices of every tenant are returned.
+///         Ok(vec![
+///             Invoice { tenant: "acme".to_string(), amount: 100 },
+///             Invoice { tenant: "globex".to_string(), amount: 200 },
+///         ])
+///     }
+/// }
+///
+/// let query_bus = query_bus! {
+///     ListInvoicesQuery => FilteredHandler::new(ListInvoicesQueryHandler, TenantPolicy),
+/// };
+///
+/// let invoices = CallerId::new("acme").scope(query_bus.dispatch(ListInvoicesQuery)).await.unwrap();
+///
+/// assert_eq!(invoices.len(), 1);
+/// assert_eq!(invoices[0].amount, 100);
+///
+/// // Anonymous callers don't see any invoice.
+/// assert!(query_bus.dispatch(ListInvoicesQuery).await.unwrap().is_empty());
+/// # });
+/// ```
+pub struct FilteredHandler<H, P> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    policy: P,
+}
+
+/// The `FilteredHandler` implementation.
+impl<H, P> FilteredHandler<H, P> {
+    /// Creates a new `FilteredHandler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler whose output is filtered.
+    /// * `policy` - The policy deciding which rows the caller is allowed to see.
+    pub fn new(handler: H, policy: P) -> Self {
+        Self { handler, policy }
+    }
+}
+
+#[async_trait]
+impl<Q, H, P> QueryHandler<Q> for FilteredHandler<H, P>
+where
+    Q: Query,
+    Q::Output: Rows,
+    H: QueryHandler<Q>,
+    P: RowLevelPolicy<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        let mut output = self.handler.handle(query).await?;
+
+        let caller = CallerId::current();
+        output.retain_rows(|row| self.policy.allows(caller.as_ref(), row));
+
+        Ok(output)
+    }
+}
+
+/// Debug implementation for `FilteredHandler`
+impl<H, P> Debug for FilteredHandler<H, P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("FilteredHandler").finish()
+    }
+}
@@ -0,0 +1,286 @@
+//! The `pool` module provides a handler wrapper that manages a pool of resources.
+//!
+//! Some handlers need resources that cannot be shared between concurrent dispatches, e.g. non-clonable
+//! clients such as certain FFI bindings, which are often neither `Sync`, nor cheap to create. The [PooledHandler]
+//! keeps a pool of such resources, checks one out for every dispatch, passes it to the handler function, and
+//! checks it back in once the dispatch is complete.
+//!
+//! - [PooledHandler]: A handler that dispatches to a handler function, using a pool of resources.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use async_lock::Semaphore;
+use async_lock::SemaphoreGuard;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+use crate::runtime::BoxFuture;
+
+/// A handler that dispatches to a handler function, using a pool of resources.
+///
+/// Each dispatch checks out a resource from the pool, and passes it to the handler function, along with the
+/// message. The resource is checked back in once the dispatch is complete, or cancelled. When all resources are
+/// checked out, dispatches wait until one is checked back in.
+///
+/// Resources only need to be `Send`, since each of them is used by a single dispatch at a time.
+///
+/// `PooledHandler` implements [CommandHandler] for every command, and [QueryHandler] for every query, the
+/// handler function accepts.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct RenderReportCommand {
+/// #    report_id: u64,
+/// # }
+/// #
+/// # impl Command for RenderReportCommand {
+/// #   type Metadata = Vec<u8>;
+/// #   type Error = std::io::Error;
+/// # }
+/// use std::cell::Cell;
+///
+/// use discern::command_bus;
+/// use discern::pool::PooledHandler;
+///
+/// // A client that must not be used concurrently, and is therefore not `Sync`.
+/// struct Renderer {
+///     rendered: Cell<usize>,
+/// }
+///
+/// impl Renderer {
+///     fn connect() -> Self {
+///         Renderer { rendered: Cell::new(0) }
+///     }
+///
+///     fn render(&mut self, report_id: u64) -> Vec<u8> {
+///         self.rendered.set(self.rendered.get() + 1);
+///
+///         report_id.to_be_bytes().to_vec()
+///     }
+/// }
+///
+/// // Create up to 4 clients, lazily.
+/// let handler = PooledHandler::new(
+///     4,
+///     Renderer::connect,
+///     |renderer: &mut Renderer, command: RenderReportCommand| {
+///         Box::pin(async move { Ok(renderer.render(command.report_id)) })
+///     },
+/// );
+///
+/// let command_bus = command_bus! {
+///     RenderReportCommand => handler,
+/// };
+///
+/// let report = command_bus.dispatch(RenderReportCommand { report_id: 1 }).await;
+/// # assert_eq!(report.unwrap(), vec![0, 0, 0, 0, 0, 0, 0, 1]);
+/// # });
+/// ```
+pub struct PooledHandler<R, F> {
+    #[doc(hidden)]
+    idle: Mutex<Vec<R>>,
+    #[doc(hidden)]
+    permits: Semaphore,
+    #[doc(hidden)]
+    max_size: usize,
+    #[doc(hidden)]
+    factory: Option<Box<dyn Fn() -> R + Send + Sync>>,
+    #[doc(hidden)]
+    handler: F,
+}
+
+/// The `PooledHandler` implementation.
+impl<R: Send, F> PooledHandler<R, F> {
+    /// Creates a new `PooledHandler` that lazily creates up to `max_size` resources.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - The maximum number of resources.
+    /// * `factory` - A function creating a new resource.
+    /// * `handler` - The handler function, called with a checked out resource, and the message.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `max_size` is zero.
+    pub fn new<M, T>(
+        max_size: usize,
+        factory: impl Fn() -> R + Send + Sync + 'static,
+        handler: F,
+    ) -> Self
+    where
+        F: for<'a> Fn(&'a mut R, M) -> BoxFuture<'a, T> + Send + Sync,
+    {
+        assert!(max_size > 0, "the pool size must be greater than zero");
+
+        Self {
+            idle: Mutex::new(Vec::with_capacity(max_size)),
+            permits: Semaphore::new(max_size),
+            max_size,
+            factory: Some(Box::new(factory)),
+            handler,
+        }
+    }
+
+    /// Creates a new `PooledHandler` from a fixed set of resources.
+    ///
+    /// # Arguments
+    ///
+    /// * `resources` - The resources.
+    /// * `handler` - The handler function, called with a checked out resource, and the message.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `resources` is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::pool::PooledHandler;
+    ///
+    /// # struct Connection;
+    /// # #[derive(Debug)]
+    /// # struct PingCommand;
+    /// # impl discern::command::Command for PingCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// let handler = PooledHandler::from_resources(
+    ///     vec![Connection, Connection],
+    ///     |_connection: &mut Connection, _command: PingCommand| {
+    ///         Box::pin(async { Ok::<(), std::io::Error>(()) })
+    ///     },
+    /// );
+    ///
+    /// assert_eq!(handler.max_size(), 2);
+    /// assert_eq!(handler.idle(), 2);
+    /// ```
+    pub fn from_resources<M, T>(resources: Vec<R>, handler: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut R, M) -> BoxFuture<'a, T> + Send + Sync,
+    {
+        assert!(
+            !resources.is_empty(),
+            "the pool size must be greater than zero"
+        );
+
+        Self {
+            max_size: resources.len(),
+            permits: Semaphore::new(resources.len()),
+            idle: Mutex::new(resources),
+            factory: None,
+            handler,
+        }
+    }
+
+    /// Returns the maximum number of resources.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns the number of resources that are created, and currently checked in.
+    pub fn idle(&self) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Checks out a resource, waiting for one to be checked in if necessary.
+    async fn checkout(&self) -> Checkout<'_, R, F> {
+        let permit = self.permits.acquire().await;
+        let resource = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+
+        // Holding a permit guarantees that either an idle resource exists, or that fewer than
+        // `max_size` resources were created, in which case a factory is available.
+        let resource = resource.unwrap_or_else(|| (self.factory.as_ref().unwrap())());
+
+        Checkout {
+            pool: self,
+            resource: Some(resource),
+            _permit: permit,
+        }
+    }
+}
+
+#[async_trait]
+impl<C, R, F> CommandHandler<C> for PooledHandler<R, F>
+where
+    C: Command,
+    R: Send,
+    F: for<'a> Fn(&'a mut R, C) -> BoxFuture<'a, Result<C::Metadata, C::Error>> + Send + Sync,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        let mut checkout = self.checkout().await;
+
+        (self.handler)(checkout.resource(), command).await
+    }
+}
+
+#[async_trait]
+impl<Q, R, F> QueryHandler<Q> for PooledHandler<R, F>
+where
+    Q: Query,
+    R: Send,
+    F: for<'a> Fn(&'a mut R, Q) -> BoxFuture<'a, Result<Q::Output, Q::Error>> + Send + Sync,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        let mut checkout = self.checkout().await;
+
+        (self.handler)(checkout.resource(), query).await
+    }
+}
+
+/// Debug implementation for `PooledHandler`
+impl<R: Send, F> Debug for PooledHandler<R, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("PooledHandler")
+            .field("max_size", &self.max_size)
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+/// A resource checked out from a [PooledHandler].
+///
+/// The resource is checked back in when dropped, before the permit is released.
+#[doc(hidden)]
+struct Checkout<'a, R, F> {
+    pool: &'a PooledHandler<R, F>,
+    resource: Option<R>,
+    _permit: SemaphoreGuard<'a>,
+}
+
+impl<R, F> Checkout<'_, R, F> {
+    fn resource(&mut self) -> &mut R {
+        self.resource.as_mut().unwrap()
+    }
+}
+
+impl<R, F> Drop for Checkout<'_, R, F> {
+    fn drop(&mut self) {
+        if let Some(resource) = self.resource.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(resource);
+        }
+    }
+}
@@ -6,20 +6,32 @@
 //! The `QueryBus` is responsible for dispatching queries to their respective handlers. It utilizes the
 //! `QueryHandlerRegistry` from the [registry](crate::registry) module to manage and retrieve the appropriate handlers.
 //!
-//! - [Query]: Represents a query in the system.
+//! - [Query]: Represents a query in the system, and derives it when the `derive` feature is enabled.
 //! - [QueryHandler]: Trait for handling queries.
+//! - [QueryHandlerFn]: A query handler defined by a closure.
+//! - [BorrowedQueryHandler]: Trait for handling queries by reference.
 //! - [QueryBus]: Dispatches queries to the appropriate handlers.
 //!
 //! # See Also
 //!
 //! - [QueryHandlerRegistry]: Manages query handlers.
 
-use std::any::Any;
-use std::fmt::Debug;
-use std::sync::Arc;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::any::Any;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::future::Future;
+use core::pin::pin;
 
 use crate::async_trait;
+use crate::diagnostics::MissingHandler;
+use crate::error::DispatchError;
 use crate::registry::QueryHandlerRegistry;
+use crate::registry::SharedRegistry;
+use crate::stats::BusStats;
+use crate::stats::Counters;
 
 /// The `Query` trait represents a query that retrieves data from the system.
 ///
@@ -65,8 +77,36 @@ pub trait Query: Send + Sync + Any + Debug {
     ///
     /// This type must implement the `Debug`, `Send`, and `Sync` traits.
     type Error: Debug + Send + Sync;
+
+    /// Returns the name of the query, used to identify it in logs, metrics, and when routing by name.
+    ///
+    /// Defaults to the type name of the query, which changes whenever the type is moved, or renamed. Queries
+    /// implementing [MessageName](crate::message::MessageName) should return its name instead, which
+    /// `#[derive(Query)]` does.
+    fn name() -> &'static str
+    where
+        Self: Sized,
+    {
+        core::any::type_name::<Self>()
+    }
+
+    /// Returns the descriptor of the query, see [MessageDescriptor](crate::message::MessageDescriptor).
+    ///
+    /// Defaults to the name of the query, at version `1`, with an unknown schema hash. Queries implementing
+    /// [MessageName](crate::message::MessageName) should return
+    /// [MessageDescriptor::of](crate::message::MessageDescriptor::of) instead, which `#[derive(Query)]` does.
+    fn descriptor() -> crate::message::MessageDescriptor
+    where
+        Self: Sized,
+    {
+        crate::message::MessageDescriptor::new(Self::name(), crate::message::MessageKind::Query)
+    }
 }
 
+/// Derives the [Query] trait, using the `#[query(output = ..., error = ...)]` attribute.
+#[cfg(feature = "derive")]
+pub use discern_derive::Query;
+
 /// The `QueryHandler` trait represents a handler that processes a query.
 ///
 /// # Example
@@ -128,6 +168,177 @@ pub trait QueryHandler<Q: Query>: Send + Sync {
     async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error>;
 }
 
+/// A query handler defined by a closure.
+///
+/// The closure receives the query, and returns a future resolving to the result of the query. Closures can also be
+/// registered directly using the [query_bus](crate::query_bus) and [query_registry](crate::query_registry) macros.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUserQuery {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUserQuery {
+/// #   type Output = String;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::query::QueryBus;
+/// use discern::query::QueryHandlerFn;
+/// use discern::registry::QueryHandlerRegistry;
+///
+/// let mut registry = QueryHandlerRegistry::new();
+/// registry.register(QueryHandlerFn::new(|query: GetUserQuery| async move {
+///     Ok(format!("user-{}", query.user_id))
+/// }));
+///
+/// let query_bus = QueryBus::new(registry);
+///
+/// assert_eq!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap(), "user-1");
+/// # });
+/// ```
+pub struct QueryHandlerFn<F> {
+    #[doc(hidden)]
+    handler: F,
+}
+
+/// The `QueryHandlerFn` implementation.
+impl<F> QueryHandlerFn<F> {
+    /// Creates a new `QueryHandlerFn` from the given closure.
+    pub fn new<Q, Fut>(handler: F) -> Self
+    where
+        Q: Query,
+        F: Fn(Q) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<Q::Output, Q::Error>> + Send,
+    {
+        Self { handler }
+    }
+}
+
+#[async_trait]
+impl<Q, F, Fut> QueryHandler<Q> for QueryHandlerFn<F>
+where
+    Q: Query,
+    F: Fn(Q) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Q::Output, Q::Error>> + Send,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        (self.handler)(query).await
+    }
+}
+
+/// Debug implementation for `QueryHandlerFn`
+impl<F> Debug for QueryHandlerFn<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("QueryHandlerFn").finish()
+    }
+}
+
+/// The `BorrowedQueryHandler` trait represents a handler that processes a query by reference.
+///
+/// Borrowed handlers allow dispatching large queries using [QueryBus::dispatch_ref], without
+/// transferring ownership of the query to the bus, avoiding a copy on the hot path.
+///
+/// # Example
+///
+/// ```
+/// # use discern::query::Query;
+/// # use discern::async_trait;
+/// #
+/// # #[derive(Debug)]
+/// # struct CountMatchesQuery {
+/// #    haystack: Vec<u64>,
+/// #    needle: u64,
+/// # }
+/// #
+/// # impl Query for CountMatchesQuery {
+/// #   type Output = usize;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::query::BorrowedQueryHandler;
+///
+/// struct CountMatchesQueryHandler;
+///
+/// #[async_trait]
+/// impl BorrowedQueryHandler<CountMatchesQuery> for CountMatchesQueryHandler {
+///    async fn handle(&self, query: &CountMatchesQuery) -> Result<usize, std::io::Error> {
+///       Ok(query.haystack.iter().filter(|value| **value == query.needle).count())
+///   }
+/// }
+/// ```
+#[async_trait]
+pub trait BorrowedQueryHandler<Q: Query>: Send + Sync {
+    /// Handles the processing of a query by reference.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query to be processed.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, which includes the output data or an error.
+    async fn handle(&self, query: &Q) -> Result<Q::Output, Q::Error>;
+}
+
+/// The `SyncQueryHandler` trait represents a handler that processes a query synchronously.
+///
+/// Synchronous handlers are useful for simple handlers ( e.g. in-memory lookups, pure computation ) that
+/// do not need to await anything. They are registered using
+/// [QueryHandlerRegistry::register_sync], and are invoked directly by the `QueryBus`, without
+/// allocating a future for each dispatch.
+///
+/// Synchronous handlers don't implement [QueryHandler], as a blanket implementation would overlap with every
+/// other implementation of it, use the [SyncHandler](crate::sync::SyncHandler) adapter where an asynchronous handler
+/// is required.
+///
+/// # Example
+///
+/// ```
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUsernameQuery {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUsernameQuery {
+/// #   type Output = Option<String>;
+/// #   type Error = std::io::Error;
+/// # }
+/// use std::collections::HashMap;
+///
+/// use discern::query::SyncQueryHandler;
+///
+/// struct GetUsernameQueryHandler {
+///     usernames: HashMap<u64, String>,
+/// }
+///
+/// impl SyncQueryHandler<GetUsernameQuery> for GetUsernameQueryHandler {
+///     fn handle(&self, query: GetUsernameQuery) -> Result<Option<String>, std::io::Error> {
+///         Ok(self.usernames.get(&query.user_id).cloned())
+///     }
+/// }
+/// ```
+#[cfg(feature = "sync")]
+pub trait SyncQueryHandler<Q: Query>: Send + Sync {
+    /// Handles the processing of a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to be processed.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, which includes the output data or an error.
+    fn handle(&self, query: Q) -> Result<Q::Output, Q::Error>;
+}
+
 /// The `QueryBus` is responsible for dispatching queries to their respective handlers.
 ///
 /// Queries are dispatched through the `QueryBus` to the appropriate handler, which processes
@@ -199,9 +410,10 @@ pub trait QueryHandler<Q: Query>: Send + Sync {
 ///     },
 ///     Err(err) => {
 ///         # assert!(false);
-///         match err {
-///             GetUserError::UserNotFound => println!("User not found"),
-///             GetUserError::DatabaseError => println!("A database error occurred"),
+///         match err.into_handler_error() {
+///             Some(GetUserError::UserNotFound) => println!("User not found"),
+///             Some(GetUserError::DatabaseError) => println!("A database error occurred"),
+///             None => println!("The query was not handled"),
 ///         }
 ///     }
 /// }
@@ -210,7 +422,18 @@ pub trait QueryHandler<Q: Query>: Send + Sync {
 #[derive(Clone, Debug)]
 pub struct QueryBus {
     #[doc(hidden)]
-    registry: Arc<QueryHandlerRegistry>,
+    registry: Arc<SharedRegistry<QueryHandlerRegistry>>,
+    #[doc(hidden)]
+    counters: Arc<Counters>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    read_only: bool,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    permits: Option<Arc<async_lock::Semaphore>>,
+    #[cfg(feature = "std")]
+    #[doc(hidden)]
+    cache: Option<crate::cache::QueryCache>,
 }
 
 /// The `QueryBus` implementation.
@@ -237,23 +460,119 @@ impl QueryBus {
     /// ```
     pub fn new(registry: QueryHandlerRegistry) -> Self {
         Self {
-            registry: Arc::new(registry),
+            registry: Arc::new(SharedRegistry::new(registry)),
+            counters: Arc::new(Counters::default()),
+            #[cfg(feature = "std")]
+            read_only: false,
+            #[cfg(feature = "std")]
+            permits: None,
+            #[cfg(feature = "std")]
+            cache: None,
         }
     }
 
-    /// Dispatches a query to its respective handler.
+    /// Creates a new `QueryBus` instance, with the handlers registered in the inventory.
     ///
-    /// # Arguments
+    /// Handlers are registered in the inventory using the
+    /// [query_handler](crate::inventory::query_handler) attribute, see the [inventory](crate::inventory) module.
+    #[cfg(feature = "inventory")]
+    pub fn from_inventory() -> Self {
+        Self::new(QueryHandlerRegistry::from_inventory())
+    }
+
+    /// Enforces that queries dispatched through this `QueryBus` don't dispatch commands.
     ///
-    /// * `query` - The query to dispatch.
+    /// Queries must not mutate state. When enforcement is enabled, dispatching a command while a query
+    /// handler is being polled panics, which catches CQRS discipline breaches in tests rather than in code review.
     ///
-    /// # Returns
+    /// Only commands dispatched from within the query handler itself are detected, commands dispatched by tasks
+    /// spawned from the handler are not.
     ///
-    /// The result of the query handler, which includes the output data or an error.
+    /// # Example
+    ///
+    /// ```should_panic
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// # use discern::command::CommandHandler;
+    /// # use discern::query::Query;
+    /// # use discern::async_trait;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct RecordVisitCommand;
+    /// #
+    /// # impl Command for RecordVisitCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # struct RecordVisitCommandHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl CommandHandler<RecordVisitCommand> for RecordVisitCommandHandler {
+    /// #   async fn handle(&self, _command: RecordVisitCommand) -> Result<(), std::io::Error> {
+    /// #     Ok(())
+    /// #   }
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct GetUserQuery;
+    /// #
+    /// # impl Query for GetUserQuery {
+    /// #   type Output = String;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::command::CommandBus;
+    /// use discern::command_bus;
+    /// use discern::query::QueryHandler;
+    /// use discern::query_bus;
+    ///
+    /// struct GetUserQueryHandler {
+    ///     command_bus: CommandBus,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+    ///     async fn handle(&self, _query: GetUserQuery) -> Result<String, std::io::Error> {
+    ///         // Oops, this query mutates state!
+    ///         let _ = self.command_bus.dispatch(RecordVisitCommand).await;
+    ///
+    ///         Ok("alice".to_string())
+    ///     }
+    /// }
+    ///
+    /// let command_bus = command_bus! {
+    ///     RecordVisitCommand => RecordVisitCommandHandler,
+    /// };
+    ///
+    /// let query_bus = query_bus! {
+    ///     GetUserQuery => GetUserQueryHandler { command_bus },
+    /// }
+    /// .enforce_read_only();
+    ///
+    /// // Panics, since the query handler dispatches a command.
+    /// let _ = query_bus.dispatch(GetUserQuery).await;
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn enforce_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Limits the number of queries handled concurrently by this `QueryBus`.
+    ///
+    /// Once `max_in_flight` queries are being handled, further dispatches wait for one of them to complete
+    /// before their handler is called, which protects shared resources, e.g. a database connection pool, from
+    /// load spikes. Clones of this `QueryBus` made afterwards share the limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_in_flight` - The maximum number of queries handled concurrently.
     ///
     /// # Panics
     ///
-    /// This method will panic if the query handler is not found.
+    /// This method will panic if `max_in_flight` is zero.
     ///
     /// # Example
     ///
@@ -261,77 +580,850 @@ impl QueryBus {
     /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
     /// # rt.block_on(async {
     /// # use discern::query::Query;
-    /// # use discern::async_trait;
-    /// # use discern::query::QueryHandler;
     /// #
     /// # #[derive(Debug)]
-    /// # struct User {
-    /// #     id: u64,
-    /// #     username: String,
-    /// #     email: String,
+    /// # struct GetUserQuery {
+    /// #    user_id: u64,
     /// # }
     /// #
-    /// # #[derive(Debug)]
-    /// # enum GetUserError {
-    /// #     UserNotFound,
-    /// #     DatabaseError,
+    /// # impl Query for GetUserQuery {
+    /// #   type Output = u64;
+    /// #   type Error = std::io::Error;
     /// # }
+    /// use discern::query::QueryBus;
+    /// use discern::query_registry;
+    ///
+    /// let registry = query_registry! {
+    ///     GetUserQuery => |query| async move { Ok(query.user_id) },
+    /// };
+    /// let query_bus = QueryBus::new(registry).with_max_in_flight(16);
+    ///
+    /// assert_eq!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap(), 1);
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        assert!(
+            max_in_flight > 0,
+            "the maximum number of queries in flight must be greater than zero"
+        );
+
+        self.permits = Some(Arc::new(async_lock::Semaphore::new(max_in_flight)));
+        self
+    }
+
+    /// Serves the results of the query types cached by the given cache from it, see
+    /// [QueryCache](crate::cache::QueryCache).
+    ///
+    /// Cached results are served without invoking the handler, and therefore, without waiting for the rate limit,
+    /// or the maximum number of queries in flight. Queries dispatched by reference, using [QueryBus::dispatch_ref],
+    /// share the same cached results.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache of the query results.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use std::time::Duration;
+    /// # use discern::cache::CacheKey;
+    /// # use discern::cache::CachedQuery;
+    /// # use discern::query::Query;
     /// #
     /// # #[derive(Debug)]
     /// # struct GetUserQuery {
-    /// #    user_id: u64,
+    /// #     user_id: u64,
     /// # }
     /// #
     /// # impl Query for GetUserQuery {
-    /// #   type Output = User;
-    /// #   type Error = GetUserError;
+    /// #     type Output = String;
+    /// #     type Error = std::io::Error;
     /// # }
     /// #
-    /// # struct GetUserQueryHandler;
+    /// # impl CacheKey for GetUserQuery {
+    /// #     type Key = u64;
     /// #
-    /// # #[async_trait]
-    /// # impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
-    /// #    async fn handle(&self, query: GetUserQuery) -> Result<User, GetUserError> {
-    /// #       Ok(User {
-    /// #           id: query.user_id,
-    /// #           username: "Alice".to_string(),
-    /// #           email: "alice@example.com".to_string(),
-    /// #       })
-    /// #   }
+    /// #     fn cache_key(&self) -> u64 {
+    /// #         self.user_id
+    /// #     }
     /// # }
-    /// use discern::query::QueryBus;
-    /// use discern::query_registry;
+    /// #
+    /// # impl CachedQuery for GetUserQuery {
+    /// #     const TTL: Duration = Duration::from_secs(60);
+    /// # }
+    /// use std::sync::atomic::AtomicUsize;
+    /// use std::sync::atomic::Ordering;
+    /// use std::sync::Arc;
     ///
-    /// let registry = query_registry! {
-    ///    GetUserQuery => GetUserQueryHandler { /* ... */ },
-    /// };
+    /// use discern::async_trait;
+    /// use discern::cache::QueryCache;
+    /// use discern::query::BorrowedQueryHandler;
+    /// use discern::query::QueryBus;
+    /// use discern::registry::QueryHandlerRegistry;
     ///
-    /// let query_bus = QueryBus::new(registry);
+    /// struct GetUserQueryHandler {
+    ///     lookups: Arc<AtomicUsize>,
+    /// }
     ///
-    /// let result = query_bus.dispatch(GetUserQuery { user_id: 1 }).await;
+    /// #[async_trait]
+    /// impl BorrowedQueryHandler<GetUserQuery> for GetUserQueryHandler {
+    ///     async fn handle(&self, query: &GetUserQuery) -> Result<String, std::io::Error> {
+    ///         self.lookups.fetch_add(1, Ordering::SeqCst);
     ///
-    /// match result {
-    ///     Ok(user) => {
-    ///         # assert_eq!(user.username, "Alice".to_string());
-    ///         println!("User name: {}", user.username);
-    ///     },
-    ///     Err(err) => {
-    ///         # assert!(false);
-    ///         match err {
-    ///             GetUserError::UserNotFound => println!("User not found"),
-    ///             GetUserError::DatabaseError => println!("A database error occurred"),
-    ///         }
+    ///         Ok(format!("user #{}", query.user_id))
     ///     }
     /// }
+    ///
+    /// let lookups = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let mut registry = QueryHandlerRegistry::new();
+    /// registry.register_borrowed(GetUserQueryHandler { lookups: lookups.clone() });
+    ///
+    /// let query_bus = QueryBus::new(registry).with_cache(QueryCache::new().cache::<GetUserQuery>());
+    ///
+    /// let query = GetUserQuery { user_id: 1 };
+    ///
+    /// assert_eq!(query_bus.dispatch_ref(&query).await.unwrap(), "user #1");
+    /// assert_eq!(query_bus.dispatch_ref(&query).await.unwrap(), "user #1");
+    /// assert_eq!(query_bus.dispatch(query).await.unwrap(), "user #1");
+    ///
+    /// // The later dispatches were served from the cache.
+    /// assert_eq!(lookups.load(Ordering::SeqCst), 1);
     /// # });
     /// ```
-    pub async fn dispatch<Q: Query>(&self, query: Q) -> Result<Q::Output, Q::Error> {
-        match self.registry.get_handler::<Q>() {
-            Some(handler) => handler.handle(query).await,
+    #[cfg(feature = "std")]
+    pub fn with_cache(mut self, cache: crate::cache::QueryCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Looks up the cached result of the given query, if the `QueryBus` has a cache.
+    #[cfg(feature = "std")]
+    fn lookup<Q: Query>(&self, query: &Q) -> Option<crate::cache::Lookup<Q>> {
+        self.cache.as_ref().and_then(|cache| cache.lookup(query))
+    }
+
+    /// Returns the name of `Q` if read-only enforcement is enabled.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn read_only_scope<Q: Query>(&self) -> Option<&'static str> {
+        self.read_only.then(Q::name)
+    }
+
+    /// Returns the name of `Q` if read-only enforcement is enabled, which it never is without `std`.
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn read_only_scope<Q: Query>(&self) -> Option<&'static str> {
+        let _ = Q::name;
+
+        None
+    }
+
+    /// Waits for a permit to handle a query, if the number of queries in flight is limited.
+    #[cfg(feature = "std")]
+    async fn permit(&self) -> Option<async_lock::SemaphoreGuardArc> {
+        match &self.permits {
+            Some(permits) => Some(permits.acquire_arc().await),
+            None => None,
+        }
+    }
+
+    /// Returns a snapshot of the counters maintained by the `QueryBus`.
+    ///
+    /// Clones of a `QueryBus` share the same counters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::query::QueryBus;
+    /// use discern::registry::QueryHandlerRegistry;
+    ///
+    /// let query_bus = QueryBus::new(QueryHandlerRegistry::new());
+    ///
+    /// assert_eq!(query_bus.stats().dispatched, 0);
+    /// ```
+    pub fn stats(&self) -> BusStats {
+        self.counters.snapshot()
+    }
+
+    /// Returns the names of the queries that have a registered handler, in no particular order, see [Query::name].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::query::QueryBus;
+    /// use discern::registry::QueryHandlerRegistry;
+    ///
+    /// let query_bus = QueryBus::new(QueryHandlerRegistry::new());
+    ///
+    /// assert!(query_bus.handlers().is_empty());
+    /// ```
+    pub fn handlers(&self) -> alloc::vec::Vec<&'static str> {
+        self.registry.with(|registry| registry.names().collect())
+    }
+
+    /// Returns the descriptors of the queries that have a registered handler, in no particular order, see
+    /// [MessageDescriptor](crate::message::MessageDescriptor).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::query::QueryBus;
+    /// use discern::registry::QueryHandlerRegistry;
+    ///
+    /// let query_bus = QueryBus::new(QueryHandlerRegistry::new());
+    ///
+    /// assert!(query_bus.descriptors().is_empty());
+    /// ```
+    pub fn descriptors(&self) -> alloc::vec::Vec<crate::message::MessageDescriptor> {
+        self.registry
+            .with(|registry| registry.descriptors().collect())
+    }
+
+    /// Returns diagnostics explaining why the query type `Q` cannot be dispatched, if no handler is registered for it.
+    ///
+    /// The diagnostics list the queries with a registered handler, and suggest the ones whose name is close to
+    /// the name of `Q`, along with where they were registered, see [MissingHandler].
+    pub fn missing_handler<Q: Query>(&self) -> Option<MissingHandler> {
+        self.registry.with(|registry| {
+            registry
+                .entry::<Q>()
+                .is_none()
+                .then(|| MissingHandler::new(Q::name(), registry.registrations()))
+        })
+    }
+
+    /// Returns a weak reference to this `QueryBus`, which does not keep its registry alive.
+    ///
+    /// This is used by handlers that control the bus they are registered in.
+    #[cfg(feature = "admin")]
+    pub(crate) fn downgrade(&self) -> WeakQueryBus {
+        WeakQueryBus {
+            registry: Arc::downgrade(&self.registry),
+            counters: Arc::downgrade(&self.counters),
+            read_only: self.read_only,
+            permits: self.permits.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+
+    /// Registers a query handler for a specific query type, at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to be registered for the query type `Q`.
+    ///
+    /// The registry is replaced using copy-on-write, which keeps dispatching lock-free. Since the whole registry
+    /// is copied, this is intended for occasional registrations, not for the hot path.
+    ///
+    /// The handler is visible to all clones of this `QueryBus`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::query::Query;
+    /// # use discern::async_trait;
+    /// # use discern::query::QueryHandler;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct GetUserQuery {
+    /// #     user_id: u64,
+    /// # }
+    /// #
+    /// # impl Query for GetUserQuery {
+    /// #     type Output = String;
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # struct GetUserQueryHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+    /// #     async fn handle(&self, query: GetUserQuery) -> Result<String, std::io::Error> {
+    /// #         Ok("Alice".to_string())
+    /// #     }
+    /// # }
+    /// use discern::query_bus;
+    ///
+    /// let query_bus = query_bus! {};
+    ///
+    /// query_bus.register(GetUserQueryHandler { /* ... */ });
+    ///
+    /// assert_eq!(query_bus.dispatch(GetUserQuery { user_id: 1 }).await.unwrap(), "Alice");
+    /// # });
+    /// ```
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub fn register<Q: Query>(&self, handler: impl QueryHandler<Q> + 'static) {
+        let mut registry = QueryHandlerRegistry::new();
+        registry.register(handler);
+
+        self.merge(registry);
+    }
+
+    /// Merges the handlers of a registry into the registry of this `QueryBus`, at runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry containing the handlers to add, or replace.
+    ///
+    /// See [QueryBus::register] for details.
+    #[cfg(feature = "std")]
+    pub fn merge(&self, registry: QueryHandlerRegistry) {
+        self.registry.update(|current| {
+            let mut updated = current.clone();
+            updated.merge(registry.clone());
+
+            updated
+        });
+    }
+
+    /// Atomically replaces the registry of this `QueryBus`, at runtime.
+    ///
+    /// Unlike [QueryBus::merge], handlers that are not part of the given registry are removed. Dispatches that
+    /// already started complete using the previous handlers, while later ones use the new handlers.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry replacing the current one.
+    ///
+    /// # Returns
+    ///
+    /// The previous registry, which can be used to roll back the replacement.
+    ///
+    /// See [Standby](crate::standby::Standby) for verifying a registry before switching to it.
+    #[cfg(feature = "std")]
+    pub fn replace(&self, registry: QueryHandlerRegistry) -> QueryHandlerRegistry {
+        self.registry.replace(registry)
+    }
+
+    /// Dispatches a query to its respective handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, which includes the output data or an error, or a [DispatchError] if the
+    /// rate limit of the query type is exceeded.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the query handler is not found, since it is a programming error. Use
+    /// [QueryBus::try_dispatch] to handle this case gracefully.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::query::Query;
+    /// # use discern::async_trait;
+    /// # use discern::query::QueryHandler;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct User {
+    /// #     id: u64,
+    /// #     username: String,
+    /// #     email: String,
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # enum GetUserError {
+    /// #     UserNotFound,
+    /// #     DatabaseError,
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct GetUserQuery {
+    /// #    user_id: u64,
+    /// # }
+    /// #
+    /// # impl Query for GetUserQuery {
+    /// #   type Output = User;
+    /// #   type Error = GetUserError;
+    /// # }
+    /// #
+    /// # struct GetUserQueryHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl QueryHandler<GetUserQuery> for GetUserQueryHandler {
+    /// #    async fn handle(&self, query: GetUserQuery) -> Result<User, GetUserError> {
+    /// #       Ok(User {
+    /// #           id: query.user_id,
+    /// #           username: "Alice".to_string(),
+    /// #           email: "alice@example.com".to_string(),
+    /// #       })
+    /// #   }
+    /// # }
+    /// use discern::query::QueryBus;
+    /// use discern::query_registry;
+    ///
+    /// let registry = query_registry! {
+    ///    GetUserQuery => GetUserQueryHandler { /* ... */ },
+    /// };
+    ///
+    /// let query_bus = QueryBus::new(registry);
+    ///
+    /// let result = query_bus.dispatch(GetUserQuery { user_id: 1 }).await;
+    ///
+    /// match result {
+    ///     Ok(user) => {
+    ///         # assert_eq!(user.username, "Alice".to_string());
+    ///         println!("User name: {}", user.username);
+    ///     },
+    ///     Err(err) => {
+    ///         # assert!(false);
+    ///         match err.into_handler_error() {
+    ///             Some(GetUserError::UserNotFound) => println!("User not found"),
+    ///             Some(GetUserError::DatabaseError) => println!("A database error occurred"),
+    ///             None => println!("The query was not handled"),
+    ///         }
+    ///     }
+    /// }
+    /// # });
+    /// ```
+    pub async fn dispatch<Q: Query>(&self, query: Q) -> Result<Q::Output, DispatchError<Q::Error>> {
+        let result = self.try_dispatch(query).await;
+        if let Err(DispatchError::HandlerNotFound) = result {
+            match self.missing_handler::<Q>() {
+                Some(missing) => panic!("No handler registered for query: {}", missing),
+                None => panic!("No handler registered for query: {:?}", Q::name()),
+            }
+        }
+
+        result
+    }
+
+    /// Dispatches a query whose result may be absent, requiring it to be present, see
+    /// [MaybeQuery](crate::maybe::MaybeQuery).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query, or [RequiredError::NotFound](crate::maybe::RequiredError::NotFound) if it is
+    /// absent.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic under the same conditions as [QueryBus::dispatch].
+    pub async fn dispatch_required<Q: crate::maybe::MaybeQuery>(
+        &self,
+        query: Q,
+    ) -> Result<Q::Item, crate::maybe::RequiredError<DispatchError<Q::Error>>> {
+        match self.dispatch(query).await {
+            Ok(Some(item)) => Ok(item),
+            Ok(None) => Err(crate::maybe::RequiredError::NotFound),
+            Err(error) => Err(crate::maybe::RequiredError::Query(error)),
+        }
+    }
+
+    /// Dispatches a query to its respective handler, without panicking when the query can't be dispatched.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, or a [DispatchError] if the query was rejected by the bus.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::query::Query;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct GetUserQuery {
+    /// #    user_id: u64,
+    /// # }
+    /// #
+    /// # impl Query for GetUserQuery {
+    /// #   type Output = String;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::error::DispatchError;
+    /// use discern::query_bus;
+    ///
+    /// let query_bus = query_bus! {};
+    ///
+    /// let result = query_bus.try_dispatch(GetUserQuery { user_id: 1 }).await;
+    ///
+    /// assert!(matches!(result, Err(DispatchError::HandlerNotFound)));
+    /// # });
+    /// ```
+    pub async fn try_dispatch<Q: Query>(
+        &self,
+        query: Q,
+    ) -> Result<Q::Output, DispatchError<Q::Error>> {
+        let dispatch = self.route(query);
+        #[cfg(feature = "tracing")]
+        let dispatch = crate::instrument::dispatch("query", Q::name(), dispatch);
+
+        dispatch.await
+    }
+
+    /// Looks up the handler of a query, and calls it in a read-only scope.
+    async fn route<Q: Query>(&self, query: Q) -> Result<Q::Output, DispatchError<Q::Error>> {
+        let Some(handler) = self.registry.with(|registry| registry.entry::<Q>()) else {
+            return Err(DispatchError::HandlerNotFound);
+        };
+
+        #[cfg(feature = "std")]
+        let miss = match self.lookup(&query) {
+            Some(crate::cache::Lookup::Hit(output)) => {
+                self.counters.start().finish::<_, Q::Error>(&Ok(()));
+
+                return Ok(output);
+            }
+            Some(crate::cache::Lookup::Miss(miss)) => Some(miss),
+            None => None,
+        };
+
+        #[cfg(feature = "std")]
+        if let Some(limiter) = self.registry.with(|registry| registry.limiter::<Q>()) {
+            if !limiter.acquire().await {
+                return Err(DispatchError::RateLimited);
+            }
+        }
+
+        #[cfg(feature = "std")]
+        let _permit = self.permit().await;
+
+        let in_flight = self.counters.start();
+        let future = pin!(handler.handle(query));
+        let result = scoped(future, self.read_only_scope::<Q>()).await;
+        in_flight.finish(&result);
+
+        #[cfg(feature = "std")]
+        if let (Some(miss), Ok(output)) = (miss, &result) {
+            miss(output);
+        }
+
+        result.map_err(DispatchError::Handler)
+    }
+
+    /// Dispatches a query by reference to its respective borrowed handler.
+    ///
+    /// Unlike [QueryBus::dispatch], this method does not take ownership of the query, which avoids
+    /// copying large queries that are still needed by the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, which includes the output data or an error, or a [DispatchError] if the
+    /// rate limit of the query type is exceeded, or if its handler takes the query by value, see
+    /// [DispatchError::NotBorrowable].
+    ///
+    /// The results of query types cached by the [QueryCache](crate::cache::QueryCache) of the bus are served from
+    /// it, and recorded into it, the same way as with [QueryBus::dispatch].
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if no query handler is found. Use [QueryBus::try_dispatch_ref] to handle this case
+    /// gracefully.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::query::Query;
+    /// # use discern::async_trait;
+    /// # use discern::query::BorrowedQueryHandler;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct CountMatchesQuery {
+    /// #    haystack: Vec<u64>,
+    /// #    needle: u64,
+    /// # }
+    /// #
+    /// # impl Query for CountMatchesQuery {
+    /// #   type Output = usize;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # struct CountMatchesQueryHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl BorrowedQueryHandler<CountMatchesQuery> for CountMatchesQueryHandler {
+    /// #    async fn handle(&self, query: &CountMatchesQuery) -> Result<usize, std::io::Error> {
+    /// #       Ok(query.haystack.iter().filter(|value| **value == query.needle).count())
+    /// #   }
+    /// # }
+    /// use discern::query::QueryBus;
+    /// use discern::registry::QueryHandlerRegistry;
+    ///
+    /// let mut registry = QueryHandlerRegistry::new();
+    /// registry.register_borrowed(CountMatchesQueryHandler);
+    ///
+    /// let query_bus = QueryBus::new(registry);
+    ///
+    /// let mut query = CountMatchesQuery {
+    ///     haystack: vec![1, 2, 3, 2, 1],
+    ///     needle: 1,
+    /// };
+    ///
+    /// assert_eq!(query_bus.dispatch_ref(&query).await.unwrap(), 2);
+    ///
+    /// query.needle = 2;
+    ///
+    /// assert_eq!(query_bus.dispatch_ref(&query).await.unwrap(), 2);
+    /// assert_eq!(query_bus.dispatch(query).await.unwrap(), 2);
+    /// # });
+    /// ```
+    pub async fn dispatch_ref<Q: Query>(
+        &self,
+        query: &Q,
+    ) -> Result<Q::Output, DispatchError<Q::Error>> {
+        let result = self.try_dispatch_ref(query).await;
+        if let Err(DispatchError::HandlerNotFound) = result {
+            panic!("No handler registered for query: {:?}", Q::name());
+        }
+
+        result
+    }
+
+    /// Dispatches a query by reference to its respective borrowed handler, without panicking when no handler is
+    /// registered for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A reference to the query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, or a [DispatchError] if the query was rejected by the bus.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::query::Query;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct CountMatchesQuery {
+    /// #    haystack: Vec<u64>,
+    /// #    needle: u64,
+    /// # }
+    /// #
+    /// # impl Query for CountMatchesQuery {
+    /// #   type Output = usize;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::error::DispatchError;
+    /// use discern::query_bus;
+    ///
+    /// let query = CountMatchesQuery { haystack: vec![1, 2, 3], needle: 1 };
+    ///
+    /// let query_bus = query_bus! {};
+    /// let result = query_bus.try_dispatch_ref(&query).await;
+    ///
+    /// assert!(matches!(result, Err(DispatchError::HandlerNotFound)));
+    ///
+    /// // The handler takes the query by value, so the query cannot be dispatched by reference.
+    /// let query_bus = query_bus! {
+    ///     CountMatchesQuery => |query: CountMatchesQuery| async move {
+    ///         Ok(query.haystack.iter().filter(|value| **value == query.needle).count())
+    ///     },
+    /// };
+    /// let result = query_bus.try_dispatch_ref(&query).await;
+    ///
+    /// assert!(matches!(result, Err(DispatchError::NotBorrowable)));
+    /// # });
+    /// ```
+    pub async fn try_dispatch_ref<Q: Query>(
+        &self,
+        query: &Q,
+    ) -> Result<Q::Output, DispatchError<Q::Error>> {
+        let dispatch = self.route_ref(query);
+        #[cfg(feature = "tracing")]
+        let dispatch = crate::instrument::dispatch("query", Q::name(), dispatch);
+
+        dispatch.await
+    }
+
+    /// Looks up the borrowed handler of a query, and calls it in a read-only scope.
+    async fn route_ref<Q: Query>(&self, query: &Q) -> Result<Q::Output, DispatchError<Q::Error>> {
+        let Some(handler) = self.registry.with(|registry| registry.entry::<Q>()) else {
+            return Err(DispatchError::HandlerNotFound);
+        };
+
+        let Some(future) = handler.handle_borrowed(query) else {
+            return Err(DispatchError::NotBorrowable);
+        };
+
+        #[cfg(feature = "std")]
+        let miss = match self.lookup(query) {
+            Some(crate::cache::Lookup::Hit(output)) => {
+                self.counters.start().finish::<_, Q::Error>(&Ok(()));
+
+                return Ok(output);
+            }
+            Some(crate::cache::Lookup::Miss(miss)) => Some(miss),
+            None => None,
+        };
+
+        #[cfg(feature = "std")]
+        if let Some(limiter) = self.registry.with(|registry| registry.limiter::<Q>()) {
+            if !limiter.acquire().await {
+                return Err(DispatchError::RateLimited);
+            }
+        }
+
+        #[cfg(feature = "std")]
+        let _permit = self.permit().await;
+
+        let in_flight = self.counters.start();
+        let result = scoped(future, self.read_only_scope::<Q>()).await;
+        in_flight.finish(&result);
+
+        #[cfg(feature = "std")]
+        if let (Some(miss), Ok(output)) = (miss, &result) {
+            miss(output);
+        }
+
+        result.map_err(DispatchError::Handler)
+    }
+}
+
+/// Marks the polling of a query handler future as read-only, see [QueryBus::enforce_read_only].
+#[inline]
+fn scoped<F: Future + Unpin>(
+    future: F,
+    query: Option<&'static str>,
+) -> impl Future<Output = F::Output> {
+    #[cfg(feature = "std")]
+    {
+        crate::scope::ReadOnly::new(future, query)
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = query;
+
+        future
+    }
+}
+
+/// A weak reference to a [QueryBus], created using `QueryBus::downgrade`.
+#[cfg(feature = "admin")]
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+pub(crate) struct WeakQueryBus {
+    registry: alloc::sync::Weak<SharedRegistry<QueryHandlerRegistry>>,
+    counters: alloc::sync::Weak<Counters>,
+    read_only: bool,
+    permits: Option<Arc<async_lock::Semaphore>>,
+    cache: Option<crate::cache::QueryCache>,
+}
+
+#[cfg(feature = "admin")]
+impl WeakQueryBus {
+    /// Returns the referenced `QueryBus`, or `None` if it was dropped.
+    pub(crate) fn upgrade(&self) -> Option<QueryBus> {
+        Some(QueryBus {
+            registry: self.registry.upgrade()?,
+            counters: self.counters.upgrade()?,
+            read_only: self.read_only,
+            permits: self.permits.clone(),
+            cache: self.cache.clone(),
+        })
+    }
+}
+
+/// The `SyncQueryBus` is a simplified, synchronous variant of the `QueryBus`.
+///
+/// It only dispatches queries to handlers registered using
+/// [QueryHandlerRegistry::register_sync], and does not require an async runtime,
+/// which makes it suitable for `no_std` environments.
+///
+/// # Example
+///
+/// ```
+/// # use discern::query::Query;
+/// # use discern::query::SyncQueryHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct SquareQuery(u64);
+/// #
+/// # impl Query for SquareQuery {
+/// #   type Output = u64;
+/// #   type Error = ();
+/// # }
+/// #
+/// # struct SquareQueryHandler;
+/// #
+/// # impl SyncQueryHandler<SquareQuery> for SquareQueryHandler {
+/// #   fn handle(&self, query: SquareQuery) -> Result<u64, ()> {
+/// #     Ok(query.0 * query.0)
+/// #   }
+/// # }
+/// use discern::query::SyncQueryBus;
+/// use discern::registry::QueryHandlerRegistry;
+///
+/// let mut registry = QueryHandlerRegistry::new();
+/// registry.register_sync(SquareQueryHandler);
+///
+/// let query_bus = SyncQueryBus::new(registry);
+///
+/// assert_eq!(query_bus.dispatch(SquareQuery(12)), Ok(144));
+/// ```
+#[cfg(feature = "sync")]
+#[derive(Clone, Debug)]
+pub struct SyncQueryBus {
+    #[doc(hidden)]
+    registry: Arc<QueryHandlerRegistry>,
+}
+
+/// The `SyncQueryBus` implementation.
+#[cfg(feature = "sync")]
+impl SyncQueryBus {
+    /// Creates a new `SyncQueryBus` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The query handler registry.
+    pub fn new(registry: QueryHandlerRegistry) -> Self {
+        Self {
+            registry: Arc::new(registry),
+        }
+    }
+
+    /// Dispatches a query to its respective synchronous handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if no synchronous query handler is found.
+    pub fn dispatch<Q: Query>(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        match self.registry.handle_sync(query) {
+            Some(result) => result,
             None => {
                 panic!(
-                    "No handler registered for query: {:?}",
-                    std::any::type_name::<Q>()
+                    "No synchronous handler registered for query: {:?}",
+                    Q::name()
                 );
             }
         }
@@ -0,0 +1,321 @@
+//! The `ratelimit` module limits the rate at which messages of a given type are handled.
+//!
+//! Handlers calling third-party APIs, e.g. sending emails, or charging cards, must stay within the quotas of these
+//! APIs, regardless of how many requests the application receives. A [RateLimit] set on a message type using
+//! [CommandHandlerRegistry::rate_limit](crate::registry::CommandHandlerRegistry::rate_limit), or
+//! [QueryHandlerRegistry::rate_limit](crate::registry::QueryHandlerRegistry::rate_limit), is enforced by the bus
+//! using a token bucket: messages exceeding it fail with
+//! [DispatchError::RateLimited](crate::error::DispatchError::RateLimited), or, when the limit is
+//! [queueing](RateLimit::queueing), wait until they are allowed, as long as the wait is not too long.
+//!
+//! - [RateLimit]: The rate at which messages of a given type are handled.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::runtime::Runtime;
+
+/// The rate at which messages of a given type are handled.
+///
+/// A limit of `permits` per `period` allows up to `burst` messages at once, `permits` by default, and then one
+/// message every `period / permits`.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct SendEmailCommand {
+/// #     to: String,
+/// # }
+/// #
+/// # impl Command for SendEmailCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::command::CommandBus;
+/// use discern::command_registry;
+/// use discern::error::DispatchError;
+/// use discern::ratelimit::RateLimit;
+///
+/// let mut registry = command_registry! {
+///     SendEmailCommand => |_command| async move { Ok(()) },
+/// };
+/// registry.rate_limit::<SendEmailCommand>(RateLimit::per_second(2));
+///
+/// let command_bus = CommandBus::new(registry);
+///
+/// for _ in 0..2 {
+///     let command = SendEmailCommand { to: "alice@localhost".to_string() };
+///     assert!(command_bus.try_dispatch(command).await.is_ok());
+/// }
+///
+/// let command = SendEmailCommand { to: "alice@localhost".to_string() };
+/// let result = command_bus.try_dispatch(command).await;
+/// assert!(matches!(result, Err(DispatchError::RateLimited)));
+/// # });
+/// ```
+pub struct RateLimit {
+    #[doc(hidden)]
+    permits: u32,
+    #[doc(hidden)]
+    period: Duration,
+    #[doc(hidden)]
+    burst: u32,
+    #[doc(hidden)]
+    runtime: Option<Box<dyn Runtime>>,
+    #[doc(hidden)]
+    max_wait: Duration,
+}
+
+/// The `RateLimit` implementation.
+impl RateLimit {
+    /// Creates a new `RateLimit`, allowing the given number of messages per period.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `permits` is zero, or if `period` is zero.
+    pub fn new(permits: u32, period: Duration) -> Self {
+        assert!(permits > 0, "the permits must be greater than zero");
+        assert!(!period.is_zero(), "the period must be greater than zero");
+
+        Self {
+            permits,
+            period,
+            burst: permits,
+            runtime: None,
+            max_wait: period,
+        }
+    }
+
+    /// Creates a new `RateLimit`, allowing the given number of messages per second.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `permits` is zero.
+    pub fn per_second(permits: u32) -> Self {
+        Self::new(permits, Duration::from_secs(1))
+    }
+
+    /// Sets the number of messages allowed at once, after a period of inactivity.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `burst` is zero.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        assert!(burst > 0, "the burst must be greater than zero");
+
+        self.burst = burst;
+        self
+    }
+
+    /// Makes messages exceeding the limit wait until they are allowed, instead of failing.
+    ///
+    /// Waiting messages are allowed in the order they were dispatched. Messages that would wait longer than the
+    /// maximum wait, one period by default, still fail, so that the queue doesn't grow without bounds, see
+    /// [RateLimit::with_max_wait]. A message whose dispatch is cancelled while waiting gives its turn back to the
+    /// messages dispatched after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `runtime` - The runtime used to wait.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct SendEmailCommand;
+    /// #
+    /// # impl Command for SendEmailCommand {
+    /// #     type Metadata = ();
+    /// #     type Error = std::io::Error;
+    /// # }
+    /// use std::time::Duration;
+    ///
+    /// use discern::command::CommandBus;
+    /// use discern::command_registry;
+    /// use discern::error::DispatchError;
+    /// use discern::ratelimit::RateLimit;
+    /// use discern::runtime::TokioRuntime;
+    ///
+    /// let mut registry = command_registry! {
+    ///     SendEmailCommand => |_command| async move { Ok(()) },
+    /// };
+    /// registry.rate_limit::<SendEmailCommand>(RateLimit::per_second(1).queueing(TokioRuntime));
+    ///
+    /// let command_bus = CommandBus::new(registry);
+    ///
+    /// assert!(command_bus.try_dispatch(SendEmailCommand).await.is_ok());
+    ///
+    /// // The next message waits for about a second, its dispatch is cancelled, so its turn is given back.
+    /// let cancelled = tokio::time::timeout(Duration::from_millis(10), command_bus.try_dispatch(SendEmailCommand));
+    /// assert!(cancelled.await.is_err());
+    ///
+    /// let queued = tokio::spawn({
+    ///     let command_bus = command_bus.clone();
+    ///
+    ///     async move { command_bus.try_dispatch(SendEmailCommand).await }
+    /// });
+    /// tokio::task::yield_now().await;
+    ///
+    /// // The next message would wait for about two seconds, longer than the maximum wait of one second.
+    /// let result = command_bus.try_dispatch(SendEmailCommand).await;
+    /// assert!(matches!(result, Err(DispatchError::RateLimited)));
+    ///
+    /// assert!(queued.await.unwrap().is_ok());
+    /// # });
+    /// ```
+    pub fn queueing(mut self, runtime: impl Runtime) -> Self {
+        self.runtime = Some(Box::new(runtime));
+        self
+    }
+
+    /// Sets the longest a message waits for a queueing limit, one period by default, see [RateLimit::queueing].
+    ///
+    /// Messages that would wait longer fail with
+    /// [DispatchError::RateLimited](crate::error::DispatchError::RateLimited) instead, without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_wait` - The maximum duration a message waits for its turn.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+}
+
+/// Debug implementation for `RateLimit`
+impl Debug for RateLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("RateLimit")
+            .field("permits", &self.permits)
+            .field("period", &self.period)
+            .field("burst", &self.burst)
+            .field("queueing", &self.runtime.is_some())
+            .field("max_wait", &self.max_wait)
+            .finish()
+    }
+}
+
+/// The token bucket enforcing a [RateLimit].
+#[doc(hidden)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+/// The state of a token bucket.
+#[doc(hidden)]
+#[derive(Debug)]
+struct Bucket {
+    /// The available tokens, negative when messages are waiting for tokens.
+    tokens: f64,
+    /// When the tokens were last refilled.
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    /// Creates the token bucket enforcing the given limit, starting full.
+    pub(crate) fn new(limit: RateLimit) -> Arc<Self> {
+        Arc::new(Self {
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(limit.burst),
+                refilled: Instant::now(),
+            }),
+            limit,
+        })
+    }
+
+    /// Takes a token, waiting for it if the limit is queueing.
+    ///
+    /// Returns `false` if the limit is exceeded, and not queueing, or if the wait would exceed the maximum wait.
+    pub(crate) async fn acquire(&self) -> bool {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+
+            let rate = f64::from(self.limit.permits) / self.limit.period.as_secs_f64();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(f64::from(self.limit.burst));
+            bucket.refilled = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+
+                return true;
+            }
+
+            if self.limit.runtime.is_none() {
+                return false;
+            }
+
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+            if wait > self.limit.max_wait {
+                return false;
+            }
+
+            // Reserve the token, so that the messages waiting for tokens are allowed in order.
+            bucket.tokens -= 1.0;
+
+            wait
+        };
+
+        let mut reservation = Reservation {
+            limiter: self,
+            taken: false,
+        };
+
+        if let Some(runtime) = &self.limit.runtime {
+            runtime.sleep(wait).await;
+        }
+
+        reservation.taken = true;
+
+        true
+    }
+}
+
+/// A token reserved by a message waiting for its turn, given back if the message stops waiting.
+#[doc(hidden)]
+struct Reservation<'a> {
+    limiter: &'a RateLimiter,
+    taken: bool,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.taken {
+            return;
+        }
+
+        let mut bucket = self
+            .limiter
+            .bucket
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        bucket.tokens = (bucket.tokens + 1.0).min(f64::from(self.limiter.limit.burst));
+    }
+}
+
+/// Debug implementation for `RateLimiter`
+impl Debug for RateLimiter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("RateLimiter")
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
@@ -4,43 +4,84 @@
 //! maintaining the mappings between command/query types and their corresponding handlers. These registries
 //! are used internally by the `CommandBus` and `QueryBus` to dispatch commands and queries to the correct handlers.
 //!
-//! The `executor` submodule is an internal implementation detail used by the registries to execute commands and queries.
+//! The `executor` submodule is an internal implementation detail used by the registries to execute commands and queries,
+//! while the `SharedRegistry` struct is used by the buses to share, and atomically replace, their registry.
 //!
 //! - [CommandHandlerRegistry]: The registry for command handlers.
 //! - [QueryHandlerRegistry]: The registry for query handlers.
+//! - [EventHandlerRegistry]: The registry for event handlers.
 
-use std::any::TypeId;
-use std::collections::HashMap;
-use std::fmt::Debug;
-use std::fmt::Formatter;
-use std::fmt::Result as FormatterResult;
-use std::sync::Arc;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
 
 use crate::command::Command;
 use crate::command::CommandHandler;
+use crate::command::ContextualCommandHandler;
+#[cfg(feature = "sync")]
+use crate::command::SyncCommandHandler;
+use crate::diagnostics::Registration;
+use crate::event::Event;
+use crate::event::EventHandler;
+use crate::message::MessageDescriptor;
+use crate::query::BorrowedQueryHandler;
 use crate::query::Query;
 use crate::query::QueryHandler;
-use crate::registry::executor::CommandHandlerWrapper;
-use crate::registry::executor::QueryHandlerWrapper;
+#[cfg(feature = "sync")]
+use crate::query::SyncQueryHandler;
+#[cfg(feature = "std")]
+use crate::ratelimit::RateLimit;
+#[cfg(feature = "std")]
+use crate::ratelimit::RateLimiter;
+pub(crate) use crate::registry::executor::CommandHandlerEntry;
+pub(crate) use crate::registry::executor::EventHandlerEntry;
+pub(crate) use crate::registry::executor::QueryHandlerEntry;
 
 /// The `CommandHandlerRegistry` struct manages the registration and retrieval of command handlers.
 ///
 /// This registry maintains a mapping between command types and their corresponding handlers.
 /// It is used internally by the `CommandBus` to dispatch commands to the correct handler.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct CommandHandlerRegistry {
     #[doc(hidden)]
-    pub(crate) handlers: HashMap<TypeId, Arc<dyn CommandHandlerWrapper>>,
+    pub(crate) handlers: BTreeMap<TypeId, CommandHandlerEntry>,
+    #[doc(hidden)]
+    pub(crate) names: BTreeMap<TypeId, Registration>,
+    #[doc(hidden)]
+    #[cfg(feature = "std")]
+    pub(crate) limits: BTreeMap<TypeId, Arc<RateLimiter>>,
 }
 
 /// The `QueryHandlerRegistry` struct manages the registration and retrieval of query handlers.
 ///
 /// This registry maintains a mapping between query types and their corresponding handlers.
 /// It is used internally by the `QueryBus` to dispatch queries to the correct handler.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct QueryHandlerRegistry {
     #[doc(hidden)]
-    pub(crate) handlers: HashMap<TypeId, Arc<dyn QueryHandlerWrapper>>,
+    pub(crate) handlers: BTreeMap<TypeId, QueryHandlerEntry>,
+    #[doc(hidden)]
+    pub(crate) names: BTreeMap<TypeId, Registration>,
+    #[doc(hidden)]
+    #[cfg(feature = "std")]
+    pub(crate) limits: BTreeMap<TypeId, Arc<RateLimiter>>,
+}
+
+/// The `EventHandlerRegistry` struct manages the registration and retrieval of event handlers.
+///
+/// Unlike commands and queries, an event can have any number of handlers, called subscribers.
+/// It is used internally by the `EventBus` to publish events to all of their subscribers.
+#[derive(Default, Clone)]
+pub struct EventHandlerRegistry {
+    #[doc(hidden)]
+    pub(crate) handlers: BTreeMap<TypeId, Vec<EventHandlerEntry>>,
+    #[doc(hidden)]
+    pub(crate) names: BTreeMap<TypeId, MessageDescriptor>,
 }
 
 /// `CommandHandlerRegistry` implementation.
@@ -57,10 +98,41 @@ impl CommandHandlerRegistry {
     /// ```
     pub fn new() -> Self {
         Self {
-            handlers: HashMap::new(),
+            handlers: BTreeMap::new(),
+            names: BTreeMap::new(),
+            #[cfg(feature = "std")]
+            limits: BTreeMap::new(),
         }
     }
 
+    /// Creates a new `CommandHandlerRegistry` containing the handlers registered in the inventory.
+    ///
+    /// See the [inventory](crate::inventory) module.
+    #[cfg(feature = "inventory")]
+    pub fn from_inventory() -> Self {
+        crate::inventory::command_registry()
+    }
+
+    /// Merges the handlers of another registry into this registry.
+    ///
+    /// Handlers registered in `other` replace the handlers registered in this registry for the same command type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.merge(CommandHandlerRegistry::new());
+    /// # assert!(true);
+    /// ```
+    pub fn merge(&mut self, other: CommandHandlerRegistry) {
+        self.handlers.extend(other.handlers);
+        self.names.extend(other.names);
+        #[cfg(feature = "std")]
+        self.limits.extend(other.limits);
+    }
+
     /// Registers a command handler for a specific command type.
     ///
     /// # Arguments
@@ -98,11 +170,105 @@ impl CommandHandlerRegistry {
     /// registry.register::<MyCommand>(MyCommandHandler { /* ... */ });
     /// # assert!(true);
     /// ```
+    #[track_caller]
     pub fn register<C: Command>(&mut self, handler: impl CommandHandler<C> + 'static) {
-        self.handlers.insert(
-            TypeId::of::<C>(),
-            Arc::new(Box::new(handler) as Box<dyn CommandHandler<C>>),
-        );
+        self.insert::<C>(CommandHandlerEntry::Async(Arc::new(
+            Box::new(handler) as Box<dyn CommandHandler<C>>
+        )));
+    }
+
+    /// Registers a synchronous command handler for a specific command type.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The synchronous handler to be registered for the command type `C`.
+    ///
+    /// Synchronous handlers are invoked directly by the `CommandBus`, without allocating a future.
+    /// They are also returned by [CommandHandlerRegistry::get_handler], wrapped in an asynchronous adapter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::command::{Command, SyncCommandHandler};
+    /// # use discern::registry::CommandHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyCommand;
+    /// #
+    /// # impl Command for MyCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyCommandHandler;
+    /// #
+    /// # impl SyncCommandHandler<MyCommand> for MyCommandHandler {
+    /// #   fn handle(&self, _command: MyCommand) -> Result<(), std::io::Error> {
+    /// #     Ok(())
+    /// #   }
+    /// # }
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.register_sync::<MyCommand>(MyCommandHandler { /* ... */ });
+    ///
+    /// assert!(registry.get_handler::<MyCommand>().is_some());
+    /// ```
+    #[cfg(feature = "sync")]
+    #[track_caller]
+    pub fn register_sync<C: Command>(&mut self, handler: impl SyncCommandHandler<C> + 'static) {
+        self.insert::<C>(CommandHandlerEntry::Sync(Arc::new(
+            Box::new(handler) as Box<dyn SyncCommandHandler<C>>
+        )));
+    }
+
+    /// Registers a contextual command handler for a specific command type.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The contextual handler to be registered for the command type `C`.
+    ///
+    /// Contextual handlers receive the [Context](crate::context::Context) the command was dispatched with, see
+    /// [CommandBus::dispatch_with](crate::command::CommandBus::dispatch_with). Commands dispatched without a
+    /// context, and handlers returned by [CommandHandlerRegistry::get_handler], receive an empty context.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::command::{Command, ContextualCommandHandler};
+    /// # use discern::async_trait;
+    /// # use discern::context::Context;
+    /// # use discern::registry::CommandHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyCommand;
+    /// #
+    /// # impl Command for MyCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyCommandHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl ContextualCommandHandler<MyCommand> for MyCommandHandler {
+    /// #   async fn handle(&self, _command: MyCommand, _context: &Context) -> Result<(), std::io::Error> {
+    /// #     Ok(())
+    /// #   }
+    /// # }
+    /// let mut registry = CommandHandlerRegistry::new();
+    /// registry.register_contextual::<MyCommand>(MyCommandHandler { /* ... */ });
+    ///
+    /// assert!(registry.get_handler::<MyCommand>().is_some());
+    /// ```
+    #[track_caller]
+    pub fn register_contextual<C: Command>(
+        &mut self,
+        handler: impl ContextualCommandHandler<C> + 'static,
+    ) {
+        self.insert::<C>(CommandHandlerEntry::Contextual(Arc::new(
+            Box::new(handler) as Box<dyn ContextualCommandHandler<C>>
+        )));
     }
 
     /// Retrieves the command handler for a specific command type.
@@ -147,6 +313,124 @@ impl CommandHandlerRegistry {
             .cloned()
             .map(|handler| Box::new(handler) as Box<dyn CommandHandler<C>>)
     }
+
+    /// Returns the names of the commands that have a registered handler, in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::CommandHandlerRegistry;
+    ///
+    /// let registry = CommandHandlerRegistry::new();
+    ///
+    /// assert_eq!(registry.names().count(), 0);
+    /// ```
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.names.values().map(|registration| registration.name)
+    }
+
+    /// Returns the descriptors of the commands that have a registered handler, in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyCommand;
+    /// #
+    /// # impl Command for MyCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::command_registry;
+    /// use discern::message::MessageKind;
+    ///
+    /// let registry = command_registry! {
+    ///     MyCommand => |_command| async move { Ok(()) },
+    /// };
+    ///
+    /// let descriptors: Vec<_> = registry.descriptors().collect();
+    ///
+    /// assert_eq!(descriptors.len(), 1);
+    /// assert_eq!(descriptors[0].kind, MessageKind::Command);
+    /// assert_eq!(descriptors[0].version, 1);
+    /// ```
+    pub fn descriptors(&self) -> impl Iterator<Item = MessageDescriptor> + '_ {
+        self.names
+            .values()
+            .map(|registration| registration.descriptor)
+    }
+
+    /// Returns the type id, and the name, of the command with the given name, if it has a registered handler.
+    #[cfg(feature = "admin")]
+    pub(crate) fn resolve(&self, name: &str) -> Option<(TypeId, &'static str)> {
+        self.names
+            .iter()
+            .find(|(_, registered)| registered.name == name)
+            .map(|(id, registered)| (*id, registered.name))
+    }
+
+    /// Registers the handler entry for the command type `C`.
+    #[track_caller]
+    fn insert<C: Command>(&mut self, entry: CommandHandlerEntry) {
+        self.handlers.insert(TypeId::of::<C>(), entry);
+        self.names.insert(
+            TypeId::of::<C>(),
+            Registration {
+                name: C::name(),
+                descriptor: C::descriptor(),
+                location: core::panic::Location::caller(),
+            },
+        );
+    }
+
+    /// Returns the messages with a registered handler, and where they were registered.
+    pub(crate) fn registrations(&self) -> alloc::vec::Vec<Registration> {
+        self.names.values().copied().collect()
+    }
+
+    /// Returns the entry of the handler registered for the command type `C`.
+    pub(crate) fn entry<C: Command>(&self) -> Option<CommandHandlerEntry> {
+        self.handlers.get(&TypeId::of::<C>()).cloned()
+    }
+
+    /// Limits the rate at which commands of type `C` are handled, see [RateLimit].
+    ///
+    /// Commands exceeding the limit fail with [DispatchError::RateLimited](crate::error::DispatchError::RateLimited),
+    /// unless the limit is queueing. Setting a limit replaces the previous limit of the command type.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The rate limit of the command type `C`.
+    #[cfg(feature = "std")]
+    pub fn rate_limit<C: Command>(&mut self, limit: RateLimit) {
+        self.limits
+            .insert(TypeId::of::<C>(), RateLimiter::new(limit));
+    }
+
+    /// Returns the rate limiter of the command type `C`, if any.
+    #[cfg(feature = "std")]
+    pub(crate) fn limiter<C: Command>(&self) -> Option<Arc<RateLimiter>> {
+        if self.limits.is_empty() {
+            return None;
+        }
+
+        self.limits.get(&TypeId::of::<C>()).cloned()
+    }
+
+    /// Handles a command using the synchronous handler registered for its type.
+    ///
+    /// Returns `None` if no synchronous handler is registered for the command type `C`.
+    #[cfg(feature = "sync")]
+    pub(crate) fn handle_sync<C: Command>(
+        &self,
+        command: C,
+    ) -> Option<Result<C::Metadata, C::Error>> {
+        self.handlers
+            .get(&TypeId::of::<C>())
+            .and_then(|handler| handler.handle_sync(command))
+    }
 }
 
 /// `QueryHandlerRegistry` implementation.
@@ -163,10 +447,41 @@ impl QueryHandlerRegistry {
     /// ```
     pub fn new() -> Self {
         Self {
-            handlers: HashMap::new(),
+            handlers: BTreeMap::new(),
+            names: BTreeMap::new(),
+            #[cfg(feature = "std")]
+            limits: BTreeMap::new(),
         }
     }
 
+    /// Creates a new `QueryHandlerRegistry` containing the handlers registered in the inventory.
+    ///
+    /// See the [inventory](crate::inventory) module.
+    #[cfg(feature = "inventory")]
+    pub fn from_inventory() -> Self {
+        crate::inventory::query_registry()
+    }
+
+    /// Merges the handlers of another registry into this registry.
+    ///
+    /// Handlers registered in `other` replace the handlers registered in this registry for the same query type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::QueryHandlerRegistry;
+    ///
+    /// let mut registry = QueryHandlerRegistry::new();
+    /// registry.merge(QueryHandlerRegistry::new());
+    /// # assert!(true);
+    /// ```
+    pub fn merge(&mut self, other: QueryHandlerRegistry) {
+        self.handlers.extend(other.handlers);
+        self.names.extend(other.names);
+        #[cfg(feature = "std")]
+        self.limits.extend(other.limits);
+    }
+
     /// Registers a query handler for a specific query type.
     ///
     /// # Arguments
@@ -204,11 +519,102 @@ impl QueryHandlerRegistry {
     /// registry.register(MyQueryHandler);
     /// # assert!(true);
     /// ```
+    #[track_caller]
     pub fn register<Q: Query>(&mut self, handler: impl QueryHandler<Q> + 'static) {
-        self.handlers.insert(
-            TypeId::of::<Q>(),
-            Arc::new(Box::new(handler) as Box<dyn QueryHandler<Q>>),
-        );
+        self.insert::<Q>(QueryHandlerEntry::Async(Arc::new(
+            Box::new(handler) as Box<dyn QueryHandler<Q>>
+        )));
+    }
+
+    /// Registers a synchronous query handler for a specific query type.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The synchronous handler to be registered for the query type `Q`.
+    ///
+    /// Synchronous handlers are invoked directly by the `QueryBus`, without allocating a future.
+    /// They are also returned by [QueryHandlerRegistry::get_handler], wrapped in an asynchronous adapter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::query::{Query, SyncQueryHandler};
+    /// # use discern::registry::QueryHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyQuery;
+    /// #
+    /// # impl Query for MyQuery {
+    /// #   type Output = String;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyQueryHandler;
+    /// #
+    /// # impl SyncQueryHandler<MyQuery> for MyQueryHandler {
+    /// #   fn handle(&self, _query: MyQuery) -> Result<String, std::io::Error> {
+    /// #     Ok("Result".to_string())
+    /// #   }
+    /// # }
+    /// let mut registry = QueryHandlerRegistry::new();
+    /// registry.register_sync(MyQueryHandler);
+    ///
+    /// assert!(registry.get_handler::<MyQuery>().is_some());
+    /// ```
+    #[cfg(feature = "sync")]
+    #[track_caller]
+    pub fn register_sync<Q: Query>(&mut self, handler: impl SyncQueryHandler<Q> + 'static) {
+        self.insert::<Q>(QueryHandlerEntry::Sync(Arc::new(
+            Box::new(handler) as Box<dyn SyncQueryHandler<Q>>
+        )));
+    }
+
+    /// Registers a borrowed query handler for a specific query type.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The borrowed handler to be registered for the query type `Q`.
+    ///
+    /// Borrowed handlers only need a reference to the query, which allows dispatching queries
+    /// without giving up ownership using [QueryBus::dispatch_ref](crate::query::QueryBus::dispatch_ref).
+    /// Queries dispatched by value using [QueryBus::dispatch](crate::query::QueryBus::dispatch) are passed
+    /// to the borrowed handler by reference.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::query::{Query, BorrowedQueryHandler};
+    /// # use discern::async_trait;
+    /// # use discern::registry::QueryHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyQuery;
+    /// #
+    /// # impl Query for MyQuery {
+    /// #   type Output = String;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyQueryHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl BorrowedQueryHandler<MyQuery> for MyQueryHandler {
+    /// #   async fn handle(&self, _query: &MyQuery) -> Result<String, std::io::Error> {
+    /// #     Ok("Result".to_string())
+    /// #   }
+    /// # }
+    /// let mut registry = QueryHandlerRegistry::new();
+    /// registry.register_borrowed(MyQueryHandler);
+    ///
+    /// assert!(registry.get_handler::<MyQuery>().is_some());
+    /// ```
+    #[track_caller]
+    pub fn register_borrowed<Q: Query>(&mut self, handler: impl BorrowedQueryHandler<Q> + 'static) {
+        self.insert::<Q>(QueryHandlerEntry::Borrowed(Arc::new(
+            Box::new(handler) as Box<dyn BorrowedQueryHandler<Q>>
+        )));
     }
 
     /// Retrieves the query handler for a specific query type.
@@ -253,6 +659,377 @@ impl QueryHandlerRegistry {
             .cloned()
             .map(|handler| Box::new(handler) as Box<dyn QueryHandler<Q>>)
     }
+
+    /// Returns the names of the queries that have a registered handler, in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::QueryHandlerRegistry;
+    ///
+    /// let registry = QueryHandlerRegistry::new();
+    ///
+    /// assert_eq!(registry.names().count(), 0);
+    /// ```
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.names.values().map(|registration| registration.name)
+    }
+
+    /// Returns the descriptors of the queries that have a registered handler, in no particular order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::query::Query;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyQuery;
+    /// #
+    /// # impl Query for MyQuery {
+    /// #   type Output = String;
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::query_registry;
+    /// use discern::message::MessageKind;
+    ///
+    /// let registry = query_registry! {
+    ///     MyQuery => |_query| async move { Ok("Result".to_string()) },
+    /// };
+    ///
+    /// let descriptors: Vec<_> = registry.descriptors().collect();
+    ///
+    /// assert_eq!(descriptors.len(), 1);
+    /// assert_eq!(descriptors[0].kind, MessageKind::Query);
+    /// assert_eq!(descriptors[0].version, 1);
+    /// ```
+    pub fn descriptors(&self) -> impl Iterator<Item = MessageDescriptor> + '_ {
+        self.names
+            .values()
+            .map(|registration| registration.descriptor)
+    }
+
+    /// Registers the handler entry for the query type `Q`.
+    #[track_caller]
+    fn insert<Q: Query>(&mut self, entry: QueryHandlerEntry) {
+        self.handlers.insert(TypeId::of::<Q>(), entry);
+        self.names.insert(
+            TypeId::of::<Q>(),
+            Registration {
+                name: Q::name(),
+                descriptor: Q::descriptor(),
+                location: core::panic::Location::caller(),
+            },
+        );
+    }
+
+    /// Returns the messages with a registered handler, and where they were registered.
+    pub(crate) fn registrations(&self) -> alloc::vec::Vec<Registration> {
+        self.names.values().copied().collect()
+    }
+
+    /// Returns the entry of the handler registered for the query type `Q`.
+    pub(crate) fn entry<Q: Query>(&self) -> Option<QueryHandlerEntry> {
+        self.handlers.get(&TypeId::of::<Q>()).cloned()
+    }
+
+    /// Limits the rate at which queries of type `Q` are handled, see [RateLimit].
+    ///
+    /// Queries exceeding the limit fail with [DispatchError::RateLimited](crate::error::DispatchError::RateLimited),
+    /// unless the limit is queueing. Setting a limit replaces the previous limit of the query type.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The rate limit of the query type `Q`.
+    #[cfg(feature = "std")]
+    pub fn rate_limit<Q: Query>(&mut self, limit: RateLimit) {
+        self.limits
+            .insert(TypeId::of::<Q>(), RateLimiter::new(limit));
+    }
+
+    /// Returns the rate limiter of the query type `Q`, if any.
+    #[cfg(feature = "std")]
+    pub(crate) fn limiter<Q: Query>(&self) -> Option<Arc<RateLimiter>> {
+        if self.limits.is_empty() {
+            return None;
+        }
+
+        self.limits.get(&TypeId::of::<Q>()).cloned()
+    }
+
+    /// Handles a query using the synchronous handler registered for its type.
+    ///
+    /// Returns `None` if no synchronous handler is registered for the query type `Q`.
+    #[cfg(feature = "sync")]
+    pub(crate) fn handle_sync<Q: Query>(&self, query: Q) -> Option<Result<Q::Output, Q::Error>> {
+        self.handlers
+            .get(&TypeId::of::<Q>())
+            .and_then(|handler| handler.handle_sync(query))
+    }
+}
+
+/// `EventHandlerRegistry` implementation.
+impl EventHandlerRegistry {
+    /// Creates a new, empty `EventHandlerRegistry`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::EventHandlerRegistry;
+    ///
+    /// let registry = EventHandlerRegistry::new();
+    /// # assert!(true);
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Merges the handlers of another registry into this registry.
+    ///
+    /// Handlers registered in `other` are added after the handlers registered in this registry for the same event
+    /// type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::EventHandlerRegistry;
+    ///
+    /// let mut registry = EventHandlerRegistry::new();
+    /// registry.merge(EventHandlerRegistry::new());
+    /// # assert!(true);
+    /// ```
+    pub fn merge(&mut self, other: EventHandlerRegistry) {
+        for (id, handlers) in other.handlers {
+            self.handlers.entry(id).or_default().extend(handlers);
+        }
+
+        self.names.extend(other.names);
+    }
+
+    /// Subscribes an event handler to a specific event type.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to be subscribed to the event type `E`.
+    ///
+    /// Subscribing a handler does not replace the handlers previously subscribed to the same event type.
+    /// When an event of type `E` is published, the `EventBus` will call every handler subscribed here.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::event::{Event, EventHandler};
+    /// # use discern::async_trait;
+    /// # use discern::registry::EventHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyEvent;
+    /// #
+    /// # impl Event for MyEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyEventHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl EventHandler<MyEvent> for MyEventHandler {
+    /// #   async fn handle(&self, _event: &MyEvent) -> Result<(), std::io::Error> {
+    /// #     Ok(())
+    /// #   }
+    /// # }
+    /// let mut registry = EventHandlerRegistry::new();
+    /// registry.subscribe::<MyEvent>(MyEventHandler { /* ... */ });
+    /// registry.subscribe::<MyEvent>(MyEventHandler { /* ... */ });
+    ///
+    /// assert_eq!(registry.subscribers::<MyEvent>(), 2);
+    /// ```
+    pub fn subscribe<E: Event>(&mut self, handler: impl EventHandler<E> + 'static) {
+        self.handlers
+            .entry(TypeId::of::<E>())
+            .or_default()
+            .push(EventHandlerEntry::new(handler));
+        self.names.insert(TypeId::of::<E>(), E::descriptor());
+    }
+
+    /// Returns the number of handlers subscribed to the event type `E`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::event::{Event, EventHandler};
+    /// # use discern::async_trait;
+    /// # use discern::registry::EventHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyEvent;
+    /// #
+    /// # impl Event for MyEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct OtherEvent;
+    /// #
+    /// # impl Event for OtherEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyEventHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl EventHandler<MyEvent> for MyEventHandler {
+    /// #   async fn handle(&self, _event: &MyEvent) -> Result<(), std::io::Error> {
+    /// #     Ok(())
+    /// #   }
+    /// # }
+    /// let mut registry = EventHandlerRegistry::new();
+    /// registry.subscribe::<MyEvent>(MyEventHandler { /* ... */ });
+    ///
+    /// assert_eq!(registry.subscribers::<MyEvent>(), 1);
+    /// assert_eq!(registry.subscribers::<OtherEvent>(), 0);
+    /// ```
+    pub fn subscribers<E: Event>(&self) -> usize {
+        self.handlers.get(&TypeId::of::<E>()).map_or(0, Vec::len)
+    }
+
+    /// Returns the names of the events with subscribed handlers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::registry::EventHandlerRegistry;
+    ///
+    /// let registry = EventHandlerRegistry::new();
+    ///
+    /// assert_eq!(registry.names().count(), 0);
+    /// ```
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.names.values().map(|descriptor| descriptor.name)
+    }
+
+    /// Returns the descriptors of the events with subscribed handlers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::event::{Event, EventHandler};
+    /// # use discern::async_trait;
+    /// # use discern::registry::EventHandlerRegistry;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyEvent;
+    /// #
+    /// # impl Event for MyEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct OtherEvent;
+    /// #
+    /// # impl Event for OtherEvent {
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct MyEventHandler;
+    /// #
+    /// # #[async_trait]
+    /// # impl EventHandler<MyEvent> for MyEventHandler {
+    /// #   async fn handle(&self, _event: &MyEvent) -> Result<(), std::io::Error> {
+    /// #     Ok(())
+    /// #   }
+    /// # }
+    /// use discern::message::MessageKind;
+    ///
+    /// let mut registry = EventHandlerRegistry::new();
+    /// registry.subscribe::<MyEvent>(MyEventHandler { /* ... */ });
+    /// registry.subscribe::<MyEvent>(MyEventHandler { /* ... */ });
+    ///
+    /// let descriptors: Vec<_> = registry.descriptors().collect();
+    ///
+    /// assert_eq!(descriptors.len(), 1);
+    /// assert_eq!(descriptors[0].name, MyEvent::name());
+    /// assert_eq!(descriptors[0].kind, MessageKind::Event);
+    /// ```
+    pub fn descriptors(&self) -> impl Iterator<Item = MessageDescriptor> + '_ {
+        self.names.values().copied()
+    }
+
+    /// Returns the entries of the handlers subscribed to the event type `E`.
+    pub(crate) fn entries<E: Event>(&self) -> Vec<EventHandlerEntry> {
+        self.handlers
+            .get(&TypeId::of::<E>())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// A registry shared by a bus and its clones.
+///
+/// When the `std` feature is enabled, the registry is stored in an [ArcSwap](arc_swap::ArcSwap), which
+/// keeps the dispatch path lock-free, while allowing the registry to be replaced at runtime using
+/// copy-on-write.
+#[doc(hidden)]
+pub(crate) struct SharedRegistry<R> {
+    #[cfg(feature = "std")]
+    registry: arc_swap::ArcSwap<R>,
+    #[cfg(not(feature = "std"))]
+    registry: Arc<R>,
+}
+
+impl<R> SharedRegistry<R> {
+    pub(crate) fn new(registry: R) -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            registry: arc_swap::ArcSwap::from_pointee(registry),
+            #[cfg(not(feature = "std"))]
+            registry: Arc::new(registry),
+        }
+    }
+
+    /// Calls `f` with the current registry.
+    ///
+    /// The registry must not be held across await points, values needed past `f` should be cloned out of it.
+    pub(crate) fn with<T>(&self, f: impl FnOnce(&R) -> T) -> T {
+        #[cfg(feature = "std")]
+        {
+            f(&self.registry.load())
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            f(&self.registry)
+        }
+    }
+
+    /// Atomically replaces the registry with the result of `update`.
+    ///
+    /// `update` may be called multiple times if the registry is concurrently updated.
+    #[cfg(feature = "std")]
+    pub(crate) fn update(&self, update: impl Fn(&R) -> R) {
+        self.registry.rcu(|current| update(current));
+    }
+
+    /// Atomically replaces the registry, returning the previous one.
+    #[cfg(feature = "std")]
+    pub(crate) fn replace(&self, registry: R) -> R
+    where
+        R: Clone,
+    {
+        let previous = self.registry.swap(Arc::new(registry));
+
+        Arc::try_unwrap(previous).unwrap_or_else(|previous| (*previous).clone())
+    }
+}
+
+/// Debug implementation for `SharedRegistry`
+impl<R: Debug> Debug for SharedRegistry<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        self.with(|registry| registry.fmt(f))
+    }
 }
 
 /// Debug implementation for `CommandHandlerRegistry`
@@ -269,57 +1046,189 @@ impl Debug for QueryHandlerRegistry {
     }
 }
 
+/// Debug implementation for `EventHandlerRegistry`
+impl Debug for EventHandlerRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("EventHandlerRegistry").finish()
+    }
+}
+
 #[doc(hidden)]
-mod executor {
-    use std::any::Any;
-    use std::sync::Arc;
+pub(crate) mod executor {
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+    use core::any::Any;
 
     use crate::async_trait;
     use crate::command::Command;
     use crate::command::CommandHandler;
+    use crate::command::ContextualCommandHandler;
+    #[cfg(feature = "sync")]
+    use crate::command::SyncCommandHandler;
+    use crate::context::Context;
+    use crate::event::Event;
+    use crate::event::EventHandler;
+    use crate::query::BorrowedQueryHandler;
     use crate::query::Query;
     use crate::query::QueryHandler;
+    #[cfg(feature = "sync")]
+    use crate::query::SyncQueryHandler;
+    use crate::runtime::BoxFuture;
 
-    #[async_trait]
-    pub trait CommandHandlerWrapper: Send + Sync {
-        async fn execute(&self, command: Box<dyn Any + Send>) -> Box<dyn Any + Send>;
+    #[derive(Clone)]
+    pub enum CommandHandlerEntry {
+        /// Holds a `Box<dyn CommandHandler<C>>`.
+        Async(Arc<dyn Any + Send + Sync>),
+        /// Holds a `Box<dyn SyncCommandHandler<C>>`.
+        #[cfg(feature = "sync")]
+        Sync(Arc<dyn Any + Send + Sync>),
+        /// Holds a `Box<dyn ContextualCommandHandler<C>>`.
+        Contextual(Arc<dyn Any + Send + Sync>),
     }
 
-    #[async_trait]
-    pub trait QueryHandlerWrapper: Send + Sync {
-        async fn execute(&self, query: Box<dyn Any + Send>) -> Box<dyn Any + Send>;
+    #[derive(Clone)]
+    pub enum QueryHandlerEntry {
+        /// Holds a `Box<dyn QueryHandler<Q>>`.
+        Async(Arc<dyn Any + Send + Sync>),
+        /// Holds a `Box<dyn SyncQueryHandler<Q>>`.
+        #[cfg(feature = "sync")]
+        Sync(Arc<dyn Any + Send + Sync>),
+        /// Holds a `Box<dyn BorrowedQueryHandler<Q>>`.
+        Borrowed(Arc<dyn Any + Send + Sync>),
     }
 
-    #[async_trait]
-    impl<C: Command> CommandHandlerWrapper for Box<dyn CommandHandler<C>> {
-        async fn execute(&self, command: Box<dyn Any + Send>) -> Box<dyn Any + Send> {
-            let command = *command.downcast::<C>().unwrap();
-            let result = self.handle(command).await;
-            Box::new(result) as Box<dyn Any + Send>
+    #[derive(Clone)]
+    pub struct EventHandlerEntry {
+        /// The type name of the handler.
+        pub name: &'static str,
+        /// Holds a `Box<dyn EventHandler<E>>`.
+        pub handler: Arc<dyn Any + Send + Sync>,
+    }
+
+    impl CommandHandlerEntry {
+        pub async fn handle<C: Command>(
+            &self,
+            command: C,
+            context: &Context,
+        ) -> Result<C::Metadata, C::Error> {
+            match self {
+                Self::Async(handler) => {
+                    handler
+                        .downcast_ref::<Box<dyn CommandHandler<C>>>()
+                        .unwrap()
+                        .handle(command)
+                        .await
+                }
+                #[cfg(feature = "sync")]
+                Self::Sync(handler) => handler
+                    .downcast_ref::<Box<dyn SyncCommandHandler<C>>>()
+                    .unwrap()
+                    .handle(command),
+                Self::Contextual(handler) => {
+                    handler
+                        .downcast_ref::<Box<dyn ContextualCommandHandler<C>>>()
+                        .unwrap()
+                        .handle(command, context)
+                        .await
+                }
+            }
+        }
+
+        #[cfg(feature = "sync")]
+        pub fn handle_sync<C: Command>(&self, command: C) -> Option<Result<C::Metadata, C::Error>> {
+            match self {
+                Self::Async(_) | Self::Contextual(_) => None,
+                Self::Sync(handler) => Some(
+                    handler
+                        .downcast_ref::<Box<dyn SyncCommandHandler<C>>>()
+                        .unwrap()
+                        .handle(command),
+                ),
+            }
         }
     }
 
-    #[async_trait]
-    impl<Q: Query> QueryHandlerWrapper for Box<dyn QueryHandler<Q>> {
-        async fn execute(&self, query: Box<dyn Any + Send>) -> Box<dyn Any + Send> {
-            let result = self.handle(*query.downcast::<Q>().unwrap()).await;
-            Box::new(result) as Box<dyn Any + Send>
+    impl QueryHandlerEntry {
+        pub async fn handle<Q: Query>(&self, query: Q) -> Result<Q::Output, Q::Error> {
+            match self {
+                Self::Async(handler) => {
+                    handler
+                        .downcast_ref::<Box<dyn QueryHandler<Q>>>()
+                        .unwrap()
+                        .handle(query)
+                        .await
+                }
+                #[cfg(feature = "sync")]
+                Self::Sync(handler) => handler
+                    .downcast_ref::<Box<dyn SyncQueryHandler<Q>>>()
+                    .unwrap()
+                    .handle(query),
+                Self::Borrowed(handler) => {
+                    handler
+                        .downcast_ref::<Box<dyn BorrowedQueryHandler<Q>>>()
+                        .unwrap()
+                        .handle(&query)
+                        .await
+                }
+            }
+        }
+
+        pub fn handle_borrowed<'a, Q: Query>(
+            &'a self,
+            query: &'a Q,
+        ) -> Option<BoxFuture<'a, Result<Q::Output, Q::Error>>> {
+            match self {
+                Self::Borrowed(handler) => Some(
+                    handler
+                        .downcast_ref::<Box<dyn BorrowedQueryHandler<Q>>>()
+                        .unwrap()
+                        .handle(query),
+                ),
+                _ => None,
+            }
+        }
+
+        #[cfg(feature = "sync")]
+        pub fn handle_sync<Q: Query>(&self, query: Q) -> Option<Result<Q::Output, Q::Error>> {
+            match self {
+                Self::Async(_) | Self::Borrowed(_) => None,
+                Self::Sync(handler) => Some(
+                    handler
+                        .downcast_ref::<Box<dyn SyncQueryHandler<Q>>>()
+                        .unwrap()
+                        .handle(query),
+                ),
+            }
+        }
+    }
+
+    impl EventHandlerEntry {
+        pub fn new<E: Event, H: EventHandler<E> + 'static>(handler: H) -> Self {
+            Self {
+                name: core::any::type_name::<H>(),
+                handler: Arc::new(Box::new(handler) as Box<dyn EventHandler<E>>),
+            }
+        }
+
+        pub fn handle<'a, E: Event>(&'a self, event: &'a E) -> BoxFuture<'a, Result<(), E::Error>> {
+            self.handler
+                .downcast_ref::<Box<dyn EventHandler<E>>>()
+                .unwrap()
+                .handle(event)
         }
     }
 
     #[async_trait]
-    impl<C: Command> CommandHandler<C> for Arc<dyn CommandHandlerWrapper> {
+    impl<C: Command> CommandHandler<C> for CommandHandlerEntry {
         async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
-            let result = self.execute(Box::new(command)).await;
-            *result.downcast().unwrap()
+            CommandHandlerEntry::handle(self, command, &Context::new()).await
         }
     }
 
     #[async_trait]
-    impl<Q: Query> QueryHandler<Q> for Arc<dyn QueryHandlerWrapper> {
+    impl<Q: Query> QueryHandler<Q> for QueryHandlerEntry {
         async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
-            let result = self.execute(Box::new(query)).await;
-            *result.downcast().unwrap()
+            QueryHandlerEntry::handle(self, query).await
         }
     }
 }
@@ -0,0 +1,445 @@
+//! The `retry` module provides a handler wrapper that retries failed queries, idempotent commands, and event
+//! subscribers.
+//!
+//! Transient failures, e.g. a dropped database connection, a serialization conflict, or a timeout of a downstream
+//! service, usually succeed when attempted again a moment later. A [RetryPolicy] describes how many attempts are
+//! made, how long to wait between them, and which errors are worth retrying, and the [RetryingHandler] applies it
+//! to the wrapped handler. Policies are cheap to clone, so the same policy can wrap the handlers of every command
+//! sharing an error type. Error types implementing [ErrorClass] can be retried according to their category using
+//! [RetryPolicy::classified].
+//!
+//! - [RetryPolicy]: Describes when, and how often, a failed attempt is retried.
+//! - [RetryingHandler]: A handler that retries failed attempts according to a [RetryPolicy].
+
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::hash::BuildHasher;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::command::Idempotent;
+use crate::error::ErrorClass;
+use crate::event::Event;
+use crate::event::EventHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+use crate::runtime::Runtime;
+
+/// Decides whether an error is worth retrying.
+type Predicate<E> = Arc<dyn Fn(&E) -> bool + Send + Sync>;
+
+/// Describes when, and how often, a failed attempt is retried.
+///
+/// The delay before the `n`th retry is `initial_backoff * multiplier^(n - 1)`, capped at `max_backoff`. A jitter
+/// of `j` then randomly shortens the delay by up to `j` times itself, so that callers failing at the same time
+/// don't retry in lockstep.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use discern::retry::RetryPolicy;
+///
+/// let policy = RetryPolicy::<std::io::Error>::new(4)
+///     .with_backoff(Duration::from_millis(10), Duration::from_millis(50))
+///     .with_multiplier(3.0)
+///     .retry_if(|error| error.kind() == std::io::ErrorKind::TimedOut);
+///
+/// assert_eq!(policy.backoff(1), Duration::from_millis(10));
+/// assert_eq!(policy.backoff(2), Duration::from_millis(30));
+/// assert_eq!(policy.backoff(3), Duration::from_millis(50));
+///
+/// assert!(policy.should_retry(&std::io::ErrorKind::TimedOut.into()));
+/// assert!(!policy.should_retry(&std::io::ErrorKind::NotFound.into()));
+/// ```
+pub struct RetryPolicy<E> {
+    #[doc(hidden)]
+    max_attempts: u32,
+    #[doc(hidden)]
+    initial_backoff: Duration,
+    #[doc(hidden)]
+    max_backoff: Duration,
+    #[doc(hidden)]
+    multiplier: f64,
+    #[doc(hidden)]
+    jitter: f64,
+    #[doc(hidden)]
+    predicate: Option<Predicate<E>>,
+}
+
+/// The `RetryPolicy` implementation.
+impl<E> RetryPolicy<E> {
+    /// Creates a new `RetryPolicy`, retrying every error, with a backoff starting at 100 milliseconds, doubling
+    /// after each retry, up to 10 seconds, and no jitter.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - The maximum number of attempts, including the first one.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `max_attempts` is zero.
+    pub fn new(max_attempts: u32) -> Self {
+        assert!(
+            max_attempts > 0,
+            "the maximum attempts must be greater than zero"
+        );
+
+        Self {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.0,
+            predicate: None,
+        }
+    }
+
+    /// Sets the delay before the first retry, and the maximum delay between two attempts.
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the factor the delay is multiplied by after each retry.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `multiplier` is lower than `1.0`.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        assert!(multiplier >= 1.0, "the multiplier must be at least 1.0");
+
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Sets the fraction of the delay that is randomly removed from it.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `jitter` is not between `0.0` and `1.0`.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&jitter),
+            "the jitter must be between 0.0 and 1.0"
+        );
+
+        self.jitter = jitter;
+        self
+    }
+
+    /// Only retries the errors for which the given predicate returns `true`.
+    pub fn retry_if(mut self, predicate: impl Fn(&E) -> bool + Send + Sync + 'static) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Returns the maximum number of attempts, including the first one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns whether the given error is worth retrying.
+    pub fn should_retry(&self, error: &E) -> bool {
+        self.predicate
+            .as_ref()
+            .is_none_or(|predicate| predicate(error))
+    }
+
+    /// Returns the delay before the given retry, without jitter.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - The number of the retry, starting at `1`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.powi(retry.saturating_sub(1) as i32);
+
+        self.initial_backoff
+            .mul_f64(factor.min(u32::MAX as f64))
+            .min(self.max_backoff)
+    }
+
+    /// Returns the delay before the given retry, with jitter.
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        if self.jitter == 0.0 {
+            return backoff;
+        }
+
+        let random = RandomState::new().hash_one(retry) as f64 / u64::MAX as f64;
+
+        backoff.mul_f64(1.0 - self.jitter * random)
+    }
+}
+
+/// The `RetryPolicy` implementation for classified errors.
+impl<E: ErrorClass> RetryPolicy<E> {
+    /// Only retries the errors that are retryable according to their [ErrorClass], i.e. transient errors, and
+    /// conflicts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use discern::retry::RetryPolicy;
+    ///
+    /// let policy = RetryPolicy::<std::io::Error>::new(3).classified();
+    ///
+    /// assert!(policy.should_retry(&std::io::ErrorKind::ConnectionReset.into()));
+    /// assert!(!policy.should_retry(&std::io::ErrorKind::PermissionDenied.into()));
+    /// ```
+    pub fn classified(self) -> Self {
+        self.retry_if(|error: &E| error.is_retryable())
+    }
+}
+
+impl<E> Clone for RetryPolicy<E> {
+    fn clone(&self) -> Self {
+        Self {
+            max_attempts: self.max_attempts,
+            initial_backoff: self.initial_backoff,
+            max_backoff: self.max_backoff,
+            multiplier: self.multiplier,
+            jitter: self.jitter,
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+/// Debug implementation for `RetryPolicy`
+impl<E> Debug for RetryPolicy<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .finish()
+    }
+}
+
+/// A handler that retries failed attempts according to a [RetryPolicy].
+///
+/// Since the message is sent once per attempt, it must implement `Clone`. Commands can be retried as well, as long
+/// as they implement [Idempotent], retrying a command that is not idempotent fails to compile. Event subscribers
+/// receive a reference to the event, and are retried without cloning it:
+///
+/// ```compile_fail
+/// # use discern::async_trait;
+/// # use discern::command::Command;
+/// # use discern::command::CommandHandler;
+/// # use discern::retry::RetryPolicy;
+/// # use discern::retry::RetryingHandler;
+/// # use discern::runtime::TokioRuntime;
+/// # use discern::command_bus;
+/// #
+/// #[derive(Debug, Clone)]
+/// struct ChargeCustomerCommand {
+///     amount: u64,
+/// }
+///
+/// impl Command for ChargeCustomerCommand {
+///     type Metadata = ();
+///     type Error = std::io::Error;
+/// }
+/// #
+/// # struct ChargeCustomerCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<ChargeCustomerCommand> for ChargeCustomerCommandHandler {
+/// #     async fn handle(&self, command: ChargeCustomerCommand) -> Result<(), std::io::Error> {
+/// #         Ok(())
+/// #     }
+/// # }
+///
+/// let command_bus = command_bus! {
+///     ChargeCustomerCommand => RetryingHandler::new(
+///         ChargeCustomerCommandHandler,
+///         RetryPolicy::new(3),
+///         TokioRuntime,
+///     ),
+/// };
+/// ```
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::command::Idempotent;
+/// #
+/// # #[derive(Debug, Clone)]
+/// # struct SetUserEmailCommand {
+/// #     user_id: u64,
+/// #     email: String,
+/// # }
+/// #
+/// # impl Command for SetUserEmailCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # impl Idempotent for SetUserEmailCommand {}
+/// use std::sync::atomic::AtomicU32;
+/// use std::sync::atomic::Ordering;
+/// use std::time::Duration;
+///
+/// use discern::async_trait;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+/// use discern::retry::RetryPolicy;
+/// use discern::retry::RetryingHandler;
+/// use discern::runtime::TokioRuntime;
+///
+/// struct SetUserEmailCommandHandler {
+///     attempts: AtomicU32,
+/// }
+///
+/// #[async_trait]
+/// impl CommandHandler<SetUserEmailCommand> for SetUserEmailCommandHandler {
+///     async fn handle(&self, _command: SetUserEmailCommand) -> Result<(), std::io::Error> {
+///         // The database connection drops during the first two attempts.
+///         if self.attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+///             return Err(std::io::ErrorKind::ConnectionReset.into());
+///         }
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let policy = RetryPolicy::new(3)
+///     .with_backoff(Duration::from_millis(1), Duration::from_millis(10))
+///     .with_jitter(0.5)
+///     .retry_if(|error: &std::io::Error| error.kind() == std::io::ErrorKind::ConnectionReset);
+///
+/// let command_bus = command_bus! {
+///     SetUserEmailCommand => RetryingHandler::new(
+///         SetUserEmailCommandHandler { attempts: AtomicU32::new(0) },
+///         policy,
+///         TokioRuntime,
+///     ),
+/// };
+///
+/// let command = SetUserEmailCommand { user_id: 1, email: "alice@localhost".to_string() };
+///
+/// assert!(command_bus.dispatch(command).await.is_ok());
+/// # });
+/// ```
+pub struct RetryingHandler<H, E> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    policy: RetryPolicy<E>,
+    #[doc(hidden)]
+    runtime: Box<dyn Runtime>,
+    #[doc(hidden)]
+    retries: AtomicU64,
+}
+
+/// The `RetryingHandler` implementation.
+impl<H, E> RetryingHandler<H, E> {
+    /// Creates a new `RetryingHandler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to retry.
+    /// * `policy` - The policy deciding when, and how often, to retry.
+    /// * `runtime` - The runtime used to wait between attempts.
+    pub fn new(handler: H, policy: RetryPolicy<E>, runtime: impl Runtime) -> Self {
+        Self {
+            handler,
+            policy,
+            runtime: Box::new(runtime),
+            retries: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the policy of this handler.
+    pub fn policy(&self) -> &RetryPolicy<E> {
+        &self.policy
+    }
+
+    /// Returns the number of retries made, across all messages.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Runs the attempts, until one succeeds, the error is not worth retrying, or the attempts are exhausted.
+    async fn attempt<M, T, F>(&self, message: M, handle: impl Fn(M) -> F) -> Result<T, E>
+    where
+        M: Clone,
+        F: std::future::Future<Output = Result<T, E>>,
+    {
+        let mut retry = 0;
+        loop {
+            let error = match handle(message.clone()).await {
+                Ok(output) => return Ok(output),
+                Err(error) => error,
+            };
+
+            retry += 1;
+            if retry >= self.policy.max_attempts || !self.policy.should_retry(&error) {
+                return Err(error);
+            }
+
+            self.retries.fetch_add(1, Ordering::Relaxed);
+            self.runtime.sleep(self.policy.delay(retry)).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for RetryingHandler<H, Q::Error>
+where
+    Q: Query + Clone,
+    H: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        self.attempt(query, |query| self.handler.handle(query))
+            .await
+    }
+}
+
+#[async_trait]
+impl<C, H> CommandHandler<C> for RetryingHandler<H, C::Error>
+where
+    C: Command + Idempotent + Clone,
+    H: CommandHandler<C>,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        self.attempt(command, |command| self.handler.handle(command))
+            .await
+    }
+}
+
+#[async_trait]
+impl<E, H> EventHandler<E> for RetryingHandler<H, E::Error>
+where
+    E: Event,
+    H: EventHandler<E>,
+{
+    async fn handle(&self, event: &E) -> Result<(), E::Error> {
+        self.attempt(event, |event| self.handler.handle(event))
+            .await
+    }
+}
+
+/// Debug implementation for `RetryingHandler`
+impl<H, E> Debug for RetryingHandler<H, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("RetryingHandler")
+            .field("policy", &self.policy)
+            .field("retries", &self.retries())
+            .finish()
+    }
+}
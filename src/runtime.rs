@@ -0,0 +1,302 @@
+//! The `runtime` module abstracts the async runtime services used by discern.
+//!
+//! Features such as timeouts and spawning background work need an executor, and a timer. Instead of
+//! depending on a specific executor, discern accesses these services through the [Runtime] trait, so that
+//! libraries built on top of discern don't force a specific executor on their users.
+//!
+//! - [Runtime]: Provides spawning and timers.
+//! - [Task]: A handle to a spawned task.
+//! - [timeout]: Requires a future to complete before the specified duration has elapsed.
+//! - [TokioRuntime]: The [tokio](https://tokio.rs) implementation ( requires the `tokio` feature ).
+//! - [SmolRuntime]: The [smol](https://docs.rs/smol) implementation ( requires the `smol` feature ).
+//! - [DefaultRuntime]: The runtime used by the registration options of the registry macros.
+
+use alloc::boxed::Box;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::future::Future;
+use core::pin::pin;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+use core::time::Duration;
+
+/// A pinned, boxed future that can be sent across threads.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The `Runtime` trait provides the async runtime services used by discern.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::time::Duration;
+///
+/// use discern::runtime::Runtime;
+/// use discern::runtime::TokioRuntime;
+///
+/// let runtime = TokioRuntime;
+///
+/// let task = runtime.spawn(Box::pin(async {
+///     println!("Hello from a background task!");
+/// }));
+///
+/// runtime.sleep(Duration::from_millis(1)).await;
+///
+/// task.await;
+/// # });
+/// ```
+pub trait Runtime: Send + Sync + 'static {
+    /// Spawns a future onto the runtime.
+    ///
+    /// The future starts running in the background immediately. Dropping the returned [Task]
+    /// detaches it, while [Task::abort] cancels it.
+    fn spawn(&self, future: BoxFuture<'static, ()>) -> Task;
+
+    /// Returns a future that completes after the specified duration has elapsed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The `TaskHandle` trait is implemented by the runtime specific handles wrapped by [Task].
+pub trait TaskHandle: Future<Output = ()> + Send + Unpin {
+    /// Cancels the task.
+    fn abort(&mut self);
+}
+
+/// A handle to a task spawned using [Runtime::spawn].
+///
+/// Awaiting a `Task` waits for it to finish, dropping it lets the task run in the background.
+pub struct Task {
+    #[doc(hidden)]
+    handle: Box<dyn TaskHandle>,
+}
+
+/// The `Task` implementation.
+impl Task {
+    /// Creates a new `Task` from a runtime specific handle.
+    pub fn new(handle: impl TaskHandle + 'static) -> Self {
+        Self {
+            handle: Box::new(handle),
+        }
+    }
+
+    /// Cancels the task.
+    ///
+    /// Awaiting an aborted task completes immediately.
+    pub fn abort(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl Future for Task {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        Pin::new(&mut self.handle).poll(cx)
+    }
+}
+
+/// Debug implementation for `Task`
+impl Debug for Task {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Task").finish()
+    }
+}
+
+/// The error returned by [timeout] when the duration elapsed before the future completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Elapsed {}
+
+/// Requires a future to complete before the specified duration has elapsed.
+///
+/// # Arguments
+///
+/// * `runtime` - The runtime providing the timer.
+/// * `duration` - The maximum duration the future is allowed to run.
+/// * `future` - The future to run.
+///
+/// # Returns
+///
+/// The output of the future, or [Elapsed] if the duration elapsed first, in which case the future is dropped.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::time::Duration;
+///
+/// use discern::runtime::timeout;
+/// use discern::runtime::Elapsed;
+/// use discern::runtime::Runtime;
+/// use discern::runtime::TokioRuntime;
+///
+/// let result = timeout(&TokioRuntime, Duration::from_millis(1), async {
+///     TokioRuntime.sleep(Duration::from_secs(60)).await;
+/// }).await;
+///
+/// assert_eq!(result, Err(Elapsed));
+/// # });
+/// ```
+pub async fn timeout<F: Future>(
+    runtime: &(impl Runtime + ?Sized),
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let mut future = pin!(future);
+    let mut sleep = runtime.sleep(duration);
+
+    core::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// The [tokio](https://tokio.rs) implementation of the [Runtime] trait.
+///
+/// Tasks are spawned onto the current tokio runtime, so `TokioRuntime` must be used from within one.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio")]
+impl Runtime for TokioRuntime {
+    fn spawn(&self, future: BoxFuture<'static, ()>) -> Task {
+        Task::new(TokioTaskHandle(tokio::task::spawn(future)))
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[doc(hidden)]
+struct TokioTaskHandle(tokio::task::JoinHandle<()>);
+
+#[cfg(feature = "tokio")]
+impl Future for TokioTaskHandle {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Ready(Err(error)) if error.is_panic() => {
+                std::panic::resume_unwind(error.into_panic())
+            }
+            Poll::Ready(_) => Poll::Ready(()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl TaskHandle for TokioTaskHandle {
+    fn abort(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The [smol](https://docs.rs/smol) implementation of the [Runtime] trait.
+///
+/// Tasks are spawned onto smol's global executor.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use discern::runtime::timeout;
+/// use discern::runtime::Runtime;
+/// use discern::runtime::SmolRuntime;
+///
+/// smol::block_on(async {
+///     let task = SmolRuntime.spawn(Box::pin(async {}));
+///     let result = timeout(&SmolRuntime, Duration::from_secs(1), task).await;
+///
+///     assert!(result.is_ok());
+/// });
+/// ```
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolRuntime;
+
+#[cfg(feature = "smol")]
+impl Runtime for SmolRuntime {
+    fn spawn(&self, future: BoxFuture<'static, ()>) -> Task {
+        Task::new(SmolTaskHandle(Some(smol::spawn(future))))
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let timer = smol::Timer::after(duration);
+
+        Box::pin(async move {
+            timer.await;
+        })
+    }
+}
+
+#[cfg(feature = "smol")]
+#[doc(hidden)]
+struct SmolTaskHandle(Option<smol::Task<()>>);
+
+#[cfg(feature = "smol")]
+impl Future for SmolTaskHandle {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.0.as_mut() {
+            Some(task) => Pin::new(task).poll(cx),
+            None => Poll::Ready(()),
+        }
+    }
+}
+
+#[cfg(feature = "smol")]
+impl TaskHandle for SmolTaskHandle {
+    fn abort(&mut self) {
+        // Dropping a smol task cancels it.
+        self.0 = None;
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Drop for SmolTaskHandle {
+    fn drop(&mut self) {
+        if let Some(task) = self.0.take() {
+            task.detach();
+        }
+    }
+}
+
+/// The runtime used by the registration options of the [command_registry](crate::command_registry) and
+/// [query_registry](crate::query_registry) macros.
+///
+/// This is [TokioRuntime] when the `tokio` feature is enabled, and [SmolRuntime] otherwise.
+#[cfg(feature = "tokio")]
+pub type DefaultRuntime = TokioRuntime;
+
+/// The runtime used by the registration options of the [command_registry](crate::command_registry) and
+/// [query_registry](crate::query_registry) macros.
+///
+/// This is [TokioRuntime] when the `tokio` feature is enabled, and [SmolRuntime] otherwise.
+#[cfg(all(feature = "smol", not(feature = "tokio")))]
+pub type DefaultRuntime = SmolRuntime;
@@ -0,0 +1,184 @@
+//! The `scope` module provides values scoped to the polling of a dispatch.
+//!
+//! Values are stored in thread-locals, which are set while the future handling a message is being polled, and
+//! restored once the poll returns. Since handlers are polled by the bus, the values are visible to everything
+//! the handler runs inline, including nested dispatches, but not to tasks it spawns.
+
+use core::cell::Cell;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+use std::thread::LocalKey;
+
+use crate::caller::CallerId;
+use crate::effect::EffectLedger;
+use crate::metadata::EventMetadata;
+use crate::outcome::EventLog;
+use crate::spawn::DispatchScope;
+
+std::thread_local! {
+    /// The type name of the query being handled by a read-only `QueryBus`, if any.
+    static READ_ONLY: Cell<Option<&'static str>> = const { Cell::new(None) };
+
+    /// The caller on whose behalf the current dispatch runs, if any.
+    static CALLER: RefCell<Option<CallerId>> = const { RefCell::new(None) };
+
+    /// The dispatch scope tracking the tasks spawned on behalf of the current dispatch, if any.
+    static DISPATCH_SCOPE: RefCell<Option<DispatchScope>> = const { RefCell::new(None) };
+
+    /// The ledger recording the side effects of the current dispatch, if any.
+    static EFFECT_LEDGER: RefCell<Option<EffectLedger>> = const { RefCell::new(None) };
+
+    /// The log recording the events published by the current dispatch, if any.
+    static EVENT_LOG: RefCell<Option<EventLog>> = const { RefCell::new(None) };
+
+    /// The metadata of the event being handled by a subscriber, if any.
+    static EVENT_METADATA: RefCell<Option<EventMetadata>> = const { RefCell::new(None) };
+}
+
+/// A future marking the polling of a query handler as read-only.
+#[doc(hidden)]
+pub(crate) struct ReadOnly<F> {
+    future: F,
+    query: Option<&'static str>,
+}
+
+impl<F> ReadOnly<F> {
+    /// Marks the polling of `future` as handling the given query, if `query` is not `None`.
+    pub(crate) fn new(future: F, query: Option<&'static str>) -> Self {
+        Self { future, query }
+    }
+}
+
+impl<F: Future + Unpin> Future for ReadOnly<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let Some(query) = self.query else {
+            return Pin::new(&mut self.future).poll(cx);
+        };
+
+        let _restore = Restore(READ_ONLY.replace(Some(query)));
+
+        Pin::new(&mut self.future).poll(cx)
+    }
+}
+
+/// Restores the previous read-only scope when dropped, including when the handler panics.
+#[doc(hidden)]
+struct Restore(Option<&'static str>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        READ_ONLY.set(self.0);
+    }
+}
+
+/// Panics if a command is dispatched while a read-only `QueryBus` is handling a query.
+#[track_caller]
+pub(crate) fn assert_writable(command: &'static str) {
+    if let Some(query) = READ_ONLY.get() {
+        panic!(
+            "Command {:?} dispatched while handling query {:?}, queries must not mutate state",
+            command, query
+        );
+    }
+}
+
+/// Returns the caller on whose behalf the current dispatch runs, if any.
+pub(crate) fn caller() -> Option<CallerId> {
+    CALLER.with_borrow(Clone::clone)
+}
+
+/// Calls `f` with `caller` as the current caller.
+///
+/// The caller is moved into the scope for the duration of `f`, and moved back out afterwards, including when `f` panics.
+pub(crate) fn with_caller<T>(caller: &mut Option<CallerId>, f: impl FnOnce() -> T) -> T {
+    with_value(&CALLER, caller, f)
+}
+
+/// Returns the dispatch scope of the current dispatch, if any.
+pub(crate) fn dispatch_scope() -> Option<DispatchScope> {
+    DISPATCH_SCOPE.with_borrow(Clone::clone)
+}
+
+/// Calls `f` with `scope` as the current dispatch scope.
+///
+/// The scope is moved into the thread-local for the duration of `f`, and moved back out afterwards, including when `f`
+/// panics.
+pub(crate) fn with_dispatch_scope<T>(
+    scope: &mut Option<DispatchScope>,
+    f: impl FnOnce() -> T,
+) -> T {
+    with_value(&DISPATCH_SCOPE, scope, f)
+}
+
+/// Returns the ledger recording the side effects of the current dispatch, if any.
+pub(crate) fn effect_ledger() -> Option<EffectLedger> {
+    EFFECT_LEDGER.with_borrow(Clone::clone)
+}
+
+/// Calls `f` with `ledger` as the current effect ledger.
+///
+/// The ledger is moved into the thread-local for the duration of `f`, and moved back out afterwards, including when
+/// `f` panics.
+pub(crate) fn with_effect_ledger<T>(ledger: &mut Option<EffectLedger>, f: impl FnOnce() -> T) -> T {
+    with_value(&EFFECT_LEDGER, ledger, f)
+}
+
+/// Returns the log recording the events published by the current dispatch, if any.
+pub(crate) fn event_log() -> Option<EventLog> {
+    EVENT_LOG.with_borrow(Clone::clone)
+}
+
+/// Calls `f` with `log` as the current event log.
+///
+/// The log is moved into the thread-local for the duration of `f`, and moved back out afterwards, including when
+/// `f` panics.
+pub(crate) fn with_event_log<T>(log: &mut Option<EventLog>, f: impl FnOnce() -> T) -> T {
+    with_value(&EVENT_LOG, log, f)
+}
+
+/// Returns the metadata of the event being handled, if any.
+pub(crate) fn event_metadata() -> Option<EventMetadata> {
+    EVENT_METADATA.with_borrow(Clone::clone)
+}
+
+/// Calls `f` with `metadata` as the metadata of the event being handled.
+///
+/// The metadata is moved into the thread-local for the duration of `f`, and moved back out afterwards, including
+/// when `f` panics.
+pub(crate) fn with_event_metadata<T>(
+    metadata: &mut Option<EventMetadata>,
+    f: impl FnOnce() -> T,
+) -> T {
+    with_value(&EVENT_METADATA, metadata, f)
+}
+
+/// Calls `f` with `value` moved into the given thread-local.
+fn with_value<V: 'static, T>(
+    key: &'static LocalKey<RefCell<Option<V>>>,
+    value: &mut Option<V>,
+    f: impl FnOnce() -> T,
+) -> T {
+    key.with_borrow_mut(|current| core::mem::swap(current, value));
+    let _restore = RestoreValue { key, value };
+
+    f()
+}
+
+/// Swaps the previous value back into the thread-local when dropped.
+#[doc(hidden)]
+struct RestoreValue<'a, V: 'static> {
+    key: &'static LocalKey<RefCell<Option<V>>>,
+    value: &'a mut Option<V>,
+}
+
+impl<V: 'static> Drop for RestoreValue<'_, V> {
+    fn drop(&mut self) {
+        self.key
+            .with_borrow_mut(|current| core::mem::swap(current, self.value));
+    }
+}
@@ -0,0 +1,329 @@
+//! The `spawn` module provides structured concurrency for the work spawned on behalf of a dispatch.
+//!
+//! Handlers sometimes start background work, e.g. dispatching a follow-up command without waiting for it. When the
+//! request that caused the dispatch is aborted, such work keeps running, detached from anything that could wait for
+//! it, or cancel it. A [DispatchScope] tracks the tasks spawned on behalf of a dispatch, so that they can be awaited,
+//! or cancelled, and cancels the remaining ones once the scope is dropped.
+//!
+//! - [DispatchScope]: Tracks the tasks spawned on behalf of a dispatch.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::future::Future;
+use std::mem;
+use std::pin::pin;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::Weak;
+use std::task::Poll;
+
+use crate::command::Command;
+use crate::command::CommandBus;
+use crate::runtime::Runtime;
+use crate::runtime::Task;
+use crate::scope;
+
+/// Tracks the tasks spawned on behalf of a dispatch.
+///
+/// A `DispatchScope` is a handle, cloning it returns a handle to the same scope. Futures run using
+/// [DispatchScope::run], including the handlers they dispatch to, can retrieve the scope using
+/// [DispatchScope::current], and spawn tasks on it. Tasks spawned on the scope run within it as well, so the tasks
+/// they spawn are tracked too.
+///
+/// Once every handle to the scope is dropped, the tasks that are still running are cancelled.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use std::sync::atomic::AtomicUsize;
+/// # use std::sync::atomic::Ordering;
+/// # use std::sync::Arc;
+/// #
+/// # use discern::async_trait;
+/// # use discern::command::Command;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct SendWelcomeEmailCommand {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Command for SendWelcomeEmailCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # struct SendWelcomeEmailCommandHandler {
+/// #     sent: Arc<AtomicUsize>,
+/// # }
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<SendWelcomeEmailCommand> for SendWelcomeEmailCommandHandler {
+/// #     async fn handle(&self, command: SendWelcomeEmailCommand) -> Result<(), std::io::Error> {
+/// #         self.sent.fetch_add(1, Ordering::SeqCst);
+/// #
+/// #         Ok(())
+/// #     }
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::command::CommandBus;
+/// use discern::command_bus;
+/// use discern::runtime::TokioRuntime;
+/// use discern::spawn::DispatchScope;
+///
+/// struct CreateUserCommandHandler {
+///     command_bus: CommandBus,
+/// }
+///
+/// #[async_trait]
+/// impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+///     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+///         let user_id = 1;
+///
+///         // Send the welcome email in the background, without delaying the response.
+///         if let Some(scope) = DispatchScope::current() {
+///             scope.dispatch(&self.command_bus, SendWelcomeEmailCommand { user_id });
+///         }
+///
+///         Ok(user_id)
+///     }
+/// }
+///
+/// let sent = Arc::new(AtomicUsize::new(0));
+/// let command_bus = command_bus! {
+///     SendWelcomeEmailCommand => SendWelcomeEmailCommandHandler { sent: sent.clone() },
+/// };
+///
+/// command_bus.register(CreateUserCommandHandler { command_bus: command_bus.clone() });
+///
+/// let scope = DispatchScope::new(TokioRuntime);
+///
+/// let user_id = scope.run(command_bus.dispatch(CreateUserCommand {
+///     username: "alice".to_string(),
+/// })).await.unwrap();
+///
+/// assert_eq!(user_id, 1);
+///
+/// // Wait for the background work to complete before responding.
+/// scope.join().await;
+///
+/// assert_eq!(sent.load(Ordering::SeqCst), 1);
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct DispatchScope {
+    #[doc(hidden)]
+    inner: Arc<Inner>,
+}
+
+/// The state shared between the handles of a [DispatchScope].
+#[doc(hidden)]
+struct Inner {
+    runtime: Box<dyn Runtime>,
+    tasks: Mutex<Vec<Task>>,
+    running: Arc<AtomicUsize>,
+}
+
+/// The `DispatchScope` implementation.
+impl DispatchScope {
+    /// Creates a new `DispatchScope`.
+    ///
+    /// # Arguments
+    ///
+    /// * `runtime` - The runtime used to spawn the tasks.
+    pub fn new(runtime: impl Runtime) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                runtime: Box::new(runtime),
+                tasks: Mutex::new(Vec::new()),
+                running: Arc::new(AtomicUsize::new(0)),
+            }),
+        }
+    }
+
+    /// Returns the scope of the current dispatch, if it runs within one.
+    pub fn current() -> Option<DispatchScope> {
+        scope::dispatch_scope()
+    }
+
+    /// Runs the given future within this scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - The future to run, usually a dispatch.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut current = Some(self.clone());
+
+        std::future::poll_fn(|cx| {
+            scope::with_dispatch_scope(&mut current, || future.as_mut().poll(cx))
+        })
+        .await
+    }
+
+    /// Spawns a task within this scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - The future to run in the background.
+    pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        let running = Running::new(self.inner.running.clone());
+        let inner = Arc::downgrade(&self.inner);
+
+        let task = self.inner.runtime.spawn(Box::pin(async move {
+            let _running = running;
+            let mut future = pin!(future);
+
+            // The task only holds a weak reference to the scope, so that dropping every handle cancels it.
+            std::future::poll_fn(|cx| {
+                let mut current = Weak::upgrade(&inner).map(|inner| DispatchScope { inner });
+
+                scope::with_dispatch_scope(&mut current, || future.as_mut().poll(cx))
+            })
+            .await
+        }));
+
+        self.inner
+            .tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(task);
+    }
+
+    /// Dispatches the given command in a task spawned within this scope, without waiting for it.
+    ///
+    /// The result of the command is discarded, use [DispatchScope::spawn] to handle it.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_bus` - The command bus to dispatch the command to.
+    /// * `command` - The command to dispatch.
+    pub fn dispatch<C: Command>(&self, command_bus: &CommandBus, command: C) {
+        let command_bus = command_bus.clone();
+
+        self.spawn(async move {
+            let _ = command_bus.try_dispatch(command).await;
+        });
+    }
+
+    /// Returns the number of tasks of this scope that are still running.
+    pub fn running(&self) -> usize {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+
+    /// Waits for every task of this scope to complete, including the tasks spawned while waiting.
+    ///
+    /// The tasks stay tracked by the scope until they complete, so dropping the returned future, e.g. because the
+    /// request waiting for it was aborted, doesn't detach them, they can still be cancelled using
+    /// [DispatchScope::cancel], or by dropping the scope.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// use std::time::Duration;
+    ///
+    /// use discern::runtime::Runtime;
+    /// use discern::runtime::TokioRuntime;
+    /// use discern::runtime::timeout;
+    /// use discern::spawn::DispatchScope;
+    ///
+    /// let scope = DispatchScope::new(TokioRuntime);
+    /// scope.spawn(async {
+    ///     TokioRuntime.sleep(Duration::from_secs(60)).await;
+    /// });
+    ///
+    /// // The request is aborted while waiting for the background work.
+    /// assert!(timeout(&TokioRuntime, Duration::from_millis(1), scope.join()).await.is_err());
+    /// assert_eq!(scope.running(), 1);
+    ///
+    /// scope.cancel();
+    /// TokioRuntime.sleep(Duration::from_millis(1)).await;
+    ///
+    /// assert_eq!(scope.running(), 0);
+    /// # });
+    /// ```
+    pub async fn join(&self) {
+        std::future::poll_fn(|cx| {
+            let mut tasks = self
+                .inner
+                .tasks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            tasks.retain_mut(|task| Pin::new(task).poll(cx).is_pending());
+            if tasks.is_empty() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Cancels every task of this scope that is still running.
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+}
+
+/// Debug implementation for `DispatchScope`
+impl Debug for DispatchScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("DispatchScope")
+            .field("running", &self.running())
+            .finish()
+    }
+}
+
+/// The `Inner` implementation.
+impl Inner {
+    /// Cancels every task that is still running.
+    fn cancel(&self) {
+        let tasks = mem::take(&mut *self.tasks.lock().unwrap_or_else(PoisonError::into_inner));
+        for mut task in tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Counts a task as running until dropped, i.e. once the task completed, or was cancelled.
+#[doc(hidden)]
+struct Running(Arc<AtomicUsize>);
+
+impl Running {
+    fn new(running: Arc<AtomicUsize>) -> Self {
+        running.fetch_add(1, Ordering::SeqCst);
+
+        Self(running)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
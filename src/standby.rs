@@ -0,0 +1,234 @@
+//! The `standby` module provides blue/green deployments of handlers within a long-lived process.
+//!
+//! Replacing handlers at runtime, e.g. after reloading configuration, or a plugin, is risky: a new handler that
+//! fails to start, or misbehaves under real traffic, takes the whole bus down with it. A [Standby] holds a second
+//! registry on a bus of its own, which can be verified, using startup checks, and shadow traffic, before the live
+//! bus is switched over to it. The switch is atomic, and can be rolled back using the returned [Promoted] handle.
+//!
+//! - [Standby]: A warm standby registry, waiting to be promoted.
+//! - [Promoted]: A promoted registry, which can be rolled back.
+//! - [Swappable]: A bus whose registry can be replaced at runtime.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+
+use crate::command::CommandBus;
+use crate::query::QueryBus;
+use crate::registry::CommandHandlerRegistry;
+use crate::registry::QueryHandlerRegistry;
+
+/// The `Swappable` trait represents a bus whose registry can be replaced at runtime.
+///
+/// It is implemented by the [CommandBus], and the [QueryBus].
+pub trait Swappable: Clone {
+    /// The registry of the bus.
+    type Registry: Clone;
+
+    /// Creates a new bus from the given registry.
+    fn from_registry(registry: Self::Registry) -> Self;
+
+    /// Atomically replaces the registry of the bus, returning the previous one.
+    fn replace(&self, registry: Self::Registry) -> Self::Registry;
+
+    /// Returns the type names of the messages that have a registered handler.
+    fn handlers(&self) -> Vec<&'static str>;
+}
+
+impl Swappable for CommandBus {
+    type Registry = CommandHandlerRegistry;
+
+    fn from_registry(registry: CommandHandlerRegistry) -> Self {
+        CommandBus::new(registry)
+    }
+
+    fn replace(&self, registry: CommandHandlerRegistry) -> CommandHandlerRegistry {
+        CommandBus::replace(self, registry)
+    }
+
+    fn handlers(&self) -> Vec<&'static str> {
+        CommandBus::handlers(self)
+    }
+}
+
+impl Swappable for QueryBus {
+    type Registry = QueryHandlerRegistry;
+
+    fn from_registry(registry: QueryHandlerRegistry) -> Self {
+        QueryBus::new(registry)
+    }
+
+    fn replace(&self, registry: QueryHandlerRegistry) -> QueryHandlerRegistry {
+        QueryBus::replace(self, registry)
+    }
+
+    fn handlers(&self) -> Vec<&'static str> {
+        QueryBus::handlers(self)
+    }
+}
+
+/// A warm standby registry, waiting to be promoted.
+///
+/// The standby registry is available on a bus of its own, see [Standby::bus], which shares nothing with the live
+/// bus, so that it can be verified without affecting the live traffic, e.g. by dispatching a copy of the live
+/// messages to it. Once verified, [Standby::promote] atomically switches the live bus over to it.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::query::Query;
+/// #
+/// # #[derive(Debug)]
+/// # struct GetPriceQuery {
+/// #     product_id: u64,
+/// # }
+/// #
+/// # impl Query for GetPriceQuery {
+/// #     type Output = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::query::QueryHandler;
+/// use discern::query_bus;
+/// use discern::query_registry;
+/// use discern::standby::Standby;
+///
+/// struct GetPriceQueryHandler {
+///     version: u64,
+/// }
+///
+/// #[async_trait]
+/// impl QueryHandler<GetPriceQuery> for GetPriceQueryHandler {
+///     async fn handle(&self, query: GetPriceQuery) -> Result<u64, std::io::Error> {
+///         Ok(100 * self.version)
+///     }
+/// }
+///
+/// let query_bus = query_bus! {
+///     GetPriceQuery => GetPriceQueryHandler { version: 1 },
+/// };
+///
+/// let standby = Standby::new(&query_bus, query_registry! {
+///     GetPriceQuery => GetPriceQueryHandler { version: 2 },
+/// });
+///
+/// // Verify the new handlers before they receive live traffic.
+/// assert!(standby.missing().is_empty());
+/// assert_eq!(standby.bus().dispatch(GetPriceQuery { product_id: 1 }).await.unwrap(), 200);
+/// assert_eq!(query_bus.dispatch(GetPriceQuery { product_id: 1 }).await.unwrap(), 100);
+///
+/// let promoted = standby.promote();
+/// assert_eq!(query_bus.dispatch(GetPriceQuery { product_id: 1 }).await.unwrap(), 200);
+///
+/// // The new handlers misbehave, switch back to the previous ones.
+/// promoted.rollback();
+/// assert_eq!(query_bus.dispatch(GetPriceQuery { product_id: 1 }).await.unwrap(), 100);
+/// # });
+/// ```
+pub struct Standby<B: Swappable> {
+    #[doc(hidden)]
+    live: B,
+    #[doc(hidden)]
+    standby: B,
+    #[doc(hidden)]
+    registry: B::Registry,
+}
+
+/// The `Standby` implementation.
+impl<B: Swappable> Standby<B> {
+    /// Creates a new `Standby`.
+    ///
+    /// # Arguments
+    ///
+    /// * `live` - The live bus, which is switched over to the standby registry once promoted.
+    /// * `registry` - The standby registry.
+    pub fn new(live: &B, registry: B::Registry) -> Self {
+        Self {
+            live: live.clone(),
+            standby: B::from_registry(registry.clone()),
+            registry,
+        }
+    }
+
+    /// Returns the bus of the standby registry, used to verify it.
+    pub fn bus(&self) -> &B {
+        &self.standby
+    }
+
+    /// Returns the type names of the messages handled by the live bus, that have no handler in the standby
+    /// registry.
+    ///
+    /// Once promoted, dispatching such messages to the live bus fails.
+    pub fn missing(&self) -> Vec<&'static str> {
+        let handlers = self.standby.handlers();
+
+        let mut missing: Vec<&'static str> = self
+            .live
+            .handlers()
+            .into_iter()
+            .filter(|name| !handlers.contains(name))
+            .collect();
+
+        missing.sort_unstable();
+
+        missing
+    }
+
+    /// Atomically switches the live bus over to the standby registry.
+    ///
+    /// Dispatches that already started complete using the previous handlers, while later ones use the standby
+    /// handlers.
+    pub fn promote(self) -> Promoted<B> {
+        let previous = self.live.replace(self.registry);
+
+        Promoted {
+            live: self.live,
+            previous,
+        }
+    }
+}
+
+/// Debug implementation for `Standby`
+impl<B: Swappable> Debug for Standby<B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Standby")
+            .field("handlers", &self.standby.handlers())
+            .field("missing", &self.missing())
+            .finish()
+    }
+}
+
+/// A promoted registry, which can be rolled back.
+///
+/// `Promoted` is created using [Standby::promote]. Dropping it keeps the promoted registry live.
+pub struct Promoted<B: Swappable> {
+    #[doc(hidden)]
+    live: B,
+    #[doc(hidden)]
+    previous: B::Registry,
+}
+
+/// The `Promoted` implementation.
+impl<B: Swappable> Promoted<B> {
+    /// Atomically switches the live bus back to the registry it used before the promotion.
+    ///
+    /// # Returns
+    ///
+    /// A `Standby` holding the rolled back registry, which can be promoted again.
+    pub fn rollback(self) -> Standby<B> {
+        let registry = self.live.replace(self.previous);
+
+        Standby::new(&self.live, registry)
+    }
+}
+
+/// Debug implementation for `Promoted`
+impl<B: Swappable> Debug for Promoted<B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Promoted")
+            .field("handlers", &self.live.handlers())
+            .finish()
+    }
+}
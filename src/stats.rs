@@ -0,0 +1,168 @@
+//! The `stats` module provides the internal counters maintained by the `CommandBus` and `QueryBus`.
+//!
+//! Every bus keeps track of how many messages it dispatched, how many of them succeeded or failed, and
+//! how many are currently being handled. These counters are cheap to maintain, and are useful to evaluate
+//! the behavior of a bus under load, e.g. in benchmarks, or when exposing health information.
+//!
+//! - [BusStats]: A snapshot of the counters of a bus.
+
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+/// A snapshot of the counters maintained by a bus.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::async_trait;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct DeleteUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<DeleteUserCommand> for DeleteUserCommandHandler {
+/// #    async fn handle(&self, command: DeleteUserCommand) -> Result<(), std::io::Error> {
+/// #       if command.user_id == 0 {
+/// #           return Err(std::io::Error::other("user not found"));
+/// #       }
+/// #
+/// #       Ok(())
+/// #   }
+/// # }
+/// use discern::command_bus;
+/// use discern::stats::BusStats;
+///
+/// let command_bus = command_bus! {
+///    DeleteUserCommand => DeleteUserCommandHandler { /* ... */ },
+/// };
+///
+/// let _ = command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await;
+/// let _ = command_bus.dispatch(DeleteUserCommand { user_id: 0 }).await;
+///
+/// assert_eq!(command_bus.stats(), BusStats {
+///     dispatched: 2,
+///     succeeded: 1,
+///     failed: 1,
+///     in_flight: 0,
+/// });
+/// # });
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BusStats {
+    /// The number of messages dispatched through the bus.
+    pub dispatched: usize,
+    /// The number of messages that were handled successfully.
+    pub succeeded: usize,
+    /// The number of messages whose handler returned an error.
+    pub failed: usize,
+    /// The number of messages currently being handled.
+    pub in_flight: usize,
+}
+
+/// The counters maintained by a bus.
+///
+/// Only two atomic operations are performed per dispatch, the number of messages in flight
+/// is derived from the other counters.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    dispatched: AtomicUsize,
+    succeeded: AtomicUsize,
+    failed: AtomicUsize,
+    cancelled: AtomicUsize,
+    #[cfg(feature = "std")]
+    undelivered: AtomicUsize,
+    failed_followups: AtomicUsize,
+}
+
+impl Counters {
+    /// Records the start of a dispatch.
+    ///
+    /// The result of the dispatch is recorded using [InFlight::finish], dispatches whose guard is
+    /// dropped without being finished ( e.g. cancelled, or panicked ) are no longer counted as in flight.
+    pub(crate) fn start(&self) -> InFlight<'_> {
+        self.dispatched.fetch_add(1, Ordering::Relaxed);
+
+        InFlight {
+            counters: self,
+            finished: false,
+        }
+    }
+
+    /// Records events published by succeeded commands, which were not delivered to every subscriber.
+    #[cfg(feature = "std")]
+    pub(crate) fn record_undelivered(&self, count: usize) {
+        self.undelivered.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Returns the number of events published by succeeded commands, which were not delivered to every subscriber.
+    #[cfg(feature = "std")]
+    pub(crate) fn undelivered(&self) -> usize {
+        self.undelivered.load(Ordering::Relaxed)
+    }
+
+    /// Records follow-up commands that failed, or were skipped.
+    pub(crate) fn record_failed_followups(&self, count: usize) {
+        self.failed_followups.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Returns the number of follow-up commands that failed, or were skipped.
+    pub(crate) fn failed_followups(&self) -> usize {
+        self.failed_followups.load(Ordering::Relaxed)
+    }
+
+    /// Returns a snapshot of the counters.
+    pub(crate) fn snapshot(&self) -> BusStats {
+        let succeeded = self.succeeded.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let cancelled = self.cancelled.load(Ordering::Relaxed);
+        let dispatched = self.dispatched.load(Ordering::Relaxed);
+
+        BusStats {
+            dispatched,
+            succeeded,
+            failed,
+            in_flight: dispatched.saturating_sub(succeeded + failed + cancelled),
+        }
+    }
+}
+
+/// A guard that marks a dispatch as in flight until finished, or dropped.
+#[doc(hidden)]
+pub(crate) struct InFlight<'a> {
+    counters: &'a Counters,
+    finished: bool,
+}
+
+impl InFlight<'_> {
+    /// Records the result of the dispatch.
+    pub(crate) fn finish<T, E>(mut self, result: &Result<T, E>) {
+        self.finished = true;
+
+        match result {
+            Ok(_) => self.counters.succeeded.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.counters.failed.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.counters.cancelled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! The `stream` module provides streaming command results.
+//!
+//! Some commands produce many outputs over time, e.g. an export producing file chunks. Instead of buffering all
+//! of them into the metadata, a [StreamingCommand] returns a [CommandStream], which the caller consumes as the
+//! handler produces the outputs.
+//!
+//! - [StreamingCommand]: A command whose metadata is a stream.
+//! - [CommandStream]: The stream of outputs returned by a streaming command.
+
+use alloc::boxed::Box;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::pin::Pin;
+use core::task::Context;
+use core::task::Poll;
+
+pub use futures_core::Stream;
+
+use crate::command::Command;
+
+/// The `StreamingCommand` trait represents a command whose metadata is a stream of outputs.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use discern::async_trait;
+/// use discern::command::Command;
+/// use discern::command::CommandHandler;
+/// use discern::command_bus;
+/// use discern::stream::CommandStream;
+/// use discern::stream::StreamingCommand;
+///
+/// #[derive(Debug)]
+/// struct ExportReportCommand {
+///     size: usize,
+/// }
+///
+/// impl Command for ExportReportCommand {
+///     type Metadata = CommandStream<Vec<u8>>;
+///     type Error = std::io::Error;
+/// }
+///
+/// impl StreamingCommand for ExportReportCommand {
+///     type Item = Vec<u8>;
+/// }
+///
+/// struct ExportReportCommandHandler;
+///
+/// #[async_trait]
+/// impl CommandHandler<ExportReportCommand> for ExportReportCommandHandler {
+///     async fn handle(&self, command: ExportReportCommand) -> Result<CommandStream<Vec<u8>>, std::io::Error> {
+///         let chunks = (0..command.size).step_by(4).map(move |offset| {
+///             vec![0; 4.min(command.size - offset)]
+///         });
+///
+///         Ok(CommandStream::from_iter(chunks))
+///     }
+/// }
+///
+/// let command_bus = command_bus! {
+///     ExportReportCommand => ExportReportCommandHandler,
+/// };
+///
+/// let mut stream = command_bus.dispatch(ExportReportCommand { size: 10 }).await.unwrap();
+///
+/// let mut size = 0;
+/// while let Some(chunk) = stream.next().await {
+///     size += chunk.len();
+/// }
+///
+/// assert_eq!(size, 10);
+/// # });
+/// ```
+pub trait StreamingCommand: Command<Metadata = CommandStream<Self::Item>> {
+    /// The type of the outputs produced by the command.
+    type Item: Send + 'static;
+}
+
+/// The stream of outputs returned by a [StreamingCommand].
+///
+/// A `CommandStream` can be consumed using [CommandStream::next], or through its [Stream] implementation.
+pub struct CommandStream<T> {
+    #[doc(hidden)]
+    stream: Pin<Box<dyn Stream<Item = T> + Send>>,
+}
+
+/// The `CommandStream` implementation.
+impl<T> CommandStream<T> {
+    /// Creates a new `CommandStream` from a stream.
+    pub fn new(stream: impl Stream<Item = T> + Send + 'static) -> Self {
+        Self {
+            stream: Box::pin(stream),
+        }
+    }
+
+    /// Creates a new `CommandStream` from an iterator, whose items are produced as the stream is consumed.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: Send + Unpin + 'static,
+    {
+        Self::new(Iter(iter.into_iter()))
+    }
+
+    /// Returns the next output, or `None` once the stream is exhausted.
+    pub async fn next(&mut self) -> Option<T> {
+        core::future::poll_fn(|cx| self.stream.as_mut().poll_next(cx)).await
+    }
+}
+
+impl<T> Stream for CommandStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.stream.as_mut().poll_next(cx)
+    }
+}
+
+// SAFETY: the stream is only accessed through `&mut CommandStream`, or `Pin<&mut CommandStream>`, a shared
+// reference to a `CommandStream` gives no access to it, which is why `size_hint` is not forwarded. Sharing one
+// between threads is therefore sound, and allows using a `CommandStream` as the metadata of a command, which must
+// be `Sync`.
+unsafe impl<T> Sync for CommandStream<T> {}
+
+/// Debug implementation for `CommandStream`
+impl<T> Debug for CommandStream<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("CommandStream").finish()
+    }
+}
+
+/// A stream producing the items of an iterator.
+#[doc(hidden)]
+struct Iter<I>(I);
+
+impl<I: Iterator + Unpin> Stream for Iter<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.0.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
@@ -0,0 +1,224 @@
+//! The `switchboard` module provides runtime kill switches for command types.
+//!
+//! Operations such as database migrations require the write side of an application to be stopped, while
+//! the read side keeps serving queries. The [Switchboard] of a `CommandBus` disables specific command types,
+//! or puts the whole bus into read-only maintenance mode, without redeploying the application.
+//!
+//! - [Switchboard]: Disables command types at runtime.
+//! - [SwitchboardStatus]: A snapshot of the switchboard, suitable for health output.
+
+use std::any::TypeId;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::sync::RwLockReadGuard;
+
+use crate::command::Command;
+
+/// Disables command types at runtime.
+///
+/// Dispatching a disabled command using [CommandBus::try_dispatch](crate::command::CommandBus::try_dispatch) returns
+/// [DispatchError::Disabled](crate::error::DispatchError::Disabled) immediately, without invoking its handler.
+///
+/// The switchboard is shared between all clones of a `CommandBus`, and is only consulted when at least one
+/// command type is disabled, or the bus is in maintenance mode, so it adds no overhead otherwise.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// # use discern::async_trait;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand {
+/// #    user_id: u64,
+/// # }
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #   type Metadata = ();
+/// #   type Error = std::io::Error;
+/// # }
+/// #
+/// # struct DeleteUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<DeleteUserCommand> for DeleteUserCommandHandler {
+/// #    async fn handle(&self, command: DeleteUserCommand) -> Result<(), std::io::Error> {
+/// #       Ok(())
+/// #   }
+/// # }
+/// use discern::command_bus;
+/// use discern::error::DispatchError;
+///
+/// let command_bus = command_bus! {
+///    DeleteUserCommand => DeleteUserCommandHandler { /* ... */ },
+/// };
+///
+/// // Stop the write side before migrating the database.
+/// command_bus.switchboard().enter_maintenance();
+///
+/// let result = command_bus.dispatch(DeleteUserCommand { user_id: 1 }).await;
+/// assert!(matches!(result, Err(DispatchError::Disabled)));
+///
+/// // Resume the write side once the migration is complete.
+/// command_bus.switchboard().exit_maintenance();
+///
+/// let result = command_bus.try_dispatch(DeleteUserCommand { user_id: 1 }).await;
+/// assert!(result.is_ok());
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Switchboard {
+    #[doc(hidden)]
+    engaged: AtomicBool,
+    #[doc(hidden)]
+    state: RwLock<State>,
+}
+
+/// The state of a [Switchboard].
+#[doc(hidden)]
+#[derive(Debug, Default)]
+struct State {
+    maintenance: bool,
+    disabled: BTreeMap<TypeId, &'static str>,
+    exempt: BTreeSet<TypeId>,
+}
+
+/// A snapshot of a [Switchboard].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct SwitchboardStatus {
+    /// Whether the bus is in maintenance mode.
+    pub maintenance: bool,
+    /// The names of the disabled commands, in no particular order.
+    pub disabled: Vec<&'static str>,
+}
+
+/// The `Switchboard` implementation.
+impl Switchboard {
+    /// Creates a new `Switchboard`, with all commands enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disables the command type `C`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use discern::command::Command;
+    /// #
+    /// # #[derive(Debug)]
+    /// # struct DeleteUserCommand;
+    /// #
+    /// # impl Command for DeleteUserCommand {
+    /// #   type Metadata = ();
+    /// #   type Error = std::io::Error;
+    /// # }
+    /// use discern::switchboard::Switchboard;
+    ///
+    /// let switchboard = Switchboard::new();
+    ///
+    /// switchboard.disable::<DeleteUserCommand>();
+    /// assert!(switchboard.is_disabled::<DeleteUserCommand>());
+    ///
+    /// switchboard.enable::<DeleteUserCommand>();
+    /// assert!(!switchboard.is_disabled::<DeleteUserCommand>());
+    /// ```
+    pub fn disable<C: Command>(&self) {
+        self.disable_id(TypeId::of::<C>(), C::name());
+    }
+
+    /// Enables the command type `C`, previously disabled using [Switchboard::disable].
+    ///
+    /// Commands remain rejected while the bus is in maintenance mode.
+    pub fn enable<C: Command>(&self) {
+        self.enable_id(TypeId::of::<C>());
+    }
+
+    /// Puts the bus into read-only maintenance mode, disabling all command types.
+    pub fn enter_maintenance(&self) {
+        self.update(|state| state.maintenance = true);
+    }
+
+    /// Takes the bus out of maintenance mode.
+    ///
+    /// Command types disabled using [Switchboard::disable] remain disabled.
+    pub fn exit_maintenance(&self) {
+        self.update(|state| state.maintenance = false);
+    }
+
+    /// Returns whether the bus is in maintenance mode.
+    pub fn is_maintenance(&self) -> bool {
+        self.read().maintenance
+    }
+
+    /// Returns whether dispatching a command of type `C` is currently rejected.
+    pub fn is_disabled<C: Command>(&self) -> bool {
+        self.is_disabled_id(TypeId::of::<C>())
+    }
+
+    /// Returns a snapshot of the switchboard.
+    pub fn status(&self) -> SwitchboardStatus {
+        let state = self.read();
+
+        SwitchboardStatus {
+            maintenance: state.maintenance,
+            disabled: state.disabled.values().copied().collect(),
+        }
+    }
+
+    /// Returns whether dispatching a command with the given type id is currently rejected.
+    pub(crate) fn is_disabled_id(&self, id: TypeId) -> bool {
+        if !self.engaged.load(Ordering::Acquire) {
+            return false;
+        }
+
+        let state = self.read();
+
+        (state.maintenance && !state.exempt.contains(&id)) || state.disabled.contains_key(&id)
+    }
+
+    /// Disables the command with the given type id.
+    pub(crate) fn disable_id(&self, id: TypeId, name: &'static str) {
+        self.update(|state| {
+            state.disabled.insert(id, name);
+        });
+    }
+
+    /// Enables the command with the given type id.
+    pub(crate) fn enable_id(&self, id: TypeId) {
+        self.update(|state| {
+            state.disabled.remove(&id);
+        });
+    }
+
+    /// Keeps the command type `C` enabled in maintenance mode.
+    ///
+    /// This is used by the administrative commands, which must remain available to take the bus out of maintenance mode.
+    #[cfg(feature = "admin")]
+    pub(crate) fn exempt<C: Command>(&self) {
+        self.update(|state| {
+            state.exempt.insert(TypeId::of::<C>());
+        });
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        f(&mut state);
+
+        self.engaged.store(
+            state.maintenance || !state.disabled.is_empty(),
+            Ordering::Release,
+        );
+    }
+}
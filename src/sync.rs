@@ -0,0 +1,119 @@
+//! The `sync` module adapts synchronous handlers to the asynchronous handler traits ( requires the `sync` feature ).
+//!
+//! Synchronous handlers are registered using
+//! [CommandHandlerRegistry::register_sync](crate::registry::CommandHandlerRegistry::register_sync) and
+//! [QueryHandlerRegistry::register_sync](crate::registry::QueryHandlerRegistry::register_sync), and are invoked
+//! directly by the buses, without allocating a future for each dispatch. Some APIs only accept asynchronous
+//! handlers though, e.g. handler wrappers such as [TimeoutHandler](crate::timeout::TimeoutHandler), which is what
+//! the [SyncHandler] adapter is for.
+//!
+//! The adapter is a wrapper type, rather than a blanket implementation of [CommandHandler] for every
+//! [SyncCommandHandler], because such an implementation is rejected by the coherence rules: it would overlap with
+//! every other implementation of [CommandHandler], since nothing prevents a type from implementing both traits,
+//! and the same goes for queries.
+//!
+//! - [SyncHandler]: Adapts a synchronous handler to the asynchronous handler traits.
+
+use alloc::boxed::Box;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::command::SyncCommandHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+use crate::query::SyncQueryHandler;
+
+/// Adapts a synchronous handler to the asynchronous handler traits.
+///
+/// `SyncHandler` implements [CommandHandler] for every command its handler implements [SyncCommandHandler] for, and
+/// [QueryHandler] for every query its handler implements [SyncQueryHandler] for.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct DoubleCommand(u64);
+/// #
+/// # impl Command for DoubleCommand {
+/// #   type Metadata = u64;
+/// #   type Error = std::io::Error;
+/// # }
+/// use discern::command::CommandHandler;
+/// use discern::command::SyncCommandHandler;
+/// use discern::sync::SyncHandler;
+///
+/// struct DoubleCommandHandler;
+///
+/// impl SyncCommandHandler<DoubleCommand> for DoubleCommandHandler {
+///     fn handle(&self, command: DoubleCommand) -> Result<u64, std::io::Error> {
+///         Ok(command.0 * 2)
+///     }
+/// }
+///
+/// async fn handle_twice(handler: &impl CommandHandler<DoubleCommand>) -> u64 {
+///     let once = handler.handle(DoubleCommand(1)).await.unwrap();
+///
+///     handler.handle(DoubleCommand(once)).await.unwrap()
+/// }
+///
+/// assert_eq!(handle_twice(&SyncHandler::new(DoubleCommandHandler)).await, 4);
+/// # });
+/// ```
+pub struct SyncHandler<H> {
+    #[doc(hidden)]
+    handler: H,
+}
+
+/// The `SyncHandler` implementation.
+impl<H> SyncHandler<H> {
+    /// Creates a new `SyncHandler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The synchronous handler to adapt.
+    pub fn new(handler: H) -> Self {
+        Self { handler }
+    }
+
+    /// Returns the adapted handler.
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+#[async_trait]
+impl<C, H> CommandHandler<C> for SyncHandler<H>
+where
+    C: Command,
+    H: SyncCommandHandler<C>,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        self.handler.handle(command)
+    }
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for SyncHandler<H>
+where
+    Q: Query,
+    H: SyncQueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        self.handler.handle(query)
+    }
+}
+
+/// Debug implementation for `SyncHandler`
+impl<H> Debug for SyncHandler<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("SyncHandler").finish()
+    }
+}
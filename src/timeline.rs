@@ -0,0 +1,319 @@
+//! The `timeline` module captures the time spent in each middleware, and in the handler, of every dispatch.
+//!
+//! When a command is slow, the total duration of its dispatch doesn't tell whether the time was spent in the
+//! handler, or in one of the middleware wrapping it, e.g. a validation step querying the database. A [Timeline]
+//! added to a [CommandBus](crate::command::CommandBus) using
+//! [CommandBus::with_timeline](crate::command::CommandBus::with_timeline) records a [Trace] per dispatch, made of
+//! one [Segment] per step of the pipeline, which can be exported for offline analysis:
+//!
+//! - [Timeline::to_chrome_trace]: The Chrome trace-event format, for `chrome://tracing`, or Perfetto.
+//! - [Timeline::to_folded_stacks]: The folded-stack format, for `inferno`, or `flamegraph.pl`.
+//!
+//! - [Timeline]: Records the traces of recent dispatches.
+//! - [Trace]: The timing of a single dispatch.
+//! - [Segment]: The timing of a single step of a dispatch.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FormatterResult;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The number of traces recorded by default.
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// The name of the segment of the handler, the innermost step of the pipeline.
+pub const HANDLER_SEGMENT: &str = "handler";
+
+/// The timing of a single step of a dispatch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Segment {
+    /// The name of the step, the type name of the middleware, or [HANDLER_SEGMENT].
+    pub name: &'static str,
+    /// The position of the step in the pipeline, `0` being the outermost middleware.
+    pub depth: usize,
+    /// When the step started, relative to the creation of the [Timeline].
+    pub start: Duration,
+    /// The time spent in the step, including the steps after it.
+    pub duration: Duration,
+}
+
+/// The timing of a single dispatch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Trace {
+    /// The name of the command, see [Command::name](crate::command::Command::name).
+    pub name: &'static str,
+    /// When the dispatch started, relative to the creation of the [Timeline].
+    pub start: Duration,
+    /// The time spent in the pipeline.
+    pub duration: Duration,
+    /// The steps of the pipeline, outermost first.
+    pub segments: Vec<Segment>,
+}
+
+/// Records the traces of recent dispatches.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::command::Command;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::async_trait;
+/// use discern::command::CommandBus;
+/// use discern::command_registry;
+/// use discern::middleware::CommandMiddleware;
+/// use discern::middleware::Next;
+/// use discern::middleware::Outcome;
+/// use discern::timeline::Timeline;
+///
+/// struct ValidationMiddleware;
+///
+/// #[async_trait]
+/// impl CommandMiddleware for ValidationMiddleware {
+///     async fn handle(&self, next: Next<'_>) -> Outcome {
+///         next.run().await
+///     }
+/// }
+///
+/// let timeline = Timeline::new();
+///
+/// let registry = command_registry! {
+///     CreateUserCommand => |command| async move { Ok(command.username.len() as u64) },
+/// };
+/// let command_bus = CommandBus::new(registry)
+///     .with_middleware(ValidationMiddleware)
+///     .with_timeline(timeline.clone());
+///
+/// command_bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+///
+/// let traces = timeline.traces();
+/// assert_eq!(traces.len(), 1);
+/// assert_eq!(traces[0].segments.len(), 2);
+/// assert!(traces[0].segments[0].name.ends_with("ValidationMiddleware"));
+/// assert_eq!(traces[0].segments[1].name, "handler");
+///
+/// assert!(timeline.to_chrome_trace().starts_with(r#"{"traceEvents":["#));
+/// assert!(timeline.to_folded_stacks().contains("ValidationMiddleware;handler "));
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct Timeline {
+    #[doc(hidden)]
+    origin: Instant,
+    #[doc(hidden)]
+    traces: Arc<Mutex<VecDeque<Trace>>>,
+    #[doc(hidden)]
+    capacity: usize,
+}
+
+/// The `Timeline` implementation.
+impl Timeline {
+    /// Creates a new `Timeline`, recording the last 1000 traces.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new `Timeline`, recording the given number of traces.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity must be greater than zero");
+
+        Self {
+            origin: Instant::now(),
+            traces: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Returns the recorded traces, oldest first.
+    pub fn traces(&self) -> Vec<Trace> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Removes all the recorded traces.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Exports the recorded traces in the Chrome trace-event format.
+    ///
+    /// Each trace is a complete event named after the command, and each segment a complete event nested in it,
+    /// all dispatches sharing the same track.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut events = Vec::new();
+        for trace in self.lock().iter() {
+            events.push(chrome_event(
+                trace.name,
+                "dispatch",
+                trace.start,
+                trace.duration,
+            ));
+            for segment in &trace.segments {
+                events.push(chrome_event(
+                    segment.name,
+                    "segment",
+                    segment.start,
+                    segment.duration,
+                ));
+            }
+        }
+
+        format!(
+            r#"{{"traceEvents":[{}],"displayTimeUnit":"ms"}}"#,
+            events.join(",")
+        )
+    }
+
+    /// Exports the recorded traces in the folded-stack format.
+    ///
+    /// Each line is a stack, made of the name of the command, followed by the names of the segments, and the
+    /// time spent in its innermost segment, excluding the segments after it, in microseconds. Identical stacks are
+    /// merged.
+    pub fn to_folded_stacks(&self) -> String {
+        let mut stacks: Vec<(String, u128)> = Vec::new();
+        for trace in self.lock().iter() {
+            let mut stack = trace.name.to_string();
+            for (index, segment) in trace.segments.iter().enumerate() {
+                stack.push(';');
+                stack.push_str(segment.name);
+
+                let inner = trace
+                    .segments
+                    .get(index + 1)
+                    .map_or(Duration::ZERO, |inner| inner.duration);
+                let exclusive = segment.duration.saturating_sub(inner).as_micros();
+
+                match stacks.iter_mut().find(|(existing, _)| *existing == stack) {
+                    Some((_, total)) => *total += exclusive,
+                    None => stacks.push((stack.clone(), exclusive)),
+                }
+            }
+        }
+
+        let mut folded = String::new();
+        for (stack, total) in stacks {
+            let _ = writeln!(folded, "{} {}", stack, total);
+        }
+
+        folded
+    }
+
+    /// Starts recording the segments of a dispatch.
+    pub(crate) fn start(&self) -> Recording {
+        Recording {
+            origin: self.origin,
+            start: Instant::now(),
+            segments: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records the trace of a finished dispatch, evicting the oldest one if the capacity is reached.
+    pub(crate) fn finish(&self, name: &'static str, recording: Recording) {
+        let mut segments = recording
+            .segments
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        segments.sort_by_key(|segment| segment.depth);
+
+        let trace = Trace {
+            name,
+            start: recording.start.duration_since(self.origin),
+            duration: recording.start.elapsed(),
+            segments,
+        };
+
+        let mut traces = self.lock();
+        if traces.len() == self.capacity {
+            traces.pop_front();
+        }
+
+        traces.push_back(trace);
+    }
+
+    /// Locks the recorded traces.
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Trace>> {
+        self.traces.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debug implementation for `Timeline`
+impl Debug for Timeline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("Timeline")
+            .field("capacity", &self.capacity)
+            .field("traces", &self.lock().len())
+            .finish()
+    }
+}
+
+/// The segments of a dispatch being recorded.
+#[doc(hidden)]
+#[derive(Debug)]
+pub(crate) struct Recording {
+    origin: Instant,
+    start: Instant,
+    segments: Mutex<Vec<Segment>>,
+}
+
+impl Recording {
+    /// Returns the current time, to be passed to [Recording::segment] once the step finished.
+    pub(crate) fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Records a finished step of the pipeline.
+    pub(crate) fn segment(&self, name: &'static str, depth: usize, start: Instant) {
+        let segment = Segment {
+            name,
+            depth,
+            start: start.duration_since(self.origin),
+            duration: start.elapsed(),
+        };
+
+        self.segments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(segment);
+    }
+}
+
+/// Returns a complete event of the Chrome trace-event format.
+fn chrome_event(name: &str, category: &str, start: Duration, duration: Duration) -> String {
+    format!(
+        r#"{{"name":"{}","cat":"{}","ph":"X","ts":{},"dur":{},"pid":1,"tid":1}}"#,
+        escape(name),
+        category,
+        start.as_micros(),
+        duration.as_micros()
+    )
+}
+
+/// Escapes the quotes, and backslashes, of a name embedded in a JSON string.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
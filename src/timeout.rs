@@ -0,0 +1,180 @@
+//! The `timeout` module provides a handler wrapper that limits how long a message may be handled.
+//!
+//! A handler waiting on a stalled downstream service holds on to its caller, and to every resource acquired for
+//! the dispatch, until it completes, if ever. The [TimeoutHandler] cancels the wrapped handler once the configured
+//! duration has elapsed, and returns an error converted from [Elapsed] instead.
+//!
+//! Timeouts, and retries, are usually configured next to the registration of the handler, using the options of
+//! the [command_registry](crate::command_registry) and [query_registry](crate::query_registry) macros, e.g.
+//! `CreateUserCommand => handler, { timeout: 5s, retries: 3 }`.
+//!
+//! - [TimeoutHandler]: A handler that fails messages which are not handled within a duration.
+
+use alloc::boxed::Box;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use core::fmt::Result as FormatterResult;
+use core::time::Duration;
+
+use crate::async_trait;
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+use crate::runtime::timeout;
+use crate::runtime::Elapsed;
+use crate::runtime::Runtime;
+
+/// A handler that fails messages which are not handled within a duration.
+///
+/// When the duration elapses first, the wrapped handler is cancelled, and the [Elapsed] error is converted into the
+/// error type of the message, which must therefore implement `From<Elapsed>`.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::time::Duration;
+///
+/// use discern::command::Command;
+/// use discern::command::CommandHandlerFn;
+/// use discern::command_bus;
+/// use discern::error::DispatchError;
+/// use discern::runtime::Elapsed;
+/// use discern::runtime::Runtime;
+/// use discern::runtime::TokioRuntime;
+/// use discern::timeout::TimeoutHandler;
+///
+/// #[derive(Debug)]
+/// struct SendNewsletterCommand;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum SendNewsletterError {
+///     TimedOut,
+/// }
+///
+/// impl From<Elapsed> for SendNewsletterError {
+///     fn from(_: Elapsed) -> Self {
+///         SendNewsletterError::TimedOut
+///     }
+/// }
+///
+/// impl Command for SendNewsletterCommand {
+///     type Metadata = ();
+///     type Error = SendNewsletterError;
+/// }
+///
+/// let command_bus = command_bus! {
+///     SendNewsletterCommand => TimeoutHandler::new(
+///         CommandHandlerFn::new(|_: SendNewsletterCommand| async {
+///             // The mail server never answers.
+///             TokioRuntime.sleep(Duration::from_secs(60)).await;
+///
+///             Ok(())
+///         }),
+///         Duration::from_millis(1),
+///         TokioRuntime,
+///     ),
+/// };
+///
+/// let result = command_bus.dispatch(SendNewsletterCommand).await;
+///
+/// assert!(matches!(result, Err(DispatchError::Handler(SendNewsletterError::TimedOut))));
+/// # });
+/// ```
+pub struct TimeoutHandler<H> {
+    #[doc(hidden)]
+    handler: H,
+    #[doc(hidden)]
+    duration: Duration,
+    #[doc(hidden)]
+    runtime: Box<dyn Runtime>,
+}
+
+/// The `TimeoutHandler` implementation.
+impl<H> TimeoutHandler<H> {
+    /// Creates a new `TimeoutHandler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler to limit.
+    /// * `duration` - The maximum duration a message may be handled for.
+    /// * `runtime` - The runtime providing the timer.
+    pub fn new(handler: H, duration: Duration, runtime: impl Runtime) -> Self {
+        Self {
+            handler,
+            duration,
+            runtime: Box::new(runtime),
+        }
+    }
+
+    /// Returns the maximum duration a message may be handled for.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+#[async_trait]
+impl<C, H> CommandHandler<C> for TimeoutHandler<H>
+where
+    C: Command,
+    C::Error: From<Elapsed>,
+    H: CommandHandler<C>,
+{
+    async fn handle(&self, command: C) -> Result<C::Metadata, C::Error> {
+        timeout(&*self.runtime, self.duration, self.handler.handle(command)).await?
+    }
+}
+
+#[async_trait]
+impl<Q, H> QueryHandler<Q> for TimeoutHandler<H>
+where
+    Q: Query,
+    Q::Error: From<Elapsed>,
+    H: QueryHandler<Q>,
+{
+    async fn handle(&self, query: Q) -> Result<Q::Output, Q::Error> {
+        timeout(&*self.runtime, self.duration, self.handler.handle(query)).await?
+    }
+}
+
+/// Debug implementation for `TimeoutHandler`
+impl<H> Debug for TimeoutHandler<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        f.debug_struct("TimeoutHandler")
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+/// Parses the duration of a `timeout` registration option, e.g. `5s`, at compile time.
+///
+/// The duration is an integer, followed by one of the `ms`, `s`, `m`, and `h` units.
+#[doc(hidden)]
+pub const fn duration(literal: &str) -> Duration {
+    let bytes = literal.as_bytes();
+    let mut value: u64 = 0;
+    let mut index = 0;
+    while index < bytes.len() && bytes[index].is_ascii_digit() {
+        value = value * 10 + (bytes[index] - b'0') as u64;
+        index += 1;
+    }
+
+    if index == 0 {
+        panic!(
+            "invalid timeout, expected an integer followed by `ms`, `s`, `m`, or `h`, e.g. `5s`"
+        );
+    }
+
+    let (_, unit) = bytes.split_at(index);
+    match unit {
+        b"ms" => Duration::from_millis(value),
+        b"s" => Duration::from_secs(value),
+        b"m" => Duration::from_secs(value * 60),
+        b"h" => Duration::from_secs(value * 60 * 60),
+        _ => panic!(
+            "invalid timeout, expected an integer followed by `ms`, `s`, `m`, or `h`, e.g. `5s`"
+        ),
+    }
+}
@@ -0,0 +1,264 @@
+//! The `typed` module provides a bus whose handlers are resolved at compile time.
+//!
+//! The [CommandBus](crate::command::CommandBus), and [QueryBus](crate::query::QueryBus), look up the handler of a
+//! message at runtime, which allows registering handlers dynamically, but also means that a missing handler is only
+//! discovered when the message is dispatched. A [TypedBus] is built from a tuple of handlers, and resolves the
+//! handler of a message using trait resolution: dispatching a message without a handler fails to compile, and
+//! dispatching does not involve any lookup.
+//!
+//! - [TypedBus]: A bus whose handlers are resolved at compile time.
+//! - [CommandRoute]: Resolves the handler of a command within a tuple of handlers.
+//! - [QueryRoute]: Resolves the handler of a query within a tuple of handlers.
+//! - [Index]: The position of a handler within a tuple of handlers.
+
+use crate::command::Command;
+use crate::command::CommandHandler;
+use crate::query::Query;
+use crate::query::QueryHandler;
+
+/// The position of a handler within a tuple of handlers.
+///
+/// The index is inferred by the compiler when dispatching a message, and never needs to be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Index<const N: usize>;
+
+/// The `CommandRoute` trait resolves the handler of a command within a tuple of handlers.
+///
+/// `CommandRoute<C, Index<N>>` is implemented for tuples of up to 12 handlers, whose handler at position `N`
+/// handles the command `C`.
+pub trait CommandRoute<C: Command, I> {
+    /// The type of the handler.
+    type Handler: CommandHandler<C>;
+
+    /// Returns the handler of the command.
+    fn route(&self) -> &Self::Handler;
+}
+
+/// The `QueryRoute` trait resolves the handler of a query within a tuple of handlers.
+///
+/// `QueryRoute<Q, Index<N>>` is implemented for tuples of up to 12 handlers, whose handler at position `N` handles
+/// the query `Q`.
+pub trait QueryRoute<Q: Query, I> {
+    /// The type of the handler.
+    type Handler: QueryHandler<Q>;
+
+    /// Returns the handler of the query.
+    fn route(&self) -> &Self::Handler;
+}
+
+/// Implements the routes of a tuple of handlers, for the handler at each position.
+macro_rules! routes {
+    ($handlers:tt; $($index:tt => $handler:ident),+) => {
+        $(routes!(@route $handlers; $index => $handler);)+
+    };
+    (@route ($($handlers:ident),+); $index:tt => $handler:ident) => {
+        impl<C: Command, $($handlers),+> CommandRoute<C, Index<$index>> for ($($handlers,)+)
+        where
+            $handler: CommandHandler<C>,
+        {
+            type Handler = $handler;
+
+            fn route(&self) -> &$handler {
+                &self.$index
+            }
+        }
+
+        impl<Q: Query, $($handlers),+> QueryRoute<Q, Index<$index>> for ($($handlers,)+)
+        where
+            $handler: QueryHandler<Q>,
+        {
+            type Handler = $handler;
+
+            fn route(&self) -> &$handler {
+                &self.$index
+            }
+        }
+    };
+}
+
+routes!((H0); 0 => H0);
+routes!((H0, H1); 0 => H0, 1 => H1);
+routes!((H0, H1, H2); 0 => H0, 1 => H1, 2 => H2);
+routes!((H0, H1, H2, H3); 0 => H0, 1 => H1, 2 => H2, 3 => H3);
+routes!((H0, H1, H2, H3, H4); 0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4);
+routes!((H0, H1, H2, H3, H4, H5); 0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5);
+routes!((H0, H1, H2, H3, H4, H5, H6); 0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5, 6 => H6);
+routes!((H0, H1, H2, H3, H4, H5, H6, H7); 0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5, 6 => H6, 7 => H7);
+routes!(
+    (H0, H1, H2, H3, H4, H5, H6, H7, H8);
+    0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5, 6 => H6, 7 => H7, 8 => H8
+);
+routes!(
+    (H0, H1, H2, H3, H4, H5, H6, H7, H8, H9);
+    0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5, 6 => H6, 7 => H7, 8 => H8, 9 => H9
+);
+routes!(
+    (H0, H1, H2, H3, H4, H5, H6, H7, H8, H9, H10);
+    0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5, 6 => H6, 7 => H7, 8 => H8, 9 => H9, 10 => H10
+);
+routes!(
+    (H0, H1, H2, H3, H4, H5, H6, H7, H8, H9, H10, H11);
+    0 => H0, 1 => H1, 2 => H2, 3 => H3, 4 => H4, 5 => H5, 6 => H6, 7 => H7, 8 => H8, 9 => H9, 10 => H10, 11 => H11
+);
+
+/// A bus whose handlers are resolved at compile time.
+///
+/// A `TypedBus` is built from a tuple of up to 12 command, and query, handlers. Each message must be handled by
+/// exactly one handler of the tuple: dispatching a message without a handler, or with more than one handler, fails
+/// to compile.
+///
+/// Unlike the [CommandBus](crate::command::CommandBus), a `TypedBus` doesn't record statistics, and handlers can't
+/// be registered once it is built. It lives alongside the dynamic buses, for hot paths, or applications whose
+/// handlers are all known upfront.
+///
+/// # Example
+///
+/// ```
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::async_trait;
+/// # use discern::command::Command;
+/// # use discern::command::CommandHandler;
+/// # use discern::query::Query;
+/// # use discern::query::QueryHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand {
+/// #     username: String,
+/// # }
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # struct CreateUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+/// #     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+/// #         Ok(1)
+/// #     }
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct GetUsernameQuery {
+/// #     user_id: u64,
+/// # }
+/// #
+/// # impl Query for GetUsernameQuery {
+/// #     type Output = String;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # struct GetUsernameQueryHandler;
+/// #
+/// # #[async_trait]
+/// # impl QueryHandler<GetUsernameQuery> for GetUsernameQueryHandler {
+/// #     async fn handle(&self, query: GetUsernameQuery) -> Result<String, std::io::Error> {
+/// #         Ok("alice".to_string())
+/// #     }
+/// # }
+/// use discern::typed::TypedBus;
+///
+/// let bus = TypedBus::new((CreateUserCommandHandler, GetUsernameQueryHandler));
+///
+/// let user_id = bus.dispatch(CreateUserCommand { username: "alice".to_string() }).await.unwrap();
+/// let username = bus.query(GetUsernameQuery { user_id }).await.unwrap();
+///
+/// assert_eq!(username, "alice");
+/// # });
+/// ```
+///
+/// Dispatching a message without a handler fails to compile:
+///
+/// ```compile_fail
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// # use discern::async_trait;
+/// # use discern::command::Command;
+/// # use discern::command::CommandHandler;
+/// #
+/// # #[derive(Debug)]
+/// # struct CreateUserCommand;
+/// #
+/// # impl Command for CreateUserCommand {
+/// #     type Metadata = u64;
+/// #     type Error = std::io::Error;
+/// # }
+/// #
+/// # struct CreateUserCommandHandler;
+/// #
+/// # #[async_trait]
+/// # impl CommandHandler<CreateUserCommand> for CreateUserCommandHandler {
+/// #     async fn handle(&self, command: CreateUserCommand) -> Result<u64, std::io::Error> {
+/// #         Ok(1)
+/// #     }
+/// # }
+/// #
+/// # #[derive(Debug)]
+/// # struct DeleteUserCommand;
+/// #
+/// # impl Command for DeleteUserCommand {
+/// #     type Metadata = ();
+/// #     type Error = std::io::Error;
+/// # }
+/// use discern::typed::TypedBus;
+///
+/// let bus = TypedBus::new((CreateUserCommandHandler,));
+///
+/// bus.dispatch(DeleteUserCommand).await;
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedBus<H> {
+    #[doc(hidden)]
+    handlers: H,
+}
+
+/// The `TypedBus` implementation.
+impl<H> TypedBus<H> {
+    /// Creates a new `TypedBus` from a tuple of handlers.
+    pub fn new(handlers: H) -> Self {
+        Self { handlers }
+    }
+
+    /// Dispatches a command to its handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the command handler, which includes the metadata or an error.
+    pub async fn dispatch<C, I>(&self, command: C) -> Result<C::Metadata, C::Error>
+    where
+        C: Command,
+        H: CommandRoute<C, I>,
+    {
+        CommandHandler::handle(CommandRoute::<C, I>::route(&self.handlers), command).await
+    }
+
+    /// Dispatches a query to its handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The result of the query handler, which includes the output or an error.
+    pub async fn query<Q, I>(&self, query: Q) -> Result<Q::Output, Q::Error>
+    where
+        Q: Query,
+        H: QueryRoute<Q, I>,
+    {
+        QueryHandler::handle(QueryRoute::<Q, I>::route(&self.handlers), query).await
+    }
+
+    /// Returns the tuple of handlers.
+    pub fn handlers(&self) -> &H {
+        &self.handlers
+    }
+}